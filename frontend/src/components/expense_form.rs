@@ -1,6 +1,7 @@
-use crate::models::CreateExpenseRequest;
-use crate::services::ExpenseService;
+use crate::models::{CreateExpenseRequest, Problem};
+use crate::services::{ExpenseService, ExpenseServiceError};
 use dioxus::prelude::*;
+use uuid::Uuid;
 
 #[component]
 pub fn ExpenseForm() -> Element {
@@ -11,11 +12,17 @@ pub fn ExpenseForm() -> Element {
     #[allow(clippy::redundant_closure)]
     let mut message = use_signal(|| String::new());
     let mut is_loading = use_signal(|| false);
+    // The backend's per-field complaints about the last submission.
+    let mut problem = use_signal(|| None::<Problem>);
+    // Reused across retries of the same submission; renewed when the input
+    // changes or the expense was saved.
+    let mut idempotency_key = use_signal(Uuid::new_v4);
 
     let submit_handler = move |_| {
         spawn(async move {
             is_loading.set(true);
             message.set(String::new());
+            problem.set(None);
 
             let amount_value: Result<f64, _> = amount().parse();
             match amount_value {
@@ -25,12 +32,17 @@ pub fn ExpenseForm() -> Element {
                         category: category().clone(),
                     };
 
-                    match ExpenseService::add_expense(request).await {
+                    match ExpenseService::add_expense(request, idempotency_key()).await {
                         Ok(_) => {
+                            idempotency_key.set(Uuid::new_v4());
                             message.set("Expense added successfully!".to_string());
                             amount.set(String::new());
                             category.set(String::new());
                         }
+                        Err(ExpenseServiceError::Rejected(rejected)) => {
+                            message.set(format!("Error: {}", rejected.detail));
+                            problem.set(Some(rejected));
+                        }
                         Err(e) => {
                             message.set(format!("Error: {}", e));
                         }
@@ -67,7 +79,16 @@ pub fn ExpenseForm() -> Element {
                         step: "0.01",
                         placeholder: "0.00",
                         value: "{amount}",
-                        oninput: move |e| amount.set(e.value())
+                        oninput: move |e| {
+                            amount.set(e.value());
+                            idempotency_key.set(Uuid::new_v4());
+                        }
+                    }
+                    if let Some(error) = problem().and_then(|p| p.field_message("amount")) {
+                        p {
+                            class: "mt-1 text-sm text-red-600",
+                            "{error}"
+                        }
                     }
                 }
 
@@ -80,9 +101,18 @@ pub fn ExpenseForm() -> Element {
                     input {
                         class: "w-full px-3 py-2 border border-gray-300 rounded-md focus:outline-none focus:ring-2 focus:ring-blue-500",
                         r#type: "text",
-                        placeholder: "e.g., Food, Transport, Entertainment",
+                        placeholder: "e.g., Food, Transport (leave empty to use rules)",
                         value: "{category}",
-                        oninput: move |e| category.set(e.value())
+                        oninput: move |e| {
+                            category.set(e.value());
+                            idempotency_key.set(Uuid::new_v4());
+                        }
+                    }
+                    if let Some(error) = problem().and_then(|p| p.field_message("category")) {
+                        p {
+                            class: "mt-1 text-sm text-red-600",
+                            "{error}"
+                        }
                     }
                 }
 
@@ -1,2 +1,4 @@
 pub mod expense;
-pub use expense::*;
\ No newline at end of file
+pub mod problem;
+pub use expense::*;
+pub use problem::*;
@@ -0,0 +1,31 @@
+use serde::Deserialize;
+
+/// An error body from the backend (`application/problem+json`).
+#[allow(dead_code)]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Problem {
+    pub code: String,
+    pub detail: String,
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+    #[serde(default)]
+    pub errors: Vec<FieldViolation>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldViolation {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl Problem {
+    /// The first violation reported for `field`, if any.
+    pub fn field_message(&self, field: &str) -> Option<String> {
+        self.errors
+            .iter()
+            .find(|violation| violation.field == field)
+            .map(|violation| violation.message.clone())
+    }
+}
@@ -1,5 +1,6 @@
-use crate::models::{Expense, CreateExpenseRequest};
+use crate::models::{Expense, CreateExpenseRequest, Problem};
 use thiserror::Error;
+use uuid::Uuid;
 
 #[allow(dead_code)]
 #[allow(unused_variables)]
@@ -13,6 +14,8 @@ pub enum ExpenseServiceError {
     Serialization(#[from] serde_json::Error),
     #[error("Validation error: {0}")]
     Validation(String),
+    #[error("{}", .0.detail)]
+    Rejected(Problem),
 }
 
 #[allow(dead_code)]
@@ -28,18 +31,28 @@ impl ExpenseService {
         Ok(expenses)
     }
 
-    pub async fn add_expense(request: CreateExpenseRequest) -> Result<(), ExpenseServiceError> {
+    /// `idempotency_key` must stay the same when retrying one submission so
+    /// the backend does not record it twice.
+    pub async fn add_expense(
+        request: CreateExpenseRequest,
+        idempotency_key: Uuid,
+    ) -> Result<(), ExpenseServiceError> {
         let client = reqwest::Client::new();
         let response = client
             .post(format!("{}/expenses", API_BASE_URL))
+            .header("Idempotency-Key", idempotency_key.to_string())
             .json(&request)
             .send()
             .await?;
 
         if !response.status().is_success() {
-            return Err(ExpenseServiceError::Validation (
-                format!("Failed to add expense: {}", response.status())
-            ));
+            let status = response.status();
+            return Err(match response.json::<Problem>().await {
+                Ok(problem) => ExpenseServiceError::Rejected(problem),
+                Err(_) => ExpenseServiceError::Validation(
+                    format!("Failed to add expense: {}", status)
+                ),
+            });
         }
         
         Ok(())
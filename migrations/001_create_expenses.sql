@@ -1,6 +1,6 @@
-CREATE TABLE expenses (
+CREATE TABLE IF NOT EXISTS expenses (
     id TEXT PRIMARY KEY,
     amount REAL NOT NULL,
     category TEXT NOT NULL,
     date TEXT NOT NULL
-);
\ No newline at end of file
+);
@@ -0,0 +1,34 @@
+ALTER TABLE expenses ADD COLUMN payee TEXT;
+ALTER TABLE expenses ADD COLUMN notes TEXT;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS expenses_fts USING fts5(
+    expense_id UNINDEXED,
+    category,
+    payee,
+    notes,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO expenses_fts (expense_id, category, payee, notes)
+SELECT id, category, payee, notes FROM expenses;
+
+CREATE TRIGGER IF NOT EXISTS expenses_fts_insert
+AFTER INSERT ON expenses
+BEGIN
+    INSERT INTO expenses_fts (expense_id, category, payee, notes)
+    VALUES (new.id, new.category, new.payee, new.notes);
+END;
+
+CREATE TRIGGER IF NOT EXISTS expenses_fts_update
+AFTER UPDATE OF category, payee, notes ON expenses
+BEGIN
+    DELETE FROM expenses_fts WHERE expense_id = old.id;
+    INSERT INTO expenses_fts (expense_id, category, payee, notes)
+    VALUES (new.id, new.category, new.payee, new.notes);
+END;
+
+CREATE TRIGGER IF NOT EXISTS expenses_fts_delete
+AFTER DELETE ON expenses
+BEGIN
+    DELETE FROM expenses_fts WHERE expense_id = old.id;
+END;
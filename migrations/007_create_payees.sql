@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS payees (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS payee_aliases (
+    id TEXT PRIMARY KEY,
+    payee_id TEXT NOT NULL REFERENCES payees (id) ON DELETE CASCADE,
+    kind TEXT NOT NULL CHECK (kind IN ('exact', 'prefix', 'regex')),
+    pattern TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    UNIQUE (kind, pattern)
+);
+
+CREATE INDEX IF NOT EXISTS idx_payee_aliases_payee_id ON payee_aliases (payee_id);
+
+ALTER TABLE expenses ADD COLUMN payee_id TEXT REFERENCES payees (id);
+
+CREATE INDEX IF NOT EXISTS idx_expenses_payee_id ON expenses (payee_id);
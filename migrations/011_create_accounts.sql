@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS accounts (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    kind TEXT NOT NULL CHECK (kind IN ('cash', 'checking', 'savings', 'credit_card', 'company_card', 'other')),
+    currency TEXT NOT NULL,
+    opening_balance REAL NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL
+);
+
+ALTER TABLE expenses ADD COLUMN account_id TEXT REFERENCES accounts (id);
+
+CREATE INDEX IF NOT EXISTS idx_expenses_account_id ON expenses (account_id);
+
+-- Money moved between two accounts. Transfers change balances but are not
+-- spending, so they live apart from expenses.
+CREATE TABLE IF NOT EXISTS transfers (
+    id TEXT PRIMARY KEY,
+    from_account_id TEXT NOT NULL REFERENCES accounts (id),
+    to_account_id TEXT NOT NULL REFERENCES accounts (id),
+    amount REAL NOT NULL CHECK (amount > 0),
+    date TEXT NOT NULL,
+    notes TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_transfers_from_account_id ON transfers (from_account_id);
+CREATE INDEX IF NOT EXISTS idx_transfers_to_account_id ON transfers (to_account_id);
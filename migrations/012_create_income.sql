@@ -0,0 +1,15 @@
+-- Money coming in. Kept apart from expenses so income never shows up in
+-- spending queries.
+CREATE TABLE IF NOT EXISTS income (
+    id TEXT PRIMARY KEY,
+    amount REAL NOT NULL CHECK (amount > 0),
+    category TEXT NOT NULL,
+    source TEXT,
+    notes TEXT,
+    account_id TEXT REFERENCES accounts (id),
+    date TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_income_date ON income (date);
+CREATE INDEX IF NOT EXISTS idx_income_account_id ON income (account_id);
@@ -0,0 +1,12 @@
+-- Money returned against an expense. The sum of an expense's refunds never
+-- exceeds its amount; aggregates use the amount net of refunds.
+CREATE TABLE IF NOT EXISTS refunds (
+    id TEXT PRIMARY KEY,
+    expense_id TEXT NOT NULL REFERENCES expenses (id),
+    amount REAL NOT NULL CHECK (amount > 0),
+    reason TEXT,
+    date TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_refunds_expense_id ON refunds (expense_id);
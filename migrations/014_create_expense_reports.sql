@@ -0,0 +1,31 @@
+ALTER TABLE expenses ADD COLUMN reimbursable INTEGER NOT NULL DEFAULT 0;
+
+-- A batch of reimbursable expenses submitted for approval by its owner.
+CREATE TABLE IF NOT EXISTS expense_reports (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('draft', 'submitted', 'approved', 'rejected', 'paid')),
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_expense_reports_status ON expense_reports (status);
+
+-- An expense belongs to at most one report.
+CREATE TABLE IF NOT EXISTS expense_report_items (
+    report_id TEXT NOT NULL REFERENCES expense_reports (id),
+    expense_id TEXT NOT NULL UNIQUE REFERENCES expenses (id),
+    PRIMARY KEY (report_id, expense_id)
+);
+
+CREATE TABLE IF NOT EXISTS expense_report_comments (
+    id TEXT PRIMARY KEY,
+    report_id TEXT NOT NULL REFERENCES expense_reports (id),
+    author TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_expense_report_comments_report_id
+    ON expense_report_comments (report_id);
@@ -0,0 +1,9 @@
+-- Every listing filters on `deleted_at IS NULL`. Leading with `deleted_at`
+-- lets newest-first, highest-amount and per-category queries read the
+-- active rows in index order instead of sorting the whole table; the
+-- trash is served by the same indexes.
+DROP INDEX IF EXISTS idx_expenses_deleted_at;
+
+CREATE INDEX IF NOT EXISTS idx_expenses_deleted_at_date ON expenses (deleted_at, date);
+CREATE INDEX IF NOT EXISTS idx_expenses_deleted_at_amount ON expenses (deleted_at, amount);
+CREATE INDEX IF NOT EXISTS idx_expenses_deleted_at_category ON expenses (deleted_at, category);
@@ -0,0 +1,43 @@
+-- The search index kept its own copy of each expense and found it again by
+-- `expense_id`, an unindexed column, so every update or delete of an expense
+-- scanned the whole index. It now reads category, payee and notes from
+-- `expenses` (an external-content table) and is keyed by the expense's rowid,
+-- which the triggers use to update exactly one entry.
+DROP TRIGGER IF EXISTS expenses_fts_insert;
+DROP TRIGGER IF EXISTS expenses_fts_update;
+DROP TRIGGER IF EXISTS expenses_fts_delete;
+DROP TABLE IF EXISTS expenses_fts;
+
+CREATE VIRTUAL TABLE expenses_fts USING fts5(
+    category,
+    payee,
+    notes,
+    content = 'expenses',
+    content_rowid = 'rowid',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO expenses_fts (expenses_fts) VALUES ('rebuild');
+
+CREATE TRIGGER expenses_fts_insert
+AFTER INSERT ON expenses
+BEGIN
+    INSERT INTO expenses_fts (rowid, category, payee, notes)
+    VALUES (new.rowid, new.category, new.payee, new.notes);
+END;
+
+CREATE TRIGGER expenses_fts_update
+AFTER UPDATE OF category, payee, notes ON expenses
+BEGIN
+    INSERT INTO expenses_fts (expenses_fts, rowid, category, payee, notes)
+    VALUES ('delete', old.rowid, old.category, old.payee, old.notes);
+    INSERT INTO expenses_fts (rowid, category, payee, notes)
+    VALUES (new.rowid, new.category, new.payee, new.notes);
+END;
+
+CREATE TRIGGER expenses_fts_delete
+AFTER DELETE ON expenses
+BEGIN
+    INSERT INTO expenses_fts (expenses_fts, rowid, category, payee, notes)
+    VALUES ('delete', old.rowid, old.category, old.payee, old.notes);
+END;
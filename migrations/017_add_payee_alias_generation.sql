@@ -0,0 +1,25 @@
+-- Payee resolution caches the compiled aliases. This one-row table identifies
+-- the aliases the cache was built from: every change to an alias, or to the
+-- name of the payee it resolves to, draws a new random token. A random token
+-- (rather than a counter) is never reused by a restored or different database.
+CREATE TABLE payee_alias_generation (
+    token TEXT NOT NULL
+);
+
+INSERT INTO payee_alias_generation (token) VALUES (lower(hex(randomblob(16))));
+
+CREATE TRIGGER payee_aliases_generation_insert AFTER INSERT ON payee_aliases BEGIN
+    UPDATE payee_alias_generation SET token = lower(hex(randomblob(16)));
+END;
+
+CREATE TRIGGER payee_aliases_generation_update AFTER UPDATE ON payee_aliases BEGIN
+    UPDATE payee_alias_generation SET token = lower(hex(randomblob(16)));
+END;
+
+CREATE TRIGGER payee_aliases_generation_delete AFTER DELETE ON payee_aliases BEGIN
+    UPDATE payee_alias_generation SET token = lower(hex(randomblob(16)));
+END;
+
+CREATE TRIGGER payees_generation_rename AFTER UPDATE OF name ON payees BEGIN
+    UPDATE payee_alias_generation SET token = lower(hex(randomblob(16)));
+END;
@@ -0,0 +1,64 @@
+-- Date filters compared `julianday(date)`, which no index can serve, because
+-- stored dates were not all in one format. The server writes RFC 3339 in UTC
+-- with a `+00:00` offset and 0, 3, 6 or 9 fractional digits, in which text
+-- order is time order, so dates can be compared and indexed as text. Rows in
+-- any other format SQLite reads (`Z`, a space separator, another offset) are
+-- rewritten to it, here and by the triggers on later writes. Unreadable
+-- values are left for the integrity check to report.
+UPDATE expenses
+SET date = CASE WHEN strftime('%f', date) GLOB '*.000'
+                THEN strftime('%Y-%m-%dT%H:%M:%S', date)
+                ELSE strftime('%Y-%m-%dT%H:%M:%f', date) END || '+00:00'
+WHERE date NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]*+00:00'
+  AND julianday(date) IS NOT NULL;
+
+UPDATE income
+SET date = CASE WHEN strftime('%f', date) GLOB '*.000'
+                THEN strftime('%Y-%m-%dT%H:%M:%S', date)
+                ELSE strftime('%Y-%m-%dT%H:%M:%f', date) END || '+00:00'
+WHERE date NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]*+00:00'
+  AND julianday(date) IS NOT NULL;
+
+CREATE TRIGGER IF NOT EXISTS expenses_normalize_date_insert AFTER INSERT ON expenses
+WHEN NEW.date NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]*+00:00'
+  AND julianday(NEW.date) IS NOT NULL
+BEGIN
+    UPDATE expenses
+    SET date = CASE WHEN strftime('%f', NEW.date) GLOB '*.000'
+                    THEN strftime('%Y-%m-%dT%H:%M:%S', NEW.date)
+                    ELSE strftime('%Y-%m-%dT%H:%M:%f', NEW.date) END || '+00:00'
+    WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER IF NOT EXISTS expenses_normalize_date_update AFTER UPDATE OF date ON expenses
+WHEN NEW.date NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]*+00:00'
+  AND julianday(NEW.date) IS NOT NULL
+BEGIN
+    UPDATE expenses
+    SET date = CASE WHEN strftime('%f', NEW.date) GLOB '*.000'
+                    THEN strftime('%Y-%m-%dT%H:%M:%S', NEW.date)
+                    ELSE strftime('%Y-%m-%dT%H:%M:%f', NEW.date) END || '+00:00'
+    WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER IF NOT EXISTS income_normalize_date_insert AFTER INSERT ON income
+WHEN NEW.date NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]*+00:00'
+  AND julianday(NEW.date) IS NOT NULL
+BEGIN
+    UPDATE income
+    SET date = CASE WHEN strftime('%f', NEW.date) GLOB '*.000'
+                    THEN strftime('%Y-%m-%dT%H:%M:%S', NEW.date)
+                    ELSE strftime('%Y-%m-%dT%H:%M:%f', NEW.date) END || '+00:00'
+    WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER IF NOT EXISTS income_normalize_date_update AFTER UPDATE OF date ON income
+WHEN NEW.date NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]*+00:00'
+  AND julianday(NEW.date) IS NOT NULL
+BEGIN
+    UPDATE income
+    SET date = CASE WHEN strftime('%f', NEW.date) GLOB '*.000'
+                    THEN strftime('%Y-%m-%dT%H:%M:%S', NEW.date)
+                    ELSE strftime('%Y-%m-%dT%H:%M:%f', NEW.date) END || '+00:00'
+    WHERE rowid = NEW.rowid;
+END;
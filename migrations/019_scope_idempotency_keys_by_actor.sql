@@ -0,0 +1,26 @@
+-- Keys were shared by every client, so two clients that picked the same key
+-- got each other's responses. They are now scoped to the actor as well.
+-- Stored keys did not record who sent them; they are kept for `anonymous`,
+-- the actor of requests without `X-Actor`.
+CREATE TABLE idempotency_keys_by_actor (
+    actor TEXT NOT NULL,
+    key TEXT NOT NULL,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    request_hash TEXT NOT NULL,
+    status INTEGER,
+    response_headers TEXT,
+    response_body BLOB,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (actor, key, method, path)
+);
+
+INSERT INTO idempotency_keys_by_actor
+SELECT 'anonymous', key, method, path, request_hash, status, response_headers, response_body,
+       created_at
+FROM idempotency_keys;
+
+DROP TABLE idempotency_keys;
+ALTER TABLE idempotency_keys_by_actor RENAME TO idempotency_keys;
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_created_at ON idempotency_keys (created_at);
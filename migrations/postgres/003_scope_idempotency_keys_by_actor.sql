@@ -0,0 +1,5 @@
+-- Keys are scoped to the actor that sent them, as in SQLite migration 019.
+ALTER TABLE idempotency_keys ADD COLUMN actor TEXT NOT NULL DEFAULT 'anonymous';
+ALTER TABLE idempotency_keys ALTER COLUMN actor DROP DEFAULT;
+ALTER TABLE idempotency_keys DROP CONSTRAINT idempotency_keys_pkey;
+ALTER TABLE idempotency_keys ADD PRIMARY KEY (actor, key, method, path);
@@ -0,0 +1,255 @@
+//! `expence_tracker backup ...`, the backup routes of the API for operators
+//! at a shell plus restoring a snapshot, which only the shell offers, and
+//! `expence_tracker encryption ...` to manage the key data is encrypted with
+//! at rest. Backup commands work on `DATABASE_URL` directly,
+//! whether or not a server is running on it, except a restore, which like a
+//! key rotation refuses to run until the server is stopped. Commands print
+//! their result as JSON.
+
+use crate::config::Config;
+use crate::context::RequestContext;
+use crate::database::{self, PoolSettings};
+use crate::encryption::{self, EncryptionKey};
+use crate::models::backup::BackupKind;
+use crate::repository::Backend;
+use crate::services::backup_service::BackupService;
+use crate::storage::BlobStore;
+use anyhow::{Result, anyhow, bail};
+use serde::Serialize;
+use sqlx::sqlite::SqliteConnectOptions;
+use std::str::FromStr;
+use uuid::Uuid;
+
+pub const BACKUP_USAGE: &str =
+    "usage: expence_tracker backup <create | list | verify <name> | restore <name>>";
+pub const ENCRYPTION_USAGE: &str = "usage: expence_tracker encryption <generate-key | rotate>";
+
+#[derive(Debug, PartialEq)]
+pub enum BackupCommand {
+    Create,
+    List,
+    Verify(String),
+    Restore(String),
+}
+
+impl BackupCommand {
+    /// The command in `args` (without the program name); `None` when they do
+    /// not start with `backup`.
+    pub fn parse(args: &[String]) -> Result<Option<Self>> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let command = match args.as_slice() {
+            ["backup", rest @ ..] => match rest {
+                ["create"] => BackupCommand::Create,
+                ["list"] => BackupCommand::List,
+                ["verify", name] => BackupCommand::Verify(name.to_string()),
+                ["restore", name] => BackupCommand::Restore(name.to_string()),
+                _ => bail!(BACKUP_USAGE),
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+pub async fn run_backup(config: &Config, command: BackupCommand) -> Result<()> {
+    if Backend::from_url(&config.database_url)? != Backend::Sqlite {
+        bail!("Backups cover SQLite databases; use pg_dump for PostgreSQL");
+    }
+    if let BackupCommand::Restore(_) = command {
+        // Replacing every table under a running server is refused, as a key
+        // rotation is; checked before this command opens connections itself.
+        let options = SqliteConnectOptions::from_str(&config.database_url)?;
+        encryption::ensure_not_in_use(options.get_filename(), config.encryption_key.as_ref())
+            .await?;
+    }
+    let pool = database::create_pool_with(&config.database_url, config.sqlite_pool.clone()).await?;
+    let service = BackupService::new(
+        pool.clone(),
+        &config.backup_dir,
+        config.backup_keep,
+        config.encryption_key.clone(),
+    );
+
+    let result = run(&service, command).await;
+    pool.close().await;
+    result
+}
+
+async fn run(service: &BackupService, command: BackupCommand) -> Result<()> {
+    match command {
+        BackupCommand::Create => print(&service.create(BackupKind::Manual).await?),
+        BackupCommand::List => print(&service.list().await?),
+        BackupCommand::Verify(name) => {
+            let verification = service
+                .verify(&name)
+                .await?
+                .ok_or_else(|| anyhow!("No backup named {name}"))?;
+            print(&verification)?;
+            if !verification.ok {
+                bail!("Backup {name} failed verification");
+            }
+            Ok(())
+        }
+        BackupCommand::Restore(name) => {
+            let actor = std::env::var("USER")
+                .map_or_else(|_| "cli".to_string(), |user| format!("cli:{user}"));
+            let ctx = RequestContext::new(actor, Uuid::new_v4().to_string());
+            let outcome = service
+                .restore(&ctx, &name)
+                .await?
+                .ok_or_else(|| anyhow!("No backup named {name}"))?;
+            print(&outcome)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EncryptionCommand {
+    /// Prints a new random key.
+    GenerateKey,
+    /// Re-encrypts everything from `ENCRYPTION_KEY`, or from plain when it is
+    /// unset, to `NEW_ENCRYPTION_KEY`.
+    Rotate,
+}
+
+impl EncryptionCommand {
+    /// The command in `args` (without the program name); `None` when they do
+    /// not start with `encryption`.
+    pub fn parse(args: &[String]) -> Result<Option<Self>> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let command = match args.as_slice() {
+            ["encryption", rest @ ..] => match rest {
+                ["generate-key"] => EncryptionCommand::GenerateKey,
+                ["rotate"] => EncryptionCommand::Rotate,
+                _ => bail!(ENCRYPTION_USAGE),
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// What a key rotation re-encrypted.
+#[derive(Debug, Serialize)]
+struct Rotation {
+    key_fingerprint: String,
+    database: bool,
+    backups: usize,
+    attachments: usize,
+}
+
+pub async fn run_encryption(config: &Config, command: EncryptionCommand) -> Result<()> {
+    match command {
+        EncryptionCommand::GenerateKey => {
+            println!("{}", EncryptionKey::generate()?.to_hex());
+            Ok(())
+        }
+        EncryptionCommand::Rotate => {
+            let new =
+                EncryptionKey::from_lookup(|key| std::env::var(key).ok(), "NEW_ENCRYPTION_KEY")?
+                    .ok_or_else(|| {
+                        anyhow!("set NEW_ENCRYPTION_KEY or NEW_ENCRYPTION_KEY_FILE to the new key")
+                    })?;
+            let rotation = rotate(config, new).await?;
+            tracing::warn!(
+                key_fingerprint = %rotation.key_fingerprint,
+                "Rotated the encryption key; set ENCRYPTION_KEY to the new key before starting the server"
+            );
+            print(&rotation)
+        }
+    }
+}
+
+/// Re-encrypts the database, its snapshots and the attachments under `new`.
+/// Each step skips what is already under `new`, so an interrupted rotation
+/// is finished by running it again with the same keys.
+async fn rotate(config: &Config, new: EncryptionKey) -> Result<Rotation> {
+    if Backend::from_url(&config.database_url)? != Backend::Sqlite {
+        bail!("Encryption at rest covers SQLite databases; encrypt PostgreSQL's storage instead");
+    }
+    let old = config.encryption_key.as_ref();
+
+    let options = SqliteConnectOptions::from_str(&config.database_url)?;
+    let database = encryption::rekey_database(options.get_filename(), old, &new).await?;
+
+    let settings = PoolSettings {
+        key: Some(new.clone()),
+        ..config.sqlite_pool.clone()
+    };
+    let pool = database::create_pool_with(&config.database_url, settings).await?;
+    let backups = BackupService::new(
+        pool.clone(),
+        &config.backup_dir,
+        config.backup_keep,
+        Some(new.clone()),
+    )
+    .reencrypt(old)
+    .await;
+    pool.close().await;
+
+    let attachments = BlobStore::new(&config.attachments_dir, Some(&new))
+        .reencrypt(old)
+        .await?;
+    Ok(Rotation {
+        key_fingerprint: new.fingerprint(),
+        database,
+        backups: backups?,
+        attachments,
+    })
+}
+
+fn print(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<BackupCommand>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        BackupCommand::parse(&args)
+    }
+
+    #[test]
+    fn test_parse_backup_commands() {
+        assert_eq!(parse(&[]).unwrap(), None);
+        assert_eq!(parse(&["--demo"]).unwrap(), None);
+        assert_eq!(
+            parse(&["backup", "create"]).unwrap(),
+            Some(BackupCommand::Create)
+        );
+        assert_eq!(
+            parse(&["backup", "list"]).unwrap(),
+            Some(BackupCommand::List)
+        );
+        assert_eq!(
+            parse(&["backup", "restore", "expenses-1"]).unwrap(),
+            Some(BackupCommand::Restore("expenses-1".to_string()))
+        );
+        assert!(parse(&["backup"]).is_err());
+        assert!(parse(&["backup", "verify"]).is_err());
+        assert!(parse(&["backup", "list", "extra"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_encryption_commands() {
+        let parse = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            EncryptionCommand::parse(&args)
+        };
+
+        assert_eq!(parse(&["backup", "list"]).unwrap(), None);
+        assert_eq!(
+            parse(&["encryption", "generate-key"]).unwrap(),
+            Some(EncryptionCommand::GenerateKey)
+        );
+        assert_eq!(
+            parse(&["encryption", "rotate"]).unwrap(),
+            Some(EncryptionCommand::Rotate)
+        );
+        assert!(parse(&["encryption"]).is_err());
+        assert!(parse(&["encryption", "rotate", "now"]).is_err());
+    }
+}
@@ -0,0 +1,337 @@
+use crate::context::Credential;
+use crate::database::PoolSettings;
+use crate::encryption::EncryptionKey;
+use crate::logging::LogFormat;
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const DEFAULT_DATABASE_URL: &str = "sqlite:./expenses.db";
+const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+const DEFAULT_TRASH_PURGE_INTERVAL_SECS: u64 = 3600;
+const DEFAULT_IDEMPOTENCY_RETENTION_HOURS: u64 = 24;
+const DEFAULT_ATTACHMENTS_DIR: &str = "./attachments";
+const DEFAULT_MAX_ATTACHMENT_MB: u64 = 10;
+const DEFAULT_BACKUP_DIR: &str = "./backups";
+const DEFAULT_BACKUP_KEEP: u64 = 7;
+const DEFAULT_BACKUP_INTERVAL_HOURS: u64 = 24;
+/// Room for the multipart framing around a maximum-size upload.
+const MULTIPART_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// Runtime settings, read from environment variables with sensible defaults.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub database_url: String,
+    /// Encrypts the SQLite database, its snapshots and attachments at rest.
+    pub encryption_key: Option<EncryptionKey>,
+    /// Connection pool and lock waiting for a SQLite `database_url`.
+    pub sqlite_pool: PoolSettings,
+    /// How long a trashed expense is kept before it is purged permanently.
+    pub trash_retention: Duration,
+    /// How often the purge job looks for expired trash.
+    pub trash_purge_interval: Duration,
+    /// How long a stored response is replayed for its `Idempotency-Key`.
+    pub idempotency_retention: Duration,
+    /// Where uploaded receipts and their thumbnails are stored.
+    pub attachments_dir: PathBuf,
+    /// Largest accepted upload, in bytes.
+    pub max_attachment_bytes: usize,
+    /// Where database snapshots and their manifests are stored.
+    pub backup_dir: PathBuf,
+    /// How many snapshots are kept; older ones are removed.
+    pub backup_keep: usize,
+    /// How often a scheduled snapshot is taken; `None` turns them off.
+    pub backup_interval: Option<Duration>,
+    /// Who approves, rejects and pays expense reports: each approver's name
+    /// by the bearer token they authenticate with.
+    pub approvers: HashMap<Credential, String>,
+    /// The bearer token the audit and `/admin` routes require; `None` keeps
+    /// them closed.
+    pub admin_token: Option<Credential>,
+    pub log_format: LogFormat,
+    /// Whether actors, amounts and other personal values are masked in logs.
+    pub log_redact: bool,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self> {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let number = |key: &str, default: u64| -> Result<u64> {
+            match lookup(key) {
+                Some(value) => value
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("{key} must be a non-negative integer")),
+                None => Ok(default),
+            }
+        };
+
+        // A number of `key`'s units converted to smaller ones, `per_unit` each.
+        let scaled = |key: &str, default: u64, per_unit: u64| -> Result<u64> {
+            number(key, default)?
+                .checked_mul(per_unit)
+                .ok_or_else(|| anyhow!("{key} is too large"))
+        };
+
+        let encryption_key = EncryptionKey::from_lookup(&lookup, "ENCRYPTION_KEY")?;
+
+        Ok(Self {
+            database_url: lookup("DATABASE_URL").unwrap_or_else(|| DEFAULT_DATABASE_URL.into()),
+            sqlite_pool: PoolSettings {
+                max_connections: number(
+                    "SQLITE_MAX_CONNECTIONS",
+                    PoolSettings::default().max_connections.into(),
+                )?
+                .clamp(1, u32::MAX.into()) as u32,
+                busy_timeout: Duration::from_millis(number(
+                    "SQLITE_BUSY_TIMEOUT_MS",
+                    PoolSettings::default().busy_timeout.as_millis() as u64,
+                )?),
+                key: encryption_key.clone(),
+            },
+            encryption_key,
+            trash_retention: Duration::from_secs(scaled(
+                "TRASH_RETENTION_DAYS",
+                DEFAULT_TRASH_RETENTION_DAYS,
+                24 * 60 * 60,
+            )?),
+            trash_purge_interval: Duration::from_secs(
+                number(
+                    "TRASH_PURGE_INTERVAL_SECS",
+                    DEFAULT_TRASH_PURGE_INTERVAL_SECS,
+                )?
+                .max(1),
+            ),
+            idempotency_retention: Duration::from_secs(scaled(
+                "IDEMPOTENCY_RETENTION_HOURS",
+                DEFAULT_IDEMPOTENCY_RETENTION_HOURS,
+                60 * 60,
+            )?),
+            attachments_dir: lookup("ATTACHMENTS_DIR")
+                .unwrap_or_else(|| DEFAULT_ATTACHMENTS_DIR.into())
+                .into(),
+            max_attachment_bytes: number("MAX_ATTACHMENT_MB", DEFAULT_MAX_ATTACHMENT_MB)?
+                .max(1)
+                .checked_mul(1024 * 1024)
+                .and_then(|bytes| usize::try_from(bytes).ok())
+                .filter(|bytes| bytes.checked_add(MULTIPART_OVERHEAD_BYTES).is_some())
+                .ok_or_else(|| anyhow!("MAX_ATTACHMENT_MB is too large"))?,
+            backup_dir: lookup("BACKUP_DIR")
+                .unwrap_or_else(|| DEFAULT_BACKUP_DIR.into())
+                .into(),
+            backup_keep: number("BACKUP_KEEP", DEFAULT_BACKUP_KEEP)?.max(1) as usize,
+            backup_interval: match scaled(
+                "BACKUP_INTERVAL_HOURS",
+                DEFAULT_BACKUP_INTERVAL_HOURS,
+                60 * 60,
+            )? {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+            approvers: approver_tokens(&lookup("APPROVER_TOKENS").unwrap_or_default())?,
+            admin_token: lookup("ADMIN_TOKEN")
+                .map(|token| token.trim().to_string())
+                .filter(|token| !token.is_empty())
+                .map(|token| Credential::new(&token)),
+            log_format: match lookup("LOG_FORMAT") {
+                Some(value) => LogFormat::parse(&value)
+                    .ok_or_else(|| anyhow!("LOG_FORMAT must be 'pretty' or 'json'"))?,
+                None => LogFormat::default(),
+            },
+            log_redact: match lookup("LOG_REDACT") {
+                Some(value) => value
+                    .trim()
+                    .parse()
+                    .context("LOG_REDACT must be 'true' or 'false'")?,
+                None => true,
+            },
+        })
+    }
+
+    /// The largest request body the server accepts: an attachment upload of
+    /// `max_attachment_bytes` with its multipart framing.
+    pub fn max_request_bytes(&self) -> usize {
+        self.max_attachment_bytes + MULTIPART_OVERHEAD_BYTES
+    }
+
+    /// `database_url` with its password masked, for logs.
+    pub fn database_url_for_display(&self) -> String {
+        let url = &self.database_url;
+        let Some((scheme, rest)) = url.split_once("://") else {
+            return url.clone();
+        };
+        let authority = &rest[..rest.find('/').unwrap_or(rest.len())];
+        match authority
+            .rsplit_once('@')
+            .and_then(|(credentials, _)| credentials.split_once(':'))
+        {
+            Some((user, _)) => {
+                let host = &rest[authority.rfind('@').map_or(0, |at| at + 1)..];
+                format!("{scheme}://{user}:***@{host}")
+            }
+            None => url.clone(),
+        }
+    }
+}
+
+/// `APPROVER_TOKENS`: comma-separated `name:token` pairs.
+fn approver_tokens(value: &str) -> Result<HashMap<Credential, String>> {
+    let mut approvers = HashMap::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((name, token)) = entry
+            .split_once(':')
+            .map(|(name, token)| (name.trim(), token.trim()))
+            .filter(|(name, token)| !name.is_empty() && !token.is_empty())
+        else {
+            bail!("APPROVER_TOKENS entries must be name:token pairs");
+        };
+        approvers.insert(Credential::new(token), name.to_string());
+    }
+    Ok(approvers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config_from(vars: &[(&str, &str)]) -> Result<Config> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::from_lookup(|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = config_from(&[]).unwrap();
+
+        assert_eq!(config.database_url, DEFAULT_DATABASE_URL);
+        assert_eq!(config.encryption_key, None);
+        assert_eq!(config.sqlite_pool, PoolSettings::default());
+        assert_eq!(
+            config.trash_retention,
+            Duration::from_secs(30 * 24 * 60 * 60)
+        );
+        assert_eq!(config.trash_purge_interval, Duration::from_secs(3600));
+        assert_eq!(
+            config.idempotency_retention,
+            Duration::from_secs(24 * 60 * 60)
+        );
+        assert_eq!(
+            config.attachments_dir,
+            PathBuf::from(DEFAULT_ATTACHMENTS_DIR)
+        );
+        assert_eq!(config.max_attachment_bytes, 10 * 1024 * 1024);
+        assert_eq!(config.backup_dir, PathBuf::from(DEFAULT_BACKUP_DIR));
+        assert_eq!(config.backup_keep, 7);
+        assert_eq!(
+            config.backup_interval,
+            Some(Duration::from_secs(24 * 60 * 60))
+        );
+        assert!(config.approvers.is_empty());
+        assert_eq!(config.admin_token, None);
+        assert_eq!(config.log_format, LogFormat::Pretty);
+        assert!(config.log_redact);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let config = config_from(&[
+            ("DATABASE_URL", "sqlite::memory:"),
+            ("ENCRYPTION_KEY", &"ab".repeat(32)),
+            ("SQLITE_MAX_CONNECTIONS", "2"),
+            ("SQLITE_BUSY_TIMEOUT_MS", "250"),
+            ("TRASH_RETENTION_DAYS", "7"),
+            ("TRASH_PURGE_INTERVAL_SECS", "60"),
+            ("IDEMPOTENCY_RETENTION_HOURS", "2"),
+            ("ATTACHMENTS_DIR", "/var/lib/receipts"),
+            ("MAX_ATTACHMENT_MB", "1"),
+            ("BACKUP_DIR", "/var/backups/expenses"),
+            ("BACKUP_KEEP", "30"),
+            ("BACKUP_INTERVAL_HOURS", "0"),
+            ("APPROVER_TOKENS", "bob:b0b-token, carol:c4rol-token,,"),
+            ("ADMIN_TOKEN", " 4dm1n-token "),
+            ("LOG_FORMAT", "json"),
+            ("LOG_REDACT", "false"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.database_url, "sqlite::memory:");
+        let key = EncryptionKey::parse(&"ab".repeat(32)).unwrap();
+        assert_eq!(config.encryption_key, Some(key.clone()));
+        assert_eq!(config.sqlite_pool.key, Some(key));
+        assert_eq!(config.sqlite_pool.max_connections, 2);
+        assert_eq!(config.sqlite_pool.busy_timeout, Duration::from_millis(250));
+        assert_eq!(
+            config.trash_retention,
+            Duration::from_secs(7 * 24 * 60 * 60)
+        );
+        assert_eq!(config.trash_purge_interval, Duration::from_secs(60));
+        assert_eq!(
+            config.idempotency_retention,
+            Duration::from_secs(2 * 60 * 60)
+        );
+        assert_eq!(config.attachments_dir, PathBuf::from("/var/lib/receipts"));
+        assert_eq!(config.max_attachment_bytes, 1024 * 1024);
+        assert_eq!(config.backup_dir, PathBuf::from("/var/backups/expenses"));
+        assert_eq!(config.backup_keep, 30);
+        assert_eq!(config.backup_interval, None);
+        assert_eq!(
+            config.approvers,
+            HashMap::from([
+                (Credential::new("b0b-token"), "bob".to_string()),
+                (Credential::new("c4rol-token"), "carol".to_string()),
+            ])
+        );
+        assert_eq!(config.admin_token, Some(Credential::new("4dm1n-token")));
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert!(!config.log_redact);
+    }
+
+    #[test]
+    fn test_invalid_number() {
+        let result = config_from(&[("TRASH_RETENTION_DAYS", "a week")]);
+
+        assert!(result.is_err());
+        assert!(config_from(&[("LOG_FORMAT", "xml")]).is_err());
+        assert!(config_from(&[("APPROVER_TOKENS", "bob")]).is_err());
+    }
+
+    #[test]
+    fn test_too_large_number() {
+        let too_large = |key: &str| {
+            let error = config_from(&[(key, &u64::MAX.to_string())]).unwrap_err();
+            assert_eq!(error.to_string(), format!("{key} is too large"));
+        };
+
+        too_large("TRASH_RETENTION_DAYS");
+        too_large("IDEMPOTENCY_RETENTION_HOURS");
+        too_large("MAX_ATTACHMENT_MB");
+        too_large("BACKUP_INTERVAL_HOURS");
+    }
+
+    #[test]
+    fn test_database_url_password_is_masked() {
+        let shown = |url: &str| {
+            config_from(&[("DATABASE_URL", url)])
+                .unwrap()
+                .database_url_for_display()
+        };
+
+        assert_eq!(
+            shown("postgres://app:s3cr@t@db:5432/expenses"),
+            "postgres://app:***@db:5432/expenses"
+        );
+        assert_eq!(
+            shown("postgres://app@db/expenses"),
+            "postgres://app@db/expenses"
+        );
+        assert_eq!(shown("sqlite:./expenses.db"), "sqlite:./expenses.db");
+    }
+}
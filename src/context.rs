@@ -0,0 +1,131 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{HeaderMap, header, request::Parts},
+};
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::fmt;
+use uuid::Uuid;
+
+pub const ACTOR_HEADER: &str = "x-actor";
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const ANONYMOUS_ACTOR: &str = "anonymous";
+const MAX_HEADER_VALUE_LEN: usize = 100;
+
+/// A bearer token from the `Authorization` header, which the server checks
+/// against tokens in its configuration. Only its SHA-256 digest is kept, so
+/// the token never shows up in `Debug` output or logs.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Credential([u8; 32]);
+
+impl Credential {
+    pub fn new(token: &str) -> Self {
+        Self(Sha256::digest(token.as_bytes()).into())
+    }
+
+    /// The `Bearer` token in `headers`, if there is one.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+        let (scheme, token) = value.trim().split_once(' ')?;
+        let token = token.trim();
+        (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then(|| Self::new(token))
+    }
+}
+
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Credential([redacted])")
+    }
+}
+
+/// Who is making a request and how to correlate it, recorded with every mutation.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    /// Who the client says it is (`X-Actor`); not verified.
+    pub actor: String,
+    pub request_id: String,
+    /// The bearer token the request carries, if any. Rights the server
+    /// grants, such as approving reports, are tied to it, not to `actor`.
+    pub credential: Option<Credential>,
+}
+
+impl RequestContext {
+    pub fn new(actor: impl Into<String>, request_id: impl Into<String>) -> Self {
+        Self {
+            actor: actor.into(),
+            request_id: request_id.into(),
+            credential: None,
+        }
+    }
+
+    pub fn with_credential(self, credential: Credential) -> Self {
+        Self {
+            credential: Some(credential),
+            ..self
+        }
+    }
+
+    /// Context for work the server does on its own, such as background jobs.
+    pub fn system() -> Self {
+        Self::new("system", Uuid::new_v4().to_string())
+    }
+}
+
+/// A trimmed, non-empty header value of reasonable length.
+pub(crate) fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty() && value.len() <= MAX_HEADER_VALUE_LEN)
+        .map(str::to_string)
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for RequestContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let actor =
+            header_value(&parts.headers, ACTOR_HEADER).unwrap_or_else(|| ANONYMOUS_ACTOR.into());
+        let request_id = header_value(&parts.headers, REQUEST_ID_HEADER)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        let ctx = Self::new(actor, request_id);
+        Ok(match Credential::from_headers(&parts.headers) {
+            Some(credential) => ctx.with_credential(credential),
+            None => ctx,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_credential_from_bearer_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Credential::from_headers(&headers), None);
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer s3cret"),
+        );
+        assert_eq!(
+            Credential::from_headers(&headers),
+            Some(Credential::new("s3cret"))
+        );
+        assert_eq!(
+            format!("{:?}", Credential::new("s3cret")),
+            "Credential([redacted])"
+        );
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Basic s3cret"),
+        );
+        assert_eq!(Credential::from_headers(&headers), None);
+    }
+}
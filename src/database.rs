@@ -1,24 +1,312 @@
-use anyhow::Result;
-use sqlx::SqlitePool;
+use crate::encryption::{self, EncryptionKey};
+use crate::repository::postgres;
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::{Executor, PgPool, SqlitePool};
+use std::str::FromStr;
+use std::time::Duration;
 
+/// How connections to the SQLite database are opened and pooled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolSettings {
+    /// Readers run concurrently in WAL mode; writers still take turns.
+    pub max_connections: u32,
+    /// How long a write waits for another writer before failing with
+    /// "database is locked".
+    pub busy_timeout: Duration,
+    /// SQLCipher key the database is encrypted with; `None` for a plain
+    /// database.
+    pub key: Option<EncryptionKey>,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            max_connections: 8,
+            busy_timeout: Duration::from_secs(5),
+            key: None,
+        }
+    }
+}
+
+/// A versioned schema change, applied once and recorded in `schema_migrations`.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_expenses",
+        sql: include_str!("../migrations/001_create_expenses.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "create_audit_log",
+        sql: include_str!("../migrations/002_create_audit_log.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "add_expense_soft_delete",
+        sql: include_str!("../migrations/003_add_expense_soft_delete.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "add_expense_version",
+        sql: include_str!("../migrations/004_add_expense_version.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "create_idempotency_keys",
+        sql: include_str!("../migrations/005_create_idempotency_keys.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "add_expense_search",
+        sql: include_str!("../migrations/006_add_expense_search.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "create_payees",
+        sql: include_str!("../migrations/007_create_payees.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "create_rules",
+        sql: include_str!("../migrations/008_create_rules.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "create_tags",
+        sql: include_str!("../migrations/009_create_tags.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "create_attachments",
+        sql: include_str!("../migrations/010_create_attachments.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "create_accounts",
+        sql: include_str!("../migrations/011_create_accounts.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "create_income",
+        sql: include_str!("../migrations/012_create_income.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "create_refunds",
+        sql: include_str!("../migrations/013_create_refunds.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "create_expense_reports",
+        sql: include_str!("../migrations/014_create_expense_reports.sql"),
+    },
+    Migration {
+        version: 15,
+        name: "add_expense_indexes",
+        sql: include_str!("../migrations/015_add_expense_indexes.sql"),
+    },
+    Migration {
+        version: 16,
+        name: "key_expense_search_by_rowid",
+        sql: include_str!("../migrations/016_key_expense_search_by_rowid.sql"),
+    },
+    Migration {
+        version: 17,
+        name: "add_payee_alias_generation",
+        sql: include_str!("../migrations/017_add_payee_alias_generation.sql"),
+    },
+    Migration {
+        version: 18,
+        name: "normalize_expense_dates",
+        sql: include_str!("../migrations/018_normalize_expense_dates.sql"),
+    },
+    Migration {
+        version: 19,
+        name: "scope_idempotency_keys_by_actor",
+        sql: include_str!("../migrations/019_scope_idempotency_keys_by_actor.sql"),
+    },
+];
+
+/// The newest schema version this build knows about.
+pub fn latest_migration_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// The newest schema version applied to the database, if any.
+pub async fn applied_migration_version(pool: &SqlitePool) -> Result<Option<i64>> {
+    let version = sqlx::query_scalar("SELECT MAX(version) FROM schema_migrations")
+        .fetch_one(pool)
+        .await?;
+    Ok(version)
+}
+
+/// The pool of whichever database the server runs on, for the services
+/// every backend shares: readiness, metrics and idempotency keys.
+#[derive(Clone)]
+pub enum DatabasePool {
+    Sqlite(SqlitePool),
+    Postgres(PgPool),
+}
+
+impl From<SqlitePool> for DatabasePool {
+    fn from(pool: SqlitePool) -> Self {
+        DatabasePool::Sqlite(pool)
+    }
+}
+
+impl From<PgPool> for DatabasePool {
+    fn from(pool: PgPool) -> Self {
+        DatabasePool::Postgres(pool)
+    }
+}
+
+impl DatabasePool {
+    /// Runs a trivial query on a pooled connection.
+    pub async fn ping(&self) -> Result<()> {
+        match self {
+            DatabasePool::Sqlite(pool) => {
+                sqlx::query("SELECT 1").execute(pool).await?;
+            }
+            DatabasePool::Postgres(pool) => {
+                sqlx::query("SELECT 1").execute(pool).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The newest schema version this build knows about for the backend.
+    pub fn latest_migration_version(&self) -> i64 {
+        match self {
+            DatabasePool::Sqlite(_) => latest_migration_version(),
+            DatabasePool::Postgres(_) => postgres::MIGRATIONS.last().map_or(0, |m| m.version),
+        }
+    }
+
+    /// The newest schema version applied to the database, if any.
+    pub async fn applied_migration_version(&self) -> Result<Option<i64>> {
+        match self {
+            DatabasePool::Sqlite(pool) => applied_migration_version(pool).await,
+            DatabasePool::Postgres(pool) => {
+                let version = sqlx::query_scalar("SELECT MAX(version) FROM schema_migrations")
+                    .fetch_one(pool)
+                    .await?;
+                Ok(version)
+            }
+        }
+    }
+
+    /// Idle connections, connections in use and the most the pool opens.
+    pub fn connections(&self) -> (usize, usize, u32) {
+        let (idle, size, max) = match self {
+            DatabasePool::Sqlite(pool) => (
+                pool.num_idle(),
+                pool.size(),
+                pool.options().get_max_connections(),
+            ),
+            DatabasePool::Postgres(pool) => (
+                pool.num_idle(),
+                pool.size(),
+                pool.options().get_max_connections(),
+            ),
+        };
+        (idle, (size as usize).saturating_sub(idle), max)
+    }
+}
+
+/// [`create_pool_with`] the default settings, as tests open their databases.
+#[cfg(test)]
 pub async fn create_pool(database_url: &str) -> Result<SqlitePool> {
-    let pool = SqlitePool::connect(database_url).await?;
+    create_pool_with(database_url, PoolSettings::default()).await
+}
 
+/// Opens the database in WAL mode, so readers never wait for a writer, with
+/// foreign keys enforced on every connection, and brings the schema up to
+/// date. `synchronous = NORMAL` is durable across application crashes in
+/// WAL mode; only a power loss can undo the last commits.
+pub async fn create_pool_with(database_url: &str, settings: PoolSettings) -> Result<SqlitePool> {
+    let mut options = SqliteConnectOptions::from_str(database_url)?;
+    // SQLCipher needs the key before anything reads the file.
+    if let Some(key) = &settings.key {
+        options = options.pragma("key", key.sqlcipher_pragma());
+    }
+    let options = options
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(settings.busy_timeout)
+        .foreign_keys(true)
+        .optimize_on_close(true, None);
+    let pool = SqlitePoolOptions::new()
+        .max_connections(settings.max_connections)
+        .connect_with(options)
+        .await
+        .map_err(|e| {
+            if encryption::is_wrong_key(&e) {
+                anyhow!(
+                    "{e}: ENCRYPTION_KEY is not the key the database is encrypted with; \
+                     a plain database is encrypted with `expence_tracker encryption rotate`"
+                )
+            } else {
+                e.into()
+            }
+        })?;
+
+    run_migrations(&pool).await?;
+
+    tracing::info!("Database ready");
+    Ok(pool)
+}
+
+async fn run_migrations(pool: &SqlitePool) -> Result<()> {
     sqlx::query(
         r#"
-        CREATE TABLE IF NOT EXISTS expenses (
-            id TEXT PRIMARY KEY,
-            amount REAL NOT NULL,
-            category TEXT NOT NULL,
-            date TEXT NOT NULL
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TEXT NOT NULL
         )
         "#,
     )
-    .execute(&pool)
+    .execute(pool)
     .await?;
 
-    println!("Database created successfully");
-    Ok(pool)
+    for migration in MIGRATIONS {
+        let applied = sqlx::query("SELECT 1 FROM schema_migrations WHERE version = ?")
+            .bind(migration.version)
+            .fetch_optional(pool)
+            .await?
+            .is_some();
+        if applied {
+            continue;
+        }
+
+        let mut tx = pool.begin().await?;
+        // Through `Executor`, whose boxed future is `Send`, so the schema can
+        // also be brought up to date from a request, as a restore does.
+        tx.execute(sqlx::raw_sql(migration.sql)).await?;
+        sqlx::query("INSERT INTO schema_migrations (version, name, applied_at) VALUES (?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        tracing::info!(
+            version = migration.version,
+            name = migration.name,
+            "Applied migration"
+        );
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -39,6 +327,21 @@ mod tests {
         assert_eq!(test_value, 1);
     }
 
+    #[tokio::test]
+    async fn test_all_migrations_are_applied() {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+
+        assert_eq!(
+            applied_migration_version(&pool).await.unwrap(),
+            Some(latest_migration_version())
+        );
+        assert!(
+            MIGRATIONS
+                .windows(2)
+                .all(|pair| pair[0].version < pair[1].version)
+        );
+    }
+
     #[tokio::test]
     async fn test_table_creation() {
         let pool = create_pool("sqlite::memory:").await.unwrap();
@@ -80,4 +383,205 @@ mod tests {
         assert_eq!(result.get::<f64, _>("amount"), test_amount);
         assert_eq!(result.get::<String, _>("category"), test_category);
     }
+
+    #[tokio::test]
+    async fn test_migrations_recorded() {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+
+        let versions: Vec<i64> =
+            sqlx::query_scalar("SELECT version FROM schema_migrations ORDER BY version")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+
+        let expected: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert_eq!(versions, expected);
+    }
+
+    #[tokio::test]
+    async fn test_migrations_are_idempotent() {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+
+        run_migrations(&pool).await.unwrap();
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM schema_migrations")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, MIGRATIONS.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn test_connections_are_tuned() {
+        let dir = std::env::temp_dir().join(format!("database-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let url = format!("sqlite:{}?mode=rwc", dir.join("expenses.db").display());
+        let settings = PoolSettings {
+            max_connections: 2,
+            busy_timeout: Duration::from_millis(1500),
+            ..Default::default()
+        };
+        let pool = create_pool_with(&url, settings).await.unwrap();
+
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let synchronous: i64 = sqlx::query_scalar("PRAGMA synchronous")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let busy_timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        assert_eq!(synchronous, 1, "NORMAL");
+        assert_eq!(busy_timeout, 1500);
+        assert_eq!(pool.options().get_max_connections(), 2);
+
+        pool.close().await;
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_database_needs_its_key() {
+        let dir = std::env::temp_dir().join(format!("database-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("expenses.db");
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+        let keyed = |hex: &str| PoolSettings {
+            key: Some(EncryptionKey::parse(&hex.repeat(32)).unwrap()),
+            ..Default::default()
+        };
+
+        let pool = create_pool_with(&url, keyed("01")).await.unwrap();
+        sqlx::query("INSERT INTO expenses (id, amount, category, date) VALUES (?, ?, ?, ?)")
+            .bind("lunch")
+            .bind(12.5)
+            .bind("Food")
+            .bind("2025-01-01T00:00:00Z")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let content = std::fs::read(&path).unwrap();
+        assert!(!content.starts_with(b"SQLite format 3"));
+        for settings in [PoolSettings::default(), keyed("02")] {
+            let error = create_pool_with(&url, settings).await.unwrap_err();
+            assert!(error.to_string().contains("ENCRYPTION_KEY"));
+        }
+        let pool = create_pool_with(&url, keyed("01")).await.unwrap();
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM expenses")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        pool.close().await;
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_foreign_keys_are_enforced() {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+
+        let result = sqlx::query("INSERT INTO expense_tags (expense_id, tag_id) VALUES (?, ?)")
+            .bind("no-such-expense")
+            .bind("no-such-tag")
+            .execute(&pool)
+            .await;
+
+        assert!(result.unwrap_err().to_string().contains("FOREIGN KEY"));
+    }
+
+    #[tokio::test]
+    async fn test_listing_queries_read_indexes_in_order() {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+
+        for (query, index) in [
+            (
+                "SELECT id FROM expenses WHERE deleted_at IS NULL ORDER BY date DESC",
+                "idx_expenses_deleted_at_date",
+            ),
+            (
+                "SELECT id FROM expenses WHERE deleted_at IS NULL AND date >= ? AND date < ? \
+                 ORDER BY date DESC",
+                "idx_expenses_deleted_at_date (deleted_at=? AND date>? AND date<?)",
+            ),
+            (
+                "SELECT id FROM expenses WHERE deleted_at IS NULL ORDER BY amount DESC LIMIT 1",
+                "idx_expenses_deleted_at_amount",
+            ),
+            (
+                "SELECT category, COUNT(*) FROM expenses WHERE deleted_at IS NULL GROUP BY category",
+                "idx_expenses_deleted_at_category",
+            ),
+        ] {
+            let plan: Vec<String> = sqlx::query(&format!("EXPLAIN QUERY PLAN {query}"))
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+                .iter()
+                .map(|row| row.get("detail"))
+                .collect();
+
+            assert!(
+                plan.iter().any(|step| step.contains(index)),
+                "{query}: {plan:?}"
+            );
+            assert!(
+                !plan.iter().any(|step| step.contains("TEMP B-TREE")),
+                "{query}: {plan:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dates_are_stored_in_one_format() {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+
+        for (id, date) in [
+            ("space", "2024-03-01 10:00:00"),
+            ("zulu", "2024-03-01T10:00:00.250Z"),
+            ("offset", "2024-03-01T12:00:00+02:00"),
+        ] {
+            sqlx::query(
+                "INSERT INTO expenses (id, amount, category, date) VALUES (?, 1.0, 'Food', ?)",
+            )
+            .bind(id)
+            .bind(date)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query("UPDATE expenses SET date = '2024-03-02 08:30:00' WHERE id = 'offset'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let dates: Vec<String> = sqlx::query_scalar("SELECT date FROM expenses ORDER BY date")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            dates,
+            vec![
+                "2024-03-01T10:00:00+00:00",
+                "2024-03-01T10:00:00.250+00:00",
+                "2024-03-02T08:30:00+00:00",
+            ]
+        );
+
+        let from = "2024-03-01T10:00:00.100Z"
+            .parse::<chrono::DateTime<Utc>>()
+            .unwrap();
+        let matching: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM expenses WHERE date >= ?")
+            .bind(from)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(matching, 2);
+    }
 }
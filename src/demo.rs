@@ -0,0 +1,195 @@
+//! Generated expenses for `--demo`, so the API and frontend can be tried
+//! without a database.
+
+use crate::models::expense::Expense;
+use chrono::{DateTime, Duration, Utc};
+
+/// How many expenses `--demo` starts with.
+pub const DEMO_EXPENSES: usize = 250;
+/// Fixed so every demo shows the same data, relative to when it starts.
+pub const DEMO_SEED: u64 = 0x5eed_e7ae_4ce5_2024;
+
+const HISTORY_DAYS: i64 = 180;
+const MONTHLY_RENT: f64 = 1250.0;
+
+struct Kind {
+    category: &'static str,
+    payees: &'static [&'static str],
+    /// Amount range in whole currency units.
+    amounts: (u64, u64),
+    tags: &'static [&'static str],
+    /// Relative frequency among the kinds.
+    weight: u64,
+}
+
+const KINDS: &[Kind] = &[
+    Kind {
+        category: "Food",
+        payees: &["Green Grocer", "Corner Bakery", "Sushi Bar", "Pizza Place"],
+        amounts: (4, 60),
+        tags: &["groceries", "lunch", "dinner"],
+        weight: 40,
+    },
+    Kind {
+        category: "Transport",
+        payees: &["City Transit", "Rail Co", "Taxi Co", "Fuel Stop"],
+        amounts: (2, 80),
+        tags: &["commute", "work"],
+        weight: 20,
+    },
+    Kind {
+        category: "Utilities",
+        payees: &["Power Co", "Water Works", "Fiber Net"],
+        amounts: (30, 150),
+        tags: &["home"],
+        weight: 6,
+    },
+    Kind {
+        category: "Entertainment",
+        payees: &["Cinema", "Streaming Plus", "Concert Hall"],
+        amounts: (8, 90),
+        tags: &["weekend"],
+        weight: 12,
+    },
+    Kind {
+        category: "Health",
+        payees: &["Pharmacy", "Dental Clinic", "City Gym"],
+        amounts: (10, 120),
+        tags: &[],
+        weight: 8,
+    },
+    Kind {
+        category: "Shopping",
+        payees: &["Bookshop", "Hardware Store", "Clothing Co"],
+        amounts: (10, 200),
+        tags: &["home", "gift"],
+        weight: 10,
+    },
+    Kind {
+        category: "Travel",
+        payees: &["Airline", "Harbor Hotel"],
+        amounts: (80, 600),
+        tags: &["trip", "work"],
+        weight: 4,
+    },
+];
+
+const NOTES: &[&str] = &[
+    "Split with a friend",
+    "Paid by card",
+    "Receipt in the drawer",
+    "Monthly subscription",
+];
+
+/// `count` expenses from the last six months plus the rent of each month,
+/// newest last. A few are in the trash. The same `seed` and `now` give the
+/// same expenses.
+pub fn generate(count: usize, seed: u64, now: DateTime<Utc>) -> Vec<Expense> {
+    let mut rng = SplitMix64(seed);
+    let total_weight: u64 = KINDS.iter().map(|kind| kind.weight).sum();
+    let mut expenses = Vec::with_capacity(count + 6);
+
+    for _ in 0..count {
+        let mut pick = rng.below(total_weight);
+        let kind = KINDS
+            .iter()
+            .find(|kind| {
+                let found = pick < kind.weight;
+                pick = pick.saturating_sub(kind.weight);
+                found
+            })
+            .expect("weights cover the range");
+
+        let (low, high) = kind.amounts;
+        let cents = rng.below((high - low) * 100) + low * 100;
+        let mut expense = Expense::new(cents as f64 / 100.0, kind.category.to_string());
+        expense.date = now - Duration::minutes(rng.below(HISTORY_DAYS as u64 * 24 * 60) as i64);
+        expense.payee = Some(rng.choose(kind.payees).to_string());
+        if !kind.tags.is_empty() && rng.below(3) > 0 {
+            let tag = rng.choose(kind.tags).to_string();
+            expense.reimbursable = tag == "work";
+            expense.tags.push(tag);
+        }
+        if rng.below(6) == 0 {
+            expense.notes = Some(rng.choose(NOTES).to_string());
+        }
+        expenses.push(expense);
+    }
+
+    for month in 0..HISTORY_DAYS / 30 {
+        let mut rent = Expense::new(MONTHLY_RENT, "Housing".to_string());
+        rent.date = now - Duration::days(month * 30 + 1);
+        rent.payee = Some("Landlord Ltd".to_string());
+        rent.tags.push("home".to_string());
+        expenses.push(rent);
+    }
+
+    expenses.sort_by_key(|expense| expense.date);
+    for _ in 0..3 {
+        let index = rng.below(expenses.len() as u64) as usize;
+        let expense = &mut expenses[index];
+        if expense.deleted_at.is_none() {
+            expense.deleted_at = Some(now - Duration::hours(rng.below(72) as i64 + 1));
+            expense.version += 1;
+        }
+    }
+    for expense in &mut expenses {
+        expense.tags.sort_by_key(|tag| tag.to_lowercase());
+    }
+
+    expenses
+}
+
+/// A small, fast generator; demo data needs variety, not security.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound.max(1)
+    }
+
+    fn choose<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_expenses_are_plausible_and_repeatable() {
+        let now = Utc::now();
+        let expenses = generate(DEMO_EXPENSES, DEMO_SEED, now);
+
+        assert_eq!(expenses.len(), DEMO_EXPENSES + 6);
+        assert!(expenses.windows(2).all(|pair| pair[0].date <= pair[1].date));
+        assert!(expenses.iter().all(|expense| {
+            expense.amount > 0.0
+                && expense.date <= now
+                && expense.date > now - Duration::days(HISTORY_DAYS)
+                && (expense.amount * 100.0).round() / 100.0 == expense.amount
+        }));
+        let trashed = expenses
+            .iter()
+            .filter(|expense| expense.deleted_at.is_some())
+            .count();
+        assert!((1..=3).contains(&trashed));
+        assert!(expenses.iter().any(|expense| expense.reimbursable));
+
+        let again = generate(DEMO_EXPENSES, DEMO_SEED, now);
+        let amounts = |expenses: &[Expense]| -> Vec<f64> {
+            expenses.iter().map(|expense| expense.amount).collect()
+        };
+        assert_eq!(amounts(&again), amounts(&expenses));
+    }
+}
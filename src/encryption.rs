@@ -0,0 +1,560 @@
+//! Encryption at rest with one key, `ENCRYPTION_KEY`. The SQLite database and
+//! its snapshots are SQLCipher files keyed with it directly; attachments are
+//! sealed with AES-256-GCM under a key derived from it.
+
+use anyhow::{Context, Result, anyhow, bail};
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::hkdf;
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{Connection, SqliteConnection};
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+const KEY_LEN: usize = 32;
+const FINGERPRINT_LEN: usize = 8;
+/// Starts every sealed file; the key fingerprint and the nonce follow.
+const SEALED_MAGIC: &[u8; 8] = b"ETSEAL1\n";
+const SEALED_HEADER_LEN: usize = SEALED_MAGIC.len() + FINGERPRINT_LEN + NONCE_LEN;
+/// SQLite's result code for a file that does not read as a database, which
+/// is what a wrong or missing key looks like.
+const SQLITE_NOTADB: &str = "26";
+/// SQLite's primary result code for a lock another connection holds.
+const SQLITE_BUSY: i32 = 5;
+
+/// A 256-bit key, written as 64 hex digits. `Debug` shows its fingerprint
+/// only, so a key in a logged `Config` stays secret.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; KEY_LEN]);
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EncryptionKey({})", self.fingerprint())
+    }
+}
+
+impl EncryptionKey {
+    pub fn generate() -> Result<Self> {
+        let mut bytes = [0; KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| anyhow!("no secure random source"))?;
+        Ok(Self(bytes))
+    }
+
+    pub fn parse(hex_key: &str) -> Result<Self> {
+        hex::decode(hex_key.trim())
+            .ok()
+            .and_then(|bytes| <[u8; KEY_LEN]>::try_from(bytes).ok())
+            .map(Self)
+            .ok_or_else(|| anyhow!("an encryption key is {} hex digits", KEY_LEN * 2))
+    }
+
+    /// The key in `var`, or in the file named by `<var>_FILE`; `None` when
+    /// neither is set.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>, var: &str) -> Result<Option<Self>> {
+        let file_var = format!("{var}_FILE");
+        match (lookup(var), lookup(&file_var)) {
+            (Some(_), Some(_)) => bail!("set {var} or {file_var}, not both"),
+            (Some(hex_key), None) => Self::parse(&hex_key)
+                .with_context(|| format!("{var} is invalid"))
+                .map(Some),
+            (None, Some(path)) => {
+                let content = std::fs::read_to_string(&path)
+                    .with_context(|| format!("reading {file_var} {path}"))?;
+                Self::parse(&content)
+                    .with_context(|| format!("{file_var} {path} is invalid"))
+                    .map(Some)
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    /// Identifies the key without revealing it, in backup manifests, sealed
+    /// files and logs.
+    pub fn fingerprint(&self) -> String {
+        hex::encode(self.fingerprint_bytes())
+    }
+
+    fn fingerprint_bytes(&self) -> [u8; FINGERPRINT_LEN] {
+        let digest = Sha256::new()
+            .chain_update(b"expence_tracker key fingerprint")
+            .chain_update(self.0)
+            .finalize();
+        let mut fingerprint = [0; FINGERPRINT_LEN];
+        fingerprint.copy_from_slice(&digest[..FINGERPRINT_LEN]);
+        fingerprint
+    }
+
+    /// The key in SQLCipher's raw key syntax, which skips the passphrase
+    /// derivation; quoted, as the `key` pragma takes it.
+    pub fn sqlcipher_pragma(&self) -> String {
+        format!("\"{}\"", self.sqlcipher_raw())
+    }
+
+    /// The key in SQLCipher's raw key syntax, as `ATTACH ... KEY ?` takes it.
+    fn sqlcipher_raw(&self) -> String {
+        format!("x'{}'", self.to_hex())
+    }
+}
+
+/// The `KEY` of `ATTACH ... KEY ?` for a file encrypted with `key`, or for a
+/// plain file when `key` is `None`.
+pub fn attach_key(key: Option<&EncryptionKey>) -> String {
+    key.map_or_else(String::new, EncryptionKey::sqlcipher_raw)
+}
+
+/// A key by its fingerprint, or "no key", for messages.
+pub fn describe_key(fingerprint: Option<&str>) -> String {
+    fingerprint.map_or_else(
+        || "no key".to_string(),
+        |fingerprint| format!("key {fingerprint}"),
+    )
+}
+
+/// Whether SQLite failed to read a file as a database, as it does when the
+/// file is encrypted with another key or not encrypted as expected.
+pub fn is_wrong_key(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .and_then(|e| e.code())
+        .is_some_and(|code| code == SQLITE_NOTADB)
+}
+
+/// Seals files with AES-256-GCM under a key derived from an
+/// [`EncryptionKey`]. A sealed file is [`SEALED_MAGIC`], the key's
+/// fingerprint, a random nonce and the ciphertext with its tag. The caller's
+/// `context`, such as the file's name, is authenticated along with it, so one
+/// sealed file cannot be passed off as another.
+#[derive(Clone)]
+pub struct FileCipher {
+    key: Arc<LessSafeKey>,
+    fingerprint: [u8; FINGERPRINT_LEN],
+}
+
+impl fmt::Debug for FileCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FileCipher({})", hex::encode(self.fingerprint))
+    }
+}
+
+impl FileCipher {
+    pub fn new(key: &EncryptionKey) -> Self {
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, b"expence_tracker").extract(&key.0);
+        let okm = prk
+            .expand(&[b"files"], &AES_256_GCM)
+            .expect("an AES-256 key is a valid HKDF-SHA256 output length");
+        Self {
+            key: Arc::new(LessSafeKey::new(UnboundKey::from(okm))),
+            fingerprint: key.fingerprint_bytes(),
+        }
+    }
+
+    pub fn seal(&self, context: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("no secure random source"))?;
+
+        let mut sealed =
+            Vec::with_capacity(SEALED_HEADER_LEN + plaintext.len() + AES_256_GCM.tag_len());
+        sealed.extend_from_slice(SEALED_MAGIC);
+        sealed.extend_from_slice(&self.fingerprint);
+        sealed.extend_from_slice(&nonce);
+        let mut body = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(context.as_bytes()),
+                &mut body,
+            )
+            .map_err(|_| anyhow!("sealing {context} failed"))?;
+        sealed.extend_from_slice(&body);
+        Ok(sealed)
+    }
+
+    /// Whether `content` is sealed under this cipher's key.
+    pub fn sealed_by_this_key(&self, content: &[u8]) -> bool {
+        sealed_fingerprint(content) == Some(&self.fingerprint[..])
+    }
+
+    pub fn open(&self, context: &str, content: &[u8]) -> Result<Vec<u8>> {
+        match sealed_fingerprint(content) {
+            None => bail!("{context} is not sealed"),
+            Some(fingerprint) if fingerprint != self.fingerprint => bail!(
+                "{context} is sealed with key {}, not key {}",
+                hex::encode(fingerprint),
+                hex::encode(self.fingerprint)
+            ),
+            Some(_) => {}
+        }
+        let nonce = Nonce::try_assume_unique_for_key(
+            &content[SEALED_MAGIC.len() + FINGERPRINT_LEN..SEALED_HEADER_LEN],
+        )
+        .map_err(|_| anyhow!("{context} has a malformed nonce"))?;
+        let mut body = content[SEALED_HEADER_LEN..].to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::from(context.as_bytes()), &mut body)
+            .map_err(|_| anyhow!("{context} failed authentication"))?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+/// The fingerprint of the key `content` is sealed with; `None` when it is
+/// not sealed.
+pub fn sealed_fingerprint(content: &[u8]) -> Option<&[u8]> {
+    (content.len() >= SEALED_HEADER_LEN + AES_256_GCM.tag_len()
+        && content.starts_with(SEALED_MAGIC))
+    .then(|| &content[SEALED_MAGIC.len()..SEALED_MAGIC.len() + FINGERPRINT_LEN])
+}
+
+fn connect_options(path: &Path, key: Option<&EncryptionKey>) -> SqliteConnectOptions {
+    let options = SqliteConnectOptions::new().filename(path);
+    match key {
+        Some(key) => options.pragma("key", key.sqlcipher_pragma()),
+        None => options,
+    }
+}
+
+/// Opens the SQLite file at `path` with `key`, or as a plain database.
+pub async fn open_database(
+    path: &Path,
+    key: Option<&EncryptionKey>,
+    read_only: bool,
+) -> Result<SqliteConnection> {
+    let options = connect_options(path, key).read_only(read_only);
+    let mut conn = SqliteConnection::connect_with(&options).await?;
+    // SQLCipher only reads the file, and finds the key wrong, on first use.
+    sqlx::query("SELECT COUNT(*) FROM sqlite_master")
+        .execute(&mut conn)
+        .await?;
+    Ok(conn)
+}
+
+/// Whether the SQLite file at `path` opens with `key`, or as a plain
+/// database when `key` is `None`.
+pub async fn opens_with(path: &Path, key: Option<&EncryptionKey>) -> Result<bool> {
+    match open_database(path, key, true).await {
+        Ok(conn) => {
+            conn.close().await?;
+            Ok(true)
+        }
+        Err(e) if e.downcast_ref::<sqlx::Error>().is_some_and(is_wrong_key) => Ok(false),
+        Err(e) => Err(e.context(format!("opening {}", path.display()))),
+    }
+}
+
+/// Rewrites the SQLite file at `path` encrypted with `new`, from `old` or
+/// from a plain database when `old` is `None`. A file that already opens
+/// with `new` is left alone, so an interrupted rotation can be run again;
+/// returns whether the file was rewritten. The copy is written next to the
+/// file and renamed over it, so the file is always wholly under one key.
+/// The file is locked exclusively while it is copied, and the rotation fails
+/// without changing anything when another connection, such as a running
+/// server, has it open.
+pub async fn rekey_database(
+    path: &Path,
+    old: Option<&EncryptionKey>,
+    new: &EncryptionKey,
+) -> Result<bool> {
+    if !tokio::fs::try_exists(path).await? || opens_with(path, Some(new)).await? {
+        return Ok(false);
+    }
+
+    // Created empty here, as the connection is not allowed to create files.
+    let copy = sibling(path, "rekey");
+    tokio::fs::File::create(&copy).await?;
+    let exported = match lock_database(path, old).await {
+        Ok(mut conn) => {
+            let exported = export(&mut conn, &copy, new).await;
+            conn.close().await?;
+            exported
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = exported {
+        let _ = tokio::fs::remove_file(&copy).await;
+        return Err(e.context(format!("re-encrypting {}", path.display())));
+    }
+
+    // The write-ahead log was checkpointed and belongs to the old file.
+    for suffix in ["wal", "shm"] {
+        remove_if_present(&sibling(path, suffix)).await?;
+    }
+    tokio::fs::rename(&copy, path).await?;
+    Ok(true)
+}
+
+/// Fails, as `rekey_database` does, when another connection such as a
+/// running server has the SQLite file at `path` open. A file that does not
+/// exist yet is not in use.
+pub async fn ensure_not_in_use(path: &Path, key: Option<&EncryptionKey>) -> Result<()> {
+    if !tokio::fs::try_exists(path).await? {
+        return Ok(());
+    }
+    lock_database(path, key).await?.close().await?;
+    Ok(())
+}
+
+/// Opens the SQLite file at `path` with `key` and takes an exclusive lock on
+/// it, which the connection keeps until it is closed. Fails at once, rather
+/// than waiting, when another connection has the file open.
+async fn lock_database(path: &Path, key: Option<&EncryptionKey>) -> Result<SqliteConnection> {
+    let options = connect_options(path, key)
+        .pragma("locking_mode", "EXCLUSIVE")
+        .busy_timeout(Duration::ZERO);
+    let mut conn = SqliteConnection::connect_with(&options).await?;
+
+    let error = match take_exclusive_lock(&mut conn).await {
+        Ok(true) => return Ok(conn),
+        Ok(false) => None,
+        Err(e) => Some(e),
+    };
+    conn.close().await?;
+    match error {
+        Some(e) if is_wrong_key(&e) => Err(anyhow::Error::new(e)
+            .context(format!("opening {} with the current key", path.display()))),
+        Some(e) if !is_busy(&e) => Err(e.into()),
+        _ => bail!(
+            "{} is in use by another connection; stop the server first",
+            path.display()
+        ),
+    }
+}
+
+/// Moves the write-ahead log into the file and locks it; `false` when a
+/// connection elsewhere kept the checkpoint from finishing.
+async fn take_exclusive_lock(conn: &mut SqliteConnection) -> sqlx::Result<bool> {
+    let busy: i64 = sqlx::query_scalar("PRAGMA wal_checkpoint(TRUNCATE)")
+        .fetch_one(&mut *conn)
+        .await?;
+    if busy != 0 {
+        return Ok(false);
+    }
+    sqlx::raw_sql("BEGIN EXCLUSIVE; COMMIT")
+        .execute(&mut *conn)
+        .await?;
+    Ok(true)
+}
+
+/// Whether a lock another connection holds made SQLite give up.
+fn is_busy(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .and_then(|e| e.code())
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| code & 0xff == SQLITE_BUSY)
+}
+
+async fn export(conn: &mut SqliteConnection, copy: &Path, new: &EncryptionKey) -> Result<()> {
+    let copy = copy
+        .to_str()
+        .ok_or_else(|| anyhow!("path {} is not UTF-8", copy.display()))?
+        .to_string();
+    sqlx::query("ATTACH DATABASE ? AS rekeyed KEY ?")
+        .bind(copy)
+        .bind(attach_key(Some(new)))
+        .execute(&mut *conn)
+        .await?;
+    let exported = sqlx::query("SELECT sqlcipher_export('rekeyed')")
+        .execute(&mut *conn)
+        .await;
+    sqlx::query("DETACH DATABASE rekeyed")
+        .execute(&mut *conn)
+        .await?;
+    exported?;
+    Ok(())
+}
+
+/// `path` with `-<suffix>` appended, as SQLite names its `-wal` file.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!("-{suffix}"));
+    PathBuf::from(name)
+}
+
+async fn remove_if_present(path: &Path) -> Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn key(byte: u8) -> EncryptionKey {
+        EncryptionKey([byte; KEY_LEN])
+    }
+
+    #[test]
+    fn test_keys_from_variables_and_files() {
+        let path = std::env::temp_dir().join(format!("key-{}", Uuid::new_v4()));
+        std::fs::write(&path, format!("{}\n", key(2).to_hex())).unwrap();
+        let lookup = |vars: &[(&str, String)]| {
+            let vars: HashMap<String, String> = vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect();
+            EncryptionKey::from_lookup(|k| vars.get(k).cloned(), "ENCRYPTION_KEY")
+        };
+        let file = path.display().to_string();
+
+        assert_eq!(lookup(&[]).unwrap(), None);
+        assert_eq!(
+            lookup(&[("ENCRYPTION_KEY", key(1).to_hex())]).unwrap(),
+            Some(key(1))
+        );
+        assert_eq!(
+            lookup(&[("ENCRYPTION_KEY_FILE", file.clone())]).unwrap(),
+            Some(key(2))
+        );
+        assert!(
+            lookup(&[
+                ("ENCRYPTION_KEY", key(1).to_hex()),
+                ("ENCRYPTION_KEY_FILE", file)
+            ])
+            .is_err()
+        );
+        assert!(lookup(&[("ENCRYPTION_KEY", "abc".to_string())]).is_err());
+        assert!(!format!("{:?}", key(1)).contains(&key(1).to_hex()));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let cipher = FileCipher::new(&key(1));
+        let sealed = cipher.seal("blobs/ab", b"receipt").unwrap();
+
+        assert!(cipher.sealed_by_this_key(&sealed));
+        assert!(!sealed.windows(7).any(|window| window == b"receipt"));
+        assert_eq!(cipher.open("blobs/ab", &sealed).unwrap(), b"receipt");
+        assert!(cipher.open("blobs/cd", &sealed).is_err());
+        assert!(cipher.open("blobs/ab", b"receipt").is_err());
+
+        let other = FileCipher::new(&key(2));
+        assert!(!other.sealed_by_this_key(&sealed));
+        assert!(other.open("blobs/ab", &sealed).is_err());
+
+        let mut tampered = sealed.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(cipher.open("blobs/ab", &tampered).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rekey_database() {
+        let dir = std::env::temp_dir().join(format!("rekey-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("expenses.db");
+        let mut conn = SqliteConnection::connect_with(
+            &SqliteConnectOptions::new()
+                .filename(&path)
+                .create_if_missing(true),
+        )
+        .await
+        .unwrap();
+        sqlx::query("CREATE TABLE notes (body TEXT); INSERT INTO notes VALUES ('lunch')")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        conn.close().await.unwrap();
+
+        assert!(rekey_database(&path, None, &key(1)).await.unwrap());
+        assert!(!opens_with(&path, None).await.unwrap());
+        assert!(opens_with(&path, Some(&key(1))).await.unwrap());
+        assert!(!rekey_database(&path, None, &key(1)).await.unwrap());
+        let error = rekey_database(&path, Some(&key(3)), &key(2))
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{error:#}").contains("with the current key"),
+            "{error:#}"
+        );
+
+        assert!(rekey_database(&path, Some(&key(1)), &key(2)).await.unwrap());
+        assert!(!opens_with(&path, Some(&key(1))).await.unwrap());
+        let mut conn = open_database(&path, Some(&key(2)), true).await.unwrap();
+        let body: String = sqlx::query_scalar("SELECT body FROM notes")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(body, "lunch");
+        conn.close().await.unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_database_in_use_is_detected() {
+        let dir = std::env::temp_dir().join(format!("in-use-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("expenses.db");
+        ensure_not_in_use(&path, None).await.unwrap();
+
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+        let pool = crate::database::create_pool_with(&url, Default::default())
+            .await
+            .unwrap();
+        let error = ensure_not_in_use(&path, None).await.unwrap_err();
+        assert!(format!("{error:#}").contains("in use"), "{error:#}");
+
+        pool.close().await;
+        ensure_not_in_use(&path, None).await.unwrap();
+        // The lock is released again.
+        let pool = crate::database::create_pool_with(&url, Default::default())
+            .await
+            .unwrap();
+        pool.close().await;
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rekey_refuses_a_database_in_use() {
+        let dir = std::env::temp_dir().join(format!("rekey-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("expenses.db");
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+        let pool = crate::database::create_pool_with(&url, Default::default())
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO tags (id, name, created_at) VALUES ('t', 'travel', '2024-03-01')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let error = rekey_database(&path, None, &key(1)).await.unwrap_err();
+        assert!(format!("{error:#}").contains("in use"), "{error:#}");
+        assert!(!dir.join("expenses.db-rekey").exists());
+        sqlx::query("INSERT INTO tags (id, name, created_at) VALUES ('u', 'food', '2024-03-01')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        pool.close().await;
+        assert!(rekey_database(&path, None, &key(1)).await.unwrap());
+        let mut conn = open_database(&path, Some(&key(1)), true).await.unwrap();
+        let tags: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tags")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(tags, 2);
+        conn.close().await.unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
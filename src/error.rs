@@ -1,10 +1,19 @@
+use crate::middleware::request_id;
+use crate::models::account::UnknownAccount;
+use crate::models::backup::BackupRejected;
+use crate::models::expense_report::ExpenseLocked;
+use crate::models::integrity::CorruptRow;
+use crate::models::refund::AmountBelowRefunded;
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
-use serde_json::json;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
 
 #[allow(dead_code)]
 #[derive(Error, Debug)]
@@ -13,42 +22,337 @@ pub enum AppError {
     Database(#[from] sqlx::Error),
 
     #[error("Anyhow error: {0}")]
-    Anyhow(#[from] anyhow::Error),
+    Anyhow(anyhow::Error),
 
     #[error("Invalid input: {0}")]
     Validation(String),
 
+    /// A request body failed its `validator` rules.
+    #[error("Invalid input: {} field(s) rejected", .0.len())]
+    InvalidFields(Vec<FieldViolation>),
+
     #[error("Not found")]
     NotFound,
 
+    /// No credentials, or ones the server does not know; answered with a
+    /// `WWW-Authenticate: Bearer` challenge.
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Unprocessable: {0}")]
+    Unprocessable(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    #[error("Precondition failed")]
+    PreconditionFailed,
+
+    #[error("Precondition required")]
+    PreconditionRequired,
+
     #[error("Internal server error")]
     Internal,
+
+    #[error("Corrupt data: {0}")]
+    CorruptData(CorruptRow),
 }
 
-impl IntoResponse for AppError {
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        // Rejections raised deep inside a write are the client's fault.
+        if let Some(unknown) = error.downcast_ref::<UnknownAccount>() {
+            return AppError::Validation(unknown.to_string());
+        }
+        if let Some(below) = error.downcast_ref::<AmountBelowRefunded>() {
+            return AppError::Validation(below.to_string());
+        }
+        if let Some(locked) = error.downcast_ref::<ExpenseLocked>() {
+            return AppError::Conflict(locked.to_string());
+        }
+        if let Some(rejected) = error.downcast_ref::<BackupRejected>() {
+            return AppError::Unprocessable(rejected.to_string());
+        }
+        if let Some(corrupt) = error.downcast_ref::<CorruptRow>() {
+            return AppError::CorruptData(corrupt.clone());
+        }
+        AppError::Anyhow(error)
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut violations = Vec::new();
+        collect_violations(None, &errors, &mut violations);
+        violations.sort_by(|a, b| a.field.cmp(&b.field));
+        AppError::InvalidFields(violations)
+    }
+}
+
+/// One rule an input field broke, e.g. `{"field": "amount", "code": "range",
+/// "message": "Amount must be greater than 0"}`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FieldViolation {
+    /// Dotted path to the field; list items are indexed, as in `items[0].amount`.
+    pub field: String,
+    /// The validator rule, such as `length`, `range` or `email`.
+    pub code: String,
+    pub message: String,
+}
+
+fn collect_violations(
+    prefix: Option<&str>,
+    errors: &ValidationErrors,
+    violations: &mut Vec<FieldViolation>,
+) {
+    for (field, kind) in errors.errors() {
+        let path = match prefix {
+            Some(prefix) => format!("{prefix}.{field}"),
+            None => field.to_string(),
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                violations.extend(errors.iter().map(|error| {
+                    FieldViolation {
+                        field: path.clone(),
+                        code: error.code.to_string(),
+                        message: error
+                            .message
+                            .as_ref()
+                            .map(|message| message.to_string())
+                            .unwrap_or_else(|| format!("{path} is invalid")),
+                    }
+                }));
+            }
+            ValidationErrorsKind::Struct(errors) => {
+                collect_violations(Some(&path), errors, violations);
+            }
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    collect_violations(Some(&format!("{path}[{index}]")), errors, violations);
+                }
+            }
+        }
+    }
+}
+
+/// An RFC 7807 problem details body. `code` is stable and meant for
+/// programs; `title` and `detail` are for people and may change.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: String,
+    /// The request's `X-Request-Id`; also recorded with audit entries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldViolation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corrupt_row: Option<CorruptRow>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &str, detail: impl Into<String>) -> Self {
+        Self {
+            problem_type: format!("/problems/{}", code.replace('_', "-")),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            code: code.to_string(),
+            correlation_id: request_id::current(),
+            errors: Vec::new(),
+            corrupt_row: None,
+        }
+    }
+
+    /// The code for a status when nothing more specific is known, e.g. for
+    /// requests the extractors reject before a handler runs.
+    pub fn code_for_status(status: StatusCode) -> &'static str {
+        match status {
+            StatusCode::BAD_REQUEST => "bad_request",
+            StatusCode::UNAUTHORIZED => "unauthorized",
+            StatusCode::FORBIDDEN => "forbidden",
+            StatusCode::NOT_FOUND => "not_found",
+            StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+            StatusCode::CONFLICT => "conflict",
+            StatusCode::PRECONDITION_FAILED => "precondition_failed",
+            StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+            StatusCode::UNPROCESSABLE_ENTITY => "unprocessable",
+            StatusCode::PRECONDITION_REQUIRED => "precondition_required",
+            _ if status.is_client_error() => "client_error",
+            _ => "internal_error",
+        }
+    }
+}
+
+impl IntoResponse for Problem {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            AppError::Database(_) => (
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, Json(self)).into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(PROBLEM_CONTENT_TYPE),
+        );
+        response
+    }
+}
+
+impl From<AppError> for Problem {
+    fn from(error: AppError) -> Self {
+        // Server-side failures are reported opaquely; keep the cause in the
+        // log, next to the request id the client gets back.
+        match &error {
+            AppError::Database(e) => tracing::error!(error = %e, "Database error"),
+            AppError::Anyhow(e) => tracing::error!(error = format!("{e:#}"), "Request failed"),
+            AppError::CorruptData(row) => tracing::error!(error = %row, "Corrupt row"),
+            _ => {}
+        }
+
+        match error {
+            AppError::Database(_) => Problem::new(
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Database error".to_string(),
+                "database_error",
+                "Database error",
             ),
-            AppError::Anyhow(_) => (
+            AppError::Anyhow(_) | AppError::Internal => Problem::new(
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Anyhow error".to_string(),
+                "internal_error",
+                "Internal server error",
             ),
-            AppError::Validation(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::NotFound => (StatusCode::NOT_FOUND, "Resource not found".to_string()),
-            AppError::Internal => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Internal server error".to_string(),
+            AppError::Validation(msg) => {
+                Problem::new(StatusCode::BAD_REQUEST, "invalid_input", msg)
+            }
+            AppError::InvalidFields(errors) => Problem {
+                errors,
+                ..Problem::new(
+                    StatusCode::BAD_REQUEST,
+                    "validation_failed",
+                    "One or more fields are invalid",
+                )
+            },
+            AppError::NotFound => {
+                Problem::new(StatusCode::NOT_FOUND, "not_found", "Resource not found")
+            }
+            AppError::Unauthorized(msg) => {
+                Problem::new(StatusCode::UNAUTHORIZED, "unauthorized", msg)
+            }
+            AppError::Forbidden(msg) => Problem::new(StatusCode::FORBIDDEN, "forbidden", msg),
+            AppError::Conflict(msg) => Problem::new(StatusCode::CONFLICT, "conflict", msg),
+            AppError::Unprocessable(msg) => {
+                Problem::new(StatusCode::UNPROCESSABLE_ENTITY, "unprocessable", msg)
+            }
+            AppError::PayloadTooLarge(msg) => {
+                Problem::new(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", msg)
+            }
+            AppError::UnsupportedMediaType(msg) => Problem::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                msg,
+            ),
+            AppError::PreconditionFailed => Problem::new(
+                StatusCode::PRECONDITION_FAILED,
+                "precondition_failed",
+                "Resource has been modified; fetch it again and retry",
+            ),
+            AppError::PreconditionRequired => Problem::new(
+                StatusCode::PRECONDITION_REQUIRED,
+                "precondition_required",
+                "If-Match header is required",
             ),
+            // Say which row is broken so it can be found and repaired; see
+            // `GET /admin/integrity`.
+            AppError::CorruptData(row) => Problem {
+                corrupt_row: Some(row),
+                ..Problem::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "corrupt_data",
+                    "Stored data is corrupt",
+                )
+            },
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let challenge = matches!(self, AppError::Unauthorized(_));
+        let mut response = Problem::from(self).into_response();
+        if challenge {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::expense::CreateExpenseRequest;
+    use axum::body::to_bytes;
+    use validator::Validate;
+
+    #[test]
+    fn test_validation_errors_become_field_violations() {
+        let request = CreateExpenseRequest {
+            amount: 0.0,
+            category: String::new(),
+            ..Default::default()
         };
+        let AppError::InvalidFields(violations) = AppError::from(request.validate().unwrap_err())
+        else {
+            panic!("expected field violations");
+        };
+
+        let fields: Vec<(&str, &str)> = violations
+            .iter()
+            .map(|v| (v.field.as_str(), v.code.as_str()))
+            .collect();
+        assert_eq!(fields, vec![("amount", "range"), ("category", "length")]);
+        assert_eq!(violations[0].message, "Amount must be greater than 0");
+    }
 
-        let body = Json(json!({
-            "error": error_message,
-            "status": status.as_u16(),
-        }));
+    #[tokio::test]
+    async fn test_errors_render_as_problem_json() {
+        let response = request_id::scope("req-1".to_string(), async {
+            AppError::Conflict("Taken".to_string()).into_response()
+        })
+        .await;
 
-        (status, body).into_response()
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            PROBLEM_CONTENT_TYPE
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let problem: Problem = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            problem,
+            Problem {
+                problem_type: "/problems/conflict".to_string(),
+                title: "Conflict".to_string(),
+                status: 409,
+                detail: "Taken".to_string(),
+                code: "conflict".to_string(),
+                correlation_id: Some("req-1".to_string()),
+                errors: Vec::new(),
+                corrupt_row: None,
+            }
+        );
     }
 }
@@ -0,0 +1,128 @@
+use crate::context::RequestContext;
+use crate::error::AppError;
+use crate::models::account::{
+    Account, AccountBalance, AccountRequest, CreateTransferRequest, Statement, StatementQuery,
+    Transfer, TransferQuery,
+};
+use crate::services::account_service::{AccountService, AccountWrite};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+fn saved<T>(outcome: AccountWrite<T>) -> Result<T, AppError> {
+    match outcome {
+        AccountWrite::Saved(value) => Ok(value),
+        AccountWrite::NotFound => Err(AppError::NotFound),
+        AccountWrite::Conflict(message) => Err(AppError::Conflict(message)),
+        AccountWrite::Invalid(message) => Err(AppError::Validation(message)),
+    }
+}
+
+pub async fn create_account(
+    State(service): State<AccountService>,
+    ctx: RequestContext,
+    Json(request): Json<AccountRequest>,
+) -> Result<(StatusCode, Json<Account>), AppError> {
+    request.validate()?;
+
+    let account = saved(service.create_account(&ctx, request).await?)?;
+    Ok((StatusCode::CREATED, Json(account)))
+}
+
+pub async fn list_accounts(
+    State(service): State<AccountService>,
+) -> Result<Json<Vec<Account>>, AppError> {
+    let accounts = service.list_accounts().await?;
+    Ok(Json(accounts))
+}
+
+pub async fn get_account(
+    State(service): State<AccountService>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Account>, AppError> {
+    let account = service.get_account(id).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(account))
+}
+
+pub async fn update_account(
+    State(service): State<AccountService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+    Json(request): Json<AccountRequest>,
+) -> Result<Json<Account>, AppError> {
+    request.validate()?;
+
+    let account = saved(service.update_account(&ctx, id, request).await?)?;
+    Ok(Json(account))
+}
+
+pub async fn delete_account(
+    State(service): State<AccountService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    saved(service.delete_account(&ctx, id).await?)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_balances(
+    State(service): State<AccountService>,
+) -> Result<Json<Vec<AccountBalance>>, AppError> {
+    let balances = service.balances().await?;
+    Ok(Json(balances))
+}
+
+pub async fn get_balance(
+    State(service): State<AccountService>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<AccountBalance>, AppError> {
+    let balance = service.balance(id).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(balance))
+}
+
+pub async fn get_statement(
+    State(service): State<AccountService>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<StatementQuery>,
+) -> Result<Json<Statement>, AppError> {
+    let statement = service
+        .statement(id, &query)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(statement))
+}
+
+pub async fn create_transfer(
+    State(service): State<AccountService>,
+    ctx: RequestContext,
+    Json(request): Json<CreateTransferRequest>,
+) -> Result<(StatusCode, Json<Transfer>), AppError> {
+    request.validate()?;
+
+    let transfer = saved(service.create_transfer(&ctx, request).await?)?;
+    Ok((StatusCode::CREATED, Json(transfer)))
+}
+
+pub async fn list_transfers(
+    State(service): State<AccountService>,
+    Query(query): Query<TransferQuery>,
+) -> Result<Json<Vec<Transfer>>, AppError> {
+    let transfers = service.list_transfers(&query).await?;
+    Ok(Json(transfers))
+}
+
+pub async fn delete_transfer(
+    State(service): State<AccountService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    service
+        .delete_transfer(&ctx, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,39 @@
+use crate::error::AppError;
+use crate::models::backup::{Backup, BackupKind, BackupVerification};
+use crate::models::integrity::IntegrityReport;
+use crate::services::backup_service::BackupService;
+use crate::services::integrity_service::IntegrityService;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+
+pub async fn check_integrity(
+    State(service): State<IntegrityService>,
+) -> Result<Json<IntegrityReport>, AppError> {
+    let report = service.check().await?;
+    Ok(Json(report))
+}
+
+pub async fn create_backup(
+    State(service): State<BackupService>,
+) -> Result<(StatusCode, Json<Backup>), AppError> {
+    let backup = service.create(BackupKind::Manual).await?;
+    Ok((StatusCode::CREATED, Json(backup)))
+}
+
+pub async fn list_backups(
+    State(service): State<BackupService>,
+) -> Result<Json<Vec<Backup>>, AppError> {
+    let backups = service.list().await?;
+    Ok(Json(backups))
+}
+
+pub async fn verify_backup(
+    State(service): State<BackupService>,
+    Path(name): Path<String>,
+) -> Result<Json<BackupVerification>, AppError> {
+    let verification = service.verify(&name).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(verification))
+}
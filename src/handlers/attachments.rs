@@ -0,0 +1,178 @@
+use crate::context::RequestContext;
+use crate::error::AppError;
+use crate::handlers::conditional::if_none_match;
+use crate::models::attachment::{Attachment, Upload};
+use crate::services::attachment_service::{AttachmentService, AttachmentWrite};
+use axum::{
+    extract::{Multipart, Path, State, multipart::MultipartError},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+use uuid::Uuid;
+
+/// Name of the multipart field carrying the file.
+const FILE_FIELD: &str = "file";
+
+fn multipart_error(error: MultipartError) -> AppError {
+    if error.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        AppError::PayloadTooLarge("The upload exceeds the attachment size limit".to_string())
+    } else {
+        AppError::Validation(error.body_text())
+    }
+}
+
+pub async fn upload_attachment(
+    State(service): State<AttachmentService>,
+    ctx: RequestContext,
+    Path(expense_id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<Attachment>), AppError> {
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() != Some(FILE_FIELD) {
+            continue;
+        }
+        if upload.is_some() {
+            return Err(AppError::Validation(
+                "Upload one file per request".to_string(),
+            ));
+        }
+        let filename = field.file_name().map(str::to_string);
+        let content_type = field.content_type().map(str::to_string);
+        let content = field.bytes().await.map_err(multipart_error)?;
+        upload = Some(Upload {
+            filename,
+            content_type,
+            content: content.to_vec(),
+        });
+    }
+    let upload = upload
+        .ok_or_else(|| AppError::Validation(format!("Missing multipart field '{}'", FILE_FIELD)))?;
+
+    match service.add_attachment(&ctx, expense_id, upload).await? {
+        AttachmentWrite::Saved(attachment) => Ok((StatusCode::CREATED, Json(attachment))),
+        AttachmentWrite::NotFound => Err(AppError::NotFound),
+        AttachmentWrite::TooLarge(message) => Err(AppError::PayloadTooLarge(message)),
+        AttachmentWrite::Unsupported(message) => Err(AppError::UnsupportedMediaType(message)),
+        AttachmentWrite::Invalid(message) => Err(AppError::Validation(message)),
+    }
+}
+
+pub async fn list_attachments(
+    State(service): State<AttachmentService>,
+    Path(expense_id): Path<Uuid>,
+) -> Result<Json<Vec<Attachment>>, AppError> {
+    let attachments = service
+        .list_attachments(expense_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(attachments))
+}
+
+pub async fn get_attachment(
+    State(service): State<AttachmentService>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Attachment>, AppError> {
+    let attachment = service
+        .get_attachment(id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(attachment))
+}
+
+pub async fn delete_attachment(
+    State(service): State<AttachmentService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    service
+        .delete_attachment(&ctx, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn download_attachment(
+    State(service): State<AttachmentService>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let (attachment, content) = service.read_content(id).await?.ok_or(AppError::NotFound)?;
+    let etag = format!("\"{}\"", attachment.sha256);
+    Ok(file_response(
+        &headers,
+        &etag,
+        &attachment.content_type,
+        &attachment.filename,
+        content,
+    ))
+}
+
+pub async fn download_thumbnail(
+    State(service): State<AttachmentService>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let (attachment, thumbnail) = service
+        .read_thumbnail(id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let etag = format!("\"{}-thumbnail\"", attachment.sha256);
+    let filename = format!("thumbnail-{}.jpg", attachment.id);
+    Ok(file_response(
+        &headers,
+        &etag,
+        "image/jpeg",
+        &filename,
+        thumbnail,
+    ))
+}
+
+/// Serves stored content inline. Content never changes under a hash, so the
+/// entity tag is the hash and `If-None-Match` is answered with `304`.
+fn file_response(
+    headers: &HeaderMap,
+    etag: &str,
+    content_type: &str,
+    filename: &str,
+    content: Vec<u8>,
+) -> Response {
+    let mut response = if if_none_match(headers, etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        content.into_response()
+    };
+
+    let ascii_name: String = filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let response_headers = response.headers_mut();
+    for (name, value) in [
+        (header::CONTENT_TYPE, content_type.to_string()),
+        (
+            header::CONTENT_DISPOSITION,
+            format!("inline; filename=\"{}\"", ascii_name),
+        ),
+        (header::ETAG, etag.to_string()),
+    ] {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response_headers.insert(name, value);
+        }
+    }
+    response_headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, max-age=86400"),
+    );
+    response
+}
@@ -0,0 +1,34 @@
+use crate::error::AppError;
+use crate::models::audit::{AuditEntry, AuditQuery, ChainVerification};
+use crate::services::audit_service::{AuditService, ENTITY_EXPENSE};
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use uuid::Uuid;
+
+pub async fn get_expense_history(
+    State(service): State<AuditService>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<AuditEntry>>, AppError> {
+    let history = service.history(ENTITY_EXPENSE, &id.to_string()).await?;
+    if history.is_empty() {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(history))
+}
+
+pub async fn query_audit_log(
+    State(service): State<AuditService>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, AppError> {
+    let entries = service.query(query).await?;
+    Ok(Json(entries))
+}
+
+pub async fn verify_audit_log(
+    State(service): State<AuditService>,
+) -> Result<Json<ChainVerification>, AppError> {
+    let verification = service.verify_chain().await?;
+    Ok(Json(verification))
+}
@@ -0,0 +1,145 @@
+use crate::models::expense::Expense;
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Strong entity tag of a single expense, derived from its version.
+pub fn expense_etag(expense: &Expense) -> String {
+    format!("\"{}\"", expense.version)
+}
+
+/// Strong entity tag over a serialized representation.
+pub fn content_etag(body: &[u8]) -> String {
+    let digest = hex::encode(Sha256::digest(body));
+    format!("\"{}\"", &digest[..32])
+}
+
+/// Parsed `If-Match` header.
+#[derive(Debug, PartialEq)]
+pub enum IfMatch {
+    Any,
+    /// Versions named by strong entity tags; weak and foreign tags never match.
+    Versions(Vec<i64>),
+}
+
+pub fn if_match(headers: &HeaderMap) -> Option<IfMatch> {
+    let tags = header_tags(headers, header::IF_MATCH)?;
+    if tags.iter().any(|tag| tag == "*") {
+        return Some(IfMatch::Any);
+    }
+
+    let versions = tags
+        .iter()
+        .filter_map(|tag| tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok())
+        .collect();
+    Some(IfMatch::Versions(versions))
+}
+
+/// Whether `If-None-Match` matches `etag`, using the weak comparison RFC 9110
+/// prescribes for this header.
+pub fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(tags) = header_tags(headers, header::IF_NONE_MATCH) else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim_start_matches("W/").to_string();
+    tags.iter()
+        .any(|tag| tag == "*" || opaque(tag) == opaque(etag))
+}
+
+fn header_tags(headers: &HeaderMap, name: header::HeaderName) -> Option<Vec<String>> {
+    let values: Vec<String> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    (!values.is_empty()).then_some(values)
+}
+
+/// Serializes `value` with the given `etag`, answering `304 Not Modified`
+/// when the client already holds that representation.
+pub fn json_with_etag<T: Serialize>(headers: &HeaderMap, etag: String, value: &T) -> Response {
+    let mut response = if if_none_match(headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        axum::Json(value).into_response()
+    };
+
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+    // Let browsers keep the body but revalidate it on every use.
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
+
+/// Like [`json_with_etag`], with the tag computed from the serialized body.
+pub fn json_with_content_etag<T: Serialize>(headers: &HeaderMap, value: &T) -> Response {
+    match serde_json::to_vec(value) {
+        Ok(body) => json_with_etag(headers, content_etag(&body), value),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_if_match_parses_versions() {
+        let parsed = if_match(&headers(header::IF_MATCH, "\"3\", W/\"4\", \"x\""));
+
+        assert_eq!(parsed, Some(IfMatch::Versions(vec![3])));
+    }
+
+    #[test]
+    fn test_if_match_wildcard_and_missing() {
+        assert_eq!(
+            if_match(&headers(header::IF_MATCH, "*")),
+            Some(IfMatch::Any)
+        );
+        assert_eq!(if_match(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_if_none_match_uses_weak_comparison() {
+        let etag = content_etag(b"[]");
+
+        assert!(if_none_match(
+            &headers(header::IF_NONE_MATCH, &format!("W/{}", etag)),
+            &etag
+        ));
+        assert!(if_none_match(&headers(header::IF_NONE_MATCH, "*"), &etag));
+        assert!(!if_none_match(
+            &headers(header::IF_NONE_MATCH, "\"other\""),
+            &etag
+        ));
+        assert!(!if_none_match(&HeaderMap::new(), &etag));
+    }
+
+    #[test]
+    fn test_json_with_etag_not_modified() {
+        let etag = content_etag(b"[]");
+        let response = json_with_etag(
+            &headers(header::IF_NONE_MATCH, &etag),
+            etag.clone(),
+            &Vec::<i32>::new(),
+        );
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+    }
+}
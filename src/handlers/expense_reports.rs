@@ -0,0 +1,122 @@
+use crate::context::RequestContext;
+use crate::error::AppError;
+use crate::models::expense_report::{
+    CommentRequest, CreateReportRequest, ExpenseReport, ExpenseReportDetail, ReportComment,
+    ReportItemRequest, ReportQuery, TransitionRequest,
+};
+use crate::services::expense_report_service::{ExpenseReportService, ReportWrite};
+use axum::{
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+use uuid::Uuid;
+use validator::Validate;
+
+fn saved<T>(outcome: ReportWrite<T>) -> Result<T, AppError> {
+    match outcome {
+        ReportWrite::Saved(value) => Ok(value),
+        ReportWrite::NotFound => Err(AppError::NotFound),
+        ReportWrite::Forbidden(message) => Err(AppError::Forbidden(message)),
+        ReportWrite::Conflict(message) => Err(AppError::Conflict(message)),
+        ReportWrite::Invalid(message) => Err(AppError::Validation(message)),
+    }
+}
+
+pub async fn create_report(
+    State(service): State<ExpenseReportService>,
+    ctx: RequestContext,
+    Json(request): Json<CreateReportRequest>,
+) -> Result<(StatusCode, Json<ExpenseReport>), AppError> {
+    request.validate()?;
+
+    let report = service.create_report(&ctx, request).await?;
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+pub async fn list_reports(
+    State(service): State<ExpenseReportService>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<Vec<ExpenseReport>>, AppError> {
+    let reports = service.list_reports(&query).await?;
+    Ok(Json(reports))
+}
+
+pub async fn get_report(
+    State(service): State<ExpenseReportService>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ExpenseReportDetail>, AppError> {
+    let report = service.get_report(id).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(report))
+}
+
+pub async fn delete_report(
+    State(service): State<ExpenseReportService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    saved(service.delete_report(&ctx, id).await?)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn add_report_expense(
+    State(service): State<ExpenseReportService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+    Json(request): Json<ReportItemRequest>,
+) -> Result<Json<ExpenseReportDetail>, AppError> {
+    let report = saved(service.add_expense(&ctx, id, request.expense_id).await?)?;
+    Ok(Json(report))
+}
+
+pub async fn remove_report_expense(
+    State(service): State<ExpenseReportService>,
+    ctx: RequestContext,
+    Path((id, expense_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<ExpenseReportDetail>, AppError> {
+    let report = saved(service.remove_expense(&ctx, id, expense_id).await?)?;
+    Ok(Json(report))
+}
+
+pub async fn transition_report(
+    State(service): State<ExpenseReportService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+    Json(request): Json<TransitionRequest>,
+) -> Result<Json<ExpenseReportDetail>, AppError> {
+    request.validate()?;
+
+    let report = saved(service.transition(&ctx, id, request).await?)?;
+    Ok(Json(report))
+}
+
+pub async fn add_report_comment(
+    State(service): State<ExpenseReportService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+    Json(request): Json<CommentRequest>,
+) -> Result<(StatusCode, Json<ReportComment>), AppError> {
+    request.validate()?;
+
+    let comment = saved(service.add_comment(&ctx, id, request).await?)?;
+    Ok((StatusCode::CREATED, Json(comment)))
+}
+
+pub async fn export_report(
+    State(service): State<ExpenseReportService>,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let export = saved(service.export(id).await?)?;
+    let disposition = format!(
+        "attachment; filename=\"expense-report-{}.zip\"",
+        export.report.id
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        export.zip,
+    )
+        .into_response())
+}
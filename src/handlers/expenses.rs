@@ -1,39 +1,278 @@
+use crate::context::RequestContext;
 use crate::error::AppError;
-use crate::models::expense::{CreateExpenseRequest, Expense};
-use crate::services::expense_service::ExpenseService;
+use crate::handlers::conditional::{
+    IfMatch, expense_etag, if_match, json_with_content_etag, json_with_etag,
+};
+use crate::logging::Sensitive;
+use crate::models::batch::{BatchItemError, BatchRequest, BatchResponse, MAX_BATCH_OPERATIONS};
+use crate::models::expense::{CreateExpenseRequest, Expense, UpdateExpenseRequest};
+use crate::models::search::{SearchHit, SearchQuery};
+use crate::models::suggestion::{SuggestCategoryRequest, SuggestCategoryResponse};
+use crate::models::summary::{ExpenseFilter, ExpenseSummary};
+use crate::services::expense_service::{ExpenseService, VersionedUpdate};
+use crate::services::suggestion_service::SuggestionService;
 use anyhow::Result;
-use axum::{extract::State, response::Json};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+use uuid::Uuid;
 use validator::Validate;
 
 pub async fn add_expense(
     State(service): State<ExpenseService>,
+    ctx: RequestContext,
     Json(request): Json<CreateExpenseRequest>,
 ) -> Result<Json<Expense>, AppError> {
-    println!(
-        "Validating request: amount= {}, category= {}",
-        request.amount, request.category
+    tracing::debug!(
+        amount = %Sensitive(request.amount),
+        category = %Sensitive(&request.category),
+        "Adding expense"
     );
 
-    request
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    let request = service.apply_rules(request).await?;
+    request.validate()?;
 
-    let expense = service.add_expense(request).await?;
+    let expense = service.add_expense(&ctx, request).await?;
     Ok(Json(expense))
 }
 
 pub async fn get_all_expenses(
     State(service): State<ExpenseService>,
+    headers: HeaderMap,
+    Query(filter): Query<ExpenseFilter>,
+) -> Result<Response, AppError> {
+    let expenses = service.list_expenses(&filter).await?;
+    Ok(json_with_content_etag(&headers, &expenses))
+}
+
+pub async fn get_expense_summary(
+    State(service): State<ExpenseService>,
+    Query(filter): Query<ExpenseFilter>,
+) -> Result<Json<ExpenseSummary>, AppError> {
+    let summary = service.summarize(&filter).await?;
+    Ok(Json(summary))
+}
+
+pub async fn get_highest_expense(
+    State(service): State<ExpenseService>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    match service.get_highest_expense().await? {
+        Some(expense) => Ok(json_with_content_etag(&headers, &expense)),
+        None => Err(AppError::NotFound),
+    }
+}
+
+pub async fn search_expenses(
+    State(service): State<ExpenseService>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchHit>>, AppError> {
+    if query.match_expression().is_none() {
+        return Err(AppError::Validation(
+            "Search query must contain at least one letter or digit".to_string(),
+        ));
+    }
+
+    let hits = service.search_expenses(&query).await?;
+    Ok(Json(hits))
+}
+
+pub async fn suggest_category(
+    State(service): State<SuggestionService>,
+    Json(request): Json<SuggestCategoryRequest>,
+) -> Result<Json<SuggestCategoryResponse>, AppError> {
+    request.validate()?;
+
+    let response = service.suggest(&request).await?;
+    Ok(Json(response))
+}
+
+pub async fn get_expense(
+    State(service): State<ExpenseService>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    match service.get_expense(id).await? {
+        Some(expense) => Ok(json_with_etag(&headers, expense_etag(&expense), &expense)),
+        None => Err(AppError::NotFound),
+    }
+}
+
+/// Resolves an `If-Match` header to the version the write must apply to;
+/// `None` means any version.
+async fn expected_version(
+    service: &ExpenseService,
+    id: Uuid,
+    condition: IfMatch,
+) -> Result<Option<i64>, AppError> {
+    match condition {
+        IfMatch::Any => Ok(None),
+        IfMatch::Versions(versions) if versions.len() == 1 => Ok(Some(versions[0])),
+        IfMatch::Versions(versions) => {
+            // Several candidate tags: pick the one that is current, if any; the
+            // service re-checks it atomically.
+            let current = service.get_expense(id).await?.ok_or(AppError::NotFound)?;
+            if !versions.contains(&current.version) {
+                return Err(AppError::PreconditionFailed);
+            }
+            Ok(Some(current.version))
+        }
+    }
+}
+
+fn versioned_response(outcome: VersionedUpdate) -> Result<Response, AppError> {
+    match outcome {
+        VersionedUpdate::Updated(expense) => Ok(json_with_etag(
+            &HeaderMap::new(),
+            expense_etag(&expense),
+            &expense,
+        )),
+        VersionedUpdate::NotFound => Err(AppError::NotFound),
+        VersionedUpdate::VersionMismatch(current) => {
+            let mut response = AppError::PreconditionFailed.into_response();
+            if let Ok(etag) = HeaderValue::from_str(&expense_etag(&current)) {
+                response.headers_mut().insert(header::ETAG, etag);
+            }
+            Ok(response)
+        }
+    }
+}
+
+/// Replaces an expense. Requires `If-Match` with the expense's current ETag
+/// (or `*`) so that stale edits are rejected with `412` instead of silently
+/// overwriting someone else's change.
+pub async fn update_expense(
+    State(service): State<ExpenseService>,
+    ctx: RequestContext,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(request): Json<UpdateExpenseRequest>,
+) -> Result<Response, AppError> {
+    request.validate()?;
+
+    let condition = if_match(&headers).ok_or(AppError::PreconditionRequired)?;
+    let version = expected_version(&service, id, condition).await?;
+
+    versioned_response(service.update_expense(&ctx, id, version, request).await?)
+}
+
+/// Moves an expense to the trash; honours `If-Match` when it is sent.
+pub async fn delete_expense(
+    State(service): State<ExpenseService>,
+    ctx: RequestContext,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let version = match if_match(&headers) {
+        Some(condition) => expected_version(&service, id, condition).await?,
+        None => None,
+    };
+
+    versioned_response(service.delete_expense(&ctx, id, version).await?)
+}
+
+pub async fn get_trash(
+    State(service): State<ExpenseService>,
 ) -> Result<Json<Vec<Expense>>, AppError> {
-    let expenses = service.get_all_expenses().await?;
+    let expenses = service.get_trash().await?;
     Ok(Json(expenses))
 }
 
-pub async fn get_highest_expense(
+pub async fn restore_expense(
     State(service): State<ExpenseService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
 ) -> Result<Json<Expense>, AppError> {
-    match service.get_highest_expense().await? {
+    match service.restore_expense(&ctx, id).await? {
         Some(expense) => Ok(Json(expense)),
         None => Err(AppError::NotFound),
     }
 }
+
+/// Applies up to `MAX_BATCH_OPERATIONS` create/update/delete operations in one
+/// transaction. Responds `200` when the batch was committed, otherwise `500`
+/// if an item hit a server error, `422` if items were invalid or `409` if they
+/// failed against the stored data.
+pub async fn run_batch(
+    State(service): State<ExpenseService>,
+    ctx: RequestContext,
+    Json(request): Json<BatchRequest>,
+) -> Result<Response, AppError> {
+    if request.operations.is_empty() || request.operations.len() > MAX_BATCH_OPERATIONS {
+        return Err(AppError::Validation(format!(
+            "A batch must contain between 1 and {} operations",
+            MAX_BATCH_OPERATIONS
+        )));
+    }
+
+    let response = service.run_batch(&ctx, request).await?;
+    Ok((batch_status(&response), Json(response)).into_response())
+}
+
+fn batch_status(response: &BatchResponse) -> StatusCode {
+    let any_error = |matches: fn(&BatchItemError) -> bool| {
+        response
+            .results
+            .iter()
+            .any(|r| r.error.as_ref().is_some_and(matches))
+    };
+
+    if response.committed {
+        StatusCode::OK
+    } else if any_error(|e| matches!(e, BatchItemError::Internal)) {
+        // Not the client's doing, so not to be retried as a conflict.
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else if any_error(|e| matches!(e, BatchItemError::Invalid { .. })) {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::CONFLICT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::batch::{BatchItemResult, BatchMode};
+
+    fn response(committed: bool, errors: Vec<BatchItemError>) -> BatchResponse {
+        BatchResponse {
+            mode: BatchMode::Atomic,
+            committed,
+            results: errors
+                .into_iter()
+                .enumerate()
+                .map(|(index, error)| BatchItemResult::failed(index, error))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_batch_status() {
+        let conflict = BatchItemError::VersionConflict { current_version: 2 };
+        let invalid = BatchItemError::Invalid {
+            message: "bad".to_string(),
+        };
+
+        assert_eq!(batch_status(&response(true, vec![])), StatusCode::OK);
+        assert_eq!(
+            batch_status(&response(
+                false,
+                vec![BatchItemError::NotFound, conflict.clone()]
+            )),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            batch_status(&response(false, vec![conflict.clone(), invalid.clone()])),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            batch_status(&response(
+                false,
+                vec![conflict, invalid, BatchItemError::Internal]
+            )),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
@@ -0,0 +1,21 @@
+use crate::models::health::Readiness;
+use crate::services::health_service::HealthService;
+use axum::{extract::State, http::StatusCode, response::Json};
+use serde_json::{Value, json};
+
+/// Liveness: the process is up and serving requests.
+pub async fn healthz() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Readiness: `503` until the service can take traffic, and again once it
+/// starts shutting down, so load balancers stop routing to it.
+pub async fn readyz(State(service): State<HealthService>) -> (StatusCode, Json<Readiness>) {
+    let readiness = service.readiness().await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
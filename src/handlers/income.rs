@@ -0,0 +1,75 @@
+use crate::context::RequestContext;
+use crate::error::AppError;
+use crate::models::income::{Income, IncomeQuery, IncomeRequest};
+use crate::models::summary::{CashFlowQuery, CashFlowReport};
+use crate::services::expense_service::ExpenseService;
+use crate::services::income_service::IncomeService;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+pub async fn add_income(
+    State(service): State<IncomeService>,
+    ctx: RequestContext,
+    Json(request): Json<IncomeRequest>,
+) -> Result<(StatusCode, Json<Income>), AppError> {
+    request.validate()?;
+
+    let income = service.add_income(&ctx, request).await?;
+    Ok((StatusCode::CREATED, Json(income)))
+}
+
+pub async fn list_income(
+    State(service): State<IncomeService>,
+    Query(query): Query<IncomeQuery>,
+) -> Result<Json<Vec<Income>>, AppError> {
+    let income = service.list_income(&query).await?;
+    Ok(Json(income))
+}
+
+pub async fn get_income(
+    State(service): State<IncomeService>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Income>, AppError> {
+    let income = service.get_income(id).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(income))
+}
+
+pub async fn update_income(
+    State(service): State<IncomeService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+    Json(request): Json<IncomeRequest>,
+) -> Result<Json<Income>, AppError> {
+    request.validate()?;
+
+    let income = service
+        .update_income(&ctx, id, request)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(income))
+}
+
+pub async fn delete_income(
+    State(service): State<IncomeService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    service
+        .delete_income(&ctx, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_cash_flow(
+    State(service): State<ExpenseService>,
+    Query(query): Query<CashFlowQuery>,
+) -> Result<Json<CashFlowReport>, AppError> {
+    let report = service.cash_flow(&query).await?;
+    Ok(Json(report))
+}
@@ -1 +1,14 @@
+pub mod accounts;
+pub mod admin;
+pub mod attachments;
+pub mod audit;
+pub mod conditional;
+pub mod expense_reports;
 pub mod expenses;
+pub mod health;
+pub mod income;
+pub mod metrics;
+pub mod payees;
+pub mod refunds;
+pub mod rules;
+pub mod tags;
@@ -0,0 +1,122 @@
+use crate::context::RequestContext;
+use crate::error::AppError;
+use crate::models::payee::{
+    CreateAliasRequest, CreatePayeeRequest, Payee, PayeeSummary, PayeeSummaryQuery,
+    ResolvePayeeQuery, ResolvedPayee, UpdatePayeeRequest,
+};
+use crate::services::payee_service::{PayeeService, PayeeWrite};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+fn saved(outcome: PayeeWrite) -> Result<Payee, AppError> {
+    match outcome {
+        PayeeWrite::Saved(payee) => Ok(payee),
+        PayeeWrite::NotFound => Err(AppError::NotFound),
+        PayeeWrite::Conflict(message) => Err(AppError::Conflict(message)),
+        PayeeWrite::Invalid(message) => Err(AppError::Validation(message)),
+    }
+}
+
+pub async fn create_payee(
+    State(service): State<PayeeService>,
+    ctx: RequestContext,
+    Json(request): Json<CreatePayeeRequest>,
+) -> Result<(StatusCode, Json<Payee>), AppError> {
+    request.validate()?;
+
+    let payee = saved(service.create_payee(&ctx, request).await?)?;
+    Ok((StatusCode::CREATED, Json(payee)))
+}
+
+pub async fn list_payees(
+    State(service): State<PayeeService>,
+) -> Result<Json<Vec<Payee>>, AppError> {
+    let payees = service.list_payees().await?;
+    Ok(Json(payees))
+}
+
+pub async fn get_payee(
+    State(service): State<PayeeService>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Payee>, AppError> {
+    let payee = service.get_payee(id).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(payee))
+}
+
+pub async fn rename_payee(
+    State(service): State<PayeeService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+    Json(request): Json<UpdatePayeeRequest>,
+) -> Result<Json<Payee>, AppError> {
+    request.validate()?;
+
+    let payee = saved(service.rename_payee(&ctx, id, request).await?)?;
+    Ok(Json(payee))
+}
+
+pub async fn delete_payee(
+    State(service): State<PayeeService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    if service.delete_payee(&ctx, id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+pub async fn add_alias(
+    State(service): State<PayeeService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+    Json(request): Json<CreateAliasRequest>,
+) -> Result<(StatusCode, Json<Payee>), AppError> {
+    request.validate()?;
+
+    let payee = saved(service.add_alias(&ctx, id, request).await?)?;
+    Ok((StatusCode::CREATED, Json(payee)))
+}
+
+pub async fn remove_alias(
+    State(service): State<PayeeService>,
+    ctx: RequestContext,
+    Path((id, alias_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Payee>, AppError> {
+    let payee = saved(service.remove_alias(&ctx, id, alias_id).await?)?;
+    Ok(Json(payee))
+}
+
+pub async fn resolve_payee(
+    State(service): State<PayeeService>,
+    Query(query): Query<ResolvePayeeQuery>,
+) -> Result<Json<Option<ResolvedPayee>>, AppError> {
+    let resolved = service.resolve(&query.raw).await?;
+    Ok(Json(resolved))
+}
+
+pub async fn get_payee_summaries(
+    State(service): State<PayeeService>,
+    Query(query): Query<PayeeSummaryQuery>,
+) -> Result<Json<Vec<PayeeSummary>>, AppError> {
+    let summaries = service.summaries(&query).await?;
+    Ok(Json(summaries))
+}
+
+pub async fn get_payee_summary(
+    State(service): State<PayeeService>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<PayeeSummaryQuery>,
+) -> Result<Json<PayeeSummary>, AppError> {
+    let summary = service
+        .summary(id, &query)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(summary))
+}
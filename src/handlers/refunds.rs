@@ -0,0 +1,57 @@
+use crate::context::RequestContext;
+use crate::error::AppError;
+use crate::models::refund::{CreateRefundRequest, Refund};
+use crate::services::refund_service::{RefundService, RefundWrite};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+pub async fn add_refund(
+    State(service): State<RefundService>,
+    ctx: RequestContext,
+    Path(expense_id): Path<Uuid>,
+    Json(request): Json<CreateRefundRequest>,
+) -> Result<(StatusCode, Json<Refund>), AppError> {
+    request.validate()?;
+
+    match service.add_refund(&ctx, expense_id, request).await? {
+        RefundWrite::Saved(refund) => Ok((StatusCode::CREATED, Json(refund))),
+        RefundWrite::NotFound => Err(AppError::NotFound),
+        RefundWrite::Invalid(message) => Err(AppError::Validation(message)),
+    }
+}
+
+pub async fn list_refunds(
+    State(service): State<RefundService>,
+    Path(expense_id): Path<Uuid>,
+) -> Result<Json<Vec<Refund>>, AppError> {
+    let refunds = service
+        .list_refunds(expense_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(refunds))
+}
+
+pub async fn get_refund(
+    State(service): State<RefundService>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Refund>, AppError> {
+    let refund = service.get_refund(id).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(refund))
+}
+
+pub async fn delete_refund(
+    State(service): State<RefundService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    service
+        .delete_refund(&ctx, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(StatusCode::NO_CONTENT)
+}
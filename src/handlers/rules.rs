@@ -0,0 +1,84 @@
+use crate::context::RequestContext;
+use crate::error::AppError;
+use crate::models::rule::{Rule, RuleRequest, RuleRunRequest, RuleRunResponse};
+use crate::services::rule_service::{RuleService, RuleWrite};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+fn saved(outcome: RuleWrite) -> Result<Rule, AppError> {
+    match outcome {
+        RuleWrite::Saved(rule) => Ok(*rule),
+        RuleWrite::NotFound => Err(AppError::NotFound),
+        RuleWrite::Invalid(message) => Err(AppError::Validation(message)),
+    }
+}
+
+pub async fn create_rule(
+    State(service): State<RuleService>,
+    ctx: RequestContext,
+    Json(request): Json<RuleRequest>,
+) -> Result<(StatusCode, Json<Rule>), AppError> {
+    request.validate()?;
+
+    let rule = saved(service.create_rule(&ctx, request).await?)?;
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+pub async fn list_rules(State(service): State<RuleService>) -> Result<Json<Vec<Rule>>, AppError> {
+    let rules = service.list_rules().await?;
+    Ok(Json(rules))
+}
+
+pub async fn get_rule(
+    State(service): State<RuleService>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Rule>, AppError> {
+    let rule = service.get_rule(id).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(rule))
+}
+
+pub async fn update_rule(
+    State(service): State<RuleService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+    Json(request): Json<RuleRequest>,
+) -> Result<Json<Rule>, AppError> {
+    request.validate()?;
+
+    let rule = saved(service.update_rule(&ctx, id, request).await?)?;
+    Ok(Json(rule))
+}
+
+pub async fn delete_rule(
+    State(service): State<RuleService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    if service.delete_rule(&ctx, id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+pub async fn dry_run_rules(
+    State(service): State<RuleService>,
+    Json(request): Json<RuleRunRequest>,
+) -> Result<Json<RuleRunResponse>, AppError> {
+    let report = service.dry_run(&request).await?;
+    Ok(Json(report))
+}
+
+pub async fn apply_rules(
+    State(service): State<RuleService>,
+    ctx: RequestContext,
+    Json(request): Json<RuleRunRequest>,
+) -> Result<Json<RuleRunResponse>, AppError> {
+    let report = service.apply(&ctx, &request).await?;
+    Ok(Json(report))
+}
@@ -0,0 +1,46 @@
+use crate::context::RequestContext;
+use crate::error::AppError;
+use crate::models::tag::{MergeTagRequest, RenameTagRequest, Tag};
+use crate::services::tag_service::{TagService, TagWrite};
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+fn saved(outcome: TagWrite) -> Result<Tag, AppError> {
+    match outcome {
+        TagWrite::Saved(tag) => Ok(tag),
+        TagWrite::NotFound => Err(AppError::NotFound),
+        TagWrite::Conflict(message) => Err(AppError::Conflict(message)),
+        TagWrite::Invalid(message) => Err(AppError::Validation(message)),
+    }
+}
+
+pub async fn list_tags(State(service): State<TagService>) -> Result<Json<Vec<Tag>>, AppError> {
+    let tags = service.list_tags().await?;
+    Ok(Json(tags))
+}
+
+pub async fn rename_tag(
+    State(service): State<TagService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+    Json(request): Json<RenameTagRequest>,
+) -> Result<Json<Tag>, AppError> {
+    request.validate()?;
+
+    let tag = saved(service.rename_tag(&ctx, id, request).await?)?;
+    Ok(Json(tag))
+}
+
+pub async fn merge_tag(
+    State(service): State<TagService>,
+    ctx: RequestContext,
+    Path(id): Path<Uuid>,
+    Json(request): Json<MergeTagRequest>,
+) -> Result<Json<Tag>, AppError> {
+    let tag = saved(service.merge_tag(&ctx, id, request).await?)?;
+    Ok(Json(tag))
+}
@@ -0,0 +1,169 @@
+use crate::context::RequestContext;
+use crate::models::backup::BackupKind;
+use crate::services::attachment_service::AttachmentService;
+use crate::services::backup_service::BackupService;
+use crate::services::expense_service::ExpenseService;
+use crate::services::idempotency_service::IdempotencyService;
+use chrono::Utc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+const IDEMPOTENCY_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const BACKUP_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Periodically purges expenses that have been in the trash longer than
+/// `retention`, then deletes attachment files nothing refers to any more
+/// when the backend stores attachments.
+/// Stops before the next run once `shutdown` is cancelled; a run in
+/// progress is finished first.
+pub fn spawn_trash_purge(
+    service: ExpenseService,
+    attachments: Option<AttachmentService>,
+    retention: Duration,
+    interval: Duration,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        while next_tick(&mut ticker, &shutdown).await {
+            let Some(cutoff) = chrono::Duration::from_std(retention)
+                .ok()
+                .and_then(|retention| Utc::now().checked_sub_signed(retention))
+            else {
+                tracing::error!(?retention, "Trash retention is out of range");
+                return;
+            };
+
+            match service
+                .purge_deleted_before(&RequestContext::system(), cutoff)
+                .await
+            {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!(purged, "Purged expired expenses from trash"),
+                Err(e) => tracing::error!(error = format!("{e:#}"), "Trash purge failed"),
+            }
+
+            let Some(attachments) = &attachments else {
+                continue;
+            };
+            match attachments.remove_orphaned_files().await {
+                Ok(0) => {}
+                Ok(removed) => tracing::info!(removed, "Removed unreferenced attachment files"),
+                Err(e) => tracing::error!(error = format!("{e:#}"), "Attachment cleanup failed"),
+            }
+        }
+        tracing::info!("Trash purge stopped");
+    })
+}
+
+/// Periodically forgets idempotency keys older than their retention window.
+pub fn spawn_idempotency_purge(
+    service: IdempotencyService,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(IDEMPOTENCY_PURGE_INTERVAL);
+        while next_tick(&mut ticker, &shutdown).await {
+            match service.purge_expired().await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!(purged, "Purged expired idempotency keys"),
+                Err(e) => tracing::error!(error = format!("{e:#}"), "Idempotency key purge failed"),
+            }
+        }
+        tracing::info!("Idempotency key purge stopped");
+    })
+}
+
+/// Takes a snapshot whenever the newest one is older than `interval`, so
+/// restarting the server neither skips nor repeats one. Rotation keeps only
+/// the newest ones.
+pub fn spawn_scheduled_backups(
+    service: BackupService,
+    interval: Duration,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval.min(BACKUP_CHECK_INTERVAL));
+        while next_tick(&mut ticker, &shutdown).await {
+            let due = match service.list().await {
+                Ok(backups) => backups.first().is_none_or(|newest| {
+                    (Utc::now() - newest.created_at)
+                        .to_std()
+                        .is_ok_and(|age| age >= interval)
+                }),
+                Err(e) => {
+                    tracing::error!(error = format!("{e:#}"), "Listing backups failed");
+                    continue;
+                }
+            };
+            if !due {
+                continue;
+            }
+            if let Err(e) = service.create(BackupKind::Scheduled).await {
+                tracing::error!(error = format!("{e:#}"), "Scheduled backup failed");
+            }
+        }
+        tracing::info!("Scheduled backups stopped");
+    })
+}
+
+/// Waits for the next run; `false` once the job should stop.
+async fn next_tick(ticker: &mut tokio::time::Interval, shutdown: &CancellationToken) -> bool {
+    tokio::select! {
+        biased;
+        _ = shutdown.cancelled() => false,
+        _ = ticker.tick() => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_jobs_stop_on_shutdown() {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        let shutdown = CancellationToken::new();
+        let purge = spawn_idempotency_purge(
+            IdempotencyService::new(pool, Duration::from_secs(60)),
+            shutdown.clone(),
+        );
+
+        shutdown.cancel();
+        tokio::time::timeout(Duration::from_secs(5), purge)
+            .await
+            .expect("job stops promptly")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_trash_purge_removes_expired_expenses() {
+        let mut trashed = crate::models::expense::Expense::new(12.5, "Food".to_string());
+        trashed.deleted_at = Some(Utc::now() - chrono::Duration::days(2));
+        let service = ExpenseService::with_repository(std::sync::Arc::new(
+            crate::repository::InMemoryExpenseRepository::with_expenses(vec![trashed]),
+        ));
+        let shutdown = CancellationToken::new();
+        let purge = spawn_trash_purge(
+            service.clone(),
+            None,
+            Duration::from_secs(60 * 60 * 24),
+            Duration::from_millis(10),
+            shutdown.clone(),
+        );
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !service.get_trash().await.unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("expired expense is purged");
+
+        shutdown.cancel();
+        purge.await.unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+use crate::context::{ACTOR_HEADER, REQUEST_ID_HEADER};
+use crate::metrics::Metrics;
+use axum::extract::MatchedPath;
+use axum::http::{Request, Response};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::{Span, field};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Used when `RUST_LOG` is unset or invalid.
+const DEFAULT_FILTER: &str = "info";
+const REDACTED: &str = "[redacted]";
+
+static REDACT: AtomicBool = AtomicBool::new(true);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human-readable lines for a terminal.
+    #[default]
+    Pretty,
+    /// One JSON object per event, for log collectors.
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "pretty" => Some(LogFormat::Pretty),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Installs the global subscriber. Levels come from `RUST_LOG`, e.g.
+/// `RUST_LOG=expence_tracker=debug,tower_http=warn`; the filter applies to
+/// the log output only, so `metrics` still sees every database statement.
+pub fn init(format: LogFormat, redact: bool, metrics: &Metrics) {
+    REDACT.store(redact, Ordering::Relaxed);
+
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    // Logs go to stderr, so the output of commands such as `backup list`
+    // stays parseable on stdout.
+    let output = match format {
+        LogFormat::Pretty => tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(output.with_filter(filter))
+        .with(metrics.query_layer())
+        .init();
+}
+
+/// A value that says something about a person or their money, such as an
+/// actor or an amount. It is logged as `[redacted]` unless redaction was
+/// turned off with `LOG_REDACT=false`.
+pub struct Sensitive<T>(pub T);
+
+impl<T: fmt::Display> fmt::Display for Sensitive<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if REDACT.load(Ordering::Relaxed) {
+            f.write_str(REDACTED)
+        } else {
+            self.0.fmt(f)
+        }
+    }
+}
+
+/// The span every request is handled in. `route` is the matched route
+/// template rather than the path, so ids in URLs are not logged.
+pub fn request_span<B>(request: &Request<B>) -> Span {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .unwrap_or("unmatched");
+
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route,
+        request_id = header(REQUEST_ID_HEADER),
+        actor = %Sensitive(header(ACTOR_HEADER)),
+        status = field::Empty,
+        latency_ms = field::Empty,
+    )
+}
+
+/// Records the outcome on the request span and logs one line per request.
+pub fn record_response<B>(response: &Response<B>, latency: Duration, span: &Span) {
+    let status = response.status();
+    span.record("status", status.as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+
+    if status.is_server_error() {
+        tracing::error!("request failed");
+    } else if status.is_client_error() {
+        tracing::warn!("request rejected");
+    } else {
+        tracing::info!("request finished");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_format() {
+        assert_eq!(LogFormat::parse("json"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::parse(" Pretty "), Some(LogFormat::Pretty));
+        assert_eq!(LogFormat::parse("xml"), None);
+    }
+
+    #[test]
+    fn test_sensitive_values_are_redacted_unless_disabled() {
+        assert_eq!(Sensitive(42.5).to_string(), REDACTED);
+
+        REDACT.store(false, Ordering::Relaxed);
+        let shown = Sensitive("Groceries").to_string();
+        REDACT.store(true, Ordering::Relaxed);
+        assert_eq!(shown, "Groceries");
+    }
+}
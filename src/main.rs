@@ -1,40 +1,367 @@
 use axum::{
     Router,
-    routing::{get, post},
+    extract::{DefaultBodyLimit, FromRef},
+    middleware::{from_fn, from_fn_with_state},
+    routing::{delete, get, post, put},
 };
+use repository::{Backend, InMemoryExpenseRepository, PostgresExpenseRepository};
+use services::expense_service::ExpenseService;
 use std::net::SocketAddr;
-use tower::ServiceBuilder;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use tower_http::cors::CorsLayer;
+use tower_http::trace::TraceLayer;
 
+mod cli;
+mod config;
+mod context;
 mod database;
+mod demo;
+mod encryption;
 mod error;
 mod handlers;
+mod jobs;
+mod logging;
+mod metrics;
+mod middleware;
 mod models;
+mod repository;
 mod services;
+mod shutdown;
+mod state;
+mod storage;
 
-use handlers::expenses::{add_expense, get_all_expenses, get_highest_expense};
-use services::expense_service::ExpenseService;
+use config::Config;
+use handlers::accounts::{
+    create_account, create_transfer, delete_account, delete_transfer, get_account, get_balance,
+    get_balances, get_statement, list_accounts, list_transfers, update_account,
+};
+use handlers::admin::{check_integrity, create_backup, list_backups, verify_backup};
+use handlers::attachments::{
+    delete_attachment, download_attachment, download_thumbnail, get_attachment, list_attachments,
+    upload_attachment,
+};
+use handlers::audit::{get_expense_history, query_audit_log, verify_audit_log};
+use handlers::expense_reports::{
+    add_report_comment, add_report_expense, create_report, delete_report, export_report,
+    get_report, list_reports, remove_report_expense, transition_report,
+};
+use handlers::expenses::{
+    add_expense, delete_expense, get_all_expenses, get_expense, get_expense_summary,
+    get_highest_expense, get_trash, restore_expense, run_batch, search_expenses, suggest_category,
+    update_expense,
+};
+use handlers::health::{healthz, readyz};
+use handlers::income::{
+    add_income, delete_income, get_cash_flow, get_income, list_income, update_income,
+};
+use handlers::metrics::get_metrics;
+use handlers::payees::{
+    add_alias, create_payee, delete_payee, get_payee, get_payee_summaries, get_payee_summary,
+    list_payees, remove_alias, rename_payee, resolve_payee,
+};
+use handlers::refunds::{add_refund, delete_refund, get_refund, list_refunds};
+use handlers::rules::{
+    apply_rules, create_rule, delete_rule, dry_run_rules, get_rule, list_rules, update_rule,
+};
+use handlers::tags::{list_tags, merge_tag, rename_tag};
+use state::{AppState, RepositoryState};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let database_url = "sqlite:./expenses.db";
-    let pool = database::create_pool(database_url).await?;
+    let config = Config::from_env()?;
+    let metrics = metrics::Metrics::new();
+    logging::init(config.log_format, config.log_redact, &metrics);
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = cli::BackupCommand::parse(&args)? {
+        return cli::run_backup(&config, command).await;
+    }
+    if let Some(command) = cli::EncryptionCommand::parse(&args)? {
+        return cli::run_encryption(&config, command).await;
+    }
+
+    // Cancelled on SIGTERM/SIGINT: readiness turns unready and the
+    // background jobs stop.
+    let shutdown = CancellationToken::new();
+    if args.iter().any(|arg| arg == "--demo") {
+        run_demo(&config, metrics, shutdown).await?;
+    } else {
+        match Backend::from_url(&config.database_url)? {
+            Backend::Sqlite => run_sqlite(&config, metrics, shutdown).await?,
+            Backend::Postgres => run_postgres(&config, metrics, shutdown).await?,
+        }
+    }
+    tracing::info!("Shut down cleanly");
 
-    let expense_service = ExpenseService::new(pool);
+    Ok(())
+}
+
+/// Serves every feature from the SQLite database.
+async fn run_sqlite(
+    config: &Config,
+    metrics: metrics::Metrics,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let pool = database::create_pool_with(&config.database_url, config.sqlite_pool.clone()).await?;
+    let state = AppState::new(pool.clone(), config, metrics, shutdown.clone());
+
+    let mut jobs = vec![
+        jobs::spawn_trash_purge(
+            state.expenses.clone(),
+            Some(state.attachments.clone()),
+            config.trash_retention,
+            config.trash_purge_interval,
+            shutdown.clone(),
+        ),
+        jobs::spawn_idempotency_purge(state.idempotency.clone(), shutdown.clone()),
+    ];
+    if let Some(interval) = config.backup_interval {
+        jobs.push(jobs::spawn_scheduled_backups(
+            state.backups.clone(),
+            interval,
+            shutdown.clone(),
+        ));
+    }
+
+    // The audit log and backups expose every expense: they need the admin
+    // token, and browsers on other origins may not call them.
+    let admin = Router::new()
+        .route("/audit", get(query_audit_log))
+        .route("/audit/verify", get(verify_audit_log))
+        .route("/admin/integrity", get(check_integrity))
+        .route("/admin/backups", post(create_backup).get(list_backups))
+        .route("/admin/backups/{name}/verify", get(verify_backup))
+        .route_layer(from_fn_with_state(
+            config.admin_token.clone(),
+            middleware::admin::require_admin,
+        ));
+
+    let app = expense_routes()
+        .route("/expenses/batch", post(run_batch))
+        .route("/expenses/search", get(search_expenses))
+        .route("/expenses/suggest-category", post(suggest_category))
+        .route("/expenses/{id}/history", get(get_expense_history))
+        .route(
+            "/expenses/{id}/attachments",
+            post(upload_attachment)
+                .get(list_attachments)
+                .layer(DefaultBodyLimit::max(config.max_request_bytes())),
+        )
+        .route(
+            "/attachments/{id}",
+            get(get_attachment).delete(delete_attachment),
+        )
+        .route("/expenses/{id}/refunds", post(add_refund).get(list_refunds))
+        .route("/refunds/{id}", get(get_refund).delete(delete_refund))
+        .route("/attachments/{id}/content", get(download_attachment))
+        .route("/attachments/{id}/thumbnail", get(download_thumbnail))
+        .route("/payees", post(create_payee).get(list_payees))
+        .route("/payees/resolve", get(resolve_payee))
+        .route("/payees/summary", get(get_payee_summaries))
+        .route(
+            "/payees/{id}",
+            get(get_payee).put(rename_payee).delete(delete_payee),
+        )
+        .route("/payees/{id}/aliases", post(add_alias))
+        .route("/payees/{id}/aliases/{alias_id}", delete(remove_alias))
+        .route("/payees/{id}/summary", get(get_payee_summary))
+        .route("/rules", post(create_rule).get(list_rules))
+        .route("/rules/dry-run", post(dry_run_rules))
+        .route("/rules/apply", post(apply_rules))
+        .route(
+            "/rules/{id}",
+            get(get_rule).put(update_rule).delete(delete_rule),
+        )
+        .route("/tags", get(list_tags))
+        .route("/tags/{id}", put(rename_tag))
+        .route("/tags/{id}/merge", post(merge_tag))
+        .route("/accounts", post(create_account).get(list_accounts))
+        .route("/accounts/balances", get(get_balances))
+        .route(
+            "/accounts/{id}",
+            get(get_account).put(update_account).delete(delete_account),
+        )
+        .route("/accounts/{id}/balance", get(get_balance))
+        .route("/accounts/{id}/statement", get(get_statement))
+        .route("/transfers", post(create_transfer).get(list_transfers))
+        .route("/transfers/{id}", delete(delete_transfer))
+        .route("/expense-reports", post(create_report).get(list_reports))
+        .route(
+            "/expense-reports/{id}",
+            get(get_report).delete(delete_report),
+        )
+        .route("/expense-reports/{id}/expenses", post(add_report_expense))
+        .route(
+            "/expense-reports/{id}/expenses/{expense_id}",
+            delete(remove_report_expense),
+        )
+        .route("/expense-reports/{id}/transitions", post(transition_report))
+        .route("/expense-reports/{id}/comments", post(add_report_comment))
+        .route("/expense-reports/{id}/export", get(export_report))
+        .route("/income", post(add_income).get(list_income))
+        .route(
+            "/income/{id}",
+            get(get_income).put(update_income).delete(delete_income),
+        )
+        .route("/reports/cash-flow", get(get_cash_flow))
+        .route("/metrics", get(get_metrics))
+        .route("/readyz", get(readyz))
+        .layer(CorsLayer::permissive())
+        .merge(admin)
+        .layer(from_fn_with_state(
+            state.idempotency.clone(),
+            middleware::idempotency::idempotency,
+        ))
+        .layer(from_fn_with_state(
+            state.metrics.clone(),
+            metrics::track_requests,
+        ))
+        .with_state(state);
+
+    serve(app, &config.database_url_for_display(), shutdown).await?;
+    wait_for(jobs).await;
+    pool.close().await;
+
+    Ok(())
+}
 
-    let app = Router::new()
+/// Serves the expenses stored in PostgreSQL. Only the routes
+/// [`repository::ExpenseRepository`] covers are available, plus readiness,
+/// metrics and idempotent retries; everything else still needs SQLite.
+async fn run_postgres(
+    config: &Config,
+    metrics: metrics::Metrics,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    if config.encryption_key.is_some() {
+        tracing::warn!("ENCRYPTION_KEY encrypts SQLite databases only; PostgreSQL data is not");
+    }
+    let repository = PostgresExpenseRepository::connect(&config.database_url).await?;
+    let pool = repository.pool().clone();
+    let expenses = ExpenseService::with_repository(Arc::new(repository));
+    let state = RepositoryState::new(pool.clone(), expenses, config, metrics, shutdown.clone());
+
+    serve_repository(state, config, &config.database_url_for_display(), shutdown).await?;
+    pool.close().await;
+
+    Ok(())
+}
+
+/// Generated expenses from memory, with the same routes and middleware as
+/// PostgreSQL mode. Idempotency keys go to an in-memory SQLite database, so
+/// nothing is written to disk; changes are lost on exit.
+async fn run_demo(
+    config: &Config,
+    metrics: metrics::Metrics,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let expenses = demo::generate(demo::DEMO_EXPENSES, demo::DEMO_SEED, chrono::Utc::now());
+    tracing::info!(expenses = expenses.len(), "Serving generated demo data");
+    let expenses = ExpenseService::with_repository(Arc::new(
+        InMemoryExpenseRepository::with_expenses(expenses),
+    ));
+    let pool =
+        database::create_pool_with("sqlite::memory:", database::PoolSettings::default()).await?;
+    let state = RepositoryState::new(pool.clone(), expenses, config, metrics, shutdown.clone());
+
+    serve_repository(state, config, "memory (demo)", shutdown).await?;
+    pool.close().await;
+
+    Ok(())
+}
+
+/// The expense routes with readiness, metrics and idempotent retries, for
+/// the backends [`RepositoryState`] serves, until `shutdown` fires.
+async fn serve_repository(
+    state: RepositoryState,
+    config: &Config,
+    display: &str,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let jobs = vec![
+        jobs::spawn_trash_purge(
+            state.expenses.clone(),
+            None,
+            config.trash_retention,
+            config.trash_purge_interval,
+            shutdown.clone(),
+        ),
+        jobs::spawn_idempotency_purge(state.idempotency.clone(), shutdown.clone()),
+    ];
+
+    let app = expense_routes()
+        .route("/metrics", get(get_metrics))
+        .route("/readyz", get(readyz))
+        .layer(CorsLayer::permissive())
+        .layer(from_fn_with_state(
+            state.idempotency.clone(),
+            middleware::idempotency::idempotency,
+        ))
+        .layer(from_fn_with_state(
+            state.metrics.clone(),
+            metrics::track_requests,
+        ))
+        .with_state(state);
+
+    serve(app, display, shutdown).await?;
+    wait_for(jobs).await;
+
+    Ok(())
+}
+
+/// The routes every backend serves.
+fn expense_routes<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    ExpenseService: FromRef<S>,
+{
+    Router::new()
         .route("/expenses", post(add_expense))
         .route("/expenses", get(get_all_expenses))
         .route("/expenses/highest", get(get_highest_expense))
-        .layer(ServiceBuilder::new().layer(CorsLayer::permissive()))
-        .with_state(expense_service);
+        .route("/expenses/summary", get(get_expense_summary))
+        .route("/expenses/trash", get(get_trash))
+        .route(
+            "/expenses/{id}",
+            get(get_expense).put(update_expense).delete(delete_expense),
+        )
+        .route("/expenses/{id}/restore", post(restore_expense))
+        .route("/healthz", get(healthz))
+}
+
+/// Serves `app` until SIGTERM/SIGINT, then lets in-flight requests finish.
+async fn serve(app: Router, database: &str, shutdown: CancellationToken) -> anyhow::Result<()> {
+    let app = app
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(logging::request_span)
+                .on_request(())
+                .on_response(logging::record_response)
+                .on_failure(()),
+        )
+        .layer(from_fn(middleware::request_id::request_id));
 
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-    println!("Expense Tracker API is running on  http://{}", addr);
-    println!("Database: {}", database_url);
+    tracing::info!(%addr, database, "Expense Tracker API is running");
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            shutdown::signal().await;
+            tracing::info!("Shutting down; finishing in-flight requests");
+            shutdown.cancel();
+        })
+        .await?;
 
     Ok(())
 }
+
+async fn wait_for(jobs: Vec<JoinHandle<()>>) {
+    for job in jobs {
+        if let Err(e) = job.await {
+            tracing::error!(error = %e, "Background job panicked");
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+/// sqlx reports every statement it runs as an event with this target.
+const QUERY_TARGET: &str = "sqlx::query";
+/// Latency buckets in seconds, from a cached SQLite read to a slow export.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+/// Categories are free text, so only the ones with the most expenses get a
+/// label of their own; the rest are summed under [`OTHER_CATEGORY`], which
+/// keeps the number of series bounded whatever clients send.
+const MAX_CATEGORY_LABELS: usize = 20;
+const OTHER_CATEGORY: &str = "other";
+
+/// Prometheus collectors. Request and query metrics are recorded as they
+/// happen; pool and business gauges are refreshed when `/metrics` is scraped.
+#[derive(Clone)]
+pub struct Metrics {
+    inner: Arc<Collectors>,
+}
+
+struct Collectors {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    query_duration: HistogramVec,
+    pool_connections: IntGaugeVec,
+    pool_max_connections: IntGauge,
+    expenses: IntGaugeVec,
+    expense_amount: GaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let histogram = |name: &str, help: &str, labels: &[&str]| {
+            HistogramVec::new(
+                HistogramOpts::new(name, help).buckets(LATENCY_BUCKETS.to_vec()),
+                labels,
+            )
+            .expect("valid histogram")
+        };
+        let collectors = Collectors {
+            registry: Registry::new(),
+            http_requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "HTTP requests handled"),
+                &["method", "route", "status"],
+            )
+            .expect("valid counter"),
+            http_duration: histogram(
+                "http_request_duration_seconds",
+                "Time to handle an HTTP request",
+                &["method", "route"],
+            ),
+            query_duration: histogram(
+                "db_query_duration_seconds",
+                "Time to run a database statement",
+                &["operation"],
+            ),
+            pool_connections: IntGaugeVec::new(
+                Opts::new("db_pool_connections", "Open database connections"),
+                &["state"],
+            )
+            .expect("valid gauge"),
+            pool_max_connections: IntGauge::new(
+                "db_pool_max_connections",
+                "Most connections the pool will open",
+            )
+            .expect("valid gauge"),
+            expenses: IntGaugeVec::new(
+                Opts::new("expenses", "Non-deleted expenses"),
+                &["category"],
+            )
+            .expect("valid gauge"),
+            expense_amount: GaugeVec::new(
+                Opts::new(
+                    "expense_amount",
+                    "Sum of non-deleted expenses, net of refunds",
+                ),
+                &["category"],
+            )
+            .expect("valid gauge"),
+        };
+
+        let registry = &collectors.registry;
+        registry
+            .register(Box::new(collectors.http_requests.clone()))
+            .and_then(|_| registry.register(Box::new(collectors.http_duration.clone())))
+            .and_then(|_| registry.register(Box::new(collectors.query_duration.clone())))
+            .and_then(|_| registry.register(Box::new(collectors.pool_connections.clone())))
+            .and_then(|_| registry.register(Box::new(collectors.pool_max_connections.clone())))
+            .and_then(|_| registry.register(Box::new(collectors.expenses.clone())))
+            .and_then(|_| registry.register(Box::new(collectors.expense_amount.clone())))
+            .expect("collectors have distinct names");
+
+        Self {
+            inner: Arc::new(collectors),
+        }
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        self.inner
+            .http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.inner
+            .http_duration
+            .with_label_values(&[method, route])
+            .observe(seconds);
+    }
+
+    pub fn observe_query(&self, operation: &str, seconds: f64) {
+        self.inner
+            .query_duration
+            .with_label_values(&[operation])
+            .observe(seconds);
+    }
+
+    pub fn set_pool(&self, idle: usize, in_use: usize, max: u32) {
+        let gauges = &self.inner.pool_connections;
+        gauges.with_label_values(&["idle"]).set(idle as i64);
+        gauges.with_label_values(&["in_use"]).set(in_use as i64);
+        self.inner.pool_max_connections.set(i64::from(max));
+    }
+
+    /// Replaces the per-category gauges, dropping categories that are gone.
+    /// Beyond the [`MAX_CATEGORY_LABELS`] largest, categories are counted
+    /// under [`OTHER_CATEGORY`].
+    pub fn set_categories<'a>(&self, categories: impl IntoIterator<Item = (&'a str, i64, f64)>) {
+        let mut categories: Vec<_> = categories.into_iter().collect();
+        categories.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let mut labels: BTreeMap<&str, (i64, f64)> = BTreeMap::new();
+        for (rank, (category, count, amount)) in categories.into_iter().enumerate() {
+            let label = if rank < MAX_CATEGORY_LABELS {
+                category
+            } else {
+                OTHER_CATEGORY
+            };
+            let totals = labels.entry(label).or_default();
+            totals.0 += count;
+            totals.1 += amount;
+        }
+
+        self.inner.expenses.reset();
+        self.inner.expense_amount.reset();
+        for (category, (count, amount)) in labels {
+            self.inner
+                .expenses
+                .with_label_values(&[category])
+                .set(count);
+            self.inner
+                .expense_amount
+                .with_label_values(&[category])
+                .set(amount);
+        }
+    }
+
+    /// Everything in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.inner.registry.gather(), &mut buffer)
+            .expect("text encoding does not fail");
+        String::from_utf8(buffer).expect("text encoding is UTF-8")
+    }
+
+    /// A tracing layer that times database statements from sqlx's own
+    /// statement events, whatever level the logs are filtered to.
+    pub fn query_layer<S>(&self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        QueryTimings {
+            metrics: self.clone(),
+        }
+        .with_filter(filter_fn(|metadata| metadata.target() == QUERY_TARGET))
+    }
+}
+
+/// Counts and times every request by its route template, so ids in paths do
+/// not each get their own series.
+pub async fn track_requests(
+    State(metrics): State<Metrics>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    metrics.observe_request(
+        &method,
+        &route,
+        response.status().as_u16(),
+        started.elapsed().as_secs_f64(),
+    );
+    response
+}
+
+struct QueryTimings {
+    metrics: Metrics,
+}
+
+impl<S: Subscriber> Layer<S> for QueryTimings {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = QueryFields::default();
+        event.record(&mut fields);
+        if let Some(seconds) = fields.elapsed_secs {
+            self.metrics
+                .observe_query(operation(&fields.summary), seconds);
+        }
+    }
+}
+
+#[derive(Default)]
+struct QueryFields {
+    summary: String,
+    elapsed_secs: Option<f64>,
+}
+
+impl Visit for QueryFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "elapsed_secs" {
+            self.elapsed_secs = Some(value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "summary" {
+            self.summary = value.to_string();
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "summary" {
+            self.summary = format!("{value:?}").trim_matches('"').to_string();
+        }
+    }
+}
+
+/// The statement's kind, e.g. `select`; keeps the label set small.
+fn operation(summary: &str) -> &'static str {
+    let keyword = summary
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match keyword.as_str() {
+        "select" | "with" => "select",
+        "insert" => "insert",
+        "update" => "update",
+        "delete" => "delete",
+        "begin" | "commit" | "rollback" | "savepoint" | "release" => "transaction",
+        "create" | "alter" | "drop" => "schema",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_encodes_recorded_metrics() {
+        let metrics = Metrics::new();
+        metrics.observe_request("GET", "/expenses/{id}", 200, 0.003);
+        metrics.set_pool(2, 1, 5);
+        metrics.set_categories([("Food", 3, 42.5)]);
+        metrics.set_categories([("Travel", 1, 10.0)]);
+
+        let text = metrics.encode();
+        assert!(text.contains(
+            r#"http_requests_total{method="GET",route="/expenses/{id}",status="200"} 1"#
+        ));
+        assert!(text.contains(
+            r#"http_request_duration_seconds_bucket{method="GET",route="/expenses/{id}",le="0.005"} 1"#
+        ));
+        assert!(text.contains(r#"db_pool_connections{state="in_use"} 1"#));
+        assert!(text.contains("db_pool_max_connections 5"));
+        assert!(text.contains(r#"expenses{category="Travel"} 1"#));
+        assert!(!text.contains(r#"category="Food""#));
+    }
+
+    #[test]
+    fn test_category_labels_are_bounded() {
+        let metrics = Metrics::new();
+        let names: Vec<String> = (0..MAX_CATEGORY_LABELS + 5)
+            .map(|i| format!("Category {i:02}"))
+            .collect();
+        metrics.set_categories(
+            names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.as_str(), 100 - i as i64, 1.0)),
+        );
+
+        let text = metrics.encode();
+        let series = text
+            .lines()
+            .filter(|line| line.starts_with("expenses{"))
+            .count();
+        assert_eq!(series, MAX_CATEGORY_LABELS + 1);
+        assert!(text.contains(r#"expenses{category="Category 00"} 100"#));
+        assert!(!text.contains(r#"category="Category 20""#));
+        // Categories 20 to 24 have 80 down to 76 expenses.
+        assert!(text.contains(r#"expenses{category="other"} 390"#));
+        assert!(text.contains(r#"expense_amount{category="other"} 5"#));
+    }
+
+    #[test]
+    fn test_query_layer_times_sqlx_statements() {
+        let metrics = Metrics::new();
+        let subscriber = tracing_subscriber::registry().with(metrics.query_layer());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        // The shape of the events sqlx emits for each statement.
+        tracing::debug!(
+            target: "sqlx::query",
+            summary = "SELECT id, amount …",
+            elapsed_secs = 0.002,
+        );
+        tracing::debug!(target: "sqlx::query", summary = "INSERT INTO expenses …", elapsed_secs = 0.2);
+        tracing::debug!(target: "expence_tracker", elapsed_secs = 1.0, "not a statement");
+
+        let text = metrics.encode();
+        assert!(
+            text.contains(r#"db_query_duration_seconds_bucket{operation="select",le="0.0025"} 1"#)
+        );
+        assert!(text.contains(r#"db_query_duration_seconds_count{operation="insert"} 1"#));
+        assert!(!text.contains(r#"operation="other""#));
+    }
+
+    #[test]
+    fn test_operation_from_summary() {
+        assert_eq!(operation("SELECT id, amount …"), "select");
+        assert_eq!(operation("insert into expenses …"), "insert");
+        assert_eq!(operation("PRAGMA foreign_keys"), "other");
+        assert_eq!(operation(""), "other");
+    }
+}
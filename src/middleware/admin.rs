@@ -0,0 +1,84 @@
+use crate::context::Credential;
+use crate::error::AppError;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+/// Lets a request through only when it carries `Authorization: Bearer
+/// <ADMIN_TOKEN>`. Without a configured token the routes stay closed, since
+/// the audit log and backups expose every expense.
+pub async fn require_admin(
+    State(admin_token): State<Option<Credential>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(admin_token) = admin_token else {
+        return Err(AppError::Forbidden(
+            "Admin routes are disabled; set ADMIN_TOKEN to enable them".to_string(),
+        ));
+    };
+    match Credential::from_headers(request.headers()) {
+        Some(credential) if credential == admin_token => Ok(next.run(request).await),
+        Some(_) => Err(AppError::Forbidden(
+            "The bearer token is not the admin token".to_string(),
+        )),
+        None => Err(AppError::Unauthorized(
+            "Admin routes need Authorization: Bearer <ADMIN_TOKEN>".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        http::{StatusCode, header},
+        middleware::from_fn_with_state,
+        routing::get,
+    };
+    use tower::ServiceExt;
+
+    fn create_app(admin_token: Option<&str>) -> Router {
+        Router::new()
+            .route("/audit", get(|| async { "entries" }))
+            .layer(from_fn_with_state(
+                admin_token.map(Credential::new),
+                require_admin,
+            ))
+    }
+
+    async fn get_audit(app: Router, authorization: Option<&str>) -> Response {
+        let mut builder = Request::builder().uri("/audit");
+        if let Some(authorization) = authorization {
+            builder = builder.header(header::AUTHORIZATION, authorization);
+        }
+        app.oneshot(builder.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_admin_token_is_required() {
+        let app = create_app(Some("s3cret"));
+
+        let allowed = get_audit(app.clone(), Some("Bearer s3cret")).await;
+        let missing = get_audit(app.clone(), None).await;
+        let wrong = get_audit(app, Some("Bearer guess")).await;
+
+        assert_eq!(allowed.status(), StatusCode::OK);
+        assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(missing.headers()[header::WWW_AUTHENTICATE], "Bearer");
+        assert_eq!(wrong.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_routes_are_closed_without_admin_token() {
+        let response = get_audit(create_app(None), Some("Bearer anything")).await;
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
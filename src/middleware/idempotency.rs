@@ -0,0 +1,271 @@
+use crate::context::RequestContext;
+use crate::error::AppError;
+use crate::services::idempotency_service::{Claim, IdempotencyService, KeyScope, StoredResponse};
+use axum::{
+    body::{Body, to_bytes},
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderName, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+const MAX_KEY_LEN: usize = 255;
+/// Response headers worth replaying; everything else is recomputed.
+const REPLAYED_HEADERS: [HeaderName; 3] = [header::CONTENT_TYPE, header::ETAG, header::LOCATION];
+
+/// Makes mutating requests that carry an `Idempotency-Key` safe to retry:
+/// the first response is stored and replayed for repeats of the same
+/// request, and reusing the key for a different request is rejected.
+pub async fn idempotency(
+    State(service): State<IdempotencyService>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return Ok(next.run(request).await);
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.run(request).await);
+    };
+
+    let key = key
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN)
+        .ok_or_else(|| {
+            AppError::Validation(format!(
+                "Idempotency-Key must be 1 to {} visible ASCII characters",
+                MAX_KEY_LEN
+            ))
+        })?
+        .to_string();
+
+    let (mut parts, body) = request.into_parts();
+    let body = to_bytes(body, service.max_body_bytes())
+        .await
+        .map_err(|_| AppError::Validation("Request body is too large".to_string()))?;
+
+    let Ok(ctx) = RequestContext::from_request_parts(&mut parts, &()).await;
+    let scope = KeyScope {
+        actor: ctx.actor,
+        key,
+        method: parts.method.to_string(),
+        path: parts.uri.path().to_string(),
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(parts.uri.query().unwrap_or_default().as_bytes());
+    hasher.update([0]);
+    hasher.update(&body);
+    let request_hash = hex::encode(hasher.finalize());
+
+    match service.claim(&scope, &request_hash).await? {
+        Claim::Acquired => {}
+        Claim::Replay(stored) => return Ok(replay(stored)),
+        Claim::Mismatch => {
+            return Err(AppError::Unprocessable(
+                "Idempotency-Key was already used for a different request".to_string(),
+            ));
+        }
+        Claim::InProgress => {
+            return Err(AppError::Conflict(
+                "A request with this Idempotency-Key is still being processed".to_string(),
+            ));
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    // Server-side failures are not remembered so the client can retry them.
+    if response.status().is_server_error() {
+        service.release(&scope).await?;
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, service.max_body_bytes()).await {
+        Ok(body) => body,
+        Err(_) => {
+            service.release(&scope).await?;
+            return Err(AppError::Internal);
+        }
+    };
+
+    let stored = StoredResponse {
+        status: parts.status.as_u16(),
+        headers: REPLAYED_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = parts.headers.get(name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect(),
+        body: body.to_vec(),
+    };
+    service.complete(&scope, &stored).await?;
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let mut response = (status, stored.body).into_response();
+
+    let headers = response.headers_mut();
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            headers.insert(name, value);
+        }
+    }
+    headers.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_pool;
+    use axum::{Router, extract::DefaultBodyLimit, middleware::from_fn_with_state, routing::post};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    async fn create_app() -> (Router, Arc<AtomicUsize>) {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+        let service = IdempotencyService::new(pool, Duration::from_secs(3600));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+
+        let app = Router::new()
+            .route(
+                "/expenses",
+                post(move |body: String| {
+                    let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move { format!("created #{} from {}", n, body) }
+                }),
+            )
+            .layer(from_fn_with_state(service, idempotency));
+
+        (app, calls)
+    }
+
+    fn post_request(key: Option<&str>, body: &str) -> Request {
+        let mut builder = Request::builder().method(Method::POST).uri("/expenses");
+        if let Some(key) = key {
+            builder = builder.header(IDEMPOTENCY_KEY_HEADER, key);
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_same_key_replays_first_response() {
+        let (app, calls) = create_app().await;
+
+        let first = app
+            .clone()
+            .oneshot(post_request(Some("abc"), "a"))
+            .await
+            .unwrap();
+        let retry = app
+            .clone()
+            .oneshot(post_request(Some("abc"), "a"))
+            .await
+            .unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(first.headers().get(REPLAYED_HEADER).is_none());
+        assert_eq!(retry.headers()[REPLAYED_HEADER], "true");
+        assert_eq!(body_text(first).await, "created #1 from a");
+        assert_eq!(body_text(retry).await, "created #1 from a");
+    }
+
+    #[tokio::test]
+    async fn test_key_reuse_with_different_body_is_rejected() {
+        let (app, calls) = create_app().await;
+
+        app.clone()
+            .oneshot(post_request(Some("abc"), "a"))
+            .await
+            .unwrap();
+        let reuse = app
+            .clone()
+            .oneshot(post_request(Some("abc"), "b"))
+            .await
+            .unwrap();
+
+        assert_eq!(reuse.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_key_are_not_deduplicated() {
+        let (app, calls) = create_app().await;
+
+        app.clone().oneshot(post_request(None, "a")).await.unwrap();
+        app.clone().oneshot(post_request(None, "a")).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_same_key_from_another_actor_is_not_replayed() {
+        let (app, calls) = create_app().await;
+        let from = |actor: &str| {
+            let mut request = post_request(Some("abc"), "a");
+            request.headers_mut().insert(
+                crate::context::ACTOR_HEADER,
+                HeaderValue::from_str(actor).unwrap(),
+            );
+            request
+        };
+
+        app.clone().oneshot(from("alice")).await.unwrap();
+        let bob = app.clone().oneshot(from("bob")).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(bob.headers().get(REPLAYED_HEADER).is_none());
+        assert_eq!(body_text(bob).await, "created #2 from a");
+    }
+
+    #[tokio::test]
+    async fn test_bodies_up_to_the_configured_limit_are_buffered() {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+        let body = "x".repeat(20 * 1024 * 1024);
+        let app = |service: IdempotencyService| {
+            Router::new()
+                .route(
+                    "/expenses",
+                    post(|body: String| async move { body.len().to_string() }),
+                )
+                .layer(DefaultBodyLimit::disable())
+                .layer(from_fn_with_state(service, idempotency))
+        };
+        let service = IdempotencyService::new(pool, Duration::from_secs(3600));
+
+        let refused = app(service.clone())
+            .oneshot(post_request(Some("big"), &body))
+            .await
+            .unwrap();
+        assert_eq!(refused.status(), StatusCode::BAD_REQUEST);
+
+        let accepted = app(service.with_max_body_bytes(32 * 1024 * 1024))
+            .oneshot(post_request(Some("big"), &body))
+            .await
+            .unwrap();
+        assert_eq!(accepted.status(), StatusCode::OK);
+        assert_eq!(body_text(accepted).await, body.len().to_string());
+    }
+}
@@ -0,0 +1,3 @@
+pub mod admin;
+pub mod idempotency;
+pub mod request_id;
@@ -0,0 +1,179 @@
+use crate::context::{REQUEST_ID_HEADER, header_value};
+use crate::error::Problem;
+use axum::{
+    body::to_bytes,
+    extract::Request,
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::future::Future;
+use uuid::Uuid;
+
+/// Extractor rejections are short plain-text messages.
+const MAX_REJECTION_BYTES: usize = 64 * 1024;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Runs `future` with `id` as the current request id.
+pub async fn scope<F: Future>(id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(id, future).await
+}
+
+/// The id of the request being handled, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Gives every request an `X-Request-Id`, generated when the client sent
+/// none, and echoes it on the response. The id is what `RequestContext`
+/// records with audit entries and what error bodies carry as
+/// `correlation_id`. Plain-text rejections from extractors and the router
+/// are turned into problem+json bodies like every other error.
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let id = header_value(request.headers(), REQUEST_ID_HEADER)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let value = HeaderValue::try_from(id.as_str()).expect("request id is a valid header value");
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, value.clone());
+
+    let mut response = scope(id.clone(), async move {
+        let response = next.run(request).await;
+        if needs_problem_body(&response) {
+            into_problem(response).await
+        } else {
+            response
+        }
+    })
+    .await;
+
+    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    response
+}
+
+fn needs_problem_body(response: &Response) -> bool {
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return false;
+    }
+    match response.headers().get(header::CONTENT_TYPE) {
+        None => true,
+        Some(content_type) => content_type
+            .to_str()
+            .is_ok_and(|value| value.starts_with("text/plain")),
+    }
+}
+
+async fn into_problem(response: Response) -> Response {
+    let (parts, body) = response.into_parts();
+    let status = parts.status;
+    let detail = to_bytes(body, MAX_REJECTION_BYTES)
+        .await
+        .ok()
+        .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+        .filter(|text| !text.is_empty())
+        .unwrap_or_else(|| status.canonical_reason().unwrap_or("Error").to_string());
+
+    let mut problem =
+        Problem::new(status, Problem::code_for_status(status), detail).into_response();
+    // Keep headers such as `Allow` on 405s.
+    for (name, value) in &parts.headers {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            problem.headers_mut().insert(name, value.clone());
+        }
+    }
+    problem
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::RequestContext;
+    use crate::error::{AppError, PROBLEM_CONTENT_TYPE};
+    use axum::{
+        Json, Router,
+        body::Body,
+        http::{Method, StatusCode},
+        middleware::from_fn,
+        routing::{get, post},
+    };
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    #[derive(Deserialize)]
+    struct Payload {
+        #[allow(dead_code)]
+        amount: f64,
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/context",
+                get(|ctx: RequestContext| async move { ctx.request_id }),
+            )
+            .route("/fail", get(|| async { Err::<(), _>(AppError::NotFound) }))
+            .route("/json", post(|Json(_): Json<Payload>| async {}))
+            .layer(from_fn(request_id))
+    }
+
+    async fn read_problem(response: Response) -> Problem {
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            PROBLEM_CONTENT_TYPE
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_shared_with_context_and_errors() {
+        let request = Request::builder()
+            .uri("/context")
+            .header(REQUEST_ID_HEADER, "abc")
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "abc");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "abc");
+
+        let request = Request::builder().uri("/fail").body(Body::empty()).unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        let generated = response.headers()[REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let problem = read_problem(response).await;
+        assert_eq!(problem.code, "not_found");
+        assert_eq!(problem.correlation_id, Some(generated));
+    }
+
+    #[tokio::test]
+    async fn test_rejections_become_problems() {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/json")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"amount": "lots"}"#))
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let problem = read_problem(response).await;
+        assert_eq!(problem.code, "unprocessable");
+        assert!(problem.detail.contains("amount"));
+
+        let request = Request::builder()
+            .method(Method::DELETE)
+            .uri("/json")
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert!(response.headers().contains_key(header::ALLOW));
+        assert_eq!(read_problem(response).await.code, "method_not_allowed");
+    }
+}
@@ -0,0 +1,222 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountKind {
+    Cash,
+    Checking,
+    Savings,
+    CreditCard,
+    CompanyCard,
+    Other,
+}
+
+impl AccountKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountKind::Cash => "cash",
+            AccountKind::Checking => "checking",
+            AccountKind::Savings => "savings",
+            AccountKind::CreditCard => "credit_card",
+            AccountKind::CompanyCard => "company_card",
+            AccountKind::Other => "other",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "cash" => Some(AccountKind::Cash),
+            "checking" => Some(AccountKind::Checking),
+            "savings" => Some(AccountKind::Savings),
+            "credit_card" => Some(AccountKind::CreditCard),
+            "company_card" => Some(AccountKind::CompanyCard),
+            "other" => Some(AccountKind::Other),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<String> for AccountKind {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value).ok_or_else(|| format!("unknown account kind '{value}'"))
+    }
+}
+
+/// A source of funds expenses are paid from. Balances are kept in the
+/// account's own currency; a card's debt is a negative balance.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Account {
+    pub id: Uuid,
+    pub name: String,
+    pub kind: AccountKind,
+    /// ISO 4217 code, e.g. `EUR`.
+    pub currency: String,
+    pub opening_balance: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+fn validate_currency(currency: &str) -> Result<(), ValidationError> {
+    if currency.len() == 3 && currency.bytes().all(|b| b.is_ascii_uppercase()) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("currency")
+            .with_message("Currency must be a three-letter ISO 4217 code, e.g. EUR".into()))
+    }
+}
+
+/// Creates an account, or replaces one with `PUT`.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct AccountRequest {
+    #[validate(length(
+        min = 1,
+        max = 50,
+        message = "Account name must be between 1 and 50 characters"
+    ))]
+    pub name: String,
+
+    pub kind: AccountKind,
+
+    #[validate(custom(function = "validate_currency"))]
+    pub currency: String,
+
+    #[serde(default)]
+    pub opening_balance: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AccountBalance {
+    pub account: Account,
+    /// Non-deleted expenses paid from the account, net of their refunds.
+    pub spent: f64,
+    /// Income paid into the account.
+    pub income: f64,
+    pub transferred_in: f64,
+    pub transferred_out: f64,
+    /// `opening_balance - spent + income + transferred_in - transferred_out`.
+    pub balance: f64,
+}
+
+/// Money moved from one account to another. Transfers move balances but are
+/// never counted as spending.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Transfer {
+    pub id: Uuid,
+    pub from_account_id: Uuid,
+    pub to_account_id: Uuid,
+    pub amount: f64,
+    pub date: DateTime<Utc>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreateTransferRequest {
+    pub from_account_id: Uuid,
+    pub to_account_id: Uuid,
+
+    #[validate(range(min = 0.01, message = "Amount must be greater than 0"))]
+    pub amount: f64,
+
+    /// Defaults to now.
+    #[serde(default)]
+    pub date: Option<DateTime<Utc>>,
+
+    #[serde(default)]
+    #[validate(length(max = 1000, message = "Notes must be at most 1000 characters"))]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct TransferQuery {
+    /// Only transfers into or out of this account.
+    pub account_id: Option<Uuid>,
+}
+
+/// Date range of a statement; `from` is inclusive, `to` exclusive.
+#[derive(Debug, Deserialize, Default)]
+pub struct StatementQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementEntryKind {
+    Expense,
+    Refund,
+    Income,
+    TransferIn,
+    TransferOut,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StatementEntry {
+    pub kind: StatementEntryKind,
+    /// The expense, refund, income or transfer behind the entry.
+    pub id: Uuid,
+    pub date: DateTime<Utc>,
+    pub description: String,
+    /// Signed change to the balance: negative for spending and outgoing
+    /// transfers.
+    pub amount: f64,
+    pub balance: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Statement {
+    pub account: Account,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Balance before the first entry of the period.
+    pub opening_balance: f64,
+    pub entries: Vec<StatementEntry>,
+    pub closing_balance: f64,
+}
+
+/// An expense referred to an account that does not exist.
+#[derive(Debug, Error)]
+#[error("Account {0} does not exist")]
+pub struct UnknownAccount(pub Uuid);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(currency: &str) -> AccountRequest {
+        AccountRequest {
+            name: "Visa".to_string(),
+            kind: AccountKind::CreditCard,
+            currency: currency.to_string(),
+            opening_balance: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_currency_must_be_iso_code() {
+        assert!(request("EUR").validate().is_ok());
+        assert!(request("eur").validate().is_err());
+        assert!(request("EURO").validate().is_err());
+        assert!(request("").validate().is_err());
+    }
+
+    #[test]
+    fn test_kind_round_trips() {
+        for kind in [
+            AccountKind::Cash,
+            AccountKind::Checking,
+            AccountKind::Savings,
+            AccountKind::CreditCard,
+            AccountKind::CompanyCard,
+            AccountKind::Other,
+        ] {
+            assert_eq!(AccountKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(AccountKind::parse("wallet"), None);
+    }
+}
@@ -0,0 +1,154 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const MAX_FILENAME_LEN: usize = 200;
+const DEFAULT_FILENAME: &str = "receipt";
+
+/// A receipt stored with an expense. The content lives in the attachment
+/// store under its SHA-256, so identical uploads share one file.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub expense_id: Uuid,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+    pub sha256: String,
+    pub has_thumbnail: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A received file, before it has been checked and stored.
+#[derive(Debug)]
+pub struct Upload {
+    pub filename: Option<String>,
+    /// The content type the client declared, if any.
+    pub content_type: Option<String>,
+    pub content: Vec<u8>,
+}
+
+/// Content types accepted as receipts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiptType {
+    Jpeg,
+    Png,
+    Webp,
+    Pdf,
+}
+
+impl ReceiptType {
+    /// Identifies the type from the leading bytes of the content, ignoring
+    /// whatever the client claimed.
+    pub fn sniff(content: &[u8]) -> Option<Self> {
+        if content.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ReceiptType::Jpeg)
+        } else if content.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ReceiptType::Png)
+        } else if content.len() >= 12 && &content[..4] == b"RIFF" && &content[8..12] == b"WEBP" {
+            Some(ReceiptType::Webp)
+        } else if content.starts_with(b"%PDF-") {
+            Some(ReceiptType::Pdf)
+        } else {
+            None
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ReceiptType::Jpeg => "image/jpeg",
+            ReceiptType::Png => "image/png",
+            ReceiptType::Webp => "image/webp",
+            ReceiptType::Pdf => "application/pdf",
+        }
+    }
+
+    pub fn is_image(&self) -> bool {
+        !matches!(self, ReceiptType::Pdf)
+    }
+
+    /// Whether a client-declared content type agrees with the sniffed one.
+    /// Missing and generic declarations are accepted.
+    pub fn matches_declared(&self, declared: Option<&str>) -> bool {
+        let Some(declared) = declared else {
+            return true;
+        };
+        let essence = declared
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        essence.is_empty()
+            || essence == "application/octet-stream"
+            || essence == self.mime()
+            || (self == &ReceiptType::Jpeg && essence == "image/jpg")
+    }
+}
+
+/// Reduces a client-supplied file name to its last path component with
+/// control characters and quotes removed, so it is safe to echo in a
+/// `Content-Disposition` header.
+pub fn sanitize_filename(name: Option<&str>) -> String {
+    let base = name
+        .unwrap_or_default()
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .take(MAX_FILENAME_LEN)
+        .collect();
+    let cleaned = cleaned.trim();
+
+    if cleaned.is_empty() || cleaned == "." || cleaned == ".." {
+        DEFAULT_FILENAME.to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_by_magic_bytes() {
+        assert_eq!(
+            ReceiptType::sniff(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some(ReceiptType::Jpeg)
+        );
+        assert_eq!(
+            ReceiptType::sniff(b"\x89PNG\r\n\x1a\n...."),
+            Some(ReceiptType::Png)
+        );
+        assert_eq!(
+            ReceiptType::sniff(b"RIFF\x10\0\0\0WEBPVP8 "),
+            Some(ReceiptType::Webp)
+        );
+        assert_eq!(ReceiptType::sniff(b"%PDF-1.7\n"), Some(ReceiptType::Pdf));
+        assert_eq!(ReceiptType::sniff(b"<html></html>"), None);
+        assert_eq!(ReceiptType::sniff(b""), None);
+    }
+
+    #[test]
+    fn test_declared_type_must_agree() {
+        let png = ReceiptType::Png;
+
+        assert!(png.matches_declared(None));
+        assert!(png.matches_declared(Some("application/octet-stream")));
+        assert!(png.matches_declared(Some("Image/PNG")));
+        assert!(!png.matches_declared(Some("application/pdf")));
+        assert!(ReceiptType::Jpeg.matches_declared(Some("image/jpg")));
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename(Some("scan.pdf")), "scan.pdf");
+        assert_eq!(sanitize_filename(Some("../../etc/passwd")), "passwd");
+        assert_eq!(sanitize_filename(Some("C:\\tmp\\a\"b.png")), "ab.png");
+        assert_eq!(sanitize_filename(Some("..")), "receipt");
+        assert_eq!(sanitize_filename(None), "receipt");
+    }
+}
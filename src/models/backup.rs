@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Why a snapshot was taken.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    /// Requested through the API or the `backup create` command.
+    Manual,
+    /// Taken by the background job every `BACKUP_INTERVAL_HOURS`.
+    Scheduled,
+    /// Taken of the current database just before a restore overwrote it.
+    PreRestore,
+}
+
+/// A snapshot of the SQLite database, stored as `<name>.db` next to a
+/// `<name>.json` manifest holding this record.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Backup {
+    pub name: String,
+    pub kind: BackupKind,
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: u64,
+    /// SHA-256 of the snapshot file, checked by verification.
+    pub sha256: String,
+    /// Newest migration applied to the snapshot.
+    pub schema_version: i64,
+    /// Expenses in the snapshot, trashed ones included.
+    pub expenses: i64,
+    /// Fingerprint of the key the snapshot is encrypted with; absent for a
+    /// plain snapshot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_fingerprint: Option<String>,
+}
+
+/// Result of checking a snapshot's checksum and running SQLite's integrity
+/// and foreign key checks on it.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct BackupVerification {
+    pub name: String,
+    pub ok: bool,
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RestoreOutcome {
+    pub restored: Backup,
+    /// Snapshot of the database as it was before the restore, to undo it.
+    pub pre_restore: Backup,
+}
+
+/// A snapshot that cannot be restored, because it failed verification or
+/// was made by a newer build.
+#[derive(Debug, Error)]
+#[error("Backup {name} cannot be restored: {reason}")]
+pub struct BackupRejected {
+    pub name: String,
+    pub reason: String,
+}
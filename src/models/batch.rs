@@ -0,0 +1,248 @@
+use crate::models::account::UnknownAccount;
+use crate::models::expense::{CreateExpenseRequest, Expense, UpdateExpenseRequest};
+use crate::models::expense_report::ExpenseLocked;
+use crate::models::refund::AmountBelowRefunded;
+use crate::models::rule::RuleSet;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+use validator::Validate;
+
+pub const MAX_BATCH_OPERATIONS: usize = 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    /// Commit every operation or none of them.
+    #[default]
+    Atomic,
+    /// Commit the operations that succeed and report the ones that fail.
+    BestEffort,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    #[serde(default)]
+    pub mode: BatchMode,
+    /// Kept as raw JSON so a malformed item is reported against its index
+    /// instead of rejecting the whole request.
+    pub operations: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    Create(CreateExpenseRequest),
+    Update {
+        id: Uuid,
+        version: Option<i64>,
+        #[serde(flatten)]
+        changes: UpdateExpenseRequest,
+    },
+    Delete {
+        id: Uuid,
+        version: Option<i64>,
+    },
+}
+
+impl BatchOperation {
+    fn validate(&self) -> Result<(), validator::ValidationErrors> {
+        match self {
+            BatchOperation::Create(request) => request.validate(),
+            BatchOperation::Update { changes, .. } => changes.validate(),
+            BatchOperation::Delete { .. } => Ok(()),
+        }
+    }
+}
+
+impl BatchRequest {
+    /// Parses and validates every operation, keeping their order. Creates
+    /// have `rules` applied first, as single creates do.
+    pub fn parse_operations(self, rules: &RuleSet) -> Vec<Result<BatchOperation, BatchItemError>> {
+        self.operations
+            .into_iter()
+            .map(|value| {
+                let mut operation: BatchOperation =
+                    serde_json::from_value(value).map_err(|e| BatchItemError::Invalid {
+                        message: e.to_string(),
+                    })?;
+                if let BatchOperation::Create(request) = &mut operation {
+                    rules.apply_to_request(request, Utc::now());
+                }
+                operation.validate().map_err(|e| BatchItemError::Invalid {
+                    message: e.to_string(),
+                })?;
+                Ok(operation)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum BatchItemError {
+    Invalid { message: String },
+    NotFound,
+    VersionConflict { current_version: i64 },
+    Internal,
+}
+
+impl From<anyhow::Error> for BatchItemError {
+    fn from(error: anyhow::Error) -> Self {
+        if let Some(unknown) = error.downcast_ref::<UnknownAccount>() {
+            return BatchItemError::Invalid {
+                message: unknown.to_string(),
+            };
+        }
+        if let Some(below) = error.downcast_ref::<AmountBelowRefunded>() {
+            return BatchItemError::Invalid {
+                message: below.to_string(),
+            };
+        }
+        if let Some(locked) = error.downcast_ref::<ExpenseLocked>() {
+            return BatchItemError::Invalid {
+                message: locked.to_string(),
+            };
+        }
+        tracing::error!(error = format!("{error:#}"), "Batch operation failed");
+        BatchItemError::Internal
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchItemStatus {
+    Ok,
+    Error,
+    /// Succeeded, but undone because another item of an atomic batch failed.
+    RolledBack,
+    /// Skipped because an earlier item of an atomic batch failed.
+    NotExecuted,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BatchItemResult {
+    pub index: usize,
+    pub status: BatchItemStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expense: Option<Expense>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<BatchItemError>,
+}
+
+impl BatchItemResult {
+    pub fn succeeded(index: usize, expense: Expense) -> Self {
+        Self {
+            index,
+            status: BatchItemStatus::Ok,
+            expense: Some(expense),
+            error: None,
+        }
+    }
+
+    pub fn failed(index: usize, error: BatchItemError) -> Self {
+        Self {
+            index,
+            status: BatchItemStatus::Error,
+            expense: None,
+            error: Some(error),
+        }
+    }
+
+    pub fn not_executed(index: usize) -> Self {
+        Self {
+            index,
+            status: BatchItemStatus::NotExecuted,
+            expense: None,
+            error: None,
+        }
+    }
+
+    pub fn roll_back(&mut self) {
+        if self.status == BatchItemStatus::Ok {
+            self.status = BatchItemStatus::RolledBack;
+            self.expense = None;
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResponse {
+    pub mode: BatchMode,
+    pub committed: bool,
+    pub results: Vec<BatchItemResult>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(operations: Vec<Value>) -> BatchRequest {
+        BatchRequest {
+            mode: BatchMode::Atomic,
+            operations,
+        }
+    }
+
+    #[test]
+    fn test_parse_operations() {
+        let id = Uuid::new_v4();
+        let parsed = request(vec![
+            json!({"op": "create", "amount": 10.0, "category": "Food"}),
+            json!({"op": "update", "id": id, "version": 2, "amount": 5.0, "category": "Food"}),
+            json!({"op": "delete", "id": id}),
+        ])
+        .parse_operations(&RuleSet::default());
+
+        assert!(matches!(parsed[0], Ok(BatchOperation::Create(_))));
+        assert!(matches!(
+            parsed[1],
+            Ok(BatchOperation::Update {
+                version: Some(2),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parsed[2],
+            Ok(BatchOperation::Delete { version: None, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_operations_reports_invalid_items() {
+        let parsed = request(vec![
+            json!({"op": "create", "amount": 10.0, "category": "Food"}),
+            json!({"op": "create", "amount": -1.0, "category": "Food"}),
+            json!({"op": "rename"}),
+        ])
+        .parse_operations(&RuleSet::default());
+
+        assert!(parsed[0].is_ok());
+        assert!(matches!(
+            &parsed[1],
+            Err(BatchItemError::Invalid { message }) if message.contains("Amount must be greater than 0")
+        ));
+        assert!(matches!(parsed[2], Err(BatchItemError::Invalid { .. })));
+    }
+
+    #[test]
+    fn test_item_errors_serialize_with_code() {
+        let error = BatchItemError::Invalid {
+            message: "bad".to_string(),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({"code": "invalid", "message": "bad"})
+        );
+    }
+
+    #[test]
+    fn test_default_mode_is_atomic() {
+        let request: BatchRequest = serde_json::from_value(json!({"operations": []})).unwrap();
+
+        assert_eq!(request.mode, BatchMode::Atomic);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::models::tag::validate_tags;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -10,19 +11,107 @@ pub struct Expense {
     pub amount: f64,
     pub category: String,
     pub date: DateTime<Utc>,
+    #[serde(default)]
+    pub payee: Option<String>,
+    /// The managed payee `payee` was normalized to, if any alias matched.
+    #[serde(default)]
+    pub payee_id: Option<Uuid>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// The account the expense was paid from.
+    #[serde(default)]
+    pub account_id: Option<Uuid>,
+    /// A work cost the company pays back through an expense report.
+    #[serde(default)]
+    pub reimbursable: bool,
+    /// Tag names, sorted ignoring case.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Sum of the refunds against the expense; aggregates count
+    /// `amount - refunded`.
+    #[serde(default)]
+    pub refunded: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Incremented on every change; exposed to clients as the `ETag`.
+    #[serde(default = "initial_version")]
+    pub version: i64,
 }
 
-#[derive(Serialize, Deserialize, Validate)]
+fn initial_version() -> i64 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, Default)]
 pub struct CreateExpenseRequest {
     #[validate(range(min = 0.01, message = "Amount must be greater than 0"))]
     pub amount: f64,
 
+    /// May be omitted when a categorization rule fills it in.
+    #[serde(default)]
     #[validate(length(
         min = 1,
         max = 50,
         message = "Category must be between 1 and 50 characters"
     ))]
     pub category: String,
+
+    #[serde(default)]
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Payee must be between 1 and 100 characters"
+    ))]
+    pub payee: Option<String>,
+
+    #[serde(default)]
+    #[validate(length(max = 1000, message = "Notes must be at most 1000 characters"))]
+    pub notes: Option<String>,
+
+    #[serde(default)]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Vec<String>,
+
+    #[serde(default)]
+    pub account_id: Option<Uuid>,
+
+    #[serde(default)]
+    pub reimbursable: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, Default)]
+pub struct UpdateExpenseRequest {
+    #[validate(range(min = 0.01, message = "Amount must be greater than 0"))]
+    pub amount: f64,
+
+    #[validate(length(
+        min = 1,
+        max = 50,
+        message = "Category must be between 1 and 50 characters"
+    ))]
+    pub category: String,
+
+    #[serde(default)]
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Payee must be between 1 and 100 characters"
+    ))]
+    pub payee: Option<String>,
+
+    #[serde(default)]
+    #[validate(length(max = 1000, message = "Notes must be at most 1000 characters"))]
+    pub notes: Option<String>,
+
+    #[serde(default)]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Vec<String>,
+
+    #[serde(default)]
+    pub account_id: Option<Uuid>,
+
+    #[serde(default)]
+    pub reimbursable: bool,
 }
 
 impl Expense {
@@ -32,6 +121,15 @@ impl Expense {
             amount,
             category,
             date: Utc::now(),
+            payee: None,
+            payee_id: None,
+            notes: None,
+            account_id: None,
+            reimbursable: false,
+            tags: Vec::new(),
+            refunded: 0.0,
+            deleted_at: None,
+            version: initial_version(),
         }
     }
 }
@@ -55,6 +153,7 @@ mod tests {
         let request = CreateExpenseRequest {
             amount: 25.50,
             category: "Groceries".to_string(),
+            ..Default::default()
         };
 
         assert!(request.validate().is_ok());
@@ -65,6 +164,7 @@ mod tests {
         let request = CreateExpenseRequest {
             amount: -10.0,
             category: "Groceries".to_string(),
+            ..Default::default()
         };
 
         assert!(request.validate().is_err());
@@ -77,6 +177,7 @@ mod tests {
         let request = CreateExpenseRequest {
             amount: 0.0,
             category: "Groceries".to_string(),
+            ..Default::default()
         };
 
         assert!(request.validate().is_err());
@@ -87,6 +188,7 @@ mod tests {
         let request = CreateExpenseRequest {
             amount: 25.50,
             category: "".to_string(),
+            ..Default::default()
         };
 
         assert!(request.validate().is_err());
@@ -103,6 +205,7 @@ mod tests {
         let request = CreateExpenseRequest {
             amount: 25.50,
             category: "a".repeat(51),
+            ..Default::default()
         };
 
         assert!(request.validate().is_err());
@@ -113,8 +216,60 @@ mod tests {
         let request = CreateExpenseRequest {
             amount: 25.50,
             category: "a".repeat(50),
+            ..Default::default()
+        };
+
+        assert!(request.validate().is_ok());
+    }
+
+    #[test]
+    fn test_new_expense_starts_at_version_one() {
+        let expense = Expense::new(25.50, "Groceries".to_string());
+
+        assert_eq!(expense.version, 1);
+    }
+
+    #[test]
+    fn test_invalid_update_request() {
+        let request = UpdateExpenseRequest {
+            amount: 0.0,
+            category: "".to_string(),
+            ..Default::default()
         };
 
+        let errors = request.validate().unwrap_err();
+        assert!(errors.to_string().contains("Amount must be greater than 0"));
+        assert!(
+            errors
+                .to_string()
+                .contains("Category must be between 1 and 50 characters")
+        );
+    }
+
+    #[test]
+    fn test_payee_and_notes_are_optional() {
+        let request: CreateExpenseRequest =
+            serde_json::from_str(r#"{"amount": 5.0, "category": "Food"}"#).unwrap();
+
+        assert!(request.payee.is_none());
+        assert!(request.notes.is_none());
         assert!(request.validate().is_ok());
     }
+
+    #[test]
+    fn test_invalid_notes_too_long() {
+        let request = CreateExpenseRequest {
+            amount: 25.50,
+            category: "Groceries".to_string(),
+            notes: Some("a".repeat(1001)),
+            ..Default::default()
+        };
+
+        let errors = request.validate().unwrap_err();
+        assert!(
+            errors
+                .to_string()
+                .contains("Notes must be at most 1000 characters")
+        );
+    }
 }
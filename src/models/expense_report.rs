@@ -0,0 +1,225 @@
+use crate::models::expense::Expense;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStatus {
+    Draft,
+    Submitted,
+    Approved,
+    Rejected,
+    Paid,
+}
+
+impl ReportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportStatus::Draft => "draft",
+            ReportStatus::Submitted => "submitted",
+            ReportStatus::Approved => "approved",
+            ReportStatus::Rejected => "rejected",
+            ReportStatus::Paid => "paid",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "draft" => Some(ReportStatus::Draft),
+            "submitted" => Some(ReportStatus::Submitted),
+            "approved" => Some(ReportStatus::Approved),
+            "rejected" => Some(ReportStatus::Rejected),
+            "paid" => Some(ReportStatus::Paid),
+            _ => None,
+        }
+    }
+
+    /// Whether expenses can still be added to or removed from the report.
+    pub fn is_editable(&self) -> bool {
+        *self == ReportStatus::Draft
+    }
+
+    /// Whether the report's expenses are frozen: they cannot be edited or
+    /// deleted while under review or once approved.
+    pub fn locks_expenses(&self) -> bool {
+        matches!(
+            self,
+            ReportStatus::Submitted | ReportStatus::Approved | ReportStatus::Paid
+        )
+    }
+}
+
+impl TryFrom<String> for ReportStatus {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value).ok_or_else(|| format!("unknown report status '{value}'"))
+    }
+}
+
+/// A step in the report lifecycle:
+///
+/// ```text
+/// draft --submit--> submitted --approve--> approved --pay--> paid
+///   ^                   |
+///   +----reopen---- rejected <--reject--+
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportAction {
+    Submit,
+    Approve,
+    Reject,
+    Reopen,
+    Pay,
+}
+
+/// Who may take an action on a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Actor {
+    Owner,
+    /// An approver other than the report's owner.
+    Approver,
+}
+
+impl ReportAction {
+    /// The status the action moves from, the status it moves to, and who
+    /// may take it.
+    pub fn transition(&self) -> (ReportStatus, ReportStatus, Actor) {
+        match self {
+            ReportAction::Submit => (ReportStatus::Draft, ReportStatus::Submitted, Actor::Owner),
+            ReportAction::Approve => (
+                ReportStatus::Submitted,
+                ReportStatus::Approved,
+                Actor::Approver,
+            ),
+            ReportAction::Reject => (
+                ReportStatus::Submitted,
+                ReportStatus::Rejected,
+                Actor::Approver,
+            ),
+            ReportAction::Reopen => (ReportStatus::Rejected, ReportStatus::Draft, Actor::Owner),
+            ReportAction::Pay => (ReportStatus::Approved, ReportStatus::Paid, Actor::Approver),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExpenseReport {
+    pub id: Uuid,
+    pub title: String,
+    /// The actor who created the report.
+    pub owner: String,
+    pub status: ReportStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReportComment {
+    pub id: Uuid,
+    pub author: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A report with its expenses and discussion.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExpenseReportDetail {
+    #[serde(flatten)]
+    pub report: ExpenseReport,
+    pub expenses: Vec<Expense>,
+    /// Sum of the expenses net of refunds; what is paid back.
+    pub total: f64,
+    pub comments: Vec<ReportComment>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreateReportRequest {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Title must be between 1 and 100 characters"
+    ))]
+    pub title: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportItemRequest {
+    pub expense_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct TransitionRequest {
+    pub action: ReportAction,
+
+    /// Recorded as a comment along with the transition.
+    #[serde(default)]
+    #[validate(length(
+        min = 1,
+        max = 2000,
+        message = "Comment must be between 1 and 2000 characters"
+    ))]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CommentRequest {
+    #[validate(length(
+        min = 1,
+        max = 2000,
+        message = "Comment must be between 1 and 2000 characters"
+    ))]
+    pub body: String,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ReportQuery {
+    pub status: Option<ReportStatus>,
+    pub owner: Option<String>,
+}
+
+/// An expense cannot change while a report containing it is under review
+/// or settled.
+#[derive(Debug, Error)]
+#[error("Expense is part of expense report {report_id}, which is {}", status.as_str())]
+pub struct ExpenseLocked {
+    pub report_id: Uuid,
+    pub status: ReportStatus,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_round_trips() {
+        for status in [
+            ReportStatus::Draft,
+            ReportStatus::Submitted,
+            ReportStatus::Approved,
+            ReportStatus::Rejected,
+            ReportStatus::Paid,
+        ] {
+            assert_eq!(ReportStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(ReportStatus::parse("archived"), None);
+    }
+
+    #[test]
+    fn test_only_approvers_decide() {
+        for action in [
+            ReportAction::Approve,
+            ReportAction::Reject,
+            ReportAction::Pay,
+        ] {
+            assert_eq!(action.transition().2, Actor::Approver);
+        }
+        for action in [ReportAction::Submit, ReportAction::Reopen] {
+            assert_eq!(action.transition().2, Actor::Owner);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether the service can take traffic. Unready while the database is
+/// unreachable, the schema is behind, or the server is shutting down.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Readiness {
+    pub ready: bool,
+    pub database: DatabaseCheck,
+    pub migrations: MigrationCheck,
+    pub shutting_down: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct DatabaseCheck {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct MigrationCheck {
+    pub ok: bool,
+    /// Newest applied schema version; `None` when it could not be read.
+    pub applied: Option<i64>,
+    /// Newest schema version this build knows about.
+    pub expected: i64,
+}
@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Money received, such as salary, refunds or reimbursements. Income has
+/// its own categories and never counts towards expense queries.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Income {
+    pub id: Uuid,
+    pub amount: f64,
+    pub category: String,
+    /// Who paid, e.g. an employer.
+    pub source: Option<String>,
+    pub notes: Option<String>,
+    /// The account the money went into.
+    pub account_id: Option<Uuid>,
+    pub date: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Records income, or replaces it with `PUT`.
+#[derive(Debug, Serialize, Deserialize, Validate, Default)]
+pub struct IncomeRequest {
+    #[validate(range(min = 0.01, message = "Amount must be greater than 0"))]
+    pub amount: f64,
+
+    #[validate(length(
+        min = 1,
+        max = 50,
+        message = "Category must be between 1 and 50 characters"
+    ))]
+    pub category: String,
+
+    #[serde(default)]
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Source must be between 1 and 100 characters"
+    ))]
+    pub source: Option<String>,
+
+    #[serde(default)]
+    #[validate(length(max = 1000, message = "Notes must be at most 1000 characters"))]
+    pub notes: Option<String>,
+
+    #[serde(default)]
+    pub account_id: Option<Uuid>,
+
+    /// Defaults to now.
+    #[serde(default)]
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// Narrows income listings; `from` is inclusive, `to` exclusive.
+#[derive(Debug, Deserialize, Default)]
+pub struct IncomeQuery {
+    pub category: Option<String>,
+    pub account_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
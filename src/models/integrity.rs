@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A stored row that cannot be read back into its model, e.g. because an
+/// id is not a UUID or a date is not a timestamp.
+#[derive(Debug, Error, Serialize, Deserialize, Clone, PartialEq)]
+#[error(
+    "Row {} in {table} cannot be read{}: {reason}",
+    id.as_deref().unwrap_or("?"),
+    column.as_deref().map(|c| format!(" (column {c})")).unwrap_or_default()
+)]
+pub struct CorruptRow {
+    pub table: String,
+    /// The row's id, when it is readable as text.
+    pub id: Option<String>,
+    /// The column that failed to decode, when known.
+    pub column: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TableCheck {
+    pub table: String,
+    pub rows_checked: usize,
+    pub corrupt: usize,
+}
+
+/// Result of decoding every stored row of the checked tables.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct IntegrityReport {
+    pub ok: bool,
+    pub tables: Vec<TableCheck>,
+    pub corrupt_rows: Vec<CorruptRow>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corrupt_row_message_names_the_column() {
+        let row = CorruptRow {
+            table: "expenses".to_string(),
+            id: Some("abc".to_string()),
+            column: Some("date".to_string()),
+            reason: "not a timestamp".to_string(),
+        };
+        assert_eq!(
+            row.to_string(),
+            "Row abc in expenses cannot be read (column date): not a timestamp"
+        );
+
+        let row = CorruptRow {
+            column: None,
+            id: None,
+            ..row
+        };
+        assert_eq!(
+            row.to_string(),
+            "Row ? in expenses cannot be read: not a timestamp"
+        );
+    }
+}
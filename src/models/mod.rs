@@ -1 +1,17 @@
+pub mod account;
+pub mod attachment;
+pub mod audit;
+pub mod backup;
+pub mod batch;
 pub mod expense;
+pub mod expense_report;
+pub mod health;
+pub mod income;
+pub mod integrity;
+pub mod payee;
+pub mod refund;
+pub mod rule;
+pub mod search;
+pub mod suggestion;
+pub mod summary;
+pub mod tag;
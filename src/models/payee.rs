@@ -0,0 +1,159 @@
+use crate::models::summary::CategoryTotal;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AliasKind {
+    /// Matches the whole raw payee, ignoring case and surrounding spaces.
+    Exact,
+    /// Matches raw payees starting with the pattern, ignoring case.
+    Prefix,
+    /// Matches raw payees against a regular expression.
+    Regex,
+}
+
+impl AliasKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AliasKind::Exact => "exact",
+            AliasKind::Prefix => "prefix",
+            AliasKind::Regex => "regex",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "exact" => Some(AliasKind::Exact),
+            "prefix" => Some(AliasKind::Prefix),
+            "regex" => Some(AliasKind::Regex),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<String> for AliasKind {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value).ok_or_else(|| format!("unknown alias kind '{value}'"))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PayeeAlias {
+    pub id: Uuid,
+    pub payee_id: Uuid,
+    pub kind: AliasKind,
+    pub pattern: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Payee {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub aliases: Vec<PayeeAlias>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreateAliasRequest {
+    pub kind: AliasKind,
+
+    #[validate(length(
+        min = 1,
+        max = 200,
+        message = "Pattern must be between 1 and 200 characters"
+    ))]
+    pub pattern: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreatePayeeRequest {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Payee must be between 1 and 100 characters"
+    ))]
+    pub name: String,
+
+    #[serde(default)]
+    #[validate(nested)]
+    pub aliases: Vec<CreateAliasRequest>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UpdatePayeeRequest {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Payee must be between 1 and 100 characters"
+    ))]
+    pub name: String,
+}
+
+/// The managed payee a raw payee string normalizes to.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ResolvedPayee {
+    pub payee_id: Uuid,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolvePayeeQuery {
+    pub raw: String,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct PayeeSummaryQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PayeeSummary {
+    pub payee_id: Uuid,
+    pub name: String,
+    pub expense_count: i64,
+    pub total: f64,
+    pub average: f64,
+    pub first_expense: Option<DateTime<Utc>>,
+    pub last_expense: Option<DateTime<Utc>>,
+    /// Only filled in for the single-payee summary.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub by_category: Vec<CategoryTotal>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_kind_round_trip() {
+        for kind in [AliasKind::Exact, AliasKind::Prefix, AliasKind::Regex] {
+            assert_eq!(AliasKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(AliasKind::parse("fuzzy"), None);
+    }
+
+    #[test]
+    fn test_create_payee_validates_aliases() {
+        let request = CreatePayeeRequest {
+            name: "Amazon".to_string(),
+            aliases: vec![CreateAliasRequest {
+                kind: AliasKind::Prefix,
+                pattern: "".to_string(),
+            }],
+        };
+
+        let errors = request.validate().unwrap_err();
+        assert!(
+            errors
+                .to_string()
+                .contains("Pattern must be between 1 and 200 characters")
+        );
+    }
+}
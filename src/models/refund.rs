@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Money returned against an expense, in full or in part.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Refund {
+    pub id: Uuid,
+    pub expense_id: Uuid,
+    pub amount: f64,
+    pub reason: Option<String>,
+    pub date: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, Default)]
+pub struct CreateRefundRequest {
+    #[validate(range(min = 0.01, message = "Amount must be greater than 0"))]
+    pub amount: f64,
+
+    #[serde(default)]
+    #[validate(length(max = 500, message = "Reason must be at most 500 characters"))]
+    pub reason: Option<String>,
+
+    /// Defaults to now.
+    #[serde(default)]
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// An expense update would lower the amount below what was already refunded.
+#[derive(Debug, Error)]
+#[error("Amount cannot be lower than the {refunded:.2} already refunded")]
+pub struct AmountBelowRefunded {
+    pub refunded: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refund_amount_must_be_positive() {
+        let request = CreateRefundRequest {
+            amount: 0.0,
+            ..Default::default()
+        };
+        assert!(request.validate().is_err());
+
+        let request = CreateRefundRequest {
+            amount: 5.0,
+            reason: Some("Returned one item".to_string()),
+            date: None,
+        };
+        assert!(request.validate().is_ok());
+    }
+}
@@ -0,0 +1,512 @@
+use crate::models::expense::{CreateExpenseRequest, Expense};
+use crate::models::tag::{normalize_tags, validate_tags};
+use chrono::{DateTime, Datelike, Utc, Weekday};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextOp {
+    Equals,
+    Contains,
+    StartsWith,
+    Regex,
+}
+
+/// Matches a text field. All operators except `regex` ignore case; a
+/// missing field never matches.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TextCondition {
+    pub op: TextOp,
+    pub value: String,
+}
+
+/// Every present condition must hold for a rule to match.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct RuleConditions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payee: Option<TextCondition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<TextCondition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_max: Option<f64>,
+    /// Weekdays (UTC) of the expense date, e.g. `["Sat", "Sun"]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weekdays: Vec<Weekday>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Validate)]
+pub struct RuleActions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(
+        min = 1,
+        max = 50,
+        message = "Category must be between 1 and 50 characters"
+    ))]
+    pub category: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Payee must be between 1 and 100 characters"
+    ))]
+    pub payee: Option<String>,
+
+    /// Added to the expense's tags; tags from all matching rules add up.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Vec<String>,
+}
+
+impl RuleActions {
+    pub fn is_empty(&self) -> bool {
+        self.category.is_none() && self.payee.is_none() && self.tags.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Rule {
+    pub id: Uuid,
+    pub name: String,
+    /// Lower numbers are evaluated first.
+    pub priority: i64,
+    pub enabled: bool,
+    pub conditions: RuleConditions,
+    pub actions: RuleActions,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+fn default_priority() -> i64 {
+    100
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Body of `POST /rules` and `PUT /rules/{id}`.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct RuleRequest {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Name must be between 1 and 100 characters"
+    ))]
+    pub name: String,
+
+    #[serde(default = "default_priority")]
+    pub priority: i64,
+
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    #[serde(default)]
+    pub conditions: RuleConditions,
+
+    #[validate(nested)]
+    pub actions: RuleActions,
+}
+
+/// Selects the rules a dry-run or retroactive run uses: all enabled rules
+/// when `rule_ids` is absent, otherwise the listed ones (even if disabled,
+/// so a draft rule can be tried before enabling it).
+#[derive(Debug, Deserialize, Default)]
+pub struct RuleRunRequest {
+    #[serde(default)]
+    pub rule_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct FieldChange {
+    pub from: Option<String>,
+    pub to: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RuleChange {
+    pub expense_id: Uuid,
+    pub matched_rules: Vec<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<FieldChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payee: Option<FieldChange>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags_added: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleRunResponse {
+    pub applied: bool,
+    pub expenses_checked: usize,
+    pub changes: Vec<RuleChange>,
+}
+
+/// The fields of an expense that rules look at.
+#[derive(Debug, Clone, Copy)]
+pub struct RuleInput<'a> {
+    pub payee: Option<&'a str>,
+    pub notes: Option<&'a str>,
+    pub amount: f64,
+    pub date: DateTime<Utc>,
+}
+
+impl<'a> From<&'a Expense> for RuleInput<'a> {
+    fn from(expense: &'a Expense) -> Self {
+        Self {
+            payee: expense.payee.as_deref(),
+            notes: expense.notes.as_deref(),
+            amount: expense.amount,
+            date: expense.date,
+        }
+    }
+}
+
+/// What the matching rules would set; the first matching rule wins for
+/// category and payee, tags are collected from all of them.
+#[derive(Debug, Default, PartialEq)]
+pub struct RuleOutcome {
+    pub matched_rules: Vec<Uuid>,
+    pub category: Option<String>,
+    pub payee: Option<String>,
+    pub tags: Vec<String>,
+}
+
+struct CompiledText {
+    op: TextOp,
+    value: String,
+    regex: Option<Regex>,
+}
+
+impl CompiledText {
+    fn compile(condition: &TextCondition) -> Result<Self, regex::Error> {
+        let regex = match condition.op {
+            TextOp::Regex => Some(Regex::new(&condition.value)?),
+            _ => None,
+        };
+        Ok(Self {
+            op: condition.op,
+            value: condition.value.to_lowercase(),
+            regex,
+        })
+    }
+
+    fn matches(&self, text: Option<&str>) -> bool {
+        let Some(text) = text else {
+            return false;
+        };
+        if let Some(regex) = &self.regex {
+            return regex.is_match(text);
+        }
+        let text = text.trim().to_lowercase();
+        match self.op {
+            TextOp::Equals => text == self.value.trim(),
+            TextOp::Contains => text.contains(&self.value),
+            TextOp::StartsWith => text.starts_with(self.value.trim_start()),
+            TextOp::Regex => false,
+        }
+    }
+}
+
+struct CompiledRule {
+    id: Uuid,
+    payee: Option<CompiledText>,
+    notes: Option<CompiledText>,
+    amount_min: Option<f64>,
+    amount_max: Option<f64>,
+    weekdays: Vec<Weekday>,
+    actions: RuleActions,
+}
+
+impl CompiledRule {
+    fn matches(&self, input: &RuleInput) -> bool {
+        self.payee.as_ref().is_none_or(|c| c.matches(input.payee))
+            && self.notes.as_ref().is_none_or(|c| c.matches(input.notes))
+            && self.amount_min.is_none_or(|min| input.amount >= min)
+            && self.amount_max.is_none_or(|max| input.amount <= max)
+            && (self.weekdays.is_empty() || self.weekdays.contains(&input.date.weekday()))
+    }
+}
+
+/// Rules compiled for evaluation, in priority order.
+#[derive(Default)]
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+}
+
+impl RuleSet {
+    /// Compiles `rules`, which must already be in evaluation order.
+    pub fn new(rules: &[Rule]) -> Result<Self, regex::Error> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let text = |c: &Option<TextCondition>| c.as_ref().map(CompiledText::compile);
+                Ok(CompiledRule {
+                    id: rule.id,
+                    payee: text(&rule.conditions.payee).transpose()?,
+                    notes: text(&rule.conditions.notes).transpose()?,
+                    amount_min: rule.conditions.amount_min,
+                    amount_max: rule.conditions.amount_max,
+                    weekdays: rule.conditions.weekdays.clone(),
+                    actions: rule.actions.clone(),
+                })
+            })
+            .collect::<Result<_, regex::Error>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn evaluate(&self, input: &RuleInput) -> RuleOutcome {
+        let mut outcome = RuleOutcome::default();
+        for rule in self.rules.iter().filter(|rule| rule.matches(input)) {
+            outcome.matched_rules.push(rule.id);
+            if outcome.category.is_none() {
+                outcome.category = rule.actions.category.clone();
+            }
+            if outcome.payee.is_none() {
+                outcome.payee = rule.actions.payee.clone();
+            }
+            outcome.tags.extend(rule.actions.tags.iter().cloned());
+        }
+        outcome.tags = normalize_tags(&outcome.tags);
+        outcome
+    }
+
+    /// Fills the fields a new expense left empty. Values sent by the
+    /// client always win over rules.
+    pub fn apply_to_request(&self, request: &mut CreateExpenseRequest, date: DateTime<Utc>) {
+        let outcome = self.evaluate(&RuleInput {
+            payee: request.payee.as_deref(),
+            notes: request.notes.as_deref(),
+            amount: request.amount,
+            date,
+        });
+        if request.category.trim().is_empty()
+            && let Some(category) = outcome.category
+        {
+            request.category = category;
+        }
+        if request.payee.is_none() {
+            request.payee = outcome.payee;
+        }
+        request.tags.extend(outcome.tags);
+        request.tags = normalize_tags(&request.tags);
+    }
+}
+
+/// Checks what validator cannot: the rule must do something, its amount
+/// range must be ordered and its regexes must compile.
+pub fn check_rule(request: &RuleRequest) -> Result<(), String> {
+    if request.actions.is_empty() {
+        return Err("A rule needs at least one action".to_string());
+    }
+    let conditions = &request.conditions;
+    if let (Some(min), Some(max)) = (conditions.amount_min, conditions.amount_max)
+        && min > max
+    {
+        return Err("amount_min must not be greater than amount_max".to_string());
+    }
+    for condition in [&conditions.payee, &conditions.notes].into_iter().flatten() {
+        if condition.value.is_empty() {
+            return Err("Condition values must not be empty".to_string());
+        }
+        CompiledText::compile(condition).map_err(|e| format!("Invalid regex: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn rule(priority: i64, conditions: RuleConditions, actions: RuleActions) -> Rule {
+        Rule {
+            id: Uuid::new_v4(),
+            name: format!("rule {}", priority),
+            priority,
+            enabled: true,
+            conditions,
+            actions,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn text(op: TextOp, value: &str) -> Option<TextCondition> {
+        Some(TextCondition {
+            op,
+            value: value.to_string(),
+        })
+    }
+
+    fn category(name: &str) -> RuleActions {
+        RuleActions {
+            category: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    // 2025-01-04 is a Saturday.
+    fn saturday() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 4, 12, 0, 0).unwrap()
+    }
+
+    fn input<'a>(payee: Option<&'a str>, amount: f64) -> RuleInput<'a> {
+        RuleInput {
+            payee,
+            notes: None,
+            amount,
+            date: saturday(),
+        }
+    }
+
+    #[test]
+    fn test_all_conditions_must_match() {
+        let rules = RuleSet::new(&[rule(
+            1,
+            RuleConditions {
+                payee: text(TextOp::Contains, "shell"),
+                amount_min: Some(20.0),
+                amount_max: Some(100.0),
+                weekdays: vec![Weekday::Sat, Weekday::Sun],
+                ..Default::default()
+            },
+            category("Fuel"),
+        )])
+        .unwrap();
+
+        let fuel = rules.evaluate(&input(Some("SHELL 1234"), 40.0));
+        assert_eq!(fuel.category.as_deref(), Some("Fuel"));
+
+        assert!(
+            rules
+                .evaluate(&input(Some("SHELL 1234"), 5.0))
+                .category
+                .is_none()
+        );
+        assert!(rules.evaluate(&input(Some("BP"), 40.0)).category.is_none());
+        assert!(rules.evaluate(&input(None, 40.0)).category.is_none());
+
+        let monday = RuleInput {
+            date: saturday() + chrono::Duration::days(2),
+            ..input(Some("SHELL 1234"), 40.0)
+        };
+        assert!(rules.evaluate(&monday).category.is_none());
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins_per_field() {
+        let rules = RuleSet::new(&[
+            rule(
+                1,
+                RuleConditions {
+                    payee: text(TextOp::StartsWith, "uber"),
+                    ..Default::default()
+                },
+                category("Transport"),
+            ),
+            rule(
+                2,
+                RuleConditions {
+                    payee: text(TextOp::Regex, r"EATS"),
+                    ..Default::default()
+                },
+                RuleActions {
+                    category: Some("Food".to_string()),
+                    payee: Some("Uber Eats".to_string()),
+                    tags: vec!["delivery".to_string()],
+                },
+            ),
+        ])
+        .unwrap();
+
+        let outcome = rules.evaluate(&input(Some("UBER EATS 42"), 15.0));
+
+        assert_eq!(outcome.matched_rules.len(), 2);
+        assert_eq!(outcome.category.as_deref(), Some("Transport"));
+        assert_eq!(outcome.payee.as_deref(), Some("Uber Eats"));
+        assert_eq!(outcome.tags, vec!["delivery"]);
+    }
+
+    #[test]
+    fn test_apply_to_request_keeps_client_values() {
+        let rules = RuleSet::new(&[rule(
+            1,
+            RuleConditions::default(),
+            RuleActions {
+                category: Some("Misc".to_string()),
+                payee: Some("Somebody".to_string()),
+                tags: vec!["auto".to_string()],
+            },
+        )])
+        .unwrap();
+
+        let mut empty = CreateExpenseRequest {
+            amount: 5.0,
+            ..Default::default()
+        };
+        rules.apply_to_request(&mut empty, saturday());
+        assert_eq!(empty.category, "Misc");
+        assert_eq!(empty.payee.as_deref(), Some("Somebody"));
+        assert_eq!(empty.tags, vec!["auto"]);
+
+        let mut filled = CreateExpenseRequest {
+            amount: 5.0,
+            category: "Food".to_string(),
+            payee: Some("Cafe".to_string()),
+            ..Default::default()
+        };
+        rules.apply_to_request(&mut filled, saturday());
+        assert_eq!(filled.category, "Food");
+        assert_eq!(filled.payee.as_deref(), Some("Cafe"));
+        assert_eq!(filled.tags, vec!["auto"]);
+    }
+
+    #[test]
+    fn test_check_rule() {
+        let request = |conditions, actions| RuleRequest {
+            name: "r".to_string(),
+            priority: 1,
+            enabled: true,
+            conditions,
+            actions,
+        };
+
+        assert!(check_rule(&request(RuleConditions::default(), category("Food"))).is_ok());
+        assert!(check_rule(&request(RuleConditions::default(), RuleActions::default())).is_err());
+        assert!(
+            check_rule(&request(
+                RuleConditions {
+                    amount_min: Some(10.0),
+                    amount_max: Some(1.0),
+                    ..Default::default()
+                },
+                category("Food"),
+            ))
+            .is_err()
+        );
+        assert!(
+            check_rule(&request(
+                RuleConditions {
+                    notes: text(TextOp::Regex, "(oops"),
+                    ..Default::default()
+                },
+                category("Food"),
+            ))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_weekdays_deserialize_from_short_names() {
+        let conditions: RuleConditions =
+            serde_json::from_str(r#"{"weekdays": ["Sat", "sunday"]}"#).unwrap();
+
+        assert_eq!(conditions.weekdays, vec![Weekday::Sat, Weekday::Sun]);
+    }
+}
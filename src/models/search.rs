@@ -0,0 +1,126 @@
+use crate::models::expense::Expense;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_SEARCH_LIMIT: i64 = 20;
+pub const MAX_SEARCH_LIMIT: i64 = 100;
+/// Delimit matches in the raw highlights FTS5 returns; private-use
+/// characters, so they never clash with markup in the stored text.
+pub const MATCH_START: char = '\u{E000}';
+pub const MATCH_END: char = '\u{E001}';
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+impl SearchQuery {
+    /// Builds an FTS5 `MATCH` expression in which every word of `q` must
+    /// appear as a prefix of some indexed token.
+    ///
+    /// Words are split the way the `unicode61` tokenizer splits them and then
+    /// quoted, so user input can never inject FTS5 query syntax. Returns
+    /// `None` when `q` contains no searchable characters.
+    pub fn match_expression(&self) -> Option<String> {
+        let terms: Vec<String> = self
+            .q
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| format!("\"{}\"*", word))
+            .collect();
+
+        (!terms.is_empty()).then(|| terms.join(" "))
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT)
+    }
+}
+
+/// Turns a raw FTS5 highlight into HTML: the stored text is escaped, so it
+/// renders as text, and only the matches between [`MATCH_START`] and
+/// [`MATCH_END`] become `<mark>` elements.
+pub fn marked_html(highlight: &str) -> String {
+    let mut html = String::with_capacity(highlight.len());
+    for c in highlight.chars() {
+        match c {
+            MATCH_START => html.push_str("<mark>"),
+            MATCH_END => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+/// Matched fields as HTML: the text is escaped and the matching terms are
+/// wrapped in `<mark>` tags; long notes are cut down to a snippet around the
+/// match.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SearchHighlights {
+    pub category: Option<String>,
+    pub payee: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchHit {
+    pub expense: Expense,
+    /// Relevance (higher is better), derived from FTS5's BM25 ranking.
+    pub score: f64,
+    pub highlights: SearchHighlights,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(q: &str) -> SearchQuery {
+        SearchQuery {
+            q: q.to_string(),
+            limit: None,
+        }
+    }
+
+    #[test]
+    fn test_match_expression_uses_prefix_terms() {
+        assert_eq!(
+            query("hardware store").match_expression().as_deref(),
+            Some("\"hardware\"* \"store\"*")
+        );
+    }
+
+    #[test]
+    fn test_match_expression_strips_query_syntax() {
+        assert_eq!(
+            query("AMZN* OR \"x\" NEAR(1)")
+                .match_expression()
+                .as_deref(),
+            Some("\"AMZN\"* \"OR\"* \"x\"* \"NEAR\"* \"1\"*")
+        );
+        assert_eq!(query(" *\"- ").match_expression(), None);
+    }
+
+    #[test]
+    fn test_marked_html_escapes_stored_text() {
+        assert_eq!(
+            marked_html("<img src=x onerror=\"alert('\u{E000}paint\u{E001}')\"> & co"),
+            "&lt;img src=x onerror=&quot;alert(&#39;<mark>paint</mark>&#39;)&quot;&gt; &amp; co"
+        );
+    }
+
+    #[test]
+    fn test_limit_is_clamped() {
+        let mut q = query("food");
+        assert_eq!(q.limit(), DEFAULT_SEARCH_LIMIT);
+
+        q.limit = Some(10_000);
+        assert_eq!(q.limit(), MAX_SEARCH_LIMIT);
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+const DEFAULT_SUGGESTIONS: usize = 3;
+const MAX_SUGGESTIONS: usize = 10;
+
+/// What is known about an expense before its category is chosen.
+#[derive(Debug, Serialize, Deserialize, Validate, Default)]
+pub struct SuggestCategoryRequest {
+    #[serde(default)]
+    #[validate(length(max = 100, message = "Payee must be at most 100 characters"))]
+    pub payee: Option<String>,
+
+    #[serde(default)]
+    #[validate(length(max = 1000, message = "Notes must be at most 1000 characters"))]
+    pub notes: Option<String>,
+
+    #[serde(default)]
+    #[validate(range(min = 0.01, message = "Amount must be greater than 0"))]
+    pub amount: Option<f64>,
+
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl SuggestCategoryRequest {
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_SUGGESTIONS)
+            .clamp(1, MAX_SUGGESTIONS)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CategorySuggestion {
+    pub category: String,
+    /// Posterior probability in `0.0..=1.0`; all categories sum to 1.
+    pub confidence: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuggestCategoryResponse {
+    /// Best suggestion first; empty until some expenses exist.
+    pub suggestions: Vec<CategorySuggestion>,
+    /// Number of expenses the model was trained on.
+    pub trained_on: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_defaults_and_clamps() {
+        let request = |limit| SuggestCategoryRequest {
+            limit,
+            ..Default::default()
+        };
+
+        assert_eq!(request(None).limit(), DEFAULT_SUGGESTIONS);
+        assert_eq!(request(Some(0)).limit(), 1);
+        assert_eq!(request(Some(500)).limit(), MAX_SUGGESTIONS);
+    }
+}
@@ -0,0 +1,165 @@
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryTotal {
+    pub category: String,
+    pub expense_count: i64,
+    pub total: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TagTotal {
+    pub tag: String,
+    pub expense_count: i64,
+    pub total: f64,
+}
+
+/// Narrows expense listings and summaries. Tag lists are comma-separated
+/// and matched ignoring case; `from` is inclusive, `to` exclusive.
+#[derive(Debug, Deserialize, Default)]
+pub struct ExpenseFilter {
+    /// Expenses with at least one of these tags.
+    pub tags_any: Option<String>,
+    /// Expenses with every one of these tags.
+    pub tags_all: Option<String>,
+    /// Expenses with none of these tags.
+    pub tags_none: Option<String>,
+    /// Expenses paid from this account.
+    pub account_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+fn split_tags(value: &Option<String>) -> Vec<String> {
+    value
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl ExpenseFilter {
+    pub fn any_tags(&self) -> Vec<String> {
+        split_tags(&self.tags_any)
+    }
+
+    pub fn all_tags(&self) -> Vec<String> {
+        split_tags(&self.tags_all)
+    }
+
+    pub fn no_tags(&self) -> Vec<String> {
+        split_tags(&self.tags_none)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExpenseSummary {
+    pub expense_count: i64,
+    pub total: f64,
+    pub by_category: Vec<CategoryTotal>,
+    /// An expense counts towards each of its tags, so these totals can
+    /// add up to more than `total`.
+    pub by_tag: Vec<TagTotal>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Day,
+    Week,
+    #[default]
+    Month,
+    Year,
+}
+
+impl Period {
+    /// Names the period a day falls in. Weeks are ISO 8601 weeks: they start
+    /// on Monday and belong to the year of their Thursday, so a week is never
+    /// split across New Year (2024-12-30 is in `2025-W01`).
+    pub fn label(&self, day: NaiveDate) -> String {
+        match self {
+            Period::Day => day.format("%Y-%m-%d").to_string(),
+            Period::Week => {
+                let week = day.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Period::Month => day.format("%Y-%m").to_string(),
+            Period::Year => day.format("%Y").to_string(),
+        }
+    }
+}
+
+/// `from` is inclusive, `to` exclusive.
+#[derive(Debug, Deserialize, Default)]
+pub struct CashFlowQuery {
+    #[serde(default)]
+    pub period: Period,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CashFlow {
+    pub income: f64,
+    pub expenses: f64,
+    /// `income - expenses`.
+    pub net: f64,
+}
+
+impl CashFlow {
+    pub fn new(income: f64, expenses: f64) -> Self {
+        Self {
+            income,
+            expenses,
+            net: income - expenses,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CashFlowRow {
+    /// e.g. `2025-01` for months or `2025-W03` for weeks.
+    pub period: String,
+    #[serde(flatten)]
+    pub flow: CashFlow,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CashFlowReport {
+    pub period: Period,
+    /// Periods with income or expenses, oldest first.
+    pub rows: Vec<CashFlowRow>,
+    pub total: CashFlow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_lists_are_comma_separated() {
+        let filter = ExpenseFilter {
+            tags_any: Some("travel, work,,".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(filter.any_tags(), vec!["travel", "work"]);
+        assert!(filter.all_tags().is_empty());
+    }
+
+    #[test]
+    fn test_weeks_are_iso_weeks() {
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        assert_eq!(Period::Week.label(day(2024, 12, 30)), "2025-W01");
+        assert_eq!(Period::Week.label(day(2025, 1, 5)), "2025-W01");
+        assert_eq!(Period::Week.label(day(2025, 1, 6)), "2025-W02");
+        assert_eq!(Period::Week.label(day(2021, 1, 3)), "2020-W53");
+        assert_eq!(Period::Month.label(day(2024, 12, 30)), "2024-12");
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+pub const MAX_TAGS_PER_EXPENSE: usize = 20;
+const MAX_TAG_LEN: usize = 50;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Tag {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// Number of non-deleted expenses carrying the tag.
+    pub expense_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct RenameTagRequest {
+    #[validate(length(min = 1, max = 50, message = "Tag must be between 1 and 50 characters"))]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeTagRequest {
+    /// The tag that takes over the merged tag's expenses.
+    pub into: Uuid,
+}
+
+/// Trims tags and drops blanks and case-insensitive duplicates, keeping the
+/// first spelling.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|tag| tag.trim()) {
+        if !tag.is_empty() && !normalized.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+/// `validator` custom check for tag lists on requests.
+pub fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+    if tags.len() > MAX_TAGS_PER_EXPENSE {
+        return Err(ValidationError::new("tags")
+            .with_message(format!("At most {} tags are allowed", MAX_TAGS_PER_EXPENSE).into()));
+    }
+    if tags
+        .iter()
+        .any(|tag| tag.trim().is_empty() || tag.trim().chars().count() > MAX_TAG_LEN)
+    {
+        return Err(ValidationError::new("tags").with_message(
+            format!("Tags must be between 1 and {} characters", MAX_TAG_LEN).into(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_normalize_tags() {
+        assert_eq!(
+            normalize_tags(&tags(&[" Travel ", "travel", "", "Work"])),
+            tags(&["Travel", "Work"])
+        );
+    }
+
+    #[test]
+    fn test_validate_tags() {
+        assert!(validate_tags(&tags(&["a", "b"])).is_ok());
+        assert!(validate_tags(&tags(&["  "])).is_err());
+        assert!(validate_tags(&["x".repeat(51)]).is_err());
+        assert!(validate_tags(&vec!["t".to_string(); 21]).is_err());
+    }
+}
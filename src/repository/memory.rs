@@ -0,0 +1,467 @@
+use crate::context::RequestContext;
+use crate::models::account::UnknownAccount;
+use crate::models::audit::AuditAction;
+use crate::models::expense::{CreateExpenseRequest, Expense, UpdateExpenseRequest};
+use crate::models::summary::{CategoryTotal, ExpenseFilter, ExpenseSummary, TagTotal};
+use crate::models::tag::normalize_tags;
+use crate::repository::ExpenseRepository;
+use crate::services::audit_service::{AuditRecord, ENTITY_EXPENSE, GENESIS_HASH, NewAuditEntry};
+use crate::services::expense_service::VersionedUpdate;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+/// Expenses kept in memory, for tests and `--demo`. Behaves like the SQLite
+/// repository without payees, accounts, refunds or expense reports: payees
+/// are kept as entered and every account is unknown. Everything is lost
+/// when the repository is dropped.
+#[derive(Default)]
+pub struct InMemoryExpenseRepository {
+    store: Mutex<Store>,
+}
+
+#[derive(Default)]
+struct Store {
+    /// In insertion order.
+    expenses: Vec<Expense>,
+    /// Tag names as first spelled; compared case-insensitively.
+    tags: Vec<String>,
+    audit_log: Vec<AuditRecord>,
+}
+
+impl InMemoryExpenseRepository {
+    /// A repository holding `expenses` as they are, e.g. generated ones with
+    /// dates in the past. Their tags become the known tags.
+    pub fn with_expenses(expenses: Vec<Expense>) -> Self {
+        let mut store = Store::default();
+        for expense in &expenses {
+            for tag in &expense.tags {
+                store.tag_name(tag);
+            }
+        }
+        store.expenses = expenses;
+        Self {
+            store: Mutex::new(store),
+        }
+    }
+
+    /// Hashes of the audit entries written so far, oldest first, as
+    /// `(prev_hash, hash)`.
+    #[cfg(test)]
+    fn audit_chain(&self) -> Vec<(String, String)> {
+        self.lock()
+            .audit_log
+            .iter()
+            .map(|record| (record.prev_hash.clone(), record.hash.clone()))
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Store> {
+        // A panic elsewhere cannot leave a half-applied write: every write
+        // is checked and serialized before the store is touched.
+        self.store
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Store {
+    fn position(&self, id: Uuid) -> Option<usize> {
+        self.expenses.iter().position(|expense| expense.id == id)
+    }
+
+    /// The stored spelling of `tag`, registering it if it is new.
+    fn tag_name(&mut self, tag: &str) -> String {
+        match self.tags.iter().find(|t| t.eq_ignore_ascii_case(tag)) {
+            Some(existing) => existing.clone(),
+            None => {
+                self.tags.push(tag.to_string());
+                tag.to_string()
+            }
+        }
+    }
+
+    /// The stored names for `tags` in display order, as the SQLite
+    /// repository returns them.
+    fn tag_names(&mut self, tags: &[String]) -> Vec<String> {
+        let mut names: Vec<String> = normalize_tags(tags)
+            .iter()
+            .map(|tag| self.tag_name(tag))
+            .collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names
+    }
+
+    fn append_audit(&mut self, ctx: &RequestContext, entry: NewAuditEntry) {
+        let prev_hash = self
+            .audit_log
+            .last()
+            .map_or_else(|| GENESIS_HASH.to_string(), |record| record.hash.clone());
+        self.audit_log
+            .push(AuditRecord::link(prev_hash, ctx, entry));
+    }
+
+    fn active<'a>(&'a self, filter: &'a ExpenseFilter) -> impl Iterator<Item = &'a Expense> {
+        let any = filter.any_tags();
+        let all = filter.all_tags();
+        let none = filter.no_tags();
+        let has_tag = |expense: &Expense, tags: &[String]| {
+            expense
+                .tags
+                .iter()
+                .any(|tag| tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+        };
+
+        self.expenses.iter().filter(move |expense| {
+            expense.deleted_at.is_none()
+                && (any.is_empty() || has_tag(expense, &any))
+                && all
+                    .iter()
+                    .all(|tag| has_tag(expense, std::slice::from_ref(tag)))
+                && (none.is_empty() || !has_tag(expense, &none))
+                && filter
+                    .account_id
+                    .is_none_or(|account_id| expense.account_id == Some(account_id))
+                && filter.from.is_none_or(|from| expense.date >= from)
+                && filter.to.is_none_or(|to| expense.date < to)
+        })
+    }
+}
+
+/// Newest first; among equal dates, the later insert first.
+fn newest_first(expenses: &mut [Expense]) {
+    expenses.reverse();
+    expenses.sort_by_key(|expense| Reverse(expense.date));
+}
+
+fn by_total_then(a: f64, b: f64, then: Ordering) -> Ordering {
+    b.partial_cmp(&a).unwrap_or(Ordering::Equal).then(then)
+}
+
+/// There are no accounts in this backend, so every account is unknown.
+fn check_account(account_id: Option<Uuid>) -> Result<()> {
+    match account_id {
+        Some(account_id) => Err(UnknownAccount(account_id).into()),
+        None => Ok(()),
+    }
+}
+
+#[async_trait]
+impl ExpenseRepository for InMemoryExpenseRepository {
+    async fn insert(&self, ctx: &RequestContext, request: CreateExpenseRequest) -> Result<Expense> {
+        check_account(request.account_id)?;
+        let mut store = self.lock();
+        let expense = Expense {
+            payee: request.payee,
+            notes: request.notes,
+            reimbursable: request.reimbursable,
+            tags: store.tag_names(&request.tags),
+            ..Expense::new(request.amount, request.category)
+        };
+        let after = serde_json::to_value(&expense)?;
+
+        store.expenses.push(expense.clone());
+        store.append_audit(
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_EXPENSE,
+                entity_id: expense.id.to_string(),
+                action: AuditAction::Create,
+                before: None,
+                after: Some(after),
+            },
+        );
+
+        Ok(expense)
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Expense>> {
+        let store = self.lock();
+        Ok(store
+            .position(id)
+            .map(|index| store.expenses[index].clone()))
+    }
+
+    async fn list(&self, filter: &ExpenseFilter) -> Result<Vec<Expense>> {
+        let mut expenses: Vec<Expense> = self.lock().active(filter).cloned().collect();
+        newest_first(&mut expenses);
+        Ok(expenses)
+    }
+
+    async fn summarize(&self, filter: &ExpenseFilter) -> Result<ExpenseSummary> {
+        let store = self.lock();
+        let mut expense_count = 0;
+        let mut total = 0.0;
+        let mut categories: BTreeMap<&str, (i64, f64)> = BTreeMap::new();
+        let mut tags: BTreeMap<String, (&str, i64, f64)> = BTreeMap::new();
+        for expense in store.active(filter) {
+            let net = expense.amount - expense.refunded;
+            expense_count += 1;
+            total += net;
+            let category = categories.entry(&expense.category).or_default();
+            category.0 += 1;
+            category.1 += net;
+            for tag in &expense.tags {
+                let entry = tags
+                    .entry(tag.to_ascii_lowercase())
+                    .or_insert((tag, 0, 0.0));
+                entry.1 += 1;
+                entry.2 += net;
+            }
+        }
+
+        let mut by_category: Vec<CategoryTotal> = categories
+            .into_iter()
+            .map(|(category, (expense_count, total))| CategoryTotal {
+                category: category.to_string(),
+                expense_count,
+                total,
+            })
+            .collect();
+        by_category.sort_by(|a, b| by_total_then(a.total, b.total, a.category.cmp(&b.category)));
+
+        let mut by_tag: Vec<(String, TagTotal)> = tags
+            .into_iter()
+            .map(|(key, (tag, expense_count, total))| {
+                let total = TagTotal {
+                    tag: tag.to_string(),
+                    expense_count,
+                    total,
+                };
+                (key, total)
+            })
+            .collect();
+        by_tag.sort_by(|(a_key, a), (b_key, b)| by_total_then(a.total, b.total, a_key.cmp(b_key)));
+
+        Ok(ExpenseSummary {
+            expense_count,
+            total,
+            by_category,
+            by_tag: by_tag.into_iter().map(|(_, total)| total).collect(),
+        })
+    }
+
+    async fn highest(&self) -> Result<Option<Expense>> {
+        let store = self.lock();
+        let filter = ExpenseFilter::default();
+        let highest = store.active(&filter).reduce(|highest, expense| {
+            if expense.amount > highest.amount {
+                expense
+            } else {
+                highest
+            }
+        });
+        Ok(highest.cloned())
+    }
+
+    async fn update(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expected_version: Option<i64>,
+        request: UpdateExpenseRequest,
+    ) -> Result<VersionedUpdate> {
+        let mut store = self.lock();
+        let Some(index) = store.position(id) else {
+            return Ok(VersionedUpdate::NotFound);
+        };
+        let before = store.expenses[index].clone();
+        if before.deleted_at.is_some() {
+            return Ok(VersionedUpdate::NotFound);
+        }
+        if expected_version.is_some_and(|v| v != before.version) {
+            return Ok(VersionedUpdate::VersionMismatch(before));
+        }
+        check_account(request.account_id)?;
+
+        let after = Expense {
+            amount: request.amount,
+            category: request.category,
+            payee: request.payee,
+            notes: request.notes,
+            reimbursable: request.reimbursable,
+            tags: store.tag_names(&request.tags),
+            version: before.version + 1,
+            ..before.clone()
+        };
+        let entry = NewAuditEntry {
+            entity_type: ENTITY_EXPENSE,
+            entity_id: id.to_string(),
+            action: AuditAction::Update,
+            before: Some(serde_json::to_value(&before)?),
+            after: Some(serde_json::to_value(&after)?),
+        };
+
+        store.expenses[index] = after.clone();
+        store.append_audit(ctx, entry);
+
+        Ok(VersionedUpdate::Updated(after))
+    }
+
+    async fn soft_delete(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<VersionedUpdate> {
+        let mut store = self.lock();
+        let Some(index) = store.position(id) else {
+            return Ok(VersionedUpdate::NotFound);
+        };
+        let before = store.expenses[index].clone();
+        if before.deleted_at.is_some() {
+            return Ok(VersionedUpdate::NotFound);
+        }
+        if expected_version.is_some_and(|v| v != before.version) {
+            return Ok(VersionedUpdate::VersionMismatch(before));
+        }
+
+        let mut after = before.clone();
+        after.deleted_at = Some(Utc::now());
+        after.version += 1;
+        let entry = NewAuditEntry {
+            entity_type: ENTITY_EXPENSE,
+            entity_id: id.to_string(),
+            action: AuditAction::Delete,
+            before: Some(serde_json::to_value(&before)?),
+            after: Some(serde_json::to_value(&after)?),
+        };
+
+        store.expenses[index] = after.clone();
+        store.append_audit(ctx, entry);
+
+        Ok(VersionedUpdate::Updated(after))
+    }
+
+    async fn trash(&self) -> Result<Vec<Expense>> {
+        let mut trash: Vec<Expense> = self
+            .lock()
+            .expenses
+            .iter()
+            .filter(|expense| expense.deleted_at.is_some())
+            .cloned()
+            .collect();
+        trash.sort_by_key(|expense| Reverse(expense.deleted_at));
+        Ok(trash)
+    }
+
+    async fn restore(&self, ctx: &RequestContext, id: Uuid) -> Result<Option<Expense>> {
+        let mut store = self.lock();
+        let Some(index) = store.position(id) else {
+            return Ok(None);
+        };
+        let before = store.expenses[index].clone();
+        if before.deleted_at.is_none() {
+            return Ok(None);
+        }
+
+        let mut after = before.clone();
+        after.deleted_at = None;
+        after.version += 1;
+        let entry = NewAuditEntry {
+            entity_type: ENTITY_EXPENSE,
+            entity_id: id.to_string(),
+            action: AuditAction::Restore,
+            before: Some(serde_json::to_value(&before)?),
+            after: Some(serde_json::to_value(&after)?),
+        };
+
+        store.expenses[index] = after.clone();
+        store.append_audit(ctx, entry);
+
+        Ok(Some(after))
+    }
+
+    async fn purge_deleted_before(
+        &self,
+        ctx: &RequestContext,
+        cutoff: DateTime<Utc>,
+    ) -> Result<u64> {
+        let mut store = self.lock();
+        let expired = |expense: &Expense| expense.deleted_at.is_some_and(|at| at < cutoff);
+
+        let entries = store
+            .expenses
+            .iter()
+            .filter(|expense| expired(expense))
+            .map(|expense| {
+                Ok(NewAuditEntry {
+                    entity_type: ENTITY_EXPENSE,
+                    entity_id: expense.id.to_string(),
+                    action: AuditAction::Purge,
+                    before: Some(serde_json::to_value(expense)?),
+                    after: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        store.expenses.retain(|expense| !expired(expense));
+        let purged = entries.len() as u64;
+        for entry in entries {
+            store.append_audit(ctx, entry);
+        }
+
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    async fn open() -> Option<Arc<dyn ExpenseRepository>> {
+        Some(Arc::new(InMemoryExpenseRepository::default()))
+    }
+
+    crate::repository::suite::expense_repository_suite!(open());
+
+    #[tokio::test]
+    async fn test_writes_extend_the_audit_chain() {
+        let repository = InMemoryExpenseRepository::default();
+        let ctx = RequestContext::new("tester", "test-request");
+
+        let request = CreateExpenseRequest {
+            amount: 12.5,
+            category: "Food".to_string(),
+            ..Default::default()
+        };
+        let expense = repository.insert(&ctx, request).await.unwrap();
+        repository
+            .soft_delete(&ctx, expense.id, Some(1))
+            .await
+            .unwrap();
+
+        let chain = repository.audit_chain();
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].0, GENESIS_HASH);
+        assert_eq!(chain[1].0, chain[0].1);
+    }
+
+    #[tokio::test]
+    async fn test_with_expenses_keeps_them_as_given() {
+        let mut old = Expense::new(30.0, "Travel".to_string());
+        old.date = Utc::now() - chrono::Duration::days(40);
+        old.tags = vec!["Work".to_string()];
+        let repository = InMemoryExpenseRepository::with_expenses(vec![old.clone()]);
+
+        let found = repository.find(old.id).await.unwrap().unwrap();
+        assert_eq!(found.date, old.date);
+
+        let request = CreateExpenseRequest {
+            amount: 5.0,
+            category: "Food".to_string(),
+            tags: vec!["work".to_string()],
+            ..Default::default()
+        };
+        let new = repository
+            .insert(&RequestContext::new("tester", "test-request"), request)
+            .await
+            .unwrap();
+        assert_eq!(new.tags, vec!["Work"]);
+    }
+}
@@ -0,0 +1,121 @@
+//! Storage for expenses, independent of the database behind it.
+//!
+//! [`ExpenseService`](crate::services::expense_service::ExpenseService) works
+//! against [`ExpenseRepository`]; the implementation is picked from the
+//! database URL by [`Backend::from_url`], or kept in memory for tests and
+//! `--demo`.
+
+use crate::context::RequestContext;
+use crate::models::expense::{CreateExpenseRequest, Expense, UpdateExpenseRequest};
+use crate::models::summary::{ExpenseFilter, ExpenseSummary};
+use crate::services::expense_service::VersionedUpdate;
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+pub mod memory;
+pub mod postgres;
+pub mod sqlite;
+#[cfg(test)]
+pub(crate) mod suite;
+
+pub use memory::InMemoryExpenseRepository;
+pub use postgres::PostgresExpenseRepository;
+pub use sqlite::SqliteExpenseRepository;
+
+/// The database a URL points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// `sqlite:` URLs; every feature is available.
+    Sqlite,
+    /// `postgres://` or `postgresql://` URLs; expenses, their tags and
+    /// their audit trail only.
+    Postgres,
+}
+
+impl Backend {
+    pub fn from_url(url: &str) -> Result<Self> {
+        if url.starts_with("sqlite:") {
+            Ok(Backend::Sqlite)
+        } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Ok(Backend::Postgres)
+        } else {
+            bail!("DATABASE_URL must start with 'sqlite:', 'postgres://' or 'postgresql://'")
+        }
+    }
+}
+
+/// Reads and writes expenses. Every write records its audit entry in the
+/// same transaction, and versioned writes only apply to the version the
+/// caller last saw.
+#[async_trait]
+pub trait ExpenseRepository: Send + Sync {
+    async fn insert(&self, ctx: &RequestContext, request: CreateExpenseRequest) -> Result<Expense>;
+
+    /// The expense with `id`, trashed or not.
+    async fn find(&self, id: Uuid) -> Result<Option<Expense>>;
+
+    /// Non-deleted expenses matching `filter`, newest first.
+    async fn list(&self, filter: &ExpenseFilter) -> Result<Vec<Expense>>;
+
+    /// Totals of the non-deleted expenses matching `filter`, overall, per
+    /// category and per tag, net of refunds.
+    async fn summarize(&self, filter: &ExpenseFilter) -> Result<ExpenseSummary>;
+
+    /// The non-deleted expense with the largest amount.
+    async fn highest(&self) -> Result<Option<Expense>>;
+
+    async fn update(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expected_version: Option<i64>,
+        request: UpdateExpenseRequest,
+    ) -> Result<VersionedUpdate>;
+
+    /// Moves an expense to the trash. Trashed expenses count as not found.
+    async fn soft_delete(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<VersionedUpdate>;
+
+    /// Trashed expenses, most recently deleted first.
+    async fn trash(&self) -> Result<Vec<Expense>>;
+
+    /// Takes an expense back out of the trash. Returns `None` if it is not
+    /// in the trash.
+    async fn restore(&self, ctx: &RequestContext, id: Uuid) -> Result<Option<Expense>>;
+
+    /// Permanently removes expenses that were trashed before `cutoff`.
+    /// Returns the number of purged expenses.
+    async fn purge_deleted_before(
+        &self,
+        ctx: &RequestContext,
+        cutoff: DateTime<Utc>,
+    ) -> Result<u64>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_from_url() {
+        assert_eq!(
+            Backend::from_url("sqlite:./expenses.db").unwrap(),
+            Backend::Sqlite
+        );
+        assert_eq!(
+            Backend::from_url("postgres://localhost/expenses").unwrap(),
+            Backend::Postgres
+        );
+        assert_eq!(
+            Backend::from_url("postgresql://localhost/expenses").unwrap(),
+            Backend::Postgres
+        );
+        assert!(Backend::from_url("mysql://localhost/expenses").is_err());
+    }
+}
@@ -0,0 +1,789 @@
+use crate::context::RequestContext;
+use crate::database::Migration;
+use crate::models::account::UnknownAccount;
+use crate::models::audit::AuditAction;
+use crate::models::expense::{CreateExpenseRequest, Expense, UpdateExpenseRequest};
+use crate::models::summary::{CategoryTotal, ExpenseFilter, ExpenseSummary, TagTotal};
+use crate::models::tag::normalize_tags;
+use crate::repository::ExpenseRepository;
+use crate::services::audit_service::{AuditRecord, ENTITY_EXPENSE, GENESIS_HASH, NewAuditEntry};
+use crate::services::expense_service::VersionedUpdate;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, SubsecRound, Utc};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions, PgRow};
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, QueryBuilder, Row};
+use uuid::Uuid;
+
+/// Schema changes for the PostgreSQL backend, recorded in its own
+/// `schema_migrations` table like [`crate::database::MIGRATIONS`].
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_expenses",
+        sql: include_str!("../../migrations/postgres/001_create_expenses.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "create_idempotency_keys",
+        sql: include_str!("../../migrations/postgres/002_create_idempotency_keys.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "scope_idempotency_keys_by_actor",
+        sql: include_str!("../../migrations/postgres/003_scope_idempotency_keys_by_actor.sql"),
+    },
+];
+
+/// Key of the advisory lock servers hold while migrating, so several
+/// starting against one database apply each migration once.
+const MIGRATION_LOCK: i64 = 0x0065_7870_656e_6365;
+
+/// Tags are collected into an array per row so expenses can still be read
+/// with a single query.
+const EXPENSE_COLUMNS: &str = "id, amount, category, payee, payee_id, notes, account_id, \
+     reimbursable, date, deleted_at, version, ARRAY( \
+         SELECT t.name FROM expense_tags et JOIN tags t ON t.id = et.tag_id \
+         WHERE et.expense_id = expenses.id ORDER BY lower(t.name) \
+     ) AS tags";
+
+/// Expenses in a PostgreSQL database shared by several servers.
+///
+/// Payees, accounts, refunds and expense reports are not stored here yet:
+/// payees are kept as entered, no account exists to pay from and nothing
+/// is ever refunded or locked.
+#[derive(Clone)]
+pub struct PostgresExpenseRepository {
+    pool: PgPool,
+}
+
+impl PostgresExpenseRepository {
+    pub async fn connect(url: &str) -> Result<Self> {
+        Self::connect_with(url.parse()?).await
+    }
+
+    /// Connects and brings the schema up to date.
+    pub async fn connect_with(options: PgConnectOptions) -> Result<Self> {
+        let pool = PgPoolOptions::new().connect_with(options).await?;
+        run_migrations(&pool).await?;
+
+        tracing::info!("Database ready");
+        Ok(Self { pool })
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+}
+
+async fn run_migrations(pool: &PgPool) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(MIGRATION_LOCK)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version BIGINT PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL
+        )
+        "#,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    for migration in MIGRATIONS {
+        let applied = sqlx::query("SELECT 1 FROM schema_migrations WHERE version = $1")
+            .bind(migration.version)
+            .fetch_optional(&mut *tx)
+            .await?
+            .is_some();
+        if applied {
+            continue;
+        }
+
+        sqlx::raw_sql(migration.sql).execute(&mut *tx).await?;
+        sqlx::query(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES ($1, $2, $3)",
+        )
+        .bind(migration.version)
+        .bind(migration.name)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+
+        tracing::info!(
+            version = migration.version,
+            name = migration.name,
+            "Applied migration"
+        );
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[async_trait]
+impl ExpenseRepository for PostgresExpenseRepository {
+    async fn insert(&self, ctx: &RequestContext, request: CreateExpenseRequest) -> Result<Expense> {
+        check_account(request.account_id)?;
+        let mut expense = Expense {
+            payee: request.payee,
+            notes: request.notes,
+            reimbursable: request.reimbursable,
+            ..Expense::new(request.amount, request.category)
+        };
+        // PostgreSQL keeps microseconds; return what a later read returns.
+        expense.date = expense.date.trunc_subsecs(6);
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO expenses \
+             (id, amount, category, payee, notes, reimbursable, date, version) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(expense.id)
+        .bind(expense.amount)
+        .bind(&expense.category)
+        .bind(&expense.payee)
+        .bind(&expense.notes)
+        .bind(expense.reimbursable)
+        .bind(expense.date)
+        .bind(expense.version)
+        .execute(&mut *tx)
+        .await?;
+        expense.tags = set_expense_tags(&mut tx, expense.id, &request.tags).await?;
+
+        append_audit(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_EXPENSE,
+                entity_id: expense.id.to_string(),
+                action: AuditAction::Create,
+                before: None,
+                after: Some(serde_json::to_value(&expense)?),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(expense)
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Expense>> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_expense(&mut conn, id).await
+    }
+
+    async fn list(&self, filter: &ExpenseFilter) -> Result<Vec<Expense>> {
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {EXPENSE_COLUMNS} FROM expenses WHERE deleted_at IS NULL"
+        ));
+        push_filter(&mut query, filter);
+        query.push(" ORDER BY date DESC");
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        rows.iter().map(row_to_expense).collect()
+    }
+
+    async fn summarize(&self, filter: &ExpenseFilter) -> Result<ExpenseSummary> {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT COUNT(*) AS expense_count, COALESCE(SUM(amount), 0.0) AS total \
+             FROM expenses WHERE deleted_at IS NULL",
+        );
+        push_filter(&mut query, filter);
+        let totals = query.build().fetch_one(&self.pool).await?;
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT category, COUNT(*) AS expense_count, SUM(amount) AS total \
+             FROM expenses WHERE deleted_at IS NULL",
+        );
+        push_filter(&mut query, filter);
+        query.push(" GROUP BY category ORDER BY total DESC, category");
+        let by_category = query
+            .build()
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| CategoryTotal {
+                category: row.get("category"),
+                expense_count: row.get("expense_count"),
+                total: row.get("total"),
+            })
+            .collect();
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT t.name AS tag, COUNT(*) AS expense_count, SUM(expenses.amount) AS total \
+             FROM expenses \
+             JOIN expense_tags et ON et.expense_id = expenses.id \
+             JOIN tags t ON t.id = et.tag_id \
+             WHERE deleted_at IS NULL",
+        );
+        push_filter(&mut query, filter);
+        query.push(" GROUP BY t.id, t.name ORDER BY total DESC, t.name");
+        let by_tag = query
+            .build()
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| TagTotal {
+                tag: row.get("tag"),
+                expense_count: row.get("expense_count"),
+                total: row.get("total"),
+            })
+            .collect();
+
+        Ok(ExpenseSummary {
+            expense_count: totals.get("expense_count"),
+            total: totals.get("total"),
+            by_category,
+            by_tag,
+        })
+    }
+
+    async fn highest(&self) -> Result<Option<Expense>> {
+        let row = sqlx::query(&format!(
+            "SELECT {EXPENSE_COLUMNS} FROM expenses WHERE deleted_at IS NULL \
+             ORDER BY amount DESC LIMIT 1"
+        ))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(row_to_expense).transpose()
+    }
+
+    async fn update(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expected_version: Option<i64>,
+        request: UpdateExpenseRequest,
+    ) -> Result<VersionedUpdate> {
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = fetch_expense(&mut tx, id).await? else {
+            return Ok(VersionedUpdate::NotFound);
+        };
+        if before.deleted_at.is_some() {
+            return Ok(VersionedUpdate::NotFound);
+        }
+        if expected_version.is_some_and(|v| v != before.version) {
+            return Ok(VersionedUpdate::VersionMismatch(before));
+        }
+        check_account(request.account_id)?;
+
+        let mut after = Expense {
+            amount: request.amount,
+            category: request.category,
+            payee: request.payee,
+            notes: request.notes,
+            reimbursable: request.reimbursable,
+            version: before.version + 1,
+            ..before.clone()
+        };
+
+        // A concurrent writer that got there first bumped the version.
+        let updated = sqlx::query(
+            "UPDATE expenses SET amount = $1, category = $2, payee = $3, notes = $4, \
+             reimbursable = $5, version = version + 1 WHERE id = $6 AND version = $7",
+        )
+        .bind(after.amount)
+        .bind(&after.category)
+        .bind(&after.payee)
+        .bind(&after.notes)
+        .bind(after.reimbursable)
+        .bind(id)
+        .bind(before.version)
+        .execute(&mut *tx)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Ok(VersionedUpdate::VersionMismatch(before));
+        }
+        after.tags = set_expense_tags(&mut tx, id, &request.tags).await?;
+
+        append_audit(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_EXPENSE,
+                entity_id: id.to_string(),
+                action: AuditAction::Update,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(VersionedUpdate::Updated(after))
+    }
+
+    async fn soft_delete(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<VersionedUpdate> {
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = fetch_expense(&mut tx, id).await? else {
+            return Ok(VersionedUpdate::NotFound);
+        };
+        if before.deleted_at.is_some() {
+            return Ok(VersionedUpdate::NotFound);
+        }
+        if expected_version.is_some_and(|v| v != before.version) {
+            return Ok(VersionedUpdate::VersionMismatch(before));
+        }
+
+        let mut after = before.clone();
+        after.deleted_at = Some(Utc::now().trunc_subsecs(6));
+        after.version += 1;
+
+        let updated = sqlx::query(
+            "UPDATE expenses SET deleted_at = $1, version = version + 1 \
+             WHERE id = $2 AND version = $3",
+        )
+        .bind(after.deleted_at)
+        .bind(id)
+        .bind(before.version)
+        .execute(&mut *tx)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Ok(VersionedUpdate::VersionMismatch(before));
+        }
+
+        append_audit(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_EXPENSE,
+                entity_id: id.to_string(),
+                action: AuditAction::Delete,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(VersionedUpdate::Updated(after))
+    }
+
+    async fn trash(&self) -> Result<Vec<Expense>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {EXPENSE_COLUMNS} FROM expenses WHERE deleted_at IS NOT NULL \
+             ORDER BY deleted_at DESC"
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_expense).collect()
+    }
+
+    async fn restore(&self, ctx: &RequestContext, id: Uuid) -> Result<Option<Expense>> {
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = fetch_expense(&mut tx, id).await? else {
+            return Ok(None);
+        };
+        if before.deleted_at.is_none() {
+            return Ok(None);
+        }
+
+        let mut after = before.clone();
+        after.deleted_at = None;
+        after.version += 1;
+
+        let updated = sqlx::query(
+            "UPDATE expenses SET deleted_at = NULL, version = version + 1 \
+             WHERE id = $1 AND version = $2",
+        )
+        .bind(id)
+        .bind(before.version)
+        .execute(&mut *tx)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        append_audit(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_EXPENSE,
+                entity_id: id.to_string(),
+                action: AuditAction::Restore,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(Some(after))
+    }
+
+    async fn purge_deleted_before(
+        &self,
+        ctx: &RequestContext,
+        cutoff: DateTime<Utc>,
+    ) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        let rows = sqlx::query(&format!(
+            "SELECT {EXPENSE_COLUMNS} FROM expenses \
+             WHERE deleted_at IS NOT NULL AND deleted_at < $1 FOR UPDATE"
+        ))
+        .bind(cutoff)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let expired: Vec<Expense> = rows.iter().map(row_to_expense).collect::<Result<_>>()?;
+
+        for expense in &expired {
+            // Tag links go with the expense.
+            sqlx::query("DELETE FROM expenses WHERE id = $1")
+                .bind(expense.id)
+                .execute(&mut *tx)
+                .await?;
+
+            append_audit(
+                &mut tx,
+                ctx,
+                NewAuditEntry {
+                    entity_type: ENTITY_EXPENSE,
+                    entity_id: expense.id.to_string(),
+                    action: AuditAction::Purge,
+                    before: Some(serde_json::to_value(expense)?),
+                    after: None,
+                },
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(expired.len() as u64)
+    }
+}
+
+/// There are no accounts in this backend, so every account is unknown.
+fn check_account(account_id: Option<Uuid>) -> Result<()> {
+    match account_id {
+        Some(account_id) => Err(UnknownAccount(account_id).into()),
+        None => Ok(()),
+    }
+}
+
+/// Appends an entry to the audit log, continuing the same hash chain the
+/// SQLite log uses.
+///
+/// The table lock is held until the transaction ends, so entries are
+/// chained in commit order; readers are not blocked.
+async fn append_audit(
+    conn: &mut PgConnection,
+    ctx: &RequestContext,
+    entry: NewAuditEntry,
+) -> Result<()> {
+    sqlx::query("LOCK TABLE audit_log IN EXCLUSIVE MODE")
+        .execute(&mut *conn)
+        .await?;
+    let prev_hash: String =
+        sqlx::query_scalar("SELECT hash FROM audit_log ORDER BY seq DESC LIMIT 1")
+            .fetch_optional(&mut *conn)
+            .await?
+            .unwrap_or_else(|| GENESIS_HASH.to_string());
+    let record = AuditRecord::link(prev_hash, ctx, entry);
+
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (
+            id, entity_type, entity_id, action, actor, request_id,
+            occurred_at, before_json, after_json, prev_hash, hash
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        "#,
+    )
+    .bind(&record.id)
+    .bind(record.entity_type)
+    .bind(&record.entity_id)
+    .bind(record.action)
+    .bind(&record.actor)
+    .bind(&record.request_id)
+    .bind(&record.occurred_at)
+    .bind(&record.before_json)
+    .bind(&record.after_json)
+    .bind(&record.prev_hash)
+    .bind(&record.hash)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Replaces the tags of an expense, creating tags that do not exist yet.
+/// Returns the stored tag names in display order.
+async fn set_expense_tags(
+    conn: &mut PgConnection,
+    expense_id: Uuid,
+    tags: &[String],
+) -> Result<Vec<String>> {
+    sqlx::query("DELETE FROM expense_tags WHERE expense_id = $1")
+        .bind(expense_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let mut names = Vec::new();
+    for tag in normalize_tags(tags) {
+        sqlx::query(
+            "INSERT INTO tags (id, name, created_at) VALUES ($1, $2, $3) \
+             ON CONFLICT ((lower(name))) DO NOTHING",
+        )
+        .bind(Uuid::new_v4())
+        .bind(&tag)
+        .bind(Utc::now())
+        .execute(&mut *conn)
+        .await?;
+
+        // Existing tags keep their spelling.
+        let row = sqlx::query("SELECT id, name FROM tags WHERE lower(name) = lower($1)")
+            .bind(&tag)
+            .fetch_one(&mut *conn)
+            .await?;
+        sqlx::query("INSERT INTO expense_tags (expense_id, tag_id) VALUES ($1, $2)")
+            .bind(expense_id)
+            .bind(row.get::<Uuid, _>("id"))
+            .execute(&mut *conn)
+            .await?;
+        names.push(row.get::<String, _>("name"));
+    }
+
+    names.sort_by_key(|name| name.to_lowercase());
+    Ok(names)
+}
+
+/// Appends `filter` as `AND ...` conditions on the `expenses` table. Tag
+/// names match case-insensitively, as in SQLite.
+fn push_filter(query: &mut QueryBuilder<Postgres>, filter: &ExpenseFilter) {
+    const HAS_TAG: &str = "EXISTS (SELECT 1 FROM expense_tags et JOIN tags t ON t.id = et.tag_id \
+         WHERE et.expense_id = expenses.id AND lower(t.name) IN (";
+
+    let mut push_tags = |prefix: &str, tags: &[String]| {
+        query.push(prefix).push(HAS_TAG);
+        let mut separated = query.separated(", ");
+        for tag in tags {
+            separated.push_bind(tag.to_lowercase());
+        }
+        query.push("))");
+    };
+
+    let any = filter.any_tags();
+    if !any.is_empty() {
+        push_tags(" AND ", &any);
+    }
+    for tag in filter.all_tags() {
+        push_tags(" AND ", std::slice::from_ref(&tag));
+    }
+    let none = filter.no_tags();
+    if !none.is_empty() {
+        push_tags(" AND NOT ", &none);
+    }
+
+    if let Some(account_id) = filter.account_id {
+        query
+            .push(" AND expenses.account_id = ")
+            .push_bind(account_id);
+    }
+    if let Some(from) = filter.from {
+        query.push(" AND date >= ").push_bind(from);
+    }
+    if let Some(to) = filter.to {
+        query.push(" AND date < ").push_bind(to);
+    }
+}
+
+async fn fetch_expense(conn: &mut PgConnection, id: Uuid) -> Result<Option<Expense>> {
+    let row = sqlx::query(&format!(
+        "SELECT {EXPENSE_COLUMNS} FROM expenses WHERE id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    row.as_ref().map(row_to_expense).transpose()
+}
+
+/// The columns of [`EXPENSE_COLUMNS`]; PostgreSQL enforces their types, so
+/// unlike SQLite rows these cannot be corrupt.
+#[derive(sqlx::FromRow)]
+struct ExpenseRow {
+    id: Uuid,
+    amount: f64,
+    category: String,
+    payee: Option<String>,
+    payee_id: Option<Uuid>,
+    notes: Option<String>,
+    account_id: Option<Uuid>,
+    reimbursable: bool,
+    tags: Vec<String>,
+    date: DateTime<Utc>,
+    deleted_at: Option<DateTime<Utc>>,
+    version: i64,
+}
+
+fn row_to_expense(row: &PgRow) -> Result<Expense> {
+    let row = ExpenseRow::from_row(row)?;
+    Ok(Expense {
+        id: row.id,
+        amount: row.amount,
+        category: row.category,
+        payee: row.payee,
+        payee_id: row.payee_id,
+        notes: row.notes,
+        account_id: row.account_id,
+        reimbursable: row.reimbursable,
+        tags: row.tags,
+        refunded: 0.0,
+        date: row.date,
+        deleted_at: row.deleted_at,
+        version: row.version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Connection;
+    use std::sync::Arc;
+
+    /// Points the PostgreSQL tests at a database they may create schemas
+    /// in, e.g. `postgres://postgres@localhost/expence_tracker_test`. The
+    /// tests pass without running when it is unset; CI's `test-postgres`
+    /// job sets it.
+    const TEST_URL_VAR: &str = "TEST_POSTGRES_URL";
+
+    async fn connect() -> Option<PostgresExpenseRepository> {
+        let Ok(url) = std::env::var(TEST_URL_VAR) else {
+            eprintln!("{TEST_URL_VAR} is not set; skipping PostgreSQL test");
+            return None;
+        };
+
+        // Every test gets a schema of its own, so tests can run in parallel.
+        let schema = format!("test_{}", Uuid::new_v4().simple());
+        let options: PgConnectOptions = url.parse().unwrap();
+        let mut conn = PgConnection::connect_with(&options).await.unwrap();
+        sqlx::query(&format!("CREATE SCHEMA {schema}"))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        conn.close().await.unwrap();
+
+        let repository =
+            PostgresExpenseRepository::connect_with(options.options([("search_path", &schema)]))
+                .await
+                .unwrap();
+        Some(repository)
+    }
+
+    async fn open() -> Option<Arc<dyn ExpenseRepository>> {
+        let repository = connect().await?;
+        Some(Arc::new(repository))
+    }
+
+    crate::repository::suite::expense_repository_suite!(open());
+
+    #[tokio::test]
+    async fn test_writes_extend_the_audit_chain() {
+        let Some(repository) = connect().await else {
+            return;
+        };
+        let ctx = RequestContext::new("tester", "test-request");
+
+        let request = CreateExpenseRequest {
+            amount: 12.5,
+            category: "Food".to_string(),
+            ..Default::default()
+        };
+        let expense = repository.insert(&ctx, request).await.unwrap();
+        repository
+            .soft_delete(&ctx, expense.id, Some(1))
+            .await
+            .unwrap();
+
+        let rows = sqlx::query("SELECT action, actor, prev_hash, hash FROM audit_log ORDER BY seq")
+            .fetch_all(repository.pool())
+            .await
+            .unwrap();
+        let actions: Vec<String> = rows.iter().map(|row| row.get("action")).collect();
+        assert_eq!(actions, vec!["create", "delete"]);
+        assert_eq!(rows[0].get::<String, _>("actor"), "tester");
+        assert_eq!(rows[0].get::<String, _>("prev_hash"), GENESIS_HASH);
+        assert_eq!(
+            rows[1].get::<String, _>("prev_hash"),
+            rows[0].get::<String, _>("hash")
+        );
+
+        let tampered = sqlx::query("UPDATE audit_log SET actor = 'mallory'")
+            .execute(repository.pool())
+            .await;
+        assert!(tampered.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_migrations_apply_once() {
+        let Some(repository) = connect().await else {
+            return;
+        };
+
+        run_migrations(repository.pool()).await.unwrap();
+        let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM schema_migrations")
+            .fetch_one(repository.pool())
+            .await
+            .unwrap();
+        assert_eq!(applied, MIGRATIONS.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_keys_replay() {
+        use crate::services::idempotency_service::{
+            Claim, IdempotencyService, KeyScope, StoredResponse,
+        };
+
+        let Some(repository) = connect().await else {
+            return;
+        };
+        let service = IdempotencyService::new(
+            repository.pool().clone(),
+            std::time::Duration::from_secs(3600),
+        );
+        let scope = KeyScope {
+            actor: "alice".to_string(),
+            key: "key-1".to_string(),
+            method: "POST".to_string(),
+            path: "/expenses".to_string(),
+        };
+        let response = StoredResponse {
+            status: 201,
+            headers: vec![("location".to_string(), "/expenses/1".to_string())],
+            body: b"{}".to_vec(),
+        };
+
+        assert_eq!(
+            service.claim(&scope, "hash").await.unwrap(),
+            Claim::Acquired
+        );
+        assert_eq!(
+            service.claim(&scope, "hash").await.unwrap(),
+            Claim::InProgress
+        );
+        service.complete(&scope, &response).await.unwrap();
+        assert_eq!(
+            service.claim(&scope, "hash").await.unwrap(),
+            Claim::Replay(response)
+        );
+        assert_eq!(
+            service.claim(&scope, "other").await.unwrap(),
+            Claim::Mismatch
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use crate::context::RequestContext;
+use crate::models::audit::AuditAction;
+use crate::models::expense::{CreateExpenseRequest, Expense, UpdateExpenseRequest};
+use crate::models::summary::{CategoryTotal, ExpenseFilter, ExpenseSummary, TagTotal};
+use crate::repository::ExpenseRepository;
+use crate::services::attachment_service;
+use crate::services::audit_service::{self, ENTITY_EXPENSE, NewAuditEntry};
+use crate::services::expense_service::{
+    EXPENSE_COLUMNS, NET_AMOUNT, VersionedUpdate, fetch_expense, insert_expense, push_filter,
+    row_to_expense, soft_delete_expense, update_expense,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
+use uuid::Uuid;
+
+/// Expenses in the SQLite database the rest of the application uses, so
+/// writes also see payees, accounts, refunds and expense report locks.
+#[derive(Clone)]
+pub struct SqliteExpenseRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteExpenseRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ExpenseRepository for SqliteExpenseRepository {
+    async fn insert(&self, ctx: &RequestContext, request: CreateExpenseRequest) -> Result<Expense> {
+        let mut tx = self.pool.begin().await?;
+        let expense = insert_expense(&mut tx, ctx, request).await?;
+        tx.commit().await?;
+
+        Ok(expense)
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Expense>> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_expense(&mut conn, id).await
+    }
+
+    async fn list(&self, filter: &ExpenseFilter) -> Result<Vec<Expense>> {
+        let mut query = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {EXPENSE_COLUMNS} FROM expenses WHERE deleted_at IS NULL"
+        ));
+        push_filter(&mut query, filter);
+        query.push(" ORDER BY date DESC");
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        rows.into_iter().map(row_to_expense).collect()
+    }
+
+    async fn summarize(&self, filter: &ExpenseFilter) -> Result<ExpenseSummary> {
+        let mut query = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT COUNT(*) AS expense_count, COALESCE(SUM({NET_AMOUNT}), 0.0) AS total \
+             FROM expenses WHERE deleted_at IS NULL"
+        ));
+        push_filter(&mut query, filter);
+        let totals = query.build().fetch_one(&self.pool).await?;
+
+        let mut query = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT category, COUNT(*) AS expense_count, SUM({NET_AMOUNT}) AS total \
+             FROM expenses WHERE deleted_at IS NULL"
+        ));
+        push_filter(&mut query, filter);
+        query.push(" GROUP BY category ORDER BY total DESC, category");
+        let by_category = query
+            .build()
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| CategoryTotal {
+                category: row.get("category"),
+                expense_count: row.get("expense_count"),
+                total: row.get("total"),
+            })
+            .collect();
+
+        let mut query = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT t.name AS tag, COUNT(*) AS expense_count, SUM({NET_AMOUNT}) AS total \
+             FROM expenses \
+             JOIN expense_tags et ON et.expense_id = expenses.id \
+             JOIN tags t ON t.id = et.tag_id \
+             WHERE deleted_at IS NULL"
+        ));
+        push_filter(&mut query, filter);
+        query.push(" GROUP BY t.id, t.name ORDER BY total DESC, t.name");
+        let by_tag = query
+            .build()
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| TagTotal {
+                tag: row.get("tag"),
+                expense_count: row.get("expense_count"),
+                total: row.get("total"),
+            })
+            .collect();
+
+        Ok(ExpenseSummary {
+            expense_count: totals.get("expense_count"),
+            total: totals.get("total"),
+            by_category,
+            by_tag,
+        })
+    }
+
+    async fn highest(&self) -> Result<Option<Expense>> {
+        let row = sqlx::query(&format!(
+            "SELECT {EXPENSE_COLUMNS} FROM expenses WHERE deleted_at IS NULL \
+             ORDER BY amount DESC LIMIT 1"
+        ))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(row_to_expense).transpose()
+    }
+
+    async fn update(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expected_version: Option<i64>,
+        request: UpdateExpenseRequest,
+    ) -> Result<VersionedUpdate> {
+        let mut tx = self.pool.begin().await?;
+        let outcome = update_expense(&mut tx, ctx, id, expected_version, request).await?;
+        if matches!(outcome, VersionedUpdate::Updated(_)) {
+            tx.commit().await?;
+        }
+
+        Ok(outcome)
+    }
+
+    async fn soft_delete(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<VersionedUpdate> {
+        let mut tx = self.pool.begin().await?;
+        let outcome = soft_delete_expense(&mut tx, ctx, id, expected_version).await?;
+        if matches!(outcome, VersionedUpdate::Updated(_)) {
+            tx.commit().await?;
+        }
+
+        Ok(outcome)
+    }
+
+    async fn trash(&self) -> Result<Vec<Expense>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {EXPENSE_COLUMNS} FROM expenses WHERE deleted_at IS NOT NULL \
+             ORDER BY deleted_at DESC"
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(row_to_expense).collect()
+    }
+
+    async fn restore(&self, ctx: &RequestContext, id: Uuid) -> Result<Option<Expense>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(before) = fetch_expense(&mut tx, id).await? else {
+            return Ok(None);
+        };
+        if before.deleted_at.is_none() {
+            return Ok(None);
+        }
+
+        let mut after = before.clone();
+        after.deleted_at = None;
+        after.version += 1;
+
+        sqlx::query("UPDATE expenses SET deleted_at = NULL, version = version + 1 WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_EXPENSE,
+                entity_id: id.to_string(),
+                action: AuditAction::Restore,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(after))
+    }
+
+    async fn purge_deleted_before(
+        &self,
+        ctx: &RequestContext,
+        cutoff: DateTime<Utc>,
+    ) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        let rows = sqlx::query(&format!(
+            "SELECT {EXPENSE_COLUMNS} FROM expenses \
+             WHERE deleted_at IS NOT NULL AND julianday(deleted_at) < julianday(?)"
+        ))
+        .bind(cutoff)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let expired: Vec<Expense> = rows
+            .into_iter()
+            .map(row_to_expense)
+            .collect::<Result<_>>()?;
+
+        for expense in &expired {
+            attachment_service::purge_expense_attachments(&mut tx, ctx, expense.id).await?;
+            sqlx::query("DELETE FROM expense_tags WHERE expense_id = ?")
+                .bind(expense.id.to_string())
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM refunds WHERE expense_id = ?")
+                .bind(expense.id.to_string())
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM expense_report_items WHERE expense_id = ?")
+                .bind(expense.id.to_string())
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM expenses WHERE id = ?")
+                .bind(expense.id.to_string())
+                .execute(&mut *tx)
+                .await?;
+
+            audit_service::append(
+                &mut tx,
+                ctx,
+                NewAuditEntry {
+                    entity_type: ENTITY_EXPENSE,
+                    entity_id: expense.id.to_string(),
+                    action: AuditAction::Purge,
+                    before: Some(serde_json::to_value(expense)?),
+                    after: None,
+                },
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(expired.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    async fn open() -> Option<Arc<dyn ExpenseRepository>> {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        Some(Arc::new(SqliteExpenseRepository::new(pool)))
+    }
+
+    crate::repository::suite::expense_repository_suite!(open());
+
+    const BENCH_ROWS: i64 = 1_000_000;
+
+    /// Times the list and highest queries over a million expenses, one in a
+    /// hundred trashed, with the indexes of migration 15 and without them.
+    /// Run with `cargo test --release -- --ignored --nocapture bench_`.
+    #[tokio::test]
+    #[ignore = "benchmark; seeds a million rows"]
+    async fn bench_list_and_highest_at_one_million_rows() {
+        let dir = std::env::temp_dir().join(format!("bench-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let url = format!("sqlite:{}?mode=rwc", dir.join("expenses.db").display());
+        // One connection, so a single SQLite worker thread serves every query
+        // and the decoded rows are not spread over per-thread allocator arenas.
+        let settings = crate::database::PoolSettings {
+            max_connections: 1,
+            ..Default::default()
+        };
+        let pool = crate::database::create_pool_with(&url, settings)
+            .await
+            .unwrap();
+
+        let started = Instant::now();
+        sqlx::query(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < ?) \
+             INSERT INTO expenses (id, amount, category, date, deleted_at) \
+             SELECT printf('%08x-0000-4000-8000-%012x', i, i), \
+                    (abs(random()) % 100000) / 100.0, \
+                    'Category ' || (i % 12), \
+                    strftime('%Y-%m-%dT%H:%M:%S+00:00', 1500000000 + i * 300, 'unixepoch'), \
+                    CASE WHEN i % 100 = 0 \
+                         THEN strftime('%Y-%m-%dT%H:%M:%SZ', 1800000000, 'unixepoch') END \
+             FROM n",
+        )
+        .bind(BENCH_ROWS)
+        .execute(&pool)
+        .await
+        .unwrap();
+        println!("seeded {BENCH_ROWS} expenses in {:?}", started.elapsed());
+
+        let repository = SqliteExpenseRepository::new(pool.clone());
+        let with_indexes = time_queries(&repository).await;
+
+        sqlx::raw_sql(
+            "DROP INDEX idx_expenses_deleted_at_date; \
+             DROP INDEX idx_expenses_deleted_at_amount; \
+             DROP INDEX idx_expenses_deleted_at_category; \
+             CREATE INDEX idx_expenses_deleted_at ON expenses (deleted_at);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let without_indexes = time_queries(&repository).await;
+
+        println!("{:<10} {:>16} {:>16}", "", "with indexes", "without");
+        for ((name, with), (_, without)) in with_indexes.iter().zip(&without_indexes) {
+            println!("{name:<10} {with:>16?} {without:>16?}");
+        }
+
+        pool.close().await;
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// The median time of each query, after a warm-up run.
+    async fn time_queries(repository: &SqliteExpenseRepository) -> Vec<(&'static str, Duration)> {
+        async fn median<F: Future>(runs: usize, mut query: impl FnMut() -> F) -> Duration {
+            query().await;
+            let mut times = Vec::with_capacity(runs);
+            for _ in 0..runs {
+                let started = Instant::now();
+                query().await;
+                times.push(started.elapsed());
+            }
+            times.sort();
+            times[runs / 2]
+        }
+
+        let filter = ExpenseFilter::default();
+        let highest = median(101, || async {
+            let expense = repository.highest().await.unwrap().unwrap();
+            assert!(expense.deleted_at.is_none());
+        })
+        .await;
+        let list = median(5, || async {
+            let expenses = repository.list(&filter).await.unwrap();
+            assert_eq!(expenses.len() as i64, BENCH_ROWS - BENCH_ROWS / 100);
+        })
+        .await;
+        vec![("highest", highest), ("list", list)]
+    }
+}
@@ -0,0 +1,391 @@
+//! Behavior every [`ExpenseRepository`] shares. Each backend runs the whole
+//! suite with [`expense_repository_suite!`], passing an expression that
+//! opens an empty repository, or `None` to skip when its database is not
+//! available.
+
+use crate::context::RequestContext;
+use crate::models::account::UnknownAccount;
+use crate::models::expense::{CreateExpenseRequest, Expense, UpdateExpenseRequest};
+use crate::models::summary::ExpenseFilter;
+use crate::repository::ExpenseRepository;
+use crate::services::expense_service::VersionedUpdate;
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+macro_rules! expense_repository_suite {
+    ($open:expr) => {
+        $crate::repository::suite::expense_repository_suite!(
+            @cases $open;
+            test_insert_and_find,
+            test_list_newest_first_with_filters,
+            test_summarize,
+            test_highest,
+            test_versioned_update,
+            test_soft_delete_and_restore,
+            test_purge_deleted_before,
+            test_unknown_account_is_rejected,
+        );
+    };
+    (@cases $open:expr; $($case:ident),* $(,)?) => {
+        $(
+            #[tokio::test]
+            async fn $case() {
+                if let Some(repository) = $open.await {
+                    $crate::repository::suite::$case(repository.as_ref()).await;
+                }
+            }
+        )*
+    };
+}
+
+pub(crate) use expense_repository_suite;
+
+fn ctx() -> RequestContext {
+    RequestContext::new("tester", "test-request")
+}
+
+async fn insert(
+    repository: &dyn ExpenseRepository,
+    amount: f64,
+    category: &str,
+    tags: &[&str],
+) -> Expense {
+    let request = CreateExpenseRequest {
+        amount,
+        category: category.to_string(),
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        ..Default::default()
+    };
+    repository.insert(&ctx(), request).await.unwrap()
+}
+
+fn changes(expense: &Expense) -> UpdateExpenseRequest {
+    UpdateExpenseRequest {
+        amount: expense.amount,
+        category: expense.category.clone(),
+        payee: expense.payee.clone(),
+        notes: expense.notes.clone(),
+        tags: expense.tags.clone(),
+        account_id: expense.account_id,
+        reimbursable: expense.reimbursable,
+    }
+}
+
+fn ids(expenses: &[Expense]) -> Vec<Uuid> {
+    expenses.iter().map(|expense| expense.id).collect()
+}
+
+pub(crate) async fn test_insert_and_find(repository: &dyn ExpenseRepository) {
+    let request = CreateExpenseRequest {
+        amount: 42.5,
+        category: "Travel".to_string(),
+        payee: Some("Rail Co".to_string()),
+        notes: Some("Return ticket".to_string()),
+        tags: vec!["work".to_string(), "Trip".to_string(), " Work ".to_string()],
+        reimbursable: true,
+        ..Default::default()
+    };
+    let inserted = repository.insert(&ctx(), request).await.unwrap();
+    assert_eq!(inserted.version, 1);
+    assert_eq!(inserted.tags, vec!["Trip", "work"]);
+    assert_eq!(inserted.payee.as_deref(), Some("Rail Co"));
+    assert!(inserted.reimbursable);
+
+    let found = repository.find(inserted.id).await.unwrap().unwrap();
+    assert_eq!(
+        serde_json::to_value(&found).unwrap(),
+        serde_json::to_value(&inserted).unwrap()
+    );
+
+    // Tags are shared between expenses and keep their first spelling.
+    let other = insert(repository, 3.0, "Food", &["WORK"]).await;
+    assert_eq!(other.tags, vec!["work"]);
+
+    assert!(repository.find(Uuid::new_v4()).await.unwrap().is_none());
+}
+
+pub(crate) async fn test_list_newest_first_with_filters(repository: &dyn ExpenseRepository) {
+    let lunch = insert(repository, 12.0, "Food", &["work", "lunch"]).await;
+    let between = Utc::now();
+    let taxi = insert(repository, 30.0, "Travel", &["work"]).await;
+    let cinema = insert(repository, 15.0, "Fun", &[]).await;
+    let trashed = insert(repository, 99.0, "Food", &["work"]).await;
+    repository
+        .soft_delete(&ctx(), trashed.id, None)
+        .await
+        .unwrap();
+
+    let all = repository.list(&ExpenseFilter::default()).await.unwrap();
+    assert_eq!(ids(&all), vec![cinema.id, taxi.id, lunch.id]);
+
+    let filter =
+        |tags_any: Option<&str>, tags_all: Option<&str>, tags_none: Option<&str>| ExpenseFilter {
+            tags_any: tags_any.map(str::to_string),
+            tags_all: tags_all.map(str::to_string),
+            tags_none: tags_none.map(str::to_string),
+            ..Default::default()
+        };
+    let listed =
+        |filter: ExpenseFilter| async move { ids(&repository.list(&filter).await.unwrap()) };
+
+    assert_eq!(
+        listed(filter(Some("LUNCH,nothing"), None, None)).await,
+        vec![lunch.id]
+    );
+    assert_eq!(
+        listed(filter(None, Some("work,lunch"), None)).await,
+        vec![lunch.id]
+    );
+    assert_eq!(
+        listed(filter(None, None, Some("work"))).await,
+        vec![cinema.id]
+    );
+
+    let since = ExpenseFilter {
+        from: Some(between),
+        ..Default::default()
+    };
+    assert_eq!(listed(since).await, vec![cinema.id, taxi.id]);
+    let until = ExpenseFilter {
+        to: Some(between),
+        ..Default::default()
+    };
+    assert_eq!(listed(until).await, vec![lunch.id]);
+
+    let by_account = ExpenseFilter {
+        account_id: Some(Uuid::new_v4()),
+        ..Default::default()
+    };
+    assert!(listed(by_account).await.is_empty());
+}
+
+pub(crate) async fn test_summarize(repository: &dyn ExpenseRepository) {
+    let empty = repository
+        .summarize(&ExpenseFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(empty.expense_count, 0);
+    assert_eq!(empty.total, 0.0);
+    assert!(empty.by_category.is_empty());
+
+    insert(repository, 10.0, "Food", &["work"]).await;
+    insert(repository, 5.0, "Food", &[]).await;
+    insert(repository, 20.0, "Travel", &["work", "trip"]).await;
+    let trashed = insert(repository, 100.0, "Travel", &["work"]).await;
+    repository
+        .soft_delete(&ctx(), trashed.id, None)
+        .await
+        .unwrap();
+
+    let summary = repository
+        .summarize(&ExpenseFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(summary.expense_count, 3);
+    assert_eq!(summary.total, 35.0);
+    let categories: Vec<(&str, i64, f64)> = summary
+        .by_category
+        .iter()
+        .map(|c| (c.category.as_str(), c.expense_count, c.total))
+        .collect();
+    assert_eq!(categories, vec![("Travel", 1, 20.0), ("Food", 2, 15.0)]);
+    let tags: Vec<(&str, i64, f64)> = summary
+        .by_tag
+        .iter()
+        .map(|t| (t.tag.as_str(), t.expense_count, t.total))
+        .collect();
+    assert_eq!(tags, vec![("work", 2, 30.0), ("trip", 1, 20.0)]);
+
+    let filtered = repository
+        .summarize(&ExpenseFilter {
+            tags_none: Some("work".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(filtered.expense_count, 1);
+    assert_eq!(filtered.total, 5.0);
+}
+
+pub(crate) async fn test_highest(repository: &dyn ExpenseRepository) {
+    assert!(repository.highest().await.unwrap().is_none());
+
+    insert(repository, 10.0, "Food", &[]).await;
+    let rent = insert(repository, 800.0, "Housing", &[]).await;
+    let trashed = insert(repository, 5000.0, "Car", &[]).await;
+    repository
+        .soft_delete(&ctx(), trashed.id, None)
+        .await
+        .unwrap();
+
+    assert_eq!(repository.highest().await.unwrap().unwrap().id, rent.id);
+}
+
+pub(crate) async fn test_versioned_update(repository: &dyn ExpenseRepository) {
+    let expense = insert(repository, 10.0, "Food", &["lunch"]).await;
+    let request = || UpdateExpenseRequest {
+        amount: 12.0,
+        category: "Dining".to_string(),
+        notes: Some("With tip".to_string()),
+        tags: vec!["team".to_string()],
+        ..changes(&expense)
+    };
+
+    let VersionedUpdate::VersionMismatch(current) = repository
+        .update(&ctx(), expense.id, Some(7), request())
+        .await
+        .unwrap()
+    else {
+        panic!("expected a version mismatch");
+    };
+    assert_eq!(current.version, 1);
+
+    let VersionedUpdate::Updated(updated) = repository
+        .update(&ctx(), expense.id, Some(1), request())
+        .await
+        .unwrap()
+    else {
+        panic!("expected an update");
+    };
+    assert_eq!(updated.version, 2);
+    assert_eq!(updated.amount, 12.0);
+    assert_eq!(updated.category, "Dining");
+    assert_eq!(updated.tags, vec!["team"]);
+    assert_eq!(updated.date, expense.date);
+    assert_eq!(
+        serde_json::to_value(repository.find(expense.id).await.unwrap().unwrap()).unwrap(),
+        serde_json::to_value(&updated).unwrap()
+    );
+
+    // Without a version the write is unconditional.
+    let VersionedUpdate::Updated(updated) = repository
+        .update(&ctx(), expense.id, None, changes(&updated))
+        .await
+        .unwrap()
+    else {
+        panic!("expected an update");
+    };
+    assert_eq!(updated.version, 3);
+
+    assert!(matches!(
+        repository
+            .update(&ctx(), Uuid::new_v4(), None, request())
+            .await
+            .unwrap(),
+        VersionedUpdate::NotFound
+    ));
+}
+
+pub(crate) async fn test_soft_delete_and_restore(repository: &dyn ExpenseRepository) {
+    let expense = insert(repository, 10.0, "Food", &[]).await;
+
+    assert!(matches!(
+        repository
+            .soft_delete(&ctx(), expense.id, Some(2))
+            .await
+            .unwrap(),
+        VersionedUpdate::VersionMismatch(_)
+    ));
+    let VersionedUpdate::Updated(deleted) = repository
+        .soft_delete(&ctx(), expense.id, Some(1))
+        .await
+        .unwrap()
+    else {
+        panic!("expected a delete");
+    };
+    assert_eq!(deleted.version, 2);
+    assert!(deleted.deleted_at.is_some());
+
+    // Trashed expenses can still be found, but not changed or deleted again.
+    let found = repository.find(expense.id).await.unwrap().unwrap();
+    assert_eq!(found.deleted_at, deleted.deleted_at);
+    assert!(matches!(
+        repository
+            .update(&ctx(), expense.id, None, changes(&expense))
+            .await
+            .unwrap(),
+        VersionedUpdate::NotFound
+    ));
+    assert!(matches!(
+        repository
+            .soft_delete(&ctx(), expense.id, None)
+            .await
+            .unwrap(),
+        VersionedUpdate::NotFound
+    ));
+    assert_eq!(ids(&repository.trash().await.unwrap()), vec![expense.id]);
+
+    let restored = repository
+        .restore(&ctx(), expense.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(restored.version, 3);
+    assert!(restored.deleted_at.is_none());
+    assert!(repository.trash().await.unwrap().is_empty());
+    assert!(
+        repository
+            .restore(&ctx(), expense.id)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        repository
+            .restore(&ctx(), Uuid::new_v4())
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+pub(crate) async fn test_purge_deleted_before(repository: &dyn ExpenseRepository) {
+    let kept = insert(repository, 10.0, "Food", &["work"]).await;
+    let trashed = insert(repository, 20.0, "Food", &["work"]).await;
+    repository
+        .soft_delete(&ctx(), trashed.id, None)
+        .await
+        .unwrap();
+
+    let purged = repository
+        .purge_deleted_before(&ctx(), Utc::now() - Duration::hours(1))
+        .await
+        .unwrap();
+    assert_eq!(purged, 0);
+
+    let purged = repository
+        .purge_deleted_before(&ctx(), Utc::now() + Duration::seconds(1))
+        .await
+        .unwrap();
+    assert_eq!(purged, 1);
+    assert!(repository.find(trashed.id).await.unwrap().is_none());
+    assert!(repository.trash().await.unwrap().is_empty());
+    assert_eq!(
+        repository.find(kept.id).await.unwrap().unwrap().tags,
+        vec!["work"]
+    );
+}
+
+pub(crate) async fn test_unknown_account_is_rejected(repository: &dyn ExpenseRepository) {
+    let account_id = Uuid::new_v4();
+    let request = CreateExpenseRequest {
+        amount: 10.0,
+        category: "Food".to_string(),
+        account_id: Some(account_id),
+        ..Default::default()
+    };
+    let error = repository.insert(&ctx(), request).await.unwrap_err();
+    assert_eq!(
+        error
+            .downcast_ref::<UnknownAccount>()
+            .map(|unknown| unknown.0),
+        Some(account_id)
+    );
+    assert!(
+        repository
+            .list(&ExpenseFilter::default())
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
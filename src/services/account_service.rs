@@ -0,0 +1,774 @@
+use crate::context::RequestContext;
+use crate::models::account::{
+    Account, AccountBalance, AccountKind, AccountRequest, CreateTransferRequest, Statement,
+    StatementEntry, StatementEntryKind, StatementQuery, Transfer, TransferQuery, UnknownAccount,
+};
+use crate::models::audit::AuditAction;
+use crate::services::audit_service::{self, ENTITY_ACCOUNT, ENTITY_TRANSFER, NewAuditEntry};
+use crate::services::integrity_service::decode_row;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool};
+use uuid::Uuid;
+use uuid::fmt::Hyphenated;
+
+/// Result of an account or transfer write that can be rejected.
+#[derive(Debug)]
+pub enum AccountWrite<T> {
+    Saved(T),
+    NotFound,
+    /// The name is taken, or the account is still in use.
+    Conflict(String),
+    Invalid(String),
+}
+
+#[derive(Clone)]
+pub struct AccountService {
+    pool: SqlitePool,
+}
+
+impl AccountService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_account(
+        &self,
+        ctx: &RequestContext,
+        request: AccountRequest,
+    ) -> Result<AccountWrite<Account>> {
+        let mut tx = self.pool.begin().await?;
+        let name = request.name.trim().to_string();
+
+        if name_taken(&mut tx, &name, None).await? {
+            return Ok(AccountWrite::Conflict(format!(
+                "Account '{}' already exists",
+                name
+            )));
+        }
+
+        let account = Account {
+            id: Uuid::new_v4(),
+            name,
+            kind: request.kind,
+            currency: request.currency,
+            opening_balance: request.opening_balance,
+            created_at: Utc::now(),
+        };
+        sqlx::query(
+            "INSERT INTO accounts (id, name, kind, currency, opening_balance, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(account.id.to_string())
+        .bind(&account.name)
+        .bind(account.kind.as_str())
+        .bind(&account.currency)
+        .bind(account.opening_balance)
+        .bind(account.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_ACCOUNT,
+                entity_id: account.id.to_string(),
+                action: AuditAction::Create,
+                before: None,
+                after: Some(serde_json::to_value(&account)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(AccountWrite::Saved(account))
+    }
+
+    pub async fn list_accounts(&self) -> Result<Vec<Account>> {
+        let rows = sqlx::query("SELECT * FROM accounts ORDER BY name COLLATE NOCASE")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter().map(row_to_account).collect()
+    }
+
+    pub async fn get_account(&self, id: Uuid) -> Result<Option<Account>> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_account(&mut conn, id).await
+    }
+
+    /// Replaces an account's settings. The currency is fixed once expenses
+    /// or transfers refer to the account, since their amounts are in it.
+    pub async fn update_account(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        request: AccountRequest,
+    ) -> Result<AccountWrite<Account>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(before) = fetch_account(&mut tx, id).await? else {
+            return Ok(AccountWrite::NotFound);
+        };
+        let name = request.name.trim().to_string();
+        if name_taken(&mut tx, &name, Some(id)).await? {
+            return Ok(AccountWrite::Conflict(format!(
+                "Account '{}' already exists",
+                name
+            )));
+        }
+        if request.currency != before.currency && in_use(&mut tx, id).await? {
+            return Ok(AccountWrite::Conflict(
+                "The currency of an account with expenses or transfers cannot change".to_string(),
+            ));
+        }
+
+        let after = Account {
+            name,
+            kind: request.kind,
+            currency: request.currency,
+            opening_balance: request.opening_balance,
+            ..before.clone()
+        };
+        sqlx::query(
+            "UPDATE accounts SET name = ?, kind = ?, currency = ?, opening_balance = ? \
+             WHERE id = ?",
+        )
+        .bind(&after.name)
+        .bind(after.kind.as_str())
+        .bind(&after.currency)
+        .bind(after.opening_balance)
+        .bind(id.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_ACCOUNT,
+                entity_id: id.to_string(),
+                action: AuditAction::Update,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(AccountWrite::Saved(after))
+    }
+
+    /// Deletes an account nothing refers to; accounts with expenses (trashed
+    /// ones included) or transfers are kept.
+    pub async fn delete_account(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+    ) -> Result<AccountWrite<Account>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(account) = fetch_account(&mut tx, id).await? else {
+            return Ok(AccountWrite::NotFound);
+        };
+        if in_use(&mut tx, id).await? {
+            return Ok(AccountWrite::Conflict(format!(
+                "Account '{}' still has expenses or transfers",
+                account.name
+            )));
+        }
+
+        sqlx::query("DELETE FROM accounts WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_ACCOUNT,
+                entity_id: id.to_string(),
+                action: AuditAction::Delete,
+                before: Some(serde_json::to_value(&account)?),
+                after: None,
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(AccountWrite::Saved(account))
+    }
+
+    /// Current balance of every account.
+    pub async fn balances(&self) -> Result<Vec<AccountBalance>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {BALANCE_COLUMNS} FROM accounts ORDER BY name COLLATE NOCASE"
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(row_to_balance).collect()
+    }
+
+    pub async fn balance(&self, id: Uuid) -> Result<Option<AccountBalance>> {
+        let row = sqlx::query(&format!(
+            "SELECT {BALANCE_COLUMNS} FROM accounts WHERE id = ?"
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(row_to_balance).transpose()
+    }
+
+    /// Expenses, refunds, income and transfers of an account in date order, each with the
+    /// balance after it.
+    pub async fn statement(&self, id: Uuid, query: &StatementQuery) -> Result<Option<Statement>> {
+        let mut conn = self.pool.acquire().await?;
+        let Some(account) = fetch_account(&mut conn, id).await? else {
+            return Ok(None);
+        };
+
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "SELECT 'expense' AS kind, id, date, \
+                    CASE WHEN payee IS NULL THEN category \
+                         ELSE payee || ' (' || category || ')' END AS description, \
+                    -amount AS amount \
+             FROM expenses WHERE deleted_at IS NULL AND account_id = ",
+        );
+        builder.push_bind(id.to_string());
+        builder.push(
+            " UNION ALL \
+             SELECT 'refund', r.id, r.date, 'Refund: ' || COALESCE(e.payee, e.category), \
+                    r.amount \
+             FROM refunds r JOIN expenses e ON e.id = r.expense_id \
+             WHERE e.deleted_at IS NULL AND e.account_id = ",
+        );
+        builder.push_bind(id.to_string());
+        builder.push(
+            " UNION ALL \
+             SELECT 'income', id, date, COALESCE(source || ' (' || category || ')', category), \
+                    amount \
+             FROM income WHERE account_id = ",
+        );
+        builder.push_bind(id.to_string());
+        builder.push(
+            " UNION ALL \
+             SELECT 'transfer_in', t.id, t.date, 'Transfer from ' || a.name, t.amount \
+             FROM transfers t JOIN accounts a ON a.id = t.from_account_id \
+             WHERE t.to_account_id = ",
+        );
+        builder.push_bind(id.to_string());
+        builder.push(
+            " UNION ALL \
+             SELECT 'transfer_out', t.id, t.date, 'Transfer to ' || a.name, -t.amount \
+             FROM transfers t JOIN accounts a ON a.id = t.to_account_id \
+             WHERE t.from_account_id = ",
+        );
+        builder.push_bind(id.to_string());
+        let rows = builder.build().fetch_all(&mut *conn).await?;
+
+        let mut rows = rows
+            .into_iter()
+            .map(row_to_statement_entry)
+            .collect::<Result<Vec<_>>>()?;
+        // Sorted here rather than in SQL: stored timestamps vary in precision.
+        rows.sort_by_key(|entry| entry.date);
+
+        let mut opening_balance = account.opening_balance;
+        let mut balance = opening_balance;
+        let mut entries = Vec::new();
+        for mut entry in rows {
+            if query.to.is_some_and(|to| entry.date >= to) {
+                break;
+            }
+            balance += entry.amount;
+            if query.from.is_some_and(|from| entry.date < from) {
+                opening_balance = balance;
+                continue;
+            }
+            entry.balance = balance;
+            entries.push(entry);
+        }
+
+        Ok(Some(Statement {
+            account,
+            from: query.from,
+            to: query.to,
+            opening_balance,
+            entries,
+            closing_balance: balance,
+        }))
+    }
+
+    /// Moves money between two accounts of the same currency.
+    pub async fn create_transfer(
+        &self,
+        ctx: &RequestContext,
+        request: CreateTransferRequest,
+    ) -> Result<AccountWrite<Transfer>> {
+        if request.from_account_id == request.to_account_id {
+            return Ok(AccountWrite::Invalid(
+                "A transfer needs two different accounts".to_string(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let mut currencies = Vec::with_capacity(2);
+        for account_id in [request.from_account_id, request.to_account_id] {
+            let Some(account) = fetch_account(&mut tx, account_id).await? else {
+                return Ok(AccountWrite::Invalid(format!(
+                    "Account {} does not exist",
+                    account_id
+                )));
+            };
+            currencies.push(account.currency);
+        }
+        if currencies[0] != currencies[1] {
+            return Ok(AccountWrite::Invalid(format!(
+                "Cannot transfer between {} and {} accounts",
+                currencies[0], currencies[1]
+            )));
+        }
+
+        let now = Utc::now();
+        let transfer = Transfer {
+            id: Uuid::new_v4(),
+            from_account_id: request.from_account_id,
+            to_account_id: request.to_account_id,
+            amount: request.amount,
+            date: request.date.unwrap_or(now),
+            notes: request.notes,
+            created_at: now,
+        };
+        sqlx::query(
+            "INSERT INTO transfers \
+             (id, from_account_id, to_account_id, amount, date, notes, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(transfer.id.to_string())
+        .bind(transfer.from_account_id.to_string())
+        .bind(transfer.to_account_id.to_string())
+        .bind(transfer.amount)
+        .bind(transfer.date)
+        .bind(&transfer.notes)
+        .bind(transfer.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_TRANSFER,
+                entity_id: transfer.id.to_string(),
+                action: AuditAction::Create,
+                before: None,
+                after: Some(serde_json::to_value(&transfer)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(AccountWrite::Saved(transfer))
+    }
+
+    /// Transfers, newest first.
+    pub async fn list_transfers(&self, query: &TransferQuery) -> Result<Vec<Transfer>> {
+        let mut builder = QueryBuilder::<Sqlite>::new("SELECT * FROM transfers");
+        if let Some(account_id) = query.account_id {
+            builder
+                .push(" WHERE from_account_id = ")
+                .push_bind(account_id.to_string())
+                .push(" OR to_account_id = ")
+                .push_bind(account_id.to_string());
+        }
+        builder.push(" ORDER BY julianday(date) DESC, id");
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        rows.into_iter().map(row_to_transfer).collect()
+    }
+
+    pub async fn delete_transfer(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+    ) -> Result<Option<Transfer>> {
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query("SELECT * FROM transfers WHERE id = ?")
+            .bind(id.to_string())
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(transfer) = row.map(row_to_transfer).transpose()? else {
+            return Ok(None);
+        };
+
+        sqlx::query("DELETE FROM transfers WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_TRANSFER,
+                entity_id: id.to_string(),
+                action: AuditAction::Delete,
+                before: Some(serde_json::to_value(&transfer)?),
+                after: None,
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(transfer))
+    }
+}
+
+/// Rejects references to accounts that do not exist with [`UnknownAccount`].
+pub(crate) async fn check_account(
+    conn: &mut SqliteConnection,
+    account_id: Option<Uuid>,
+) -> Result<()> {
+    let Some(account_id) = account_id else {
+        return Ok(());
+    };
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM accounts WHERE id = ?)")
+        .bind(account_id.to_string())
+        .fetch_one(&mut *conn)
+        .await?;
+    if !exists {
+        return Err(UnknownAccount(account_id).into());
+    }
+    Ok(())
+}
+
+async fn name_taken(conn: &mut SqliteConnection, name: &str, except: Option<Uuid>) -> Result<bool> {
+    let id: Option<String> = sqlx::query_scalar("SELECT id FROM accounts WHERE name = ?")
+        .bind(name)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(id.is_some_and(|id| Some(id) != except.map(|e| e.to_string())))
+}
+
+async fn in_use(conn: &mut SqliteConnection, id: Uuid) -> Result<bool> {
+    let used: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM expenses WHERE account_id = ?1) \
+             OR EXISTS (SELECT 1 FROM income WHERE account_id = ?1) \
+             OR EXISTS (SELECT 1 FROM transfers WHERE from_account_id = ?1 OR to_account_id = ?1)",
+    )
+    .bind(id.to_string())
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(used)
+}
+
+async fn fetch_account(conn: &mut SqliteConnection, id: Uuid) -> Result<Option<Account>> {
+    let row = sqlx::query("SELECT * FROM accounts WHERE id = ?")
+        .bind(id.to_string())
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    row.map(row_to_account).transpose()
+}
+
+const BALANCE_COLUMNS: &str = "*, \
+     (SELECT COALESCE(SUM(amount), 0.0) FROM expenses \
+      WHERE account_id = accounts.id AND deleted_at IS NULL) - \
+     (SELECT COALESCE(SUM(r.amount), 0.0) FROM refunds r JOIN expenses e ON e.id = r.expense_id \
+      WHERE e.account_id = accounts.id AND e.deleted_at IS NULL) AS spent, \
+     (SELECT COALESCE(SUM(amount), 0.0) FROM income \
+      WHERE account_id = accounts.id) AS income, \
+     (SELECT COALESCE(SUM(amount), 0.0) FROM transfers \
+      WHERE to_account_id = accounts.id) AS transferred_in, \
+     (SELECT COALESCE(SUM(amount), 0.0) FROM transfers \
+      WHERE from_account_id = accounts.id) AS transferred_out";
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct AccountRow {
+    id: Hyphenated,
+    name: String,
+    #[sqlx(try_from = "String")]
+    kind: AccountKind,
+    currency: String,
+    opening_balance: f64,
+    created_at: DateTime<Utc>,
+}
+
+impl From<AccountRow> for Account {
+    fn from(row: AccountRow) -> Self {
+        Account {
+            id: row.id.into_uuid(),
+            name: row.name,
+            kind: row.kind,
+            currency: row.currency,
+            opening_balance: row.opening_balance,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct BalanceRow {
+    #[sqlx(flatten)]
+    account: AccountRow,
+    spent: f64,
+    income: f64,
+    transferred_in: f64,
+    transferred_out: f64,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct TransferRow {
+    id: Hyphenated,
+    from_account_id: Hyphenated,
+    to_account_id: Hyphenated,
+    amount: f64,
+    date: DateTime<Utc>,
+    notes: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+/// A statement line, read from the table named by its `kind`.
+#[derive(sqlx::FromRow)]
+struct StatementRow {
+    id: Hyphenated,
+    date: DateTime<Utc>,
+    description: String,
+    amount: f64,
+}
+
+fn row_to_account(row: SqliteRow) -> Result<Account> {
+    let row: AccountRow = decode_row("accounts", &row)?;
+    Ok(row.into())
+}
+
+fn row_to_balance(row: SqliteRow) -> Result<AccountBalance> {
+    let row: BalanceRow = decode_row("accounts", &row)?;
+    let account = Account::from(row.account);
+    Ok(AccountBalance {
+        balance: account.opening_balance - row.spent + row.income + row.transferred_in
+            - row.transferred_out,
+        account,
+        spent: row.spent,
+        income: row.income,
+        transferred_in: row.transferred_in,
+        transferred_out: row.transferred_out,
+    })
+}
+
+fn row_to_transfer(row: SqliteRow) -> Result<Transfer> {
+    let row: TransferRow = decode_row("transfers", &row)?;
+    Ok(Transfer {
+        id: row.id.into_uuid(),
+        from_account_id: row.from_account_id.into_uuid(),
+        to_account_id: row.to_account_id.into_uuid(),
+        amount: row.amount,
+        date: row.date,
+        notes: row.notes,
+        created_at: row.created_at,
+    })
+}
+
+fn row_to_statement_entry(row: SqliteRow) -> Result<StatementEntry> {
+    let (kind, table) = match row.get::<&str, _>("kind") {
+        "expense" => (StatementEntryKind::Expense, "expenses"),
+        "income" => (StatementEntryKind::Income, "income"),
+        "refund" => (StatementEntryKind::Refund, "refunds"),
+        "transfer_in" => (StatementEntryKind::TransferIn, "transfers"),
+        _ => (StatementEntryKind::TransferOut, "transfers"),
+    };
+    let row: StatementRow = decode_row(table, &row)?;
+    Ok(StatementEntry {
+        kind,
+        id: row.id.into_uuid(),
+        date: row.date,
+        description: row.description,
+        amount: row.amount,
+        balance: 0.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::expense::CreateExpenseRequest;
+    use crate::services::expense_service::ExpenseService;
+
+    fn test_ctx() -> RequestContext {
+        RequestContext::new("tester", "test-request")
+    }
+
+    async fn create_services() -> (AccountService, ExpenseService) {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        (AccountService::new(pool.clone()), ExpenseService::new(pool))
+    }
+
+    async fn account(
+        service: &AccountService,
+        name: &str,
+        currency: &str,
+        opening_balance: f64,
+    ) -> Account {
+        let request = AccountRequest {
+            name: name.to_string(),
+            kind: AccountKind::Checking,
+            currency: currency.to_string(),
+            opening_balance,
+        };
+        match service.create_account(&test_ctx(), request).await.unwrap() {
+            AccountWrite::Saved(account) => account,
+            other => panic!("expected account, got {:?}", other),
+        }
+    }
+
+    async fn spend(service: &ExpenseService, account_id: Uuid, amount: f64) {
+        let request = CreateExpenseRequest {
+            amount,
+            category: "Food".to_string(),
+            account_id: Some(account_id),
+            ..Default::default()
+        };
+        service.add_expense(&test_ctx(), request).await.unwrap();
+    }
+
+    fn transfer(from: Uuid, to: Uuid, amount: f64) -> CreateTransferRequest {
+        CreateTransferRequest {
+            from_account_id: from,
+            to_account_id: to,
+            amount,
+            date: None,
+            notes: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_balances_count_expenses_and_transfers() {
+        let (accounts, expenses) = create_services().await;
+        let checking = account(&accounts, "Checking", "EUR", 1000.0).await;
+        let card = account(&accounts, "Visa", "EUR", 0.0).await;
+        spend(&expenses, card.id, 120.0).await;
+        spend(&expenses, checking.id, 30.0).await;
+        accounts
+            .create_transfer(&test_ctx(), transfer(checking.id, card.id, 100.0))
+            .await
+            .unwrap();
+
+        let balances = accounts.balances().await.unwrap();
+
+        assert_eq!(balances[0].account.name, "Checking");
+        assert_eq!(balances[0].balance, 870.0);
+        assert_eq!(balances[1].spent, 120.0);
+        assert_eq!(balances[1].balance, -20.0);
+        let summary = expenses.summarize(&Default::default()).await.unwrap();
+        assert_eq!(summary.total, 150.0);
+    }
+
+    #[tokio::test]
+    async fn test_statement_runs_balance_over_period() {
+        let (accounts, expenses) = create_services().await;
+        let cash = account(&accounts, "Cash", "EUR", 50.0).await;
+        let savings = account(&accounts, "Savings", "EUR", 0.0).await;
+        spend(&expenses, cash.id, 10.0).await;
+        let midpoint = Utc::now();
+        spend(&expenses, cash.id, 5.0).await;
+        accounts
+            .create_transfer(&test_ctx(), transfer(savings.id, cash.id, 20.0))
+            .await
+            .unwrap();
+
+        let query = StatementQuery {
+            from: Some(midpoint),
+            to: None,
+        };
+        let statement = accounts.statement(cash.id, &query).await.unwrap().unwrap();
+
+        assert_eq!(statement.opening_balance, 40.0);
+        assert_eq!(statement.entries.len(), 2);
+        assert_eq!(statement.entries[0].kind, StatementEntryKind::Expense);
+        assert_eq!(statement.entries[0].balance, 35.0);
+        assert_eq!(statement.entries[1].kind, StatementEntryKind::TransferIn);
+        assert_eq!(statement.entries[1].description, "Transfer from Savings");
+        assert_eq!(statement.closing_balance, 55.0);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_and_expense_references_are_checked() {
+        let (accounts, expenses) = create_services().await;
+        let eur = account(&accounts, "Euro", "EUR", 0.0).await;
+        let usd = account(&accounts, "Dollar", "USD", 0.0).await;
+
+        for request in [
+            transfer(eur.id, eur.id, 1.0),
+            transfer(eur.id, usd.id, 1.0),
+            transfer(eur.id, Uuid::new_v4(), 1.0),
+        ] {
+            assert!(matches!(
+                accounts
+                    .create_transfer(&test_ctx(), request)
+                    .await
+                    .unwrap(),
+                AccountWrite::Invalid(_)
+            ));
+        }
+
+        let request = CreateExpenseRequest {
+            amount: 1.0,
+            category: "Food".to_string(),
+            account_id: Some(Uuid::new_v4()),
+            ..Default::default()
+        };
+        let error = expenses
+            .add_expense(&test_ctx(), request)
+            .await
+            .unwrap_err();
+        assert!(error.downcast_ref::<UnknownAccount>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_accounts_in_use_cannot_be_deleted_or_change_currency() {
+        let (accounts, expenses) = create_services().await;
+        let card = account(&accounts, "Amex", "EUR", 0.0).await;
+        spend(&expenses, card.id, 5.0).await;
+
+        let request = AccountRequest {
+            name: "Amex Gold".to_string(),
+            kind: AccountKind::CreditCard,
+            currency: "USD".to_string(),
+            opening_balance: 0.0,
+        };
+        assert!(matches!(
+            accounts
+                .update_account(&test_ctx(), card.id, request)
+                .await
+                .unwrap(),
+            AccountWrite::Conflict(_)
+        ));
+        assert!(matches!(
+            accounts.delete_account(&test_ctx(), card.id).await.unwrap(),
+            AccountWrite::Conflict(_)
+        ));
+
+        let unused = account(&accounts, "Spare", "EUR", 0.0).await;
+        assert!(matches!(
+            accounts
+                .delete_account(&test_ctx(), unused.id)
+                .await
+                .unwrap(),
+            AccountWrite::Saved(_)
+        ));
+    }
+}
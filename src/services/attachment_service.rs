@@ -0,0 +1,595 @@
+use crate::context::RequestContext;
+use crate::models::attachment::{Attachment, ReceiptType, Upload, sanitize_filename};
+use crate::models::audit::AuditAction;
+use crate::services::audit_service::{self, ENTITY_ATTACHMENT, NewAuditEntry};
+use crate::services::integrity_service::decode_row;
+use crate::services::{expense_report_service, expense_service};
+use crate::storage::{BlobStore, render_thumbnail, sha256_hex};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{SqliteConnection, SqlitePool};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use uuid::fmt::Hyphenated;
+
+/// Result of an upload that can be rejected.
+#[derive(Debug)]
+pub enum AttachmentWrite {
+    Saved(Attachment),
+    /// The expense does not exist or is in the trash.
+    NotFound,
+    TooLarge(String),
+    /// The content is not a JPEG, PNG, WebP or PDF, or not what the client
+    /// declared.
+    Unsupported(String),
+    Invalid(String),
+}
+
+#[derive(Clone)]
+pub struct AttachmentService {
+    pool: SqlitePool,
+    store: BlobStore,
+    max_bytes: usize,
+    /// Uploads hold this shared from storing a blob until its row is
+    /// committed; removing blobs takes it exclusively, so a blob that is about
+    /// to be referenced is never deleted as unused.
+    blob_lock: Arc<RwLock<()>>,
+}
+
+impl AttachmentService {
+    pub fn new(pool: SqlitePool, store: BlobStore, max_bytes: usize) -> Self {
+        Self {
+            pool,
+            store,
+            max_bytes,
+            blob_lock: Arc::new(RwLock::new(())),
+        }
+    }
+
+    /// Checks and stores an uploaded receipt for a non-deleted expense,
+    /// rendering a thumbnail for images.
+    pub async fn add_attachment(
+        &self,
+        ctx: &RequestContext,
+        expense_id: Uuid,
+        upload: Upload,
+    ) -> Result<AttachmentWrite> {
+        if upload.content.len() > self.max_bytes {
+            return Ok(AttachmentWrite::TooLarge(format!(
+                "Attachments may be at most {} bytes",
+                self.max_bytes
+            )));
+        }
+        if upload.content.is_empty() {
+            return Ok(AttachmentWrite::Invalid("The file is empty".to_string()));
+        }
+        let Some(kind) = ReceiptType::sniff(&upload.content) else {
+            return Ok(AttachmentWrite::Unsupported(
+                "Only JPEG, PNG, WebP and PDF files are accepted".to_string(),
+            ));
+        };
+        if !kind.matches_declared(upload.content_type.as_deref()) {
+            return Ok(AttachmentWrite::Unsupported(format!(
+                "The file was declared as {} but contains {}",
+                upload.content_type.unwrap_or_default(),
+                kind.mime()
+            )));
+        }
+
+        let content = upload.content;
+        let (content, thumbnail) = if kind.is_image() {
+            let (content, rendered) = tokio::task::spawn_blocking(move || {
+                let rendered = render_thumbnail(&content);
+                (content, rendered)
+            })
+            .await?;
+            match rendered {
+                Ok(thumbnail) => (content, Some(thumbnail)),
+                Err(_) => {
+                    return Ok(AttachmentWrite::Invalid(
+                        "The image could not be decoded".to_string(),
+                    ));
+                }
+            }
+        } else {
+            (content, None)
+        };
+
+        let attachment = Attachment {
+            id: Uuid::new_v4(),
+            expense_id,
+            filename: sanitize_filename(upload.filename.as_deref()),
+            content_type: kind.mime().to_string(),
+            size: content.len() as i64,
+            sha256: sha256_hex(&content),
+            has_thumbnail: thumbnail.is_some(),
+            created_at: Utc::now(),
+        };
+
+        let _blobs = self.blob_lock.read().await;
+        let mut tx = self.pool.begin().await?;
+
+        let expense = expense_service::fetch_expense(&mut tx, expense_id).await?;
+        if expense.is_none_or(|e| e.deleted_at.is_some()) {
+            return Ok(AttachmentWrite::NotFound);
+        }
+        expense_report_service::check_unlocked(&mut tx, expense_id).await?;
+
+        self.store.put(&attachment.sha256, &content).await?;
+        if let Some(thumbnail) = &thumbnail {
+            self.store
+                .put_thumbnail(&attachment.sha256, thumbnail)
+                .await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO attachments \
+             (id, expense_id, filename, content_type, size, sha256, has_thumbnail, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(attachment.id.to_string())
+        .bind(expense_id.to_string())
+        .bind(&attachment.filename)
+        .bind(&attachment.content_type)
+        .bind(attachment.size)
+        .bind(&attachment.sha256)
+        .bind(attachment.has_thumbnail)
+        .bind(attachment.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_ATTACHMENT,
+                entity_id: attachment.id.to_string(),
+                action: AuditAction::Create,
+                before: None,
+                after: Some(serde_json::to_value(&attachment)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(AttachmentWrite::Saved(attachment))
+    }
+
+    /// Attachments of a non-deleted expense, oldest first.
+    pub async fn list_attachments(&self, expense_id: Uuid) -> Result<Option<Vec<Attachment>>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let expense = expense_service::fetch_expense(&mut conn, expense_id).await?;
+        if expense.is_none_or(|e| e.deleted_at.is_some()) {
+            return Ok(None);
+        }
+
+        expense_attachments(&mut conn, expense_id).await.map(Some)
+    }
+
+    /// An attachment whose expense is not in the trash.
+    pub async fn get_attachment(&self, id: Uuid) -> Result<Option<Attachment>> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_visible_attachment(&mut conn, id).await
+    }
+
+    pub async fn read_content(&self, id: Uuid) -> Result<Option<(Attachment, Vec<u8>)>> {
+        let Some(attachment) = self.get_attachment(id).await? else {
+            return Ok(None);
+        };
+        let content = self.store.read(&attachment.sha256).await?;
+        Ok(Some((attachment, content)))
+    }
+
+    /// The JPEG thumbnail of an image attachment; `None` for PDFs.
+    pub async fn read_thumbnail(&self, id: Uuid) -> Result<Option<(Attachment, Vec<u8>)>> {
+        let Some(attachment) = self.get_attachment(id).await? else {
+            return Ok(None);
+        };
+        if !attachment.has_thumbnail {
+            return Ok(None);
+        }
+        let thumbnail = self.store.read_thumbnail(&attachment.sha256).await?;
+        Ok(Some((attachment, thumbnail)))
+    }
+
+    /// Removes an attachment and, unless another attachment has the same
+    /// content, its stored file.
+    pub async fn delete_attachment(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+    ) -> Result<Option<Attachment>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(attachment) = fetch_visible_attachment(&mut tx, id).await? else {
+            return Ok(None);
+        };
+        expense_report_service::check_unlocked(&mut tx, attachment.expense_id).await?;
+        sqlx::query("DELETE FROM attachments WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_ATTACHMENT,
+                entity_id: id.to_string(),
+                action: AuditAction::Delete,
+                before: Some(serde_json::to_value(&attachment)?),
+                after: None,
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        let _blobs = self.blob_lock.write().await;
+        let references: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM attachments WHERE sha256 = ?")
+                .bind(&attachment.sha256)
+                .fetch_one(&self.pool)
+                .await?;
+        if references == 0 {
+            self.store.remove(&attachment.sha256).await?;
+        }
+
+        Ok(Some(attachment))
+    }
+
+    /// Deletes stored files no attachment refers to any more, such as those
+    /// of purged expenses. Returns how many were removed.
+    pub async fn remove_orphaned_files(&self) -> Result<u64> {
+        let _blobs = self.blob_lock.write().await;
+
+        let referenced: HashSet<String> =
+            sqlx::query_scalar("SELECT DISTINCT sha256 FROM attachments")
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .collect();
+
+        let mut removed = 0;
+        for sha256 in self.store.stored_hashes().await? {
+            if !referenced.contains(&sha256) {
+                self.store.remove(&sha256).await?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Deletes the attachment rows of an expense being purged. Their files are
+/// left to [`AttachmentService::remove_orphaned_files`].
+pub(crate) async fn purge_expense_attachments(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    expense_id: Uuid,
+) -> Result<()> {
+    let rows = sqlx::query("SELECT * FROM attachments WHERE expense_id = ?")
+        .bind(expense_id.to_string())
+        .fetch_all(&mut *conn)
+        .await?;
+
+    for row in rows {
+        let attachment = row_to_attachment(row)?;
+        sqlx::query("DELETE FROM attachments WHERE id = ?")
+            .bind(attachment.id.to_string())
+            .execute(&mut *conn)
+            .await?;
+
+        audit_service::append(
+            conn,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_ATTACHMENT,
+                entity_id: attachment.id.to_string(),
+                action: AuditAction::Purge,
+                before: Some(serde_json::to_value(&attachment)?),
+                after: None,
+            },
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Attachments of an expense, oldest first.
+pub(crate) async fn expense_attachments(
+    conn: &mut SqliteConnection,
+    expense_id: Uuid,
+) -> Result<Vec<Attachment>> {
+    let rows =
+        sqlx::query("SELECT * FROM attachments WHERE expense_id = ? ORDER BY created_at, id")
+            .bind(expense_id.to_string())
+            .fetch_all(&mut *conn)
+            .await?;
+
+    rows.into_iter().map(row_to_attachment).collect()
+}
+
+async fn fetch_visible_attachment(
+    conn: &mut SqliteConnection,
+    id: Uuid,
+) -> Result<Option<Attachment>> {
+    let row = sqlx::query(
+        "SELECT a.* FROM attachments a JOIN expenses e ON e.id = a.expense_id \
+         WHERE a.id = ? AND e.deleted_at IS NULL",
+    )
+    .bind(id.to_string())
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    row.map(row_to_attachment).transpose()
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct AttachmentRow {
+    id: Hyphenated,
+    expense_id: Hyphenated,
+    filename: String,
+    content_type: String,
+    size: i64,
+    sha256: String,
+    has_thumbnail: bool,
+    created_at: DateTime<Utc>,
+}
+
+fn row_to_attachment(row: SqliteRow) -> Result<Attachment> {
+    let row: AttachmentRow = decode_row("attachments", &row)?;
+    Ok(Attachment {
+        id: row.id.into_uuid(),
+        expense_id: row.expense_id.into_uuid(),
+        filename: row.filename,
+        content_type: row.content_type,
+        size: row.size,
+        sha256: row.sha256,
+        has_thumbnail: row.has_thumbnail,
+        created_at: row.created_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::expense::{CreateExpenseRequest, Expense};
+    use crate::services::expense_service::ExpenseService;
+    use image::ImageFormat;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    const PDF: &[u8] = b"%PDF-1.4\n1 0 obj <<>> endobj\ntrailer <<>>\n%%EOF";
+
+    fn test_ctx() -> RequestContext {
+        RequestContext::new("tester", "test-request")
+    }
+
+    struct Fixture {
+        attachments: AttachmentService,
+        expenses: ExpenseService,
+        root: PathBuf,
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    async fn fixture() -> Fixture {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        let root = std::env::temp_dir().join(format!("attachments-{}", Uuid::new_v4()));
+        Fixture {
+            attachments: AttachmentService::new(
+                pool.clone(),
+                BlobStore::new(&root, None),
+                64 * 1024,
+            ),
+            expenses: ExpenseService::new(pool),
+            root,
+        }
+    }
+
+    async fn add_expense(service: &ExpenseService) -> Expense {
+        let request = CreateExpenseRequest {
+            amount: 12.0,
+            category: "Meals".to_string(),
+            ..Default::default()
+        };
+        service.add_expense(&test_ctx(), request).await.unwrap()
+    }
+
+    fn upload(content: &[u8], content_type: Option<&str>) -> Upload {
+        Upload {
+            filename: Some("receipt.bin".to_string()),
+            content_type: content_type.map(str::to_string),
+            content: content.to_vec(),
+        }
+    }
+
+    fn png() -> Vec<u8> {
+        let mut encoded = Cursor::new(Vec::new());
+        image::RgbImage::new(600, 300)
+            .write_to(&mut encoded, ImageFormat::Png)
+            .unwrap();
+        encoded.into_inner()
+    }
+
+    async fn saved(service: &AttachmentService, expense_id: Uuid, upload: Upload) -> Attachment {
+        match service
+            .add_attachment(&test_ctx(), expense_id, upload)
+            .await
+            .unwrap()
+        {
+            AttachmentWrite::Saved(attachment) => attachment,
+            other => panic!("expected upload to succeed, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_image_upload_stores_content_and_thumbnail() {
+        let f = fixture().await;
+        let expense = add_expense(&f.expenses).await;
+        let content = png();
+
+        let attachment = saved(
+            &f.attachments,
+            expense.id,
+            upload(&content, Some("image/png")),
+        )
+        .await;
+
+        assert_eq!(attachment.content_type, "image/png");
+        assert_eq!(attachment.size, content.len() as i64);
+        assert!(attachment.has_thumbnail);
+        let (_, stored) = f
+            .attachments
+            .read_content(attachment.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored, content);
+        let (_, thumbnail) = f
+            .attachments
+            .read_thumbnail(attachment.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ReceiptType::sniff(&thumbnail), Some(ReceiptType::Jpeg));
+        let listed = f
+            .attachments
+            .list_attachments(expense.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(listed.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_oversized_unknown_and_mislabelled_files() {
+        let f = fixture().await;
+        let expense = add_expense(&f.expenses).await;
+
+        let too_large = upload(&vec![b'%'; 64 * 1024 + 1], None);
+        let html = upload(b"<html>receipt</html>", None);
+        let mislabelled = upload(PDF, Some("image/png"));
+        let broken_image = upload(b"\x89PNG\r\n\x1a\nnot really", None);
+
+        for (upload, expected) in [
+            (too_large, "too_large"),
+            (html, "unsupported"),
+            (mislabelled, "unsupported"),
+            (broken_image, "invalid"),
+        ] {
+            let outcome = f
+                .attachments
+                .add_attachment(&test_ctx(), expense.id, upload)
+                .await
+                .unwrap();
+            let actual = match outcome {
+                AttachmentWrite::TooLarge(_) => "too_large",
+                AttachmentWrite::Unsupported(_) => "unsupported",
+                AttachmentWrite::Invalid(_) => "invalid",
+                other => panic!("unexpected outcome {:?}", other),
+            };
+            assert_eq!(actual, expected);
+        }
+        assert!(
+            f.attachments
+                .store
+                .stored_hashes()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_identical_content_is_stored_once() {
+        let f = fixture().await;
+        let expense = add_expense(&f.expenses).await;
+
+        let first = saved(&f.attachments, expense.id, upload(PDF, None)).await;
+        let second = saved(&f.attachments, expense.id, upload(PDF, None)).await;
+        assert_eq!(first.sha256, second.sha256);
+        assert_eq!(f.attachments.store.stored_hashes().await.unwrap().len(), 1);
+
+        f.attachments
+            .delete_attachment(&test_ctx(), first.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            f.attachments
+                .read_content(second.id)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        f.attachments
+            .delete_attachment(&test_ctx(), second.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            f.attachments
+                .store
+                .stored_hashes()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trashed_and_purged_expenses() {
+        let f = fixture().await;
+        let expense = add_expense(&f.expenses).await;
+        let attachment = saved(&f.attachments, expense.id, upload(PDF, None)).await;
+
+        f.expenses
+            .delete_expense(&test_ctx(), expense.id, None)
+            .await
+            .unwrap();
+        assert!(
+            f.attachments
+                .get_attachment(attachment.id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(matches!(
+            f.attachments
+                .add_attachment(&test_ctx(), expense.id, upload(PDF, None))
+                .await
+                .unwrap(),
+            AttachmentWrite::NotFound
+        ));
+        assert_eq!(f.attachments.remove_orphaned_files().await.unwrap(), 0);
+
+        f.expenses
+            .purge_deleted_before(&test_ctx(), Utc::now() + chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+        assert_eq!(f.attachments.remove_orphaned_files().await.unwrap(), 1);
+        assert!(
+            f.attachments
+                .store
+                .stored_hashes()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use crate::context::RequestContext;
+use crate::models::audit::{AuditAction, AuditEntry, AuditQuery, ChainVerification};
+use crate::services::integrity_service::decode_row;
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqliteRow;
+use sqlx::types::Json;
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool};
+use uuid::Uuid;
+use uuid::fmt::Hyphenated;
+
+/// `prev_hash` of the first entry in the chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+const DEFAULT_QUERY_LIMIT: i64 = 100;
+const MAX_QUERY_LIMIT: i64 = 1000;
+
+pub const ENTITY_EXPENSE: &str = "expense";
+pub const ENTITY_PAYEE: &str = "payee";
+pub const ENTITY_RULE: &str = "rule";
+pub const ENTITY_TAG: &str = "tag";
+pub const ENTITY_ATTACHMENT: &str = "attachment";
+pub const ENTITY_ACCOUNT: &str = "account";
+pub const ENTITY_TRANSFER: &str = "transfer";
+pub const ENTITY_INCOME: &str = "income";
+pub const ENTITY_REFUND: &str = "refund";
+pub const ENTITY_EXPENSE_REPORT: &str = "expense_report";
+pub const ENTITY_BACKUP: &str = "backup";
+
+/// A mutation to record, before it has been linked into the chain.
+pub struct NewAuditEntry {
+    pub entity_type: &'static str,
+    pub entity_id: String,
+    pub action: AuditAction,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Appends an entry to the audit log on `conn`.
+///
+/// Call this inside the transaction that performs the mutation, after its
+/// write, so the transaction already holds SQLite's write lock and no other
+/// writer can extend the chain between reading the tail and inserting.
+pub async fn append(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    entry: NewAuditEntry,
+) -> Result<()> {
+    let prev_hash: String =
+        sqlx::query_scalar("SELECT hash FROM audit_log ORDER BY seq DESC LIMIT 1")
+            .fetch_optional(&mut *conn)
+            .await?
+            .unwrap_or_else(|| GENESIS_HASH.to_string());
+    let record = AuditRecord::link(prev_hash, ctx, entry);
+
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (
+            id, entity_type, entity_id, action, actor, request_id,
+            occurred_at, before_json, after_json, prev_hash, hash
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&record.id)
+    .bind(record.entity_type)
+    .bind(&record.entity_id)
+    .bind(record.action)
+    .bind(&record.actor)
+    .bind(&record.request_id)
+    .bind(&record.occurred_at)
+    .bind(&record.before_json)
+    .bind(&record.after_json)
+    .bind(&record.prev_hash)
+    .bind(&record.hash)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// An entry linked to its predecessor, with every column as it is stored.
+/// Backends other than SQLite persist these to keep the same chain.
+pub(crate) struct AuditRecord {
+    pub id: String,
+    pub entity_type: &'static str,
+    pub entity_id: String,
+    pub action: &'static str,
+    pub actor: String,
+    pub request_id: String,
+    pub occurred_at: String,
+    pub before_json: Option<String>,
+    pub after_json: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// Links `entry` to the entry whose hash is `prev_hash`, or to
+    /// [`GENESIS_HASH`] for the first entry.
+    pub(crate) fn link(prev_hash: String, ctx: &RequestContext, entry: NewAuditEntry) -> Self {
+        let id = Uuid::new_v4().to_string();
+        let occurred_at = format_timestamp(Utc::now());
+        let before_json = entry.before.map(|v| v.to_string());
+        let after_json = entry.after.map(|v| v.to_string());
+
+        let hash = chain_hash(&ChainFields {
+            prev_hash: &prev_hash,
+            id: &id,
+            entity_type: entry.entity_type,
+            entity_id: &entry.entity_id,
+            action: entry.action.as_str(),
+            actor: &ctx.actor,
+            request_id: &ctx.request_id,
+            occurred_at: &occurred_at,
+            before_json: before_json.as_deref(),
+            after_json: after_json.as_deref(),
+        });
+
+        Self {
+            id,
+            entity_type: entry.entity_type,
+            entity_id: entry.entity_id,
+            action: entry.action.as_str(),
+            actor: ctx.actor.clone(),
+            request_id: ctx.request_id.clone(),
+            occurred_at,
+            before_json,
+            after_json,
+            prev_hash,
+            hash,
+        }
+    }
+}
+
+fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// The stored columns an entry's hash covers, exactly as persisted.
+struct ChainFields<'a> {
+    prev_hash: &'a str,
+    id: &'a str,
+    entity_type: &'a str,
+    entity_id: &'a str,
+    action: &'a str,
+    actor: &'a str,
+    request_id: &'a str,
+    occurred_at: &'a str,
+    before_json: Option<&'a str>,
+    after_json: Option<&'a str>,
+}
+
+fn chain_hash(fields: &ChainFields) -> String {
+    // Hashing a JSON array keeps field boundaries unambiguous.
+    let canonical = json!([
+        fields.prev_hash,
+        fields.id,
+        fields.entity_type,
+        fields.entity_id,
+        fields.action,
+        fields.actor,
+        fields.request_id,
+        fields.occurred_at,
+        fields.before_json,
+        fields.after_json,
+    ]);
+    hex::encode(Sha256::digest(canonical.to_string().as_bytes()))
+}
+
+/// Walks the whole log on `conn` and recomputes every hash, reporting the
+/// first entry whose content or link to its predecessor has been altered.
+pub async fn verify_chain(conn: &mut SqliteConnection) -> Result<ChainVerification> {
+    let rows = sqlx::query(
+        "SELECT seq, id, entity_type, entity_id, action, actor, request_id, occurred_at, \
+         before_json, after_json, prev_hash, hash FROM audit_log ORDER BY seq ASC",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut expected_prev = GENESIS_HASH.to_string();
+    let mut entries_checked = 0;
+
+    for row in &rows {
+        let seq: i64 = row.try_get("seq")?;
+        let prev_hash: String = row.try_get("prev_hash")?;
+        let hash: String = row.try_get("hash")?;
+        let before_json: Option<String> = row.try_get("before_json")?;
+        let after_json: Option<String> = row.try_get("after_json")?;
+
+        let recomputed = chain_hash(&ChainFields {
+            prev_hash: &prev_hash,
+            id: row.try_get("id")?,
+            entity_type: row.try_get("entity_type")?,
+            entity_id: row.try_get("entity_id")?,
+            action: row.try_get("action")?,
+            actor: row.try_get("actor")?,
+            request_id: row.try_get("request_id")?,
+            occurred_at: row.try_get("occurred_at")?,
+            before_json: before_json.as_deref(),
+            after_json: after_json.as_deref(),
+        });
+
+        entries_checked += 1;
+        if prev_hash != expected_prev || recomputed != hash {
+            return Ok(ChainVerification {
+                valid: false,
+                entries_checked,
+                first_invalid_seq: Some(seq),
+            });
+        }
+        expected_prev = hash;
+    }
+
+    Ok(ChainVerification {
+        valid: true,
+        entries_checked,
+        first_invalid_seq: None,
+    })
+}
+
+#[derive(Clone)]
+pub struct AuditService {
+    pool: SqlitePool,
+}
+
+impl AuditService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn history(&self, entity_type: &str, entity_id: &str) -> Result<Vec<AuditEntry>> {
+        self.query(AuditQuery {
+            entity_type: Some(entity_type.to_string()),
+            entity_id: Some(entity_id.to_string()),
+            limit: Some(MAX_QUERY_LIMIT),
+            ..Default::default()
+        })
+        .await
+    }
+
+    pub async fn query(&self, query: AuditQuery) -> Result<Vec<AuditEntry>> {
+        let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "SELECT seq, id, entity_type, entity_id, action, actor, request_id, occurred_at, \
+             before_json, after_json, prev_hash, hash FROM audit_log WHERE 1 = 1",
+        );
+
+        if let Some(entity_type) = query.entity_type {
+            builder.push(" AND entity_type = ").push_bind(entity_type);
+        }
+        if let Some(entity_id) = query.entity_id {
+            builder.push(" AND entity_id = ").push_bind(entity_id);
+        }
+        if let Some(actor) = query.actor {
+            builder.push(" AND actor = ").push_bind(actor);
+        }
+        if let Some(action) = query.action {
+            builder.push(" AND action = ").push_bind(action.as_str());
+        }
+        if let Some(request_id) = query.request_id {
+            builder.push(" AND request_id = ").push_bind(request_id);
+        }
+        if let Some(from) = query.from {
+            builder
+                .push(" AND occurred_at >= ")
+                .push_bind(format_timestamp(from));
+        }
+        if let Some(to) = query.to {
+            builder
+                .push(" AND occurred_at < ")
+                .push_bind(format_timestamp(to));
+        }
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_QUERY_LIMIT)
+            .clamp(1, MAX_QUERY_LIMIT);
+        builder
+            .push(" ORDER BY seq ASC LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(query.offset.unwrap_or(0).max(0));
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        rows.iter().map(row_to_entry).collect()
+    }
+
+    /// Walks the whole log and recomputes every hash, reporting the first
+    /// entry whose content or link to its predecessor has been altered.
+    pub async fn verify_chain(&self) -> Result<ChainVerification> {
+        let mut conn = self.pool.acquire().await?;
+        verify_chain(&mut conn).await
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct AuditRow {
+    seq: i64,
+    id: Hyphenated,
+    entity_type: String,
+    entity_id: String,
+    #[sqlx(try_from = "String")]
+    action: AuditAction,
+    actor: String,
+    request_id: String,
+    occurred_at: DateTime<Utc>,
+    before_json: Option<Json<Value>>,
+    after_json: Option<Json<Value>>,
+    prev_hash: String,
+    hash: String,
+}
+
+fn row_to_entry(row: &SqliteRow) -> Result<AuditEntry> {
+    let row: AuditRow = decode_row("audit_log", row)?;
+    Ok(AuditEntry {
+        seq: row.seq,
+        id: row.id.into_uuid(),
+        entity_type: row.entity_type,
+        entity_id: row.entity_id,
+        action: row.action,
+        actor: row.actor,
+        request_id: row.request_id,
+        occurred_at: row.occurred_at,
+        before: row.before_json.map(|json| json.0),
+        after: row.after_json.map(|json| json.0),
+        prev_hash: row.prev_hash,
+        hash: row.hash,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_pool;
+    use crate::models::expense::CreateExpenseRequest;
+    use crate::services::expense_service::ExpenseService;
+
+    async fn seeded_services(count: usize) -> (ExpenseService, AuditService, SqlitePool) {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+        let expenses = ExpenseService::new(pool.clone());
+        let ctx = RequestContext::new("alice", "req-1");
+
+        for i in 0..count {
+            let request = CreateExpenseRequest {
+                amount: 10.0 + i as f64,
+                category: "Groceries".to_string(),
+                ..Default::default()
+            };
+            expenses.add_expense(&ctx, request).await.unwrap();
+        }
+
+        (expenses, AuditService::new(pool.clone()), pool)
+    }
+
+    #[tokio::test]
+    async fn test_add_expense_records_audit_entry() {
+        let (expenses, audit, _pool) = seeded_services(0).await;
+        let ctx = RequestContext::new("alice", "req-42");
+
+        let request = CreateExpenseRequest {
+            amount: 12.5,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let expense = expenses.add_expense(&ctx, request).await.unwrap();
+
+        let history = audit
+            .history(ENTITY_EXPENSE, &expense.id.to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(history.len(), 1);
+        let entry = &history[0];
+        assert_eq!(entry.action, AuditAction::Create);
+        assert_eq!(entry.actor, "alice");
+        assert_eq!(entry.request_id, "req-42");
+        assert!(entry.before.is_none());
+        assert_eq!(entry.after.as_ref().unwrap()["category"], "Books");
+        assert_eq!(entry.prev_hash, GENESIS_HASH);
+    }
+
+    #[tokio::test]
+    async fn test_entries_are_hash_chained() {
+        let (_expenses, audit, _pool) = seeded_services(3).await;
+
+        let entries = audit.query(AuditQuery::default()).await.unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].prev_hash, entries[0].hash);
+        assert_eq!(entries[2].prev_hash, entries[1].hash);
+
+        let verification = audit.verify_chain().await.unwrap();
+        assert_eq!(
+            verification,
+            ChainVerification {
+                valid: true,
+                entries_checked: 3,
+                first_invalid_seq: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_filters_by_actor() {
+        let (expenses, audit, _pool) = seeded_services(2).await;
+        let ctx = RequestContext::new("bob", "req-2");
+        let request = CreateExpenseRequest {
+            amount: 5.0,
+            category: "Transport".to_string(),
+            ..Default::default()
+        };
+        expenses.add_expense(&ctx, request).await.unwrap();
+
+        let entries = audit
+            .query(AuditQuery {
+                actor: Some("bob".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].actor, "bob");
+    }
+
+    #[tokio::test]
+    async fn test_audit_log_rejects_updates_and_deletes() {
+        let (_expenses, _audit, pool) = seeded_services(1).await;
+
+        let update = sqlx::query("UPDATE audit_log SET actor = 'mallory'")
+            .execute(&pool)
+            .await;
+        let delete = sqlx::query("DELETE FROM audit_log").execute(&pool).await;
+
+        assert!(update.is_err());
+        assert!(delete.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_chain_detects_tampering() {
+        let (_expenses, audit, pool) = seeded_services(3).await;
+
+        sqlx::query("DROP TRIGGER audit_log_no_update")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE audit_log SET actor = 'mallory' WHERE seq = 2")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let verification = audit.verify_chain().await.unwrap();
+
+        assert!(!verification.valid);
+        assert_eq!(verification.first_invalid_seq, Some(2));
+    }
+}
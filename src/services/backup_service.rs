@@ -0,0 +1,867 @@
+use crate::context::RequestContext;
+use crate::database::{self, PoolSettings};
+use crate::encryption::{self, EncryptionKey};
+use crate::models::audit::AuditAction;
+use crate::models::backup::{
+    Backup, BackupKind, BackupRejected, BackupVerification, RestoreOutcome,
+};
+use crate::services::audit_service::{self, ENTITY_BACKUP, NewAuditEntry};
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, Row, SqliteConnection, SqlitePool};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Takes, rotates, verifies and restores snapshots of the SQLite database.
+///
+/// Snapshots are written with `VACUUM INTO`, which reads one consistent
+/// version of the database while writers carry on, into `tmp/` first and
+/// renamed into place with their manifest, so a listed snapshot is always
+/// complete. Only the newest `keep` snapshots are kept. Snapshots of an
+/// encrypted database are encrypted with its `key`.
+#[derive(Clone)]
+pub struct BackupService {
+    pool: SqlitePool,
+    dir: PathBuf,
+    keep: usize,
+    key: Option<EncryptionKey>,
+}
+
+impl BackupService {
+    pub fn new(
+        pool: SqlitePool,
+        dir: impl Into<PathBuf>,
+        keep: usize,
+        key: Option<EncryptionKey>,
+    ) -> Self {
+        Self {
+            pool,
+            dir: dir.into(),
+            keep: keep.max(1),
+            key,
+        }
+    }
+
+    /// Takes a snapshot, then removes the ones beyond the retention count.
+    pub async fn create(&self, kind: BackupKind) -> Result<Backup> {
+        let backup = self.snapshot(kind).await?;
+        self.rotate().await?;
+        Ok(backup)
+    }
+
+    /// All snapshots, newest first.
+    pub async fn list(&self) -> Result<Vec<Backup>> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut backups = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            match read_manifest(&path).await {
+                Ok(backup) => backups.push(backup),
+                Err(e) => tracing::warn!(
+                    path = %path.display(),
+                    error = format!("{e:#}"),
+                    "Skipping unreadable backup manifest"
+                ),
+            }
+        }
+        backups.sort_by(|a, b| {
+            b.created_at
+                .cmp(&a.created_at)
+                .then_with(|| b.name.cmp(&a.name))
+        });
+        Ok(backups)
+    }
+
+    pub async fn find(&self, name: &str) -> Result<Option<Backup>> {
+        if !valid_name(name) {
+            return Ok(None);
+        }
+        match read_manifest(&self.manifest_path(name)).await {
+            Ok(backup) => Ok(Some(backup)),
+            Err(e)
+                if e.downcast_ref::<std::io::Error>()
+                    .is_some_and(|e| e.kind() == ErrorKind::NotFound) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Checks the snapshot file against its manifest and runs SQLite's
+    /// integrity and foreign key checks on it.
+    pub async fn verify(&self, name: &str) -> Result<Option<BackupVerification>> {
+        let Some(backup) = self.find(name).await? else {
+            return Ok(None);
+        };
+        let problems = self.check(&backup).await;
+        Ok(Some(BackupVerification {
+            name: backup.name,
+            ok: problems.is_empty(),
+            problems,
+        }))
+    }
+
+    /// Replaces the contents of the database with a verified snapshot, after
+    /// taking a snapshot of the current contents. Snapshots of an older
+    /// schema are migrated first. Runs in one transaction, so readers see
+    /// either the old or the restored data.
+    pub async fn restore(
+        &self,
+        ctx: &RequestContext,
+        name: &str,
+    ) -> Result<Option<RestoreOutcome>> {
+        let Some(backup) = self.find(name).await? else {
+            return Ok(None);
+        };
+        let rejected = |reason: String| BackupRejected {
+            name: backup.name.clone(),
+            reason,
+        };
+        let problems = self.check(&backup).await;
+        if !problems.is_empty() {
+            return Err(rejected(problems.join("; ")).into());
+        }
+        if backup.schema_version > database::latest_migration_version() {
+            return Err(rejected(format!(
+                "it has schema version {} but this build only knows up to {}",
+                backup.schema_version,
+                database::latest_migration_version()
+            ))
+            .into());
+        }
+
+        // Migrate a copy, so the snapshot itself stays as verified.
+        let copy = self.tmp_path(&Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(self.dir.join("tmp")).await?;
+        tokio::fs::copy(self.snapshot_path(&backup.name), &copy).await?;
+        let result = self.restore_from(ctx, &backup, &copy).await;
+        let _ = tokio::fs::remove_file(&copy).await;
+        let pre_restore = result?;
+
+        self.rotate().await?;
+        tracing::warn!(
+            backup = %backup.name,
+            pre_restore = %pre_restore.name,
+            "Restored database from backup"
+        );
+        Ok(Some(RestoreOutcome {
+            restored: backup,
+            pre_restore,
+        }))
+    }
+
+    async fn restore_from(
+        &self,
+        ctx: &RequestContext,
+        backup: &Backup,
+        copy: &Path,
+    ) -> Result<Backup> {
+        let url = format!("sqlite:{}", path_str(copy)?);
+        let migrated = database::create_pool_with(
+            &url,
+            PoolSettings {
+                max_connections: 1,
+                key: self.key.clone(),
+                ..Default::default()
+            },
+        )
+        .await
+        .context("migrating the backup")?;
+        migrated.close().await;
+
+        let pre_restore = self.snapshot(BackupKind::PreRestore).await?;
+
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("ATTACH DATABASE ? AS snapshot KEY ?")
+            .bind(path_str(copy)?.to_string())
+            .bind(encryption::attach_key(self.key.as_ref()))
+            .execute(&mut *conn)
+            .await?;
+        let entry = NewAuditEntry {
+            entity_type: ENTITY_BACKUP,
+            entity_id: backup.name.clone(),
+            action: AuditAction::Restore,
+            before: Some(serde_json::to_value(&pre_restore)?),
+            after: Some(serde_json::to_value(backup)?),
+        };
+        let copied = copy_from_snapshot(&mut conn, ctx, entry).await;
+        sqlx::query("DETACH DATABASE snapshot")
+            .execute(&mut *conn)
+            .await?;
+        copied?;
+
+        Ok(pre_restore)
+    }
+
+    /// Re-encrypts every snapshot that is plain or encrypted with `old`
+    /// under this service's key, rewriting its manifest, and returns how many
+    /// were. Snapshots already under this key are skipped, so an interrupted
+    /// rotation can be run again.
+    pub async fn reencrypt(&self, old: Option<&EncryptionKey>) -> Result<usize> {
+        let Some(key) = &self.key else {
+            return Err(anyhow!("re-encrypting backups needs the new key"));
+        };
+        let fingerprint = Some(key.fingerprint());
+
+        let mut reencrypted = 0;
+        for backup in self.list().await? {
+            if backup.key_fingerprint == fingerprint {
+                continue;
+            }
+            let path = self.snapshot_path(&backup.name);
+            encryption::rekey_database(&path, old, key).await?;
+            let rekeyed = describe(
+                &path,
+                Some(key),
+                backup.name,
+                backup.kind,
+                backup.created_at,
+            )
+            .await?;
+            self.write_manifest(&rekeyed).await?;
+            tracing::info!(backup = %rekeyed.name, "Re-encrypted backup");
+            reencrypted += 1;
+        }
+        Ok(reencrypted)
+    }
+
+    /// Removes the snapshots beyond the newest `keep`.
+    pub async fn rotate(&self) -> Result<usize> {
+        let expired: Vec<Backup> = self.list().await?.into_iter().skip(self.keep).collect();
+        for backup in &expired {
+            // The manifest goes first, so a half-removed snapshot is not listed.
+            for path in [
+                self.manifest_path(&backup.name),
+                self.snapshot_path(&backup.name),
+            ] {
+                match tokio::fs::remove_file(&path).await {
+                    Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+            tracing::info!(backup = %backup.name, "Removed expired backup");
+        }
+        Ok(expired.len())
+    }
+
+    async fn snapshot(&self, kind: BackupKind) -> Result<Backup> {
+        tokio::fs::create_dir_all(self.dir.join("tmp")).await?;
+        let created_at = Utc::now();
+        let name = format!(
+            "expenses-{}-{}",
+            created_at.format("%Y%m%dT%H%M%SZ"),
+            &Uuid::new_v4().simple().to_string()[..8]
+        );
+
+        let tmp = self.tmp_path(&name);
+        sqlx::query("VACUUM INTO ?")
+            .bind(path_str(&tmp)?.to_string())
+            .execute(&self.pool)
+            .await
+            .context("writing the snapshot")?;
+        let backup = match describe(&tmp, self.key.as_ref(), name, kind, created_at).await {
+            Ok(backup) => backup,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&tmp).await;
+                return Err(e);
+            }
+        };
+
+        tokio::fs::rename(&tmp, self.snapshot_path(&backup.name)).await?;
+        self.write_manifest(&backup).await?;
+
+        tracing::info!(
+            backup = %backup.name,
+            kind = ?backup.kind,
+            size_bytes = backup.size_bytes,
+            "Created backup"
+        );
+        Ok(backup)
+    }
+
+    async fn write_manifest(&self, backup: &Backup) -> Result<()> {
+        let manifest = self.tmp_path(&format!("{}.json", backup.name));
+        tokio::fs::create_dir_all(self.dir.join("tmp")).await?;
+        tokio::fs::write(&manifest, serde_json::to_vec_pretty(backup)?).await?;
+        tokio::fs::rename(&manifest, self.manifest_path(&backup.name)).await?;
+        Ok(())
+    }
+
+    /// Everything wrong with a snapshot; empty when it is sound.
+    async fn check(&self, backup: &Backup) -> Vec<String> {
+        let path = self.snapshot_path(&backup.name);
+        let (sha256, size_bytes) = match hash_file(&path).await {
+            Ok(hashed) => hashed,
+            Err(e) => return vec![format!("snapshot file cannot be read: {e:#}")],
+        };
+
+        let mut problems = Vec::new();
+        if size_bytes != backup.size_bytes {
+            problems.push(format!(
+                "snapshot is {size_bytes} bytes, manifest says {}",
+                backup.size_bytes
+            ));
+        }
+        if sha256 != backup.sha256 {
+            problems.push("snapshot checksum does not match its manifest".to_string());
+            // Whatever SQLite makes of a changed file, it is not the backup.
+            return problems;
+        }
+        let fingerprint = self.key.as_ref().map(EncryptionKey::fingerprint);
+        if backup.key_fingerprint != fingerprint {
+            problems.push(format!(
+                "snapshot has {}, the database has {}",
+                encryption::describe_key(backup.key_fingerprint.as_deref()),
+                encryption::describe_key(fingerprint.as_deref())
+            ));
+            return problems;
+        }
+        if let Err(e) = check_database(&path, self.key.as_ref(), backup, &mut problems).await {
+            problems.push(format!("snapshot cannot be opened: {e:#}"));
+        }
+        problems
+    }
+
+    fn snapshot_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.db"))
+    }
+
+    fn manifest_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.json"))
+    }
+
+    fn tmp_path(&self, name: &str) -> PathBuf {
+        self.dir.join("tmp").join(name)
+    }
+}
+
+/// Names are generated, so anything else is not one of ours; this also keeps
+/// names from reaching outside the backup directory.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| anyhow!("backup path {} is not UTF-8", path.display()))
+}
+
+async fn read_manifest(path: &Path) -> Result<Backup> {
+    let content = tokio::fs::read(path).await?;
+    Ok(serde_json::from_slice(&content)?)
+}
+
+async fn hash_file(path: &Path) -> Result<(String, u64)> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+        let size = std::io::copy(&mut file, &mut hasher)?;
+        Ok((hex::encode(hasher.finalize()), size))
+    })
+    .await?
+}
+
+async fn open_snapshot(path: &Path, key: Option<&EncryptionKey>) -> Result<SqliteConnection> {
+    encryption::open_database(path, key, true)
+        .await
+        .with_context(|| format!("opening snapshot {}", path.display()))
+}
+
+/// Reads what a freshly written snapshot contains.
+async fn describe(
+    path: &Path,
+    key: Option<&EncryptionKey>,
+    name: String,
+    kind: BackupKind,
+    created_at: chrono::DateTime<Utc>,
+) -> Result<Backup> {
+    let mut conn = open_snapshot(path, key).await?;
+    let schema_version: i64 =
+        sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_migrations")
+            .fetch_one(&mut conn)
+            .await?;
+    let expenses: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM expenses")
+        .fetch_one(&mut conn)
+        .await?;
+    conn.close().await?;
+
+    let (sha256, size_bytes) = hash_file(path).await?;
+    Ok(Backup {
+        name,
+        kind,
+        created_at,
+        size_bytes,
+        sha256,
+        schema_version,
+        expenses,
+        key_fingerprint: key.map(EncryptionKey::fingerprint),
+    })
+}
+
+async fn check_database(
+    path: &Path,
+    key: Option<&EncryptionKey>,
+    backup: &Backup,
+    problems: &mut Vec<String>,
+) -> Result<()> {
+    let mut conn = open_snapshot(path, key).await?;
+
+    let integrity: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(&mut conn)
+        .await?;
+    problems.extend(integrity.into_iter().filter(|message| message != "ok"));
+
+    let violations = sqlx::query("PRAGMA foreign_key_check")
+        .fetch_all(&mut conn)
+        .await?;
+    problems.extend(violations.iter().map(|row| {
+        format!(
+            "row {} of {} refers to a missing {}",
+            row.get::<Option<i64>, _>("rowid").unwrap_or_default(),
+            row.get::<String, _>("table"),
+            row.get::<String, _>("parent"),
+        )
+    }));
+
+    // Snapshots older than the audit log have no chain to check.
+    let has_audit_log: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'audit_log')",
+    )
+    .fetch_one(&mut conn)
+    .await?;
+    if has_audit_log {
+        let chain = audit_service::verify_chain(&mut conn).await?;
+        if let Some(seq) = chain.first_invalid_seq {
+            problems.push(format!(
+                "audit log entry {seq} does not match the hash chain"
+            ));
+        }
+    }
+
+    let schema_version: i64 =
+        sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_migrations")
+            .fetch_one(&mut conn)
+            .await?;
+    if schema_version != backup.schema_version {
+        problems.push(format!(
+            "snapshot has schema version {schema_version}, manifest says {}",
+            backup.schema_version
+        ));
+    }
+
+    conn.close().await?;
+    Ok(())
+}
+
+fn quoted(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Replaces every table of `main` but the audit log with its copy in the
+/// attached `snapshot`, which must have the same schema, and appends the
+/// restore to the current audit chain, so the history of what happened
+/// since the snapshot is kept. Triggers are dropped while copying, so the
+/// search index is rebuilt once rather than per row, and foreign keys are
+/// checked once everything is in place.
+async fn copy_from_snapshot(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    entry: NewAuditEntry,
+) -> Result<()> {
+    let mut tx = conn.begin().await?;
+    sqlx::query("PRAGMA defer_foreign_keys = ON")
+        .execute(&mut *tx)
+        .await?;
+
+    let triggers: Vec<(String, String)> =
+        sqlx::query_as("SELECT name, sql FROM main.sqlite_master WHERE type = 'trigger'")
+            .fetch_all(&mut *tx)
+            .await?;
+    for (name, _) in &triggers {
+        let drop = format!("DROP TRIGGER main.{}", quoted(name));
+        sqlx::query(&drop).execute(&mut *tx).await?;
+    }
+
+    // The search index is rebuilt instead: it is keyed by the rowids of
+    // `expenses`, which copying the rows does not preserve.
+    let tables: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM pragma_table_list WHERE schema = 'main' \
+         AND type = 'table' AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\' \
+         AND name <> 'audit_log'",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for table in &tables {
+        let delete = format!("DELETE FROM main.{}", quoted(table));
+        sqlx::query(&delete).execute(&mut *tx).await?;
+    }
+    for table in &tables {
+        let copy = format!(
+            "INSERT INTO main.{table} SELECT * FROM snapshot.{table}",
+            table = quoted(table)
+        );
+        sqlx::query(&copy).execute(&mut *tx).await?;
+    }
+    // The audit log keeps counting from its own last entry.
+    sqlx::query("DELETE FROM main.sqlite_sequence WHERE name <> 'audit_log'")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO main.sqlite_sequence \
+         SELECT * FROM snapshot.sqlite_sequence WHERE name <> 'audit_log'",
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("INSERT INTO main.expenses_fts (expenses_fts) VALUES ('rebuild')")
+        .execute(&mut *tx)
+        .await?;
+
+    for (_, sql) in &triggers {
+        sqlx::query(sql).execute(&mut *tx).await?;
+    }
+    audit_service::append(&mut tx, ctx, entry).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::audit::AuditQuery;
+    use crate::models::expense::CreateExpenseRequest;
+    use crate::models::suggestion::SuggestCategoryRequest;
+    use crate::models::summary::ExpenseFilter;
+    use crate::services::audit_service::{AuditService, ENTITY_EXPENSE};
+    use crate::services::expense_service::ExpenseService;
+    use crate::services::suggestion_service::SuggestionService;
+
+    struct Fixture {
+        dir: PathBuf,
+        pool: SqlitePool,
+        expenses: ExpenseService,
+        backups: BackupService,
+        ctx: RequestContext,
+    }
+
+    async fn fixture(keep: usize) -> Fixture {
+        fixture_with_key(keep, None).await
+    }
+
+    async fn fixture_with_key(keep: usize, key: Option<EncryptionKey>) -> Fixture {
+        // `VACUUM INTO` from an in-memory database writes another one in
+        // memory, so the database is a file.
+        let dir = std::env::temp_dir().join(format!("backups-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let url = format!("sqlite:{}?mode=rwc", dir.join("expenses.db").display());
+        let settings = PoolSettings {
+            key: key.clone(),
+            ..Default::default()
+        };
+        let pool = database::create_pool_with(&url, settings).await.unwrap();
+        Fixture {
+            expenses: ExpenseService::new(pool.clone()),
+            backups: BackupService::new(pool.clone(), dir.join("backups"), keep, key),
+            ctx: RequestContext::new("tester", "test-request"),
+            dir,
+            pool,
+        }
+    }
+
+    impl Fixture {
+        async fn add(&self, amount: f64) {
+            let request = CreateExpenseRequest {
+                amount,
+                category: "Food".to_string(),
+                ..Default::default()
+            };
+            self.expenses.add_expense(&self.ctx, request).await.unwrap();
+        }
+
+        async fn amounts(&self) -> Vec<f64> {
+            let mut amounts: Vec<f64> = self
+                .expenses
+                .list_expenses(&ExpenseFilter::default())
+                .await
+                .unwrap()
+                .iter()
+                .map(|expense| expense.amount)
+                .collect();
+            amounts.sort_by(f64::total_cmp);
+            amounts
+        }
+
+        async fn cleanup(self) {
+            self.pool.close().await;
+            tokio::fs::remove_dir_all(self.dir).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_list_and_verify() {
+        let f = fixture(7).await;
+        f.add(12.5).await;
+
+        let backup = f.backups.create(BackupKind::Manual).await.unwrap();
+
+        assert_eq!(backup.kind, BackupKind::Manual);
+        assert_eq!(backup.expenses, 1);
+        assert_eq!(backup.schema_version, database::latest_migration_version());
+        assert_eq!(f.backups.list().await.unwrap(), vec![backup.clone()]);
+        assert_eq!(
+            f.backups.verify(&backup.name).await.unwrap(),
+            Some(BackupVerification {
+                name: backup.name.clone(),
+                ok: true,
+                problems: Vec::new(),
+            })
+        );
+        assert!(f.backups.find("../expenses").await.unwrap().is_none());
+        assert!(f.backups.verify("missing").await.unwrap().is_none());
+
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn test_rotation_keeps_the_newest() {
+        let f = fixture(2).await;
+
+        let mut created = Vec::new();
+        for _ in 0..3 {
+            created.push(f.backups.create(BackupKind::Scheduled).await.unwrap());
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+
+        let names: Vec<String> = f
+            .backups
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|backup| backup.name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![created[2].name.clone(), created[1].name.clone()]
+        );
+        assert!(!f.backups.snapshot_path(&created[0].name).exists());
+
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn test_tampered_snapshot_fails_verification_and_restore() {
+        let f = fixture(7).await;
+        f.add(12.5).await;
+        let backup = f.backups.create(BackupKind::Manual).await.unwrap();
+
+        let path = f.backups.snapshot_path(&backup.name);
+        let mut content = tokio::fs::read(&path).await.unwrap();
+        let last = content.len() - 1;
+        content[last] ^= 0xff;
+        tokio::fs::write(&path, content).await.unwrap();
+
+        let verification = f.backups.verify(&backup.name).await.unwrap().unwrap();
+        assert!(!verification.ok);
+        assert!(verification.problems[0].contains("checksum"));
+
+        let error = f.backups.restore(&f.ctx, &backup.name).await.unwrap_err();
+        assert!(error.downcast_ref::<BackupRejected>().is_some());
+        assert_eq!(f.amounts().await, vec![12.5]);
+
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_with_broken_audit_chain_is_refused() {
+        let f = fixture(7).await;
+        f.add(12.5).await;
+        f.add(20.0).await;
+        sqlx::query("DROP TRIGGER audit_log_no_update")
+            .execute(&f.pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE audit_log SET actor = 'someone else' WHERE seq = 1")
+            .execute(&f.pool)
+            .await
+            .unwrap();
+        let backup = f.backups.create(BackupKind::Manual).await.unwrap();
+
+        let verification = f.backups.verify(&backup.name).await.unwrap().unwrap();
+        assert!(!verification.ok);
+        assert_eq!(
+            verification.problems,
+            vec!["audit log entry 1 does not match the hash chain".to_string()]
+        );
+
+        let error = f.backups.restore(&f.ctx, &backup.name).await.unwrap_err();
+        assert!(error.downcast_ref::<BackupRejected>().is_some());
+
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn test_restore_replaces_contents_and_keeps_the_audit_chain() {
+        let f = fixture(7).await;
+        f.add(12.5).await;
+        f.add(20.0).await;
+        let backup = f.backups.create(BackupKind::Manual).await.unwrap();
+
+        let suggestions = SuggestionService::new(f.pool.clone());
+        let trained_on = || async {
+            suggestions
+                .suggest(&SuggestCategoryRequest::default())
+                .await
+                .unwrap()
+                .trained_on
+        };
+        f.add(99.0).await;
+        f.add(98.0).await;
+        assert_eq!(trained_on().await, 4);
+        let trashed = f
+            .expenses
+            .list_expenses(&ExpenseFilter::default())
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|expense| expense.amount == 12.5)
+            .unwrap();
+        f.expenses
+            .delete_expense(&f.ctx, trashed.id, None)
+            .await
+            .unwrap();
+        assert_eq!(f.amounts().await, vec![20.0, 98.0, 99.0]);
+
+        let outcome = f
+            .backups
+            .restore(&f.ctx, &backup.name)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(outcome.restored, backup);
+        assert_eq!(outcome.pre_restore.kind, BackupKind::PreRestore);
+        assert_eq!(outcome.pre_restore.expenses, 4);
+        assert_eq!(f.amounts().await, vec![12.5, 20.0]);
+        // The suggestion model notices its audit history was replaced.
+        assert_eq!(trained_on().await, 2);
+
+        // What happened after the snapshot stays in the audit log, followed
+        // by the restore.
+        let audit = AuditService::new(f.pool.clone());
+        assert!(audit.verify_chain().await.unwrap().valid);
+        let trashed_history = audit
+            .history(ENTITY_EXPENSE, &trashed.id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(trashed_history.last().unwrap().action, AuditAction::Delete);
+        let history = audit.history(ENTITY_BACKUP, &backup.name).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].actor, "tester");
+        let entries = audit.query(AuditQuery::default()).await.unwrap();
+        assert_eq!(entries.len(), 6);
+        assert_eq!(entries[5].entity_type, ENTITY_BACKUP);
+
+        // The search index and triggers come back with the data.
+        let hits: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM expenses_fts")
+            .fetch_one(&f.pool)
+            .await
+            .unwrap();
+        assert_eq!(hits, 2);
+        f.add(7.0).await;
+        let hits: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM expenses_fts")
+            .fetch_one(&f.pool)
+            .await
+            .unwrap();
+        assert_eq!(hits, 3);
+
+        // Restoring the pre-restore snapshot undoes the restore.
+        f.backups
+            .restore(&f.ctx, &outcome.pre_restore.name)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(f.amounts().await, vec![20.0, 98.0, 99.0]);
+
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_snapshots_restore_and_reencrypt() {
+        let first = EncryptionKey::parse(&"01".repeat(32)).unwrap();
+        let f = fixture_with_key(7, Some(first.clone())).await;
+        f.add(12.5).await;
+        let backup = f.backups.create(BackupKind::Manual).await.unwrap();
+
+        let path = f.backups.snapshot_path(&backup.name);
+        assert_eq!(backup.key_fingerprint, Some(first.fingerprint()));
+        assert!(!encryption::opens_with(&path, None).await.unwrap());
+        assert!(f.backups.verify(&backup.name).await.unwrap().unwrap().ok);
+
+        f.add(20.0).await;
+        f.backups.restore(&f.ctx, &backup.name).await.unwrap();
+        assert_eq!(f.amounts().await, vec![12.5]);
+
+        let second = EncryptionKey::parse(&"02".repeat(32)).unwrap();
+        let rotated = BackupService::new(
+            f.pool.clone(),
+            f.backups.dir.clone(),
+            7,
+            Some(second.clone()),
+        );
+        let verification = rotated.verify(&backup.name).await.unwrap().unwrap();
+        assert!(verification.problems[0].contains("snapshot has key"));
+
+        assert_eq!(rotated.reencrypt(Some(&first)).await.unwrap(), 2);
+        assert_eq!(rotated.reencrypt(Some(&first)).await.unwrap(), 0);
+        let rekeyed = rotated.find(&backup.name).await.unwrap().unwrap();
+        assert_eq!(rekeyed.key_fingerprint, Some(second.fingerprint()));
+        assert_eq!(rekeyed.expenses, 1);
+        assert!(rotated.verify(&backup.name).await.unwrap().unwrap().ok);
+        assert!(!f.backups.verify(&backup.name).await.unwrap().unwrap().ok);
+
+        // Once the database is rotated too, its snapshots restore again.
+        f.pool.close().await;
+        let path = f.dir.join("expenses.db");
+        assert!(
+            encryption::rekey_database(&path, Some(&first), &second)
+                .await
+                .unwrap()
+        );
+        let settings = PoolSettings {
+            key: Some(second.clone()),
+            ..Default::default()
+        };
+        let url = format!("sqlite:{}", path.display());
+        let pool = database::create_pool_with(&url, settings).await.unwrap();
+        let rotated = BackupService::new(pool.clone(), f.backups.dir.clone(), 7, Some(second));
+        rotated
+            .restore(&f.ctx, &backup.name)
+            .await
+            .unwrap()
+            .unwrap();
+        let expenses: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM expenses")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(expenses, 1);
+
+        pool.close().await;
+        f.cleanup().await;
+    }
+}
@@ -0,0 +1,1045 @@
+use crate::context::{Credential, RequestContext};
+use crate::models::audit::AuditAction;
+use crate::models::expense::Expense;
+use crate::models::expense_report::{
+    Actor, CommentRequest, CreateReportRequest, ExpenseLocked, ExpenseReport, ExpenseReportDetail,
+    ReportComment, ReportQuery, ReportStatus, TransitionRequest,
+};
+use crate::services::attachment_service;
+use crate::services::audit_service::{self, ENTITY_EXPENSE_REPORT, NewAuditEntry};
+use crate::services::expense_service;
+use crate::services::integrity_service::decode_row;
+use crate::storage::BlobStore;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+use uuid::Uuid;
+use uuid::fmt::Hyphenated;
+use zip::write::SimpleFileOptions;
+
+/// Result of a report write that can be rejected.
+#[derive(Debug)]
+pub enum ReportWrite<T> {
+    Saved(T),
+    NotFound,
+    /// The caller's role or identity does not allow the change.
+    Forbidden(String),
+    /// The report is not in a state that allows the change.
+    Conflict(String),
+    Invalid(String),
+}
+
+/// An approved or paid report packaged for finance.
+pub struct ReportExport {
+    pub report: ExpenseReport,
+    pub zip: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct ExpenseReportService {
+    pool: SqlitePool,
+    store: BlobStore,
+    /// Approvers' names by the bearer token they authenticate with, from the
+    /// server's configuration.
+    approvers: Arc<HashMap<Credential, String>>,
+}
+
+impl ExpenseReportService {
+    pub fn new(pool: SqlitePool, store: BlobStore, approvers: HashMap<Credential, String>) -> Self {
+        Self {
+            pool,
+            store,
+            approvers: Arc::new(approvers),
+        }
+    }
+
+    /// The caller acting as the approver its bearer token belongs to; `None`
+    /// when it carries no approver token. `X-Actor` plays no part in this.
+    fn as_approver(&self, ctx: &RequestContext) -> Option<RequestContext> {
+        let name = self.approvers.get(ctx.credential.as_ref()?)?;
+        Some(RequestContext {
+            actor: name.clone(),
+            ..ctx.clone()
+        })
+    }
+
+    /// Starts a draft report owned by the caller.
+    pub async fn create_report(
+        &self,
+        ctx: &RequestContext,
+        request: CreateReportRequest,
+    ) -> Result<ExpenseReport> {
+        let mut tx = self.pool.begin().await?;
+
+        let now = Utc::now();
+        let report = ExpenseReport {
+            id: Uuid::new_v4(),
+            title: request.title,
+            owner: ctx.actor.clone(),
+            status: ReportStatus::Draft,
+            created_at: now,
+            updated_at: now,
+        };
+        sqlx::query(
+            "INSERT INTO expense_reports (id, title, owner, status, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(report.id.to_string())
+        .bind(&report.title)
+        .bind(&report.owner)
+        .bind(report.status.as_str())
+        .bind(report.created_at)
+        .bind(report.updated_at)
+        .execute(&mut *tx)
+        .await?;
+
+        audit(
+            &mut tx,
+            ctx,
+            report.id,
+            AuditAction::Create,
+            None,
+            Some(&report),
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(report)
+    }
+
+    /// Reports matching `query`, most recently changed first.
+    pub async fn list_reports(&self, query: &ReportQuery) -> Result<Vec<ExpenseReport>> {
+        let mut builder = QueryBuilder::<Sqlite>::new("SELECT * FROM expense_reports WHERE 1 = 1");
+        if let Some(status) = query.status {
+            builder.push(" AND status = ").push_bind(status.as_str());
+        }
+        if let Some(owner) = &query.owner {
+            builder.push(" AND owner = ").push_bind(owner.clone());
+        }
+        builder.push(" ORDER BY julianday(updated_at) DESC, id");
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        rows.into_iter().map(row_to_report).collect()
+    }
+
+    pub async fn get_report(&self, id: Uuid) -> Result<Option<ExpenseReportDetail>> {
+        let mut conn = self.pool.acquire().await?;
+        let Some(report) = fetch_report(&mut conn, id).await? else {
+            return Ok(None);
+        };
+        detail(&mut conn, report).await.map(Some)
+    }
+
+    /// Deletes a draft report; its expenses are left untouched.
+    pub async fn delete_report(&self, ctx: &RequestContext, id: Uuid) -> Result<ReportWrite<()>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(report) = fetch_report(&mut tx, id).await? else {
+            return Ok(ReportWrite::NotFound);
+        };
+        if report.owner != ctx.actor {
+            return Ok(not_owner());
+        }
+        if !report.status.is_editable() {
+            return Ok(not_editable(&report));
+        }
+
+        for table in ["expense_report_items", "expense_report_comments"] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE report_id = ?"))
+                .bind(id.to_string())
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM expense_reports WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        audit(&mut tx, ctx, id, AuditAction::Delete, Some(&report), None).await?;
+        tx.commit().await?;
+
+        Ok(ReportWrite::Saved(()))
+    }
+
+    /// Adds a reimbursable expense to a draft report. An expense can be in
+    /// only one report.
+    pub async fn add_expense(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expense_id: Uuid,
+    ) -> Result<ReportWrite<ExpenseReportDetail>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(report) = fetch_report(&mut tx, id).await? else {
+            return Ok(ReportWrite::NotFound);
+        };
+        if report.owner != ctx.actor {
+            return Ok(not_owner());
+        }
+        if !report.status.is_editable() {
+            return Ok(not_editable(&report));
+        }
+
+        let before = detail(&mut tx, report.clone()).await?;
+        let expense = expense_service::fetch_expense(&mut tx, expense_id).await?;
+        let Some(expense) = expense.filter(|e| e.deleted_at.is_none()) else {
+            return Ok(ReportWrite::Invalid(format!(
+                "Expense {} does not exist",
+                expense_id
+            )));
+        };
+        if !expense.reimbursable {
+            return Ok(ReportWrite::Invalid(
+                "Only reimbursable expenses can be added to a report".to_string(),
+            ));
+        }
+        let current: Option<String> =
+            sqlx::query_scalar("SELECT report_id FROM expense_report_items WHERE expense_id = ?")
+                .bind(expense_id.to_string())
+                .fetch_optional(&mut *tx)
+                .await?;
+        if let Some(current) = current {
+            return Ok(ReportWrite::Conflict(format!(
+                "Expense is already part of expense report {}",
+                current
+            )));
+        }
+
+        sqlx::query("INSERT INTO expense_report_items (report_id, expense_id) VALUES (?, ?)")
+            .bind(id.to_string())
+            .bind(expense_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        let after = touch(&mut tx, &report).await?;
+        let after = detail(&mut tx, after).await?;
+
+        audit(
+            &mut tx,
+            ctx,
+            id,
+            AuditAction::Update,
+            Some(&before),
+            Some(&after),
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(ReportWrite::Saved(after))
+    }
+
+    pub async fn remove_expense(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expense_id: Uuid,
+    ) -> Result<ReportWrite<ExpenseReportDetail>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(report) = fetch_report(&mut tx, id).await? else {
+            return Ok(ReportWrite::NotFound);
+        };
+        if report.owner != ctx.actor {
+            return Ok(not_owner());
+        }
+        if !report.status.is_editable() {
+            return Ok(not_editable(&report));
+        }
+
+        let before = detail(&mut tx, report.clone()).await?;
+        let removed =
+            sqlx::query("DELETE FROM expense_report_items WHERE report_id = ? AND expense_id = ?")
+                .bind(id.to_string())
+                .bind(expense_id.to_string())
+                .execute(&mut *tx)
+                .await?;
+        if removed.rows_affected() == 0 {
+            return Ok(ReportWrite::NotFound);
+        }
+        let after = touch(&mut tx, &report).await?;
+        let after = detail(&mut tx, after).await?;
+
+        audit(
+            &mut tx,
+            ctx,
+            id,
+            AuditAction::Update,
+            Some(&before),
+            Some(&after),
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(ReportWrite::Saved(after))
+    }
+
+    /// Moves a report through its lifecycle. Owners submit and reopen their
+    /// own reports; approvers approve, reject and pay other people's. A
+    /// rejection must say why.
+    pub async fn transition(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        request: TransitionRequest,
+    ) -> Result<ReportWrite<ExpenseReportDetail>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(report) = fetch_report(&mut tx, id).await? else {
+            return Ok(ReportWrite::NotFound);
+        };
+        let (from, to, actor) = request.action.transition();
+        let ctx = match actor {
+            Actor::Owner if report.owner != ctx.actor => return Ok(not_owner()),
+            Actor::Owner => ctx.clone(),
+            Actor::Approver => match self.as_approver(ctx) {
+                None => {
+                    return Ok(ReportWrite::Forbidden(
+                        "Only approvers can approve, reject or pay expense reports".to_string(),
+                    ));
+                }
+                Some(approver) if report.owner == approver.actor => {
+                    return Ok(ReportWrite::Forbidden(
+                        "Approvers cannot decide on their own expense reports".to_string(),
+                    ));
+                }
+                Some(approver) => approver,
+            },
+        };
+        let ctx = &ctx;
+        if report.status != from {
+            return Ok(ReportWrite::Conflict(format!(
+                "Cannot {} a report that is {}",
+                serde_json::to_value(request.action)?
+                    .as_str()
+                    .unwrap_or_default(),
+                report.status.as_str()
+            )));
+        }
+        if to == ReportStatus::Rejected && request.comment.is_none() {
+            return Ok(ReportWrite::Invalid(
+                "A rejection needs a comment explaining it".to_string(),
+            ));
+        }
+        if to == ReportStatus::Submitted {
+            let expenses = expense_service::fetch_report_expenses(&mut tx, id).await?;
+            if expenses.is_empty() {
+                return Ok(ReportWrite::Invalid(
+                    "Add at least one expense before submitting".to_string(),
+                ));
+            }
+            if let Some(expense) = expenses.iter().find(|e| !e.reimbursable) {
+                return Ok(ReportWrite::Invalid(format!(
+                    "Expense {} is no longer reimbursable",
+                    expense.id
+                )));
+            }
+        }
+
+        let after = ExpenseReport {
+            status: to,
+            updated_at: Utc::now(),
+            ..report.clone()
+        };
+        sqlx::query("UPDATE expense_reports SET status = ?, updated_at = ? WHERE id = ?")
+            .bind(after.status.as_str())
+            .bind(after.updated_at)
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        if let Some(body) = request.comment {
+            insert_comment(&mut tx, ctx, id, body).await?;
+        }
+
+        audit(
+            &mut tx,
+            ctx,
+            id,
+            AuditAction::Update,
+            Some(&report),
+            Some(&after),
+        )
+        .await?;
+        let detail = detail(&mut tx, after).await?;
+        tx.commit().await?;
+
+        Ok(ReportWrite::Saved(detail))
+    }
+
+    /// Comments come from the report's owner or from approvers.
+    pub async fn add_comment(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        request: CommentRequest,
+    ) -> Result<ReportWrite<ReportComment>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(report) = fetch_report(&mut tx, id).await? else {
+            return Ok(ReportWrite::NotFound);
+        };
+        let ctx = match self.as_approver(ctx) {
+            Some(approver) => approver,
+            None if report.owner == ctx.actor => ctx.clone(),
+            None => {
+                return Ok(ReportWrite::Forbidden(
+                    "Only the report's owner and approvers can comment".to_string(),
+                ));
+            }
+        };
+        let ctx = &ctx;
+
+        let comment = insert_comment(&mut tx, ctx, id, request.body).await?;
+        tx.commit().await?;
+
+        Ok(ReportWrite::Saved(comment))
+    }
+
+    /// A zip with `report.csv` listing the expenses and every receipt under
+    /// `receipts/`. Only approved and paid reports can be exported.
+    pub async fn export(&self, id: Uuid) -> Result<ReportWrite<ReportExport>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let Some(report) = fetch_report(&mut conn, id).await? else {
+            return Ok(ReportWrite::NotFound);
+        };
+        if !matches!(report.status, ReportStatus::Approved | ReportStatus::Paid) {
+            return Ok(ReportWrite::Conflict(format!(
+                "Only approved or paid reports can be exported; this one is {}",
+                report.status.as_str()
+            )));
+        }
+
+        let expenses = expense_service::fetch_report_expenses(&mut conn, id).await?;
+        let mut receipts = Vec::new();
+        let mut lines = Vec::new();
+        for (index, expense) in expenses.iter().enumerate() {
+            let mut paths = Vec::new();
+            let attachments =
+                attachment_service::expense_attachments(&mut conn, expense.id).await?;
+            for (number, attachment) in attachments.iter().enumerate() {
+                // Receipts of one expense may share a filename, so the entry
+                // is numbered by expense line and by receipt.
+                let path = format!(
+                    "receipts/{:03}-{}-{}",
+                    index + 1,
+                    number + 1,
+                    attachment.filename
+                );
+                let content = self.store.read(&attachment.sha256).await?;
+                paths.push(path.clone());
+                receipts.push((path, content));
+            }
+            lines.push(csv_line(index + 1, expense, &paths));
+        }
+        let total: f64 = expenses.iter().map(|e| e.amount - e.refunded).sum();
+        drop(conn);
+
+        let csv = format!(
+            "line,date,category,payee,amount,refunded,net,notes,receipts\n{}total,,,,,,{:.2},,\n",
+            lines.concat(),
+            total
+        );
+        let zip = tokio::task::spawn_blocking(move || write_zip(csv, receipts)).await??;
+
+        Ok(ReportWrite::Saved(ReportExport { report, zip }))
+    }
+}
+
+/// Fails with [`ExpenseLocked`] when the expense is in a report that
+/// freezes its expenses.
+pub(crate) async fn check_unlocked(conn: &mut SqliteConnection, expense_id: Uuid) -> Result<()> {
+    let row = sqlx::query(
+        "SELECT r.* FROM expense_reports r \
+         JOIN expense_report_items i ON i.report_id = r.id \
+         WHERE i.expense_id = ?",
+    )
+    .bind(expense_id.to_string())
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some(report) = row.map(row_to_report).transpose()?
+        && report.status.locks_expenses()
+    {
+        return Err(ExpenseLocked {
+            report_id: report.id,
+            status: report.status,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Expenses frozen by a report under review or settled.
+pub(crate) async fn locked_expense_ids(conn: &mut SqliteConnection) -> Result<HashSet<Uuid>> {
+    let ids: Vec<String> = sqlx::query_scalar(
+        "SELECT i.expense_id FROM expense_report_items i \
+         JOIN expense_reports r ON r.id = i.report_id \
+         WHERE r.status IN ('submitted', 'approved', 'paid')",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    ids.iter()
+        .map(|id| Ok(Uuid::parse_str(id)?))
+        .collect::<Result<_>>()
+}
+
+fn not_owner<T>() -> ReportWrite<T> {
+    ReportWrite::Forbidden("Only the report's owner can do this".to_string())
+}
+
+fn not_editable<T>(report: &ExpenseReport) -> ReportWrite<T> {
+    ReportWrite::Conflict(format!(
+        "Only draft reports can be changed; this one is {}",
+        report.status.as_str()
+    ))
+}
+
+async fn touch(conn: &mut SqliteConnection, report: &ExpenseReport) -> Result<ExpenseReport> {
+    let after = ExpenseReport {
+        updated_at: Utc::now(),
+        ..report.clone()
+    };
+    sqlx::query("UPDATE expense_reports SET updated_at = ? WHERE id = ?")
+        .bind(after.updated_at)
+        .bind(report.id.to_string())
+        .execute(&mut *conn)
+        .await?;
+    Ok(after)
+}
+
+async fn audit<T: Serialize>(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    id: Uuid,
+    action: AuditAction,
+    before: Option<&T>,
+    after: Option<&T>,
+) -> Result<()> {
+    audit_service::append(
+        conn,
+        ctx,
+        NewAuditEntry {
+            entity_type: ENTITY_EXPENSE_REPORT,
+            entity_id: id.to_string(),
+            action,
+            before: before.map(serde_json::to_value).transpose()?,
+            after: after.map(serde_json::to_value).transpose()?,
+        },
+    )
+    .await
+}
+
+async fn insert_comment(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    report_id: Uuid,
+    body: String,
+) -> Result<ReportComment> {
+    let comment = ReportComment {
+        id: Uuid::new_v4(),
+        author: ctx.actor.clone(),
+        body,
+        created_at: Utc::now(),
+    };
+    sqlx::query(
+        "INSERT INTO expense_report_comments (id, report_id, author, body, created_at) \
+         VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(comment.id.to_string())
+    .bind(report_id.to_string())
+    .bind(&comment.author)
+    .bind(&comment.body)
+    .bind(comment.created_at)
+    .execute(&mut *conn)
+    .await?;
+    Ok(comment)
+}
+
+async fn detail(conn: &mut SqliteConnection, report: ExpenseReport) -> Result<ExpenseReportDetail> {
+    let expenses = expense_service::fetch_report_expenses(conn, report.id).await?;
+    let total = expenses.iter().map(|e| e.amount - e.refunded).sum();
+
+    let rows = sqlx::query(
+        "SELECT * FROM expense_report_comments WHERE report_id = ? \
+         ORDER BY julianday(created_at), id",
+    )
+    .bind(report.id.to_string())
+    .fetch_all(&mut *conn)
+    .await?;
+    let comments = rows
+        .into_iter()
+        .map(row_to_comment)
+        .collect::<Result<_>>()?;
+
+    Ok(ExpenseReportDetail {
+        report,
+        expenses,
+        total,
+        comments,
+    })
+}
+
+async fn fetch_report(conn: &mut SqliteConnection, id: Uuid) -> Result<Option<ExpenseReport>> {
+    let row = sqlx::query("SELECT * FROM expense_reports WHERE id = ?")
+        .bind(id.to_string())
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    row.map(row_to_report).transpose()
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct ReportRow {
+    id: Hyphenated,
+    title: String,
+    owner: String,
+    #[sqlx(try_from = "String")]
+    status: ReportStatus,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct CommentRow {
+    id: Hyphenated,
+    author: String,
+    body: String,
+    created_at: DateTime<Utc>,
+}
+
+fn row_to_report(row: SqliteRow) -> Result<ExpenseReport> {
+    let row: ReportRow = decode_row("expense_reports", &row)?;
+    Ok(ExpenseReport {
+        id: row.id.into_uuid(),
+        title: row.title,
+        owner: row.owner,
+        status: row.status,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    })
+}
+
+fn row_to_comment(row: SqliteRow) -> Result<ReportComment> {
+    let row: CommentRow = decode_row("expense_report_comments", &row)?;
+    Ok(ReportComment {
+        id: row.id.into_uuid(),
+        author: row.author,
+        body: row.body,
+        created_at: row.created_at,
+    })
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_line(line: usize, expense: &Expense, receipts: &[String]) -> String {
+    format!(
+        "{},{},{},{},{:.2},{:.2},{:.2},{},{}\n",
+        line,
+        expense.date.format("%Y-%m-%d"),
+        csv_field(&expense.category),
+        csv_field(expense.payee.as_deref().unwrap_or_default()),
+        expense.amount,
+        expense.refunded,
+        expense.amount - expense.refunded,
+        csv_field(expense.notes.as_deref().unwrap_or_default()),
+        csv_field(&receipts.join(";")),
+    )
+}
+
+fn write_zip(csv: String, receipts: Vec<(String, Vec<u8>)>) -> Result<Vec<u8>> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+
+    zip.start_file("report.csv", options)?;
+    zip.write_all(csv.as_bytes())?;
+    for (path, content) in receipts {
+        zip.start_file(path, options)?;
+        zip.write_all(&content)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+    use crate::models::attachment::Upload;
+    use crate::models::expense::{CreateExpenseRequest, UpdateExpenseRequest};
+    use crate::models::expense_report::ReportAction;
+    use crate::models::refund::CreateRefundRequest;
+    use crate::services::attachment_service::{AttachmentService, AttachmentWrite};
+    use crate::services::expense_service::ExpenseService;
+    use crate::services::refund_service::{RefundService, RefundWrite};
+    use std::io::Read;
+
+    fn owner() -> RequestContext {
+        RequestContext::new("alice", "test-request")
+    }
+
+    /// Bob, authenticated with his approver token; `X-Actor` says nothing.
+    fn approver() -> RequestContext {
+        RequestContext::new("anonymous", "test-request").with_credential(Credential::new("b0b"))
+    }
+
+    async fn create_services() -> (ExpenseReportService, ExpenseService) {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        let store = BlobStore::new(
+            std::env::temp_dir().join(format!("expense-reports-{}", Uuid::new_v4())),
+            None,
+        );
+        (
+            ExpenseReportService::new(
+                pool.clone(),
+                store,
+                HashMap::from([(Credential::new("b0b"), "bob".to_string())]),
+            ),
+            ExpenseService::new(pool),
+        )
+    }
+
+    async fn add_expense(service: &ExpenseService, amount: f64, reimbursable: bool) -> Expense {
+        let request = CreateExpenseRequest {
+            amount,
+            category: "Travel".to_string(),
+            payee: Some("Rail, Inc.".to_string()),
+            reimbursable,
+            ..Default::default()
+        };
+        service.add_expense(&owner(), request).await.unwrap()
+    }
+
+    fn action(action: ReportAction, comment: Option<&str>) -> TransitionRequest {
+        TransitionRequest {
+            action,
+            comment: comment.map(str::to_string),
+        }
+    }
+
+    fn saved<T>(outcome: ReportWrite<T>) -> T {
+        match outcome {
+            ReportWrite::Saved(value) => value,
+            other => panic!("expected a saved write, got {:?}", other.kind()),
+        }
+    }
+
+    impl<T> ReportWrite<T> {
+        fn kind(&self) -> &'static str {
+            match self {
+                ReportWrite::Saved(_) => "saved",
+                ReportWrite::NotFound => "not found",
+                ReportWrite::Forbidden(_) => "forbidden",
+                ReportWrite::Conflict(_) => "conflict",
+                ReportWrite::Invalid(_) => "invalid",
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_lifecycle_with_role_checks() {
+        let (reports, expenses) = create_services().await;
+        let train = add_expense(&expenses, 120.0, true).await;
+        let lunch = add_expense(&expenses, 15.0, false).await;
+        let report = reports
+            .create_report(
+                &owner(),
+                CreateReportRequest {
+                    title: "Berlin trip".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+
+        let submit = action(ReportAction::Submit, None);
+        let outcome = reports.transition(&owner(), report.id, submit).await;
+        assert_eq!(outcome.unwrap().kind(), "invalid");
+        let outcome = reports.add_expense(&owner(), report.id, lunch.id).await;
+        assert_eq!(outcome.unwrap().kind(), "invalid");
+        let outcome = reports.add_expense(&approver(), report.id, train.id).await;
+        assert_eq!(outcome.unwrap().kind(), "forbidden");
+        saved(
+            reports
+                .add_expense(&owner(), report.id, train.id)
+                .await
+                .unwrap(),
+        );
+
+        let submit = action(ReportAction::Submit, None);
+        let detail = saved(
+            reports
+                .transition(&owner(), report.id, submit)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(detail.report.status, ReportStatus::Submitted);
+        assert_eq!(detail.total, 120.0);
+
+        let approve = action(ReportAction::Approve, None);
+        let outcome = reports.transition(&owner(), report.id, approve).await;
+        assert_eq!(outcome.unwrap().kind(), "forbidden");
+        let approve = action(ReportAction::Approve, None);
+        let claims_to_be_bob = RequestContext::new("bob", "test-request");
+        let outcome = reports
+            .transition(&claims_to_be_bob, report.id, approve)
+            .await;
+        assert_eq!(outcome.unwrap().kind(), "forbidden");
+        let alice_approves = ExpenseReportService {
+            approvers: Arc::new(HashMap::from([(
+                Credential::new("4lice"),
+                "alice".to_string(),
+            )])),
+            ..reports.clone()
+        };
+        let approve = action(ReportAction::Approve, None);
+        let alice = owner().with_credential(Credential::new("4lice"));
+        let outcome = alice_approves.transition(&alice, report.id, approve).await;
+        assert_eq!(outcome.unwrap().kind(), "forbidden");
+        let reject = action(ReportAction::Reject, None);
+        let outcome = reports.transition(&approver(), report.id, reject).await;
+        assert_eq!(outcome.unwrap().kind(), "invalid");
+
+        let reject = action(ReportAction::Reject, Some("Attach the ticket"));
+        let detail = saved(
+            reports
+                .transition(&approver(), report.id, reject)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(detail.report.status, ReportStatus::Rejected);
+        assert_eq!(detail.comments[0].author, "bob");
+
+        for (ctx, step) in [
+            (owner(), ReportAction::Reopen),
+            (owner(), ReportAction::Submit),
+            (approver(), ReportAction::Approve),
+            (approver(), ReportAction::Pay),
+        ] {
+            saved(
+                reports
+                    .transition(&ctx, report.id, action(step, None))
+                    .await
+                    .unwrap(),
+            );
+        }
+        let pay = action(ReportAction::Pay, None);
+        let outcome = reports.transition(&approver(), report.id, pay).await;
+        assert_eq!(outcome.unwrap().kind(), "conflict");
+    }
+
+    #[tokio::test]
+    async fn test_expenses_in_submitted_reports_are_locked() {
+        let (reports, expenses) = create_services().await;
+        let train = add_expense(&expenses, 120.0, true).await;
+        let report = reports
+            .create_report(
+                &owner(),
+                CreateReportRequest {
+                    title: "Berlin trip".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        saved(
+            reports
+                .add_expense(&owner(), report.id, train.id)
+                .await
+                .unwrap(),
+        );
+        let submit = action(ReportAction::Submit, None);
+        saved(
+            reports
+                .transition(&owner(), report.id, submit)
+                .await
+                .unwrap(),
+        );
+
+        let request = UpdateExpenseRequest {
+            amount: 10.0,
+            category: "Travel".to_string(),
+            reimbursable: true,
+            ..Default::default()
+        };
+        let error = expenses
+            .update_expense(&owner(), train.id, None, request)
+            .await
+            .unwrap_err();
+        assert!(error.downcast_ref::<ExpenseLocked>().is_some());
+        let error = expenses
+            .delete_expense(&owner(), train.id, None)
+            .await
+            .unwrap_err();
+        assert!(error.downcast_ref::<ExpenseLocked>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_refunds_and_receipts_of_locked_expenses_are_frozen() {
+        let (reports, expenses) = create_services().await;
+        let refunds = RefundService::new(reports.pool.clone());
+        let attachments = AttachmentService::new(reports.pool.clone(), reports.store.clone(), 1024);
+        let train = add_expense(&expenses, 120.0, true).await;
+        let receipt = || Upload {
+            filename: Some("ticket.pdf".to_string()),
+            content_type: Some("application/pdf".to_string()),
+            content: b"%PDF-1.7 ticket".to_vec(),
+        };
+        let refund = || CreateRefundRequest {
+            amount: 20.0,
+            ..Default::default()
+        };
+        let RefundWrite::Saved(kept_refund) = refunds
+            .add_refund(&owner(), train.id, refund())
+            .await
+            .unwrap()
+        else {
+            panic!("refund not recorded");
+        };
+        let AttachmentWrite::Saved(kept_receipt) = attachments
+            .add_attachment(&owner(), train.id, receipt())
+            .await
+            .unwrap()
+        else {
+            panic!("receipt not stored");
+        };
+        let report = reports
+            .create_report(
+                &owner(),
+                CreateReportRequest {
+                    title: "Berlin trip".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        saved(
+            reports
+                .add_expense(&owner(), report.id, train.id)
+                .await
+                .unwrap(),
+        );
+        let submit = action(ReportAction::Submit, None);
+        saved(
+            reports
+                .transition(&owner(), report.id, submit)
+                .await
+                .unwrap(),
+        );
+
+        let errors = [
+            refunds
+                .add_refund(&owner(), train.id, refund())
+                .await
+                .unwrap_err(),
+            refunds
+                .delete_refund(&owner(), kept_refund.id)
+                .await
+                .unwrap_err(),
+            attachments
+                .add_attachment(&owner(), train.id, receipt())
+                .await
+                .unwrap_err(),
+            attachments
+                .delete_attachment(&owner(), kept_receipt.id)
+                .await
+                .unwrap_err(),
+        ];
+        for error in errors {
+            assert!(error.downcast_ref::<ExpenseLocked>().is_some());
+            assert!(matches!(AppError::from(error), AppError::Conflict(_)));
+        }
+        assert_eq!(
+            refunds.list_refunds(train.id).await.unwrap().unwrap().len(),
+            1
+        );
+        let receipts = attachments.list_attachments(train.id).await.unwrap();
+        assert_eq!(receipts.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_export_contains_csv_and_receipts() {
+        let (reports, expenses) = create_services().await;
+        let train = add_expense(&expenses, 120.0, true).await;
+        let report = reports
+            .create_report(
+                &owner(),
+                CreateReportRequest {
+                    title: "Berlin trip".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        saved(
+            reports
+                .add_expense(&owner(), report.id, train.id)
+                .await
+                .unwrap(),
+        );
+        let outcome = reports.export(report.id).await.unwrap();
+        assert_eq!(outcome.kind(), "conflict");
+
+        let receipt = b"%PDF-1.7 ticket";
+        let sha = crate::storage::sha256_hex(receipt);
+        reports.store.put(&sha, receipt).await.unwrap();
+        // Two receipts with the same name, e.g. both uploaded without one.
+        for _ in 0..2 {
+            sqlx::query(
+                "INSERT INTO attachments \
+                 (id, expense_id, filename, content_type, size, sha256, has_thumbnail, created_at) \
+                 VALUES (?, ?, 'ticket.pdf', 'application/pdf', ?, ?, 0, ?)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(train.id.to_string())
+            .bind(receipt.len() as i64)
+            .bind(&sha)
+            .bind(Utc::now())
+            .execute(&reports.pool)
+            .await
+            .unwrap();
+        }
+        for (ctx, step) in [
+            (owner(), ReportAction::Submit),
+            (approver(), ReportAction::Approve),
+        ] {
+            saved(
+                reports
+                    .transition(&ctx, report.id, action(step, None))
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let export = saved(reports.export(report.id).await.unwrap());
+        let mut archive = zip::ZipArchive::new(Cursor::new(export.zip)).unwrap();
+        let mut csv = String::new();
+        archive
+            .by_name("report.csv")
+            .unwrap()
+            .read_to_string(&mut csv)
+            .unwrap();
+        assert!(csv.contains("\"Rail, Inc.\",120.00,0.00,120.00"));
+        assert!(csv.ends_with("total,,,,,,120.00,,\n"));
+        assert!(csv.contains("receipts/001-1-ticket.pdf;receipts/001-2-ticket.pdf"));
+        for path in ["receipts/001-1-ticket.pdf", "receipts/001-2-ticket.pdf"] {
+            let mut content = Vec::new();
+            archive
+                .by_name(path)
+                .unwrap()
+                .read_to_end(&mut content)
+                .unwrap();
+            assert_eq!(content, receipt);
+        }
+    }
+}
@@ -1,99 +1,847 @@
-use crate::models::expense::{CreateExpenseRequest, Expense};
-use anyhow::Result;
-use sqlx::{Row, SqlitePool};
+use crate::context::RequestContext;
+use crate::models::audit::AuditAction;
+use crate::models::batch::{
+    BatchItemError, BatchItemResult, BatchMode, BatchOperation, BatchRequest, BatchResponse,
+};
+use crate::models::expense::{CreateExpenseRequest, Expense, UpdateExpenseRequest};
+use crate::models::payee::ResolvedPayee;
+use crate::models::refund::AmountBelowRefunded;
+use crate::models::search::{
+    self, MATCH_END, MATCH_START, SearchHighlights, SearchHit, SearchQuery,
+};
+use crate::models::summary::{
+    CashFlow, CashFlowQuery, CashFlowReport, CashFlowRow, ExpenseFilter, ExpenseSummary,
+};
+use crate::models::tag::normalize_tags;
+use crate::repository::{ExpenseRepository, SqliteExpenseRepository};
+use crate::services::audit_service::{self, ENTITY_EXPENSE, NewAuditEntry};
+use crate::services::integrity_service::decode_row;
+use crate::services::{account_service, expense_report_service, payee_service, rule_service};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::types::Json;
+use sqlx::{Acquire, QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use uuid::fmt::Hyphenated;
+
+/// Result of a versioned write.
+#[derive(Debug)]
+pub enum VersionedUpdate {
+    Updated(Expense),
+    NotFound,
+    /// The caller's version is stale; carries the current state.
+    VersionMismatch(Expense),
+}
 
 #[derive(Clone)]
 pub struct ExpenseService {
-    pool: SqlitePool,
+    repository: Arc<dyn ExpenseRepository>,
+    /// Search, cash flow, batches and rules are only stored in SQLite.
+    pool: Option<SqlitePool>,
 }
 
 impl ExpenseService {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        Self {
+            repository: Arc::new(SqliteExpenseRepository::new(pool.clone())),
+            pool: Some(pool),
+        }
     }
 
-    pub async fn add_expense(&self, request: CreateExpenseRequest) -> Result<Expense> {
-        let expense = Expense::new(request.amount, request.category);
+    /// A service over a repository outside the SQLite database, with only
+    /// the features [`ExpenseRepository`] covers.
+    pub fn with_repository(repository: Arc<dyn ExpenseRepository>) -> Self {
+        Self {
+            repository,
+            pool: None,
+        }
+    }
 
-        sqlx::query("INSERT INTO expenses (id, amount, category, date) VALUES (?, ?, ?, ?)")
-            .bind(expense.id.to_string())
-            .bind(expense.amount)
-            .bind(&expense.category)
-            .bind(expense.date)
-            .execute(&self.pool)
-            .await?;
+    fn sqlite(&self) -> Result<&SqlitePool> {
+        self.pool
+            .as_ref()
+            .ok_or_else(|| anyhow!("this feature needs the SQLite backend"))
+    }
+
+    pub async fn add_expense(
+        &self,
+        ctx: &RequestContext,
+        request: CreateExpenseRequest,
+    ) -> Result<Expense> {
+        self.repository.insert(ctx, request).await
+    }
+
+    /// Fills the fields a new expense left empty (category, payee) from the
+    /// enabled categorization rules. Without SQLite there are no rules.
+    pub async fn apply_rules(
+        &self,
+        mut request: CreateExpenseRequest,
+    ) -> Result<CreateExpenseRequest> {
+        let Some(pool) = &self.pool else {
+            return Ok(request);
+        };
+        let mut conn = pool.acquire().await?;
+        let rules = rule_service::enabled_rules(&mut conn).await?;
+        rules.apply_to_request(&mut request, Utc::now());
+        Ok(request)
+    }
 
-        Ok(expense)
+    /// Non-deleted expenses matching `filter`, newest first.
+    pub async fn list_expenses(&self, filter: &ExpenseFilter) -> Result<Vec<Expense>> {
+        self.repository.list(filter).await
     }
 
-    pub async fn get_all_expenses(&self) -> Result<Vec<Expense>> {
-        let rows =
-            sqlx::query("SELECT id, amount, category, date FROM expenses ORDER BY date DESC")
-                .fetch_all(&self.pool)
-                .await?;
+    /// Totals of the non-deleted expenses matching `filter`, overall, per
+    /// category and per tag.
+    pub async fn summarize(&self, filter: &ExpenseFilter) -> Result<ExpenseSummary> {
+        self.repository.summarize(filter).await
+    }
+
+    /// Income against non-deleted expenses per period. Income is kept in its
+    /// own table, so it never leaks into the expense queries above; refunds
+    /// reduce the expenses of the period the original expense falls in.
+    pub async fn cash_flow(&self, query: &CashFlowQuery) -> Result<CashFlowReport> {
+        let pool = self.sqlite()?;
+        let mut flows: BTreeMap<String, (f64, f64)> = BTreeMap::new();
+        let sources = [
+            ("income", "amount", "1 = 1"),
+            ("expenses", NET_AMOUNT, "deleted_at IS NULL"),
+        ];
+        for (table, amount, condition) in sources {
+            // Totals per day; the days are bucketed into periods below, as the
+            // bundled SQLite has no ISO week format.
+            let mut builder = QueryBuilder::<Sqlite>::new(format!(
+                "SELECT date(date) AS day, SUM({amount}) AS total FROM {table} WHERE "
+            ));
+            builder.push(condition);
+            if let Some(from) = query.from {
+                builder.push(" AND date >= ").push_bind(from);
+            }
+            if let Some(to) = query.to {
+                builder.push(" AND date < ").push_bind(to);
+            }
+            builder.push(" GROUP BY day");
+
+            for row in builder.build().fetch_all(pool).await? {
+                let day: NaiveDate = row.get("day");
+                let entry = flows.entry(query.period.label(day)).or_default();
+                let total: f64 = row.get("total");
+                if table == "income" {
+                    entry.0 += total;
+                } else {
+                    entry.1 += total;
+                }
+            }
+        }
 
-        let expenses = rows
+        let rows: Vec<CashFlowRow> = flows
             .into_iter()
-            .map(|row| Expense {
-                id: uuid::Uuid::parse_str(&row.get::<String, _>("id")).unwrap(),
-                amount: row.get("amount"),
-                category: row.get("category"),
-                date: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("date"))
-                    .unwrap()
-                    .with_timezone(&chrono::Utc),
+            .map(|(period, (income, expenses))| CashFlowRow {
+                period,
+                flow: CashFlow::new(income, expenses),
             })
             .collect();
+        let total = CashFlow::new(
+            rows.iter().map(|row| row.flow.income).sum(),
+            rows.iter().map(|row| row.flow.expenses).sum(),
+        );
 
-        Ok(expenses)
+        Ok(CashFlowReport {
+            period: query.period,
+            rows,
+            total,
+        })
     }
 
     pub async fn get_highest_expense(&self) -> Result<Option<Expense>> {
-        let rows = sqlx::query(
-            "SELECT id, amount, category, date FROM expenses ORDER BY amount DESC LIMIT 1",
-        )
-        .fetch_optional(&self.pool)
+        self.repository.highest().await
+    }
+
+    /// Full-text search over category, payee and notes of non-deleted
+    /// expenses, best matches first. Returns an empty list for queries
+    /// without searchable words.
+    pub async fn search_expenses(&self, query: &SearchQuery) -> Result<Vec<SearchHit>> {
+        let Some(expression) = query.match_expression() else {
+            return Ok(Vec::new());
+        };
+
+        // bm25 column weights: category, payee, notes. Matches are delimited
+        // with markers that are turned into `<mark>` after escaping the text.
+        let rows = sqlx::query(&format!(
+            "SELECT {EXPENSE_COLUMNS}, m.score, m.category_hl, m.payee_hl, m.notes_hl \
+             FROM expenses JOIN ( \
+                 SELECT rowid, \
+                        -bm25(expenses_fts, 2.0, 4.0, 1.0) AS score, \
+                        highlight(expenses_fts, 0, ?1, ?2) AS category_hl, \
+                        highlight(expenses_fts, 1, ?1, ?2) AS payee_hl, \
+                        snippet(expenses_fts, 2, ?1, ?2, '…', 12) AS notes_hl \
+                 FROM expenses_fts WHERE expenses_fts MATCH ?3 \
+             ) m ON m.rowid = expenses.rowid \
+             WHERE deleted_at IS NULL \
+             ORDER BY m.score DESC, date DESC LIMIT ?4"
+        ))
+        .bind(MATCH_START.to_string())
+        .bind(MATCH_END.to_string())
+        .bind(expression)
+        .bind(query.limit())
+        .fetch_all(self.sqlite()?)
         .await?;
 
-        if let Some(row) = rows {
-            let expense = Expense {
-                id: uuid::Uuid::parse_str(&row.get::<String, _>("id")).unwrap(),
-                amount: row.get("amount"),
-                category: row.get("category"),
-                date: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("date"))
-                    .unwrap()
-                    .with_timezone(&chrono::Utc),
+        let non_empty = |value: Option<String>| {
+            value
+                .filter(|v| !v.is_empty())
+                .map(|v| search::marked_html(&v))
+        };
+        rows.into_iter()
+            .map(|row| {
+                let score = row.get("score");
+                let highlights = SearchHighlights {
+                    category: non_empty(row.get("category_hl")),
+                    payee: non_empty(row.get("payee_hl")),
+                    notes: non_empty(row.get("notes_hl")),
+                };
+                Ok(SearchHit {
+                    expense: row_to_expense(row)?,
+                    score,
+                    highlights,
+                })
+            })
+            .collect()
+    }
+
+    pub async fn get_expense(&self, id: Uuid) -> Result<Option<Expense>> {
+        let expense = self.repository.find(id).await?;
+        Ok(expense.filter(|e| e.deleted_at.is_none()))
+    }
+
+    /// Applies `request` only if the stored expense is still at
+    /// `expected_version`, so concurrent editors cannot overwrite each other.
+    pub async fn update_expense(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expected_version: Option<i64>,
+        request: UpdateExpenseRequest,
+    ) -> Result<VersionedUpdate> {
+        self.repository
+            .update(ctx, id, expected_version, request)
+            .await
+    }
+
+    /// Moves an expense to the trash, optionally only if it is still at
+    /// `expected_version`. Trashed expenses count as not found.
+    pub async fn delete_expense(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<VersionedUpdate> {
+        self.repository.soft_delete(ctx, id, expected_version).await
+    }
+
+    pub async fn get_trash(&self) -> Result<Vec<Expense>> {
+        self.repository.trash().await
+    }
+
+    /// Takes an expense back out of the trash. Returns `None` if it is not
+    /// in the trash.
+    pub async fn restore_expense(&self, ctx: &RequestContext, id: Uuid) -> Result<Option<Expense>> {
+        self.repository.restore(ctx, id).await
+    }
+
+    /// Permanently removes expenses that were trashed before `cutoff`.
+    /// Returns the number of purged expenses.
+    pub async fn purge_deleted_before(
+        &self,
+        ctx: &RequestContext,
+        cutoff: DateTime<Utc>,
+    ) -> Result<u64> {
+        self.repository.purge_deleted_before(ctx, cutoff).await
+    }
+
+    /// Runs a list of create/update/delete operations in one transaction.
+    ///
+    /// In [`BatchMode::Atomic`] the first failing item rolls back the whole
+    /// batch; in [`BatchMode::BestEffort`] each item runs in its own savepoint
+    /// so failed items are rolled back individually and the rest is committed.
+    pub async fn run_batch(
+        &self,
+        ctx: &RequestContext,
+        request: BatchRequest,
+    ) -> Result<BatchResponse> {
+        let mode = request.mode;
+        let pool = self.sqlite()?;
+        let rules = {
+            let mut conn = pool.acquire().await?;
+            rule_service::enabled_rules(&mut conn).await?
+        };
+        let operations = request.parse_operations(&rules);
+
+        if mode == BatchMode::Atomic && operations.iter().any(Result::is_err) {
+            let results = operations
+                .into_iter()
+                .enumerate()
+                .map(|(index, operation)| match operation {
+                    Ok(_) => BatchItemResult::not_executed(index),
+                    Err(error) => BatchItemResult::failed(index, error),
+                })
+                .collect();
+            return Ok(BatchResponse {
+                mode,
+                committed: false,
+                results,
+            });
+        }
+
+        let mut tx = pool.begin().await?;
+        let mut results = Vec::with_capacity(operations.len());
+        let mut failed = false;
+
+        for (index, operation) in operations.into_iter().enumerate() {
+            if failed {
+                results.push(BatchItemResult::not_executed(index));
+                continue;
+            }
+            let operation = match operation {
+                Ok(operation) => operation,
+                Err(error) => {
+                    results.push(BatchItemResult::failed(index, error));
+                    continue;
+                }
             };
-            Ok(Some(expense))
+
+            let mut savepoint = tx.begin().await?;
+            match apply_operation(&mut savepoint, ctx, operation).await {
+                Ok(expense) => {
+                    savepoint.commit().await?;
+                    results.push(BatchItemResult::succeeded(index, expense));
+                }
+                Err(error) => {
+                    savepoint.rollback().await?;
+                    results.push(BatchItemResult::failed(index, error));
+                    failed = mode == BatchMode::Atomic;
+                }
+            }
+        }
+
+        if failed {
+            tx.rollback().await?;
+            for result in &mut results {
+                result.roll_back();
+            }
         } else {
-            Ok(None)
+            tx.commit().await?;
+        }
+
+        Ok(BatchResponse {
+            mode,
+            committed: !failed,
+            results,
+        })
+    }
+}
+
+pub(crate) async fn insert_expense(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    request: CreateExpenseRequest,
+) -> Result<Expense> {
+    account_service::check_account(conn, request.account_id).await?;
+    let (payee, payee_id) = normalize_payee(conn, request.payee).await?;
+    let mut expense = Expense {
+        payee,
+        payee_id,
+        notes: request.notes,
+        account_id: request.account_id,
+        reimbursable: request.reimbursable,
+        ..Expense::new(request.amount, request.category)
+    };
+
+    sqlx::query(
+        "INSERT INTO expenses \
+         (id, amount, category, payee, payee_id, notes, account_id, reimbursable, date, version) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(expense.id.to_string())
+    .bind(expense.amount)
+    .bind(&expense.category)
+    .bind(&expense.payee)
+    .bind(expense.payee_id.map(|id| id.to_string()))
+    .bind(&expense.notes)
+    .bind(expense.account_id.map(|id| id.to_string()))
+    .bind(expense.reimbursable)
+    .bind(expense.date)
+    .bind(expense.version)
+    .execute(&mut *conn)
+    .await?;
+    expense.tags = set_expense_tags(conn, expense.id, &request.tags).await?;
+
+    audit_service::append(
+        conn,
+        ctx,
+        NewAuditEntry {
+            entity_type: ENTITY_EXPENSE,
+            entity_id: expense.id.to_string(),
+            action: AuditAction::Create,
+            before: None,
+            after: Some(serde_json::to_value(&expense)?),
+        },
+    )
+    .await?;
+
+    Ok(expense)
+}
+
+pub(crate) async fn update_expense(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    id: Uuid,
+    expected_version: Option<i64>,
+    request: UpdateExpenseRequest,
+) -> Result<VersionedUpdate> {
+    let Some(before) = fetch_expense(conn, id).await? else {
+        return Ok(VersionedUpdate::NotFound);
+    };
+    if before.deleted_at.is_some() {
+        return Ok(VersionedUpdate::NotFound);
+    }
+    if expected_version.is_some_and(|v| v != before.version) {
+        return Ok(VersionedUpdate::VersionMismatch(before));
+    }
+    expense_report_service::check_unlocked(conn, id).await?;
+
+    if request.amount < before.refunded {
+        return Err(AmountBelowRefunded {
+            refunded: before.refunded,
+        }
+        .into());
+    }
+
+    account_service::check_account(conn, request.account_id).await?;
+    let (payee, payee_id) = normalize_payee(conn, request.payee).await?;
+    let mut after = Expense {
+        amount: request.amount,
+        category: request.category,
+        payee,
+        payee_id,
+        notes: request.notes,
+        account_id: request.account_id,
+        reimbursable: request.reimbursable,
+        version: before.version + 1,
+        ..before.clone()
+    };
+
+    let updated = sqlx::query(
+        "UPDATE expenses SET amount = ?, category = ?, payee = ?, payee_id = ?, notes = ?, \
+         account_id = ?, reimbursable = ?, version = version + 1 WHERE id = ? AND version = ?",
+    )
+    .bind(after.amount)
+    .bind(&after.category)
+    .bind(&after.payee)
+    .bind(after.payee_id.map(|id| id.to_string()))
+    .bind(&after.notes)
+    .bind(after.account_id.map(|id| id.to_string()))
+    .bind(after.reimbursable)
+    .bind(id.to_string())
+    .bind(before.version)
+    .execute(&mut *conn)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Ok(VersionedUpdate::VersionMismatch(before));
+    }
+    after.tags = set_expense_tags(conn, id, &request.tags).await?;
+
+    audit_service::append(
+        conn,
+        ctx,
+        NewAuditEntry {
+            entity_type: ENTITY_EXPENSE,
+            entity_id: id.to_string(),
+            action: AuditAction::Update,
+            before: Some(serde_json::to_value(&before)?),
+            after: Some(serde_json::to_value(&after)?),
+        },
+    )
+    .await?;
+
+    Ok(VersionedUpdate::Updated(after))
+}
+
+/// Maps a raw payee onto its managed payee, if an alias matches. Unmatched
+/// payees are kept as entered.
+async fn normalize_payee(
+    conn: &mut SqliteConnection,
+    raw: Option<String>,
+) -> Result<(Option<String>, Option<Uuid>)> {
+    let Some(raw) = raw else {
+        return Ok((None, None));
+    };
+    match payee_service::resolve(conn, &raw).await? {
+        Some(resolved) => Ok((Some(resolved.name), Some(resolved.payee_id))),
+        None => Ok((Some(raw), None)),
+    }
+}
+
+/// Points every expense linked to payee `from` at `to`, or unlinks them
+/// (keeping the payee text) when `to` is `None`. Each changed expense gets
+/// a new version and an audit entry. Returns the number of expenses changed.
+pub(crate) async fn reassign_payee(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    from: Uuid,
+    to: Option<&ResolvedPayee>,
+) -> Result<u64> {
+    let rows = sqlx::query(&format!(
+        "SELECT {EXPENSE_COLUMNS} FROM expenses WHERE payee_id = ?"
+    ))
+    .bind(from.to_string())
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let linked: Vec<Expense> = rows
+        .into_iter()
+        .map(row_to_expense)
+        .collect::<Result<_>>()?;
+    for before in &linked {
+        let after = Expense {
+            payee: to.map(|p| p.name.clone()).or_else(|| before.payee.clone()),
+            payee_id: to.map(|p| p.payee_id),
+            version: before.version + 1,
+            ..before.clone()
+        };
+
+        sqlx::query(
+            "UPDATE expenses SET payee = ?, payee_id = ?, version = version + 1 WHERE id = ?",
+        )
+        .bind(&after.payee)
+        .bind(after.payee_id.map(|id| id.to_string()))
+        .bind(before.id.to_string())
+        .execute(&mut *conn)
+        .await?;
+
+        audit_service::append(
+            conn,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_EXPENSE,
+                entity_id: before.id.to_string(),
+                action: AuditAction::Update,
+                before: Some(serde_json::to_value(before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+    }
+
+    Ok(linked.len() as u64)
+}
+
+/// Replaces the tags of an expense, creating tags that do not exist yet.
+/// Returns the stored tag names in display order.
+async fn set_expense_tags(
+    conn: &mut SqliteConnection,
+    expense_id: Uuid,
+    tags: &[String],
+) -> Result<Vec<String>> {
+    sqlx::query("DELETE FROM expense_tags WHERE expense_id = ?")
+        .bind(expense_id.to_string())
+        .execute(&mut *conn)
+        .await?;
+
+    let mut names = Vec::new();
+    for tag in normalize_tags(tags) {
+        sqlx::query(
+            "INSERT INTO tags (id, name, created_at) VALUES (?, ?, ?) \
+             ON CONFLICT (name) DO NOTHING",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&tag)
+        .bind(Utc::now())
+        .execute(&mut *conn)
+        .await?;
+
+        // Existing tags keep their spelling.
+        let row = sqlx::query("SELECT id, name FROM tags WHERE name = ?")
+            .bind(&tag)
+            .fetch_one(&mut *conn)
+            .await?;
+        sqlx::query("INSERT INTO expense_tags (expense_id, tag_id) VALUES (?, ?)")
+            .bind(expense_id.to_string())
+            .bind(row.get::<String, _>("id"))
+            .execute(&mut *conn)
+            .await?;
+        names.push(row.get::<String, _>("name"));
+    }
+
+    names.sort_by_key(|name| name.to_lowercase());
+    Ok(names)
+}
+
+/// Records a change made to rows related to the given expenses (such as
+/// their tags): each gets a new version and an audit entry from its state
+/// in `before` to its current state.
+pub(crate) async fn record_related_change(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    before: &[Expense],
+) -> Result<()> {
+    for before in before {
+        sqlx::query("UPDATE expenses SET version = version + 1 WHERE id = ?")
+            .bind(before.id.to_string())
+            .execute(&mut *conn)
+            .await?;
+        let Some(after) = fetch_expense(conn, before.id).await? else {
+            continue;
+        };
+
+        audit_service::append(
+            conn,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_EXPENSE,
+                entity_id: before.id.to_string(),
+                action: AuditAction::Update,
+                before: Some(serde_json::to_value(before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn soft_delete_expense(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    id: Uuid,
+    expected_version: Option<i64>,
+) -> Result<VersionedUpdate> {
+    let Some(before) = fetch_expense(conn, id).await? else {
+        return Ok(VersionedUpdate::NotFound);
+    };
+    if before.deleted_at.is_some() {
+        return Ok(VersionedUpdate::NotFound);
+    }
+    if expected_version.is_some_and(|v| v != before.version) {
+        return Ok(VersionedUpdate::VersionMismatch(before));
+    }
+    expense_report_service::check_unlocked(conn, id).await?;
+
+    let mut after = before.clone();
+    after.deleted_at = Some(Utc::now());
+    after.version += 1;
+
+    sqlx::query("UPDATE expenses SET deleted_at = ?, version = version + 1 WHERE id = ?")
+        .bind(after.deleted_at)
+        .bind(id.to_string())
+        .execute(&mut *conn)
+        .await?;
+
+    audit_service::append(
+        conn,
+        ctx,
+        NewAuditEntry {
+            entity_type: ENTITY_EXPENSE,
+            entity_id: id.to_string(),
+            action: AuditAction::Delete,
+            before: Some(serde_json::to_value(&before)?),
+            after: Some(serde_json::to_value(&after)?),
+        },
+    )
+    .await?;
+
+    Ok(VersionedUpdate::Updated(after))
+}
+
+async fn apply_operation(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    operation: BatchOperation,
+) -> Result<Expense, BatchItemError> {
+    let outcome = match operation {
+        BatchOperation::Create(request) => {
+            return Ok(insert_expense(conn, ctx, request).await?);
+        }
+        BatchOperation::Update {
+            id,
+            version,
+            changes,
+        } => update_expense(conn, ctx, id, version, changes).await?,
+        BatchOperation::Delete { id, version } => {
+            soft_delete_expense(conn, ctx, id, version).await?
+        }
+    };
+
+    match outcome {
+        VersionedUpdate::Updated(expense) => Ok(expense),
+        VersionedUpdate::NotFound => Err(BatchItemError::NotFound),
+        VersionedUpdate::VersionMismatch(current) => Err(BatchItemError::VersionConflict {
+            current_version: current.version,
+        }),
+    }
+}
+
+/// Tags are collected into a JSON array per row so expenses can still be
+/// read with a single query.
+pub(crate) const EXPENSE_COLUMNS: &str = "id, amount, category, payee, payee_id, notes, account_id, \
+     reimbursable, date, deleted_at, version, (SELECT json_group_array(name) FROM ( \
+         SELECT t.name FROM expense_tags et JOIN tags t ON t.id = et.tag_id \
+         WHERE et.expense_id = expenses.id ORDER BY t.name COLLATE NOCASE \
+     )) AS tags, \
+     (SELECT COALESCE(SUM(r.amount), 0.0) FROM refunds r \
+      WHERE r.expense_id = expenses.id) AS refunded";
+
+/// An expense's amount net of its refunds, for aggregates over `expenses`.
+pub(crate) const NET_AMOUNT: &str = "(expenses.amount - (SELECT COALESCE(SUM(r.amount), 0.0) \
+     FROM refunds r WHERE r.expense_id = expenses.id))";
+
+/// Appends `filter` as `AND ...` conditions on the `expenses` table.
+pub(crate) fn push_filter(query: &mut QueryBuilder<Sqlite>, filter: &ExpenseFilter) {
+    const HAS_TAG: &str = "EXISTS (SELECT 1 FROM expense_tags et JOIN tags t ON t.id = et.tag_id \
+         WHERE et.expense_id = expenses.id AND t.name IN (";
+
+    let mut push_tags = |prefix: &str, tags: &[String]| {
+        query.push(prefix).push(HAS_TAG);
+        let mut separated = query.separated(", ");
+        for tag in tags {
+            separated.push_bind(tag.clone());
         }
+        query.push("))");
+    };
+
+    let any = filter.any_tags();
+    if !any.is_empty() {
+        push_tags(" AND ", &any);
+    }
+    for tag in filter.all_tags() {
+        push_tags(" AND ", std::slice::from_ref(&tag));
+    }
+    let none = filter.no_tags();
+    if !none.is_empty() {
+        push_tags(" AND NOT ", &none);
     }
+
+    if let Some(account_id) = filter.account_id {
+        query
+            .push(" AND expenses.account_id = ")
+            .push_bind(account_id.to_string());
+    }
+    if let Some(from) = filter.from {
+        query.push(" AND date >= ").push_bind(from);
+    }
+    if let Some(to) = filter.to {
+        query.push(" AND date < ").push_bind(to);
+    }
+}
+
+/// Non-deleted expenses, newest first.
+pub(crate) async fn fetch_active_expenses(conn: &mut SqliteConnection) -> Result<Vec<Expense>> {
+    let rows = sqlx::query(&format!(
+        "SELECT {EXPENSE_COLUMNS} FROM expenses WHERE deleted_at IS NULL ORDER BY date DESC"
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+
+    rows.into_iter().map(row_to_expense).collect()
+}
+
+/// Non-deleted expenses in an expense report, oldest first.
+pub(crate) async fn fetch_report_expenses(
+    conn: &mut SqliteConnection,
+    report_id: Uuid,
+) -> Result<Vec<Expense>> {
+    let rows = sqlx::query(&format!(
+        "SELECT {EXPENSE_COLUMNS} FROM expenses WHERE deleted_at IS NULL AND id IN \
+         (SELECT expense_id FROM expense_report_items WHERE report_id = ?) \
+         ORDER BY date, id"
+    ))
+    .bind(report_id.to_string())
+    .fetch_all(&mut *conn)
+    .await?;
+
+    rows.into_iter().map(row_to_expense).collect()
+}
+
+pub(crate) async fn fetch_expense(
+    conn: &mut SqliteConnection,
+    id: Uuid,
+) -> Result<Option<Expense>> {
+    let row = sqlx::query(&format!(
+        "SELECT {EXPENSE_COLUMNS} FROM expenses WHERE id = ?"
+    ))
+    .bind(id.to_string())
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    row.map(row_to_expense).transpose()
+}
+
+/// The columns of [`EXPENSE_COLUMNS`] as stored. Ids are UUID text and
+/// timestamps are anything SQLite or sqlx write, e.g. RFC 3339 or
+/// `YYYY-MM-DD HH:MM:SS`.
+#[derive(sqlx::FromRow)]
+pub(crate) struct ExpenseRow {
+    id: Hyphenated,
+    amount: f64,
+    category: String,
+    payee: Option<String>,
+    payee_id: Option<Hyphenated>,
+    notes: Option<String>,
+    account_id: Option<Hyphenated>,
+    reimbursable: bool,
+    tags: Json<Vec<String>>,
+    refunded: f64,
+    date: DateTime<Utc>,
+    deleted_at: Option<DateTime<Utc>>,
+    version: i64,
+}
+
+impl From<ExpenseRow> for Expense {
+    fn from(row: ExpenseRow) -> Self {
+        Expense {
+            id: row.id.into_uuid(),
+            amount: row.amount,
+            category: row.category,
+            payee: row.payee,
+            payee_id: row.payee_id.map(Hyphenated::into_uuid),
+            notes: row.notes,
+            account_id: row.account_id.map(Hyphenated::into_uuid),
+            reimbursable: row.reimbursable,
+            tags: row.tags.0,
+            refunded: row.refunded,
+            date: row.date,
+            deleted_at: row.deleted_at,
+            version: row.version,
+        }
+    }
+}
+
+/// Fails with a [`CorruptRow`] rather than panicking on a malformed row.
+pub(crate) fn row_to_expense(row: SqliteRow) -> Result<Expense> {
+    let row: ExpenseRow = decode_row("expenses", &row)?;
+    Ok(row.into())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::batch::BatchItemStatus;
     use crate::models::expense::CreateExpenseRequest;
+    use serde_json::json;
     use sqlx::SqlitePool;
 
-    async fn create_test_pool() -> SqlitePool {
-        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
-
-        sqlx::query(
-            r#"
-                CREATE TABLE expenses (
-                    id TEXT PRIMARY KEY,
-                    amount REAL NOT NULL,
-                    category TEXT NOT NULL,
-                    date TEXT NOT NULL
-                )
-            "#,
-        )
-        .execute(&pool)
-        .await
-        .unwrap();
+    fn test_ctx() -> RequestContext {
+        RequestContext::new("tester", "test-request")
+    }
 
-        pool
+    async fn create_test_pool() -> SqlitePool {
+        crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap()
     }
 
     #[tokio::test]
@@ -104,9 +852,10 @@ mod tests {
         let request = CreateExpenseRequest {
             amount: 25.50,
             category: "Groceries".to_string(),
+            ..Default::default()
         };
 
-        let expense = service.add_expense(request).await.unwrap();
+        let expense = service.add_expense(&test_ctx(), request).await.unwrap();
 
         assert_eq!(expense.amount, 25.50);
         assert_eq!(expense.category, "Groceries");
@@ -121,16 +870,21 @@ mod tests {
         let request1 = CreateExpenseRequest {
             amount: 15.50,
             category: "Transport".to_string(),
+            ..Default::default()
         };
         let request2 = CreateExpenseRequest {
             amount: 25.50,
             category: "Groceries".to_string(),
+            ..Default::default()
         };
 
-        service.add_expense(request1).await.unwrap();
-        service.add_expense(request2).await.unwrap();
+        service.add_expense(&test_ctx(), request1).await.unwrap();
+        service.add_expense(&test_ctx(), request2).await.unwrap();
 
-        let expense = service.get_all_expenses().await.unwrap();
+        let expense = service
+            .list_expenses(&ExpenseFilter::default())
+            .await
+            .unwrap();
 
         assert_eq!(expense.len(), 2);
         assert!(expense.iter().any(|e| e.amount == 15.50));
@@ -155,19 +909,22 @@ mod tests {
         let request1 = CreateExpenseRequest {
             amount: 15.50,
             category: "Transport".to_string(),
+            ..Default::default()
         };
         let request2 = CreateExpenseRequest {
             amount: 25.50,
             category: "Groceries".to_string(),
+            ..Default::default()
         };
         let request3 = CreateExpenseRequest {
             amount: 5.50,
             category: "Entertainment".to_string(),
+            ..Default::default()
         };
 
-        service.add_expense(request1).await.unwrap();
-        service.add_expense(request2).await.unwrap();
-        service.add_expense(request3).await.unwrap();
+        service.add_expense(&test_ctx(), request1).await.unwrap();
+        service.add_expense(&test_ctx(), request2).await.unwrap();
+        service.add_expense(&test_ctx(), request3).await.unwrap();
 
         let highest = service.get_highest_expense().await.unwrap();
 
@@ -184,9 +941,10 @@ mod tests {
         let request = CreateExpenseRequest {
             amount: 10.05,
             category: "Books".to_string(),
+            ..Default::default()
         };
 
-        service.add_expense(request).await.unwrap();
+        service.add_expense(&test_ctx(), request).await.unwrap();
 
         let highest = service.get_highest_expense().await.unwrap();
 
@@ -194,4 +952,696 @@ mod tests {
         assert_eq!(highest.as_ref().unwrap().amount, 10.05);
         assert_eq!(highest.as_ref().unwrap().category, "Books");
     }
+
+    #[tokio::test]
+    async fn test_delete_expense_moves_to_trash() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let request1 = CreateExpenseRequest {
+            amount: 15.50,
+            category: "Transport".to_string(),
+            ..Default::default()
+        };
+        let request2 = CreateExpenseRequest {
+            amount: 25.50,
+            category: "Groceries".to_string(),
+            ..Default::default()
+        };
+
+        service.add_expense(&test_ctx(), request1).await.unwrap();
+        let highest = service.add_expense(&test_ctx(), request2).await.unwrap();
+
+        let deleted = service
+            .delete_expense(&test_ctx(), highest.id, None)
+            .await
+            .unwrap();
+
+        let VersionedUpdate::Updated(deleted) = deleted else {
+            panic!("expected delete, got {:?}", deleted);
+        };
+        assert!(deleted.deleted_at.is_some());
+
+        let expenses = service
+            .list_expenses(&ExpenseFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(expenses.len(), 1);
+        assert_eq!(expenses[0].amount, 15.50);
+
+        let new_highest = service.get_highest_expense().await.unwrap();
+        assert_eq!(new_highest.unwrap().amount, 15.50);
+
+        let trash = service.get_trash().await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].id, highest.id);
+    }
+
+    #[tokio::test]
+    async fn test_delete_expense_twice_returns_none() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let request = CreateExpenseRequest {
+            amount: 10.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let expense = service.add_expense(&test_ctx(), request).await.unwrap();
+
+        assert!(matches!(
+            service
+                .delete_expense(&test_ctx(), expense.id, None)
+                .await
+                .unwrap(),
+            VersionedUpdate::Updated(_)
+        ));
+        assert!(matches!(
+            service
+                .delete_expense(&test_ctx(), expense.id, None)
+                .await
+                .unwrap(),
+            VersionedUpdate::NotFound
+        ));
+        assert!(matches!(
+            service
+                .delete_expense(&test_ctx(), Uuid::new_v4(), None)
+                .await
+                .unwrap(),
+            VersionedUpdate::NotFound
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_restore_expense() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let request = CreateExpenseRequest {
+            amount: 10.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let expense = service.add_expense(&test_ctx(), request).await.unwrap();
+
+        assert!(
+            service
+                .restore_expense(&test_ctx(), expense.id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        service
+            .delete_expense(&test_ctx(), expense.id, None)
+            .await
+            .unwrap();
+        let restored = service
+            .restore_expense(&test_ctx(), expense.id)
+            .await
+            .unwrap();
+
+        assert!(restored.unwrap().deleted_at.is_none());
+        assert_eq!(
+            service
+                .list_expenses(&ExpenseFilter::default())
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(service.get_trash().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_purge_only_removes_expired_trash() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let request1 = CreateExpenseRequest {
+            amount: 10.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let request2 = CreateExpenseRequest {
+            amount: 20.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let request3 = CreateExpenseRequest {
+            amount: 30.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let kept = service.add_expense(&test_ctx(), request1).await.unwrap();
+        let trashed = service.add_expense(&test_ctx(), request2).await.unwrap();
+        service.add_expense(&test_ctx(), request3).await.unwrap();
+        service
+            .delete_expense(&test_ctx(), trashed.id, None)
+            .await
+            .unwrap();
+
+        let purged = service
+            .purge_deleted_before(&test_ctx(), Utc::now() - chrono::Duration::days(1))
+            .await
+            .unwrap();
+        assert_eq!(purged, 0);
+
+        let purged = service
+            .purge_deleted_before(&test_ctx(), Utc::now() + chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+        assert_eq!(purged, 1);
+
+        assert!(service.get_trash().await.unwrap().is_empty());
+        let remaining = service
+            .list_expenses(&ExpenseFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(remaining.len(), 2);
+        assert!(remaining.iter().any(|e| e.id == kept.id));
+    }
+
+    #[tokio::test]
+    async fn test_update_expense_increments_version() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let request = CreateExpenseRequest {
+            amount: 10.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let expense = service.add_expense(&test_ctx(), request).await.unwrap();
+
+        let update = UpdateExpenseRequest {
+            amount: 12.0,
+            category: "Education".to_string(),
+            ..Default::default()
+        };
+        let result = service
+            .update_expense(&test_ctx(), expense.id, Some(1), update)
+            .await
+            .unwrap();
+
+        let VersionedUpdate::Updated(updated) = result else {
+            panic!("expected update, got {:?}", result);
+        };
+        assert_eq!(updated.version, 2);
+        assert_eq!(updated.amount, 12.0);
+
+        let stored = service.get_expense(expense.id).await.unwrap().unwrap();
+        assert_eq!(stored.version, 2);
+        assert_eq!(stored.category, "Education");
+    }
+
+    #[tokio::test]
+    async fn test_update_expense_rejects_stale_version() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let request = CreateExpenseRequest {
+            amount: 10.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let expense = service.add_expense(&test_ctx(), request).await.unwrap();
+
+        let first = UpdateExpenseRequest {
+            amount: 11.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        service
+            .update_expense(&test_ctx(), expense.id, Some(1), first)
+            .await
+            .unwrap();
+
+        let second = UpdateExpenseRequest {
+            amount: 99.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let result = service
+            .update_expense(&test_ctx(), expense.id, Some(1), second)
+            .await
+            .unwrap();
+
+        let VersionedUpdate::VersionMismatch(current) = result else {
+            panic!("expected version mismatch, got {:?}", result);
+        };
+        assert_eq!(current.version, 2);
+        assert_eq!(current.amount, 11.0);
+    }
+
+    #[tokio::test]
+    async fn test_update_missing_or_trashed_expense() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let request = CreateExpenseRequest {
+            amount: 10.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let expense = service.add_expense(&test_ctx(), request).await.unwrap();
+        service
+            .delete_expense(&test_ctx(), expense.id, None)
+            .await
+            .unwrap();
+
+        for id in [expense.id, Uuid::new_v4()] {
+            let update = UpdateExpenseRequest {
+                amount: 1.0,
+                category: "Books".to_string(),
+                ..Default::default()
+            };
+            let result = service
+                .update_expense(&test_ctx(), id, None, update)
+                .await
+                .unwrap();
+            assert!(matches!(result, VersionedUpdate::NotFound));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_expense_with_stale_version() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let request = CreateExpenseRequest {
+            amount: 10.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let expense = service.add_expense(&test_ctx(), request).await.unwrap();
+
+        let result = service
+            .delete_expense(&test_ctx(), expense.id, Some(7))
+            .await
+            .unwrap();
+
+        assert!(matches!(result, VersionedUpdate::VersionMismatch(_)));
+        assert_eq!(
+            service
+                .list_expenses(&ExpenseFilter::default())
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_atomic_commits_all_operations() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let request = CreateExpenseRequest {
+            amount: 10.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let existing = service.add_expense(&test_ctx(), request).await.unwrap();
+
+        let batch: BatchRequest = serde_json::from_value(json!({
+            "operations": [
+                {"op": "create", "amount": 5.0, "category": "Food"},
+                {"op": "create", "amount": 7.0, "category": "Food"},
+                {"op": "update", "id": existing.id, "version": 1, "amount": 11.0, "category": "Books"},
+            ]
+        }))
+        .unwrap();
+
+        let response = service.run_batch(&test_ctx(), batch).await.unwrap();
+
+        assert!(response.committed);
+        assert!(
+            response
+                .results
+                .iter()
+                .all(|r| r.status == BatchItemStatus::Ok)
+        );
+        let expenses = service
+            .list_expenses(&ExpenseFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(expenses.len(), 3);
+        assert!(expenses.iter().any(|e| e.amount == 11.0));
+    }
+
+    #[tokio::test]
+    async fn test_batch_atomic_rolls_back_on_failure() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let batch: BatchRequest = serde_json::from_value(json!({
+            "mode": "atomic",
+            "operations": [
+                {"op": "create", "amount": 5.0, "category": "Food"},
+                {"op": "delete", "id": Uuid::new_v4()},
+                {"op": "create", "amount": 7.0, "category": "Food"},
+            ]
+        }))
+        .unwrap();
+
+        let response = service.run_batch(&test_ctx(), batch).await.unwrap();
+
+        assert!(!response.committed);
+        let statuses: Vec<_> = response.results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                BatchItemStatus::RolledBack,
+                BatchItemStatus::Error,
+                BatchItemStatus::NotExecuted,
+            ]
+        );
+        assert_eq!(response.results[1].error, Some(BatchItemError::NotFound));
+        assert!(
+            service
+                .list_expenses(&ExpenseFilter::default())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_atomic_rejects_invalid_items_without_executing() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let batch: BatchRequest = serde_json::from_value(json!({
+            "operations": [
+                {"op": "create", "amount": 5.0, "category": "Food"},
+                {"op": "create", "amount": 0.0, "category": "Food"},
+            ]
+        }))
+        .unwrap();
+
+        let response = service.run_batch(&test_ctx(), batch).await.unwrap();
+
+        assert!(!response.committed);
+        assert_eq!(response.results[0].status, BatchItemStatus::NotExecuted);
+        assert!(matches!(
+            response.results[1].error,
+            Some(BatchItemError::Invalid { .. })
+        ));
+        assert!(
+            service
+                .list_expenses(&ExpenseFilter::default())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_best_effort_keeps_successful_items() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool.clone());
+
+        let request = CreateExpenseRequest {
+            amount: 10.0,
+            category: "Books".to_string(),
+            ..Default::default()
+        };
+        let existing = service.add_expense(&test_ctx(), request).await.unwrap();
+
+        let batch: BatchRequest = serde_json::from_value(json!({
+            "mode": "best_effort",
+            "operations": [
+                {"op": "create", "amount": 5.0, "category": "Food"},
+                {"op": "update", "id": existing.id, "version": 9, "amount": 1.0, "category": "Books"},
+                {"op": "create", "amount": -7.0, "category": "Food"},
+                {"op": "delete", "id": existing.id, "version": 1},
+            ]
+        }))
+        .unwrap();
+
+        let response = service.run_batch(&test_ctx(), batch).await.unwrap();
+
+        assert!(response.committed);
+        let statuses: Vec<_> = response.results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                BatchItemStatus::Ok,
+                BatchItemStatus::Error,
+                BatchItemStatus::Error,
+                BatchItemStatus::Ok,
+            ]
+        );
+        assert_eq!(
+            response.results[1].error,
+            Some(BatchItemError::VersionConflict { current_version: 1 })
+        );
+
+        let expenses = service
+            .list_expenses(&ExpenseFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(expenses.len(), 1);
+        assert_eq!(expenses[0].amount, 5.0);
+
+        // Failed items leave no trace in the audit log.
+        let audit_entries: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_log")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(audit_entries, 3);
+    }
+
+    async fn seed_search_data(service: &ExpenseService) -> Vec<Expense> {
+        let requests = vec![
+            CreateExpenseRequest {
+                amount: 42.0,
+                category: "Home".to_string(),
+                payee: Some("Hornbach Hardware Store".to_string()),
+                notes: Some("Screws and paint for the hallway".to_string()),
+                ..Default::default()
+            },
+            CreateExpenseRequest {
+                amount: 12.0,
+                category: "Groceries".to_string(),
+                payee: Some("Corner Store".to_string()),
+                notes: None,
+                ..Default::default()
+            },
+            CreateExpenseRequest {
+                amount: 8.0,
+                category: "Hardware".to_string(),
+                payee: None,
+                notes: Some("USB cable".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let mut expenses = Vec::new();
+        for request in requests {
+            expenses.push(service.add_expense(&test_ctx(), request).await.unwrap());
+        }
+        expenses
+    }
+
+    fn search(q: &str) -> SearchQuery {
+        SearchQuery {
+            q: q.to_string(),
+            limit: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_matches_prefixes_across_fields() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+        let expenses = seed_search_data(&service).await;
+
+        let hits = service.search_expenses(&search("hard stor")).await.unwrap();
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].expense.id, expenses[0].id);
+        assert_eq!(
+            hits[0].highlights.payee.as_deref(),
+            Some("Hornbach <mark>Hardware</mark> <mark>Store</mark>")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_ranks_payee_matches_above_notes() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+
+        let in_notes = CreateExpenseRequest {
+            amount: 5.0,
+            category: "Misc".to_string(),
+            payee: Some("Shop".to_string()),
+            notes: Some("paint brush".to_string()),
+            ..Default::default()
+        };
+        let in_payee = CreateExpenseRequest {
+            amount: 5.0,
+            category: "Misc".to_string(),
+            payee: Some("Paint Shop".to_string()),
+            notes: Some("brush".to_string()),
+            ..Default::default()
+        };
+        let in_notes = service.add_expense(&test_ctx(), in_notes).await.unwrap();
+        let in_payee = service.add_expense(&test_ctx(), in_payee).await.unwrap();
+
+        let hits = service.search_expenses(&search("paint")).await.unwrap();
+
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].expense.id, in_payee.id);
+        assert_eq!(hits[1].expense.id, in_notes.id);
+        assert!(hits[0].score > hits[1].score);
+        assert_eq!(
+            hits[1].highlights.notes.as_deref(),
+            Some("<mark>paint</mark> brush")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_follows_updates_and_deletes() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+        let expenses = seed_search_data(&service).await;
+
+        let update = UpdateExpenseRequest {
+            amount: 12.0,
+            category: "Groceries".to_string(),
+            payee: Some("Farmers Market".to_string()),
+            notes: None,
+            ..Default::default()
+        };
+        service
+            .update_expense(&test_ctx(), expenses[1].id, None, update)
+            .await
+            .unwrap();
+        service
+            .delete_expense(&test_ctx(), expenses[0].id, None)
+            .await
+            .unwrap();
+
+        assert!(
+            service
+                .search_expenses(&search("store"))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let hits = service.search_expenses(&search("farm")).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].expense.id, expenses[1].id);
+
+        // The index matches the expenses it is keyed to by rowid.
+        sqlx::query("INSERT INTO expenses_fts (expenses_fts, rank) VALUES ('integrity-check', 1)")
+            .execute(service.sqlite().unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_search_highlights_escape_stored_markup() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+        let request = CreateExpenseRequest {
+            amount: 5.0,
+            category: "Misc".to_string(),
+            notes: Some("<script>alert(1)</script> paint".to_string()),
+            ..Default::default()
+        };
+        service.add_expense(&test_ctx(), request).await.unwrap();
+
+        let hits = service.search_expenses(&search("paint")).await.unwrap();
+
+        assert_eq!(
+            hits[0].highlights.notes.as_deref(),
+            Some("&lt;script&gt;alert(1)&lt;/script&gt; <mark>paint</mark>")
+        );
+    }
+
+    async fn add_tagged(service: &ExpenseService, amount: f64, category: &str, tags: &[&str]) {
+        let request = CreateExpenseRequest {
+            amount,
+            category: category.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        service.add_expense(&test_ctx(), request).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tags_are_normalized_and_filterable() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+        add_tagged(&service, 10.0, "Food", &["Work", "trip", " work "]).await;
+        add_tagged(&service, 20.0, "Travel", &["trip"]).await;
+        add_tagged(&service, 40.0, "Food", &[]).await;
+
+        let filter = |any: Option<&str>, all: Option<&str>, none: Option<&str>| ExpenseFilter {
+            tags_any: any.map(str::to_string),
+            tags_all: all.map(str::to_string),
+            tags_none: none.map(str::to_string),
+            ..Default::default()
+        };
+        let amounts = |expenses: Vec<Expense>| {
+            let mut amounts: Vec<f64> = expenses.iter().map(|e| e.amount).collect();
+            amounts.sort_by(f64::total_cmp);
+            amounts
+        };
+
+        let tagged = service
+            .list_expenses(&filter(Some("WORK"), None, None))
+            .await
+            .unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].tags, vec!["trip", "Work"]);
+
+        let any = service
+            .list_expenses(&filter(Some("work,trip"), None, None))
+            .await
+            .unwrap();
+        assert_eq!(amounts(any), vec![10.0, 20.0]);
+        let all = service
+            .list_expenses(&filter(None, Some("work,trip"), None))
+            .await
+            .unwrap();
+        assert_eq!(amounts(all), vec![10.0]);
+        let none = service
+            .list_expenses(&filter(None, None, Some("work")))
+            .await
+            .unwrap();
+        assert_eq!(amounts(none), vec![20.0, 40.0]);
+    }
+
+    #[tokio::test]
+    async fn test_summarize_totals_by_category_and_tag() {
+        let pool = create_test_pool().await;
+        let service = ExpenseService::new(pool);
+        add_tagged(&service, 10.0, "Food", &["work", "trip"]).await;
+        add_tagged(&service, 20.0, "Travel", &["trip"]).await;
+        add_tagged(&service, 40.0, "Food", &[]).await;
+
+        let summary = service.summarize(&ExpenseFilter::default()).await.unwrap();
+        assert_eq!(summary.expense_count, 3);
+        assert_eq!(summary.total, 70.0);
+        assert_eq!(summary.by_category[0].category, "Food");
+        assert_eq!(summary.by_category[0].total, 50.0);
+        let by_tag: Vec<(&str, f64)> = summary
+            .by_tag
+            .iter()
+            .map(|t| (t.tag.as_str(), t.total))
+            .collect();
+        assert_eq!(by_tag, vec![("trip", 30.0), ("work", 10.0)]);
+
+        let filter = ExpenseFilter {
+            tags_none: Some("trip".to_string()),
+            ..Default::default()
+        };
+        let summary = service.summarize(&filter).await.unwrap();
+        assert_eq!(summary.total, 40.0);
+        assert!(summary.by_tag.is_empty());
+    }
 }
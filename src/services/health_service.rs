@@ -0,0 +1,94 @@
+use crate::database::DatabasePool;
+use crate::models::health::{DatabaseCheck, MigrationCheck, Readiness};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Clone)]
+pub struct HealthService {
+    pool: DatabasePool,
+    shutdown: CancellationToken,
+}
+
+impl HealthService {
+    pub fn new(pool: impl Into<DatabasePool>, shutdown: CancellationToken) -> Self {
+        Self {
+            pool: pool.into(),
+            shutdown,
+        }
+    }
+
+    /// Checks that a connection can be used and that every migration this
+    /// build knows about has been applied. Never fails; problems are
+    /// reported in the result.
+    pub async fn readiness(&self) -> Readiness {
+        let database = match self.pool.ping().await {
+            Ok(_) => DatabaseCheck {
+                ok: true,
+                error: None,
+            },
+            Err(e) => DatabaseCheck {
+                ok: false,
+                error: Some(e.to_string()),
+            },
+        };
+
+        let expected = self.pool.latest_migration_version();
+        let applied = if database.ok {
+            self.pool.applied_migration_version().await.ok().flatten()
+        } else {
+            None
+        };
+        let migrations = MigrationCheck {
+            ok: applied == Some(expected),
+            applied,
+            expected,
+        };
+
+        let shutting_down = self.shutdown.is_cancelled();
+        Readiness {
+            ready: database.ok && migrations.ok && !shutting_down,
+            database,
+            migrations,
+            shutting_down,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+
+    #[tokio::test]
+    async fn test_readiness() {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        let shutdown = CancellationToken::new();
+        let service = HealthService::new(pool.clone(), shutdown.clone());
+
+        let readiness = service.readiness().await;
+        assert!(readiness.ready);
+        assert_eq!(
+            readiness.migrations.applied,
+            Some(database::latest_migration_version())
+        );
+
+        sqlx::query("DELETE FROM schema_migrations WHERE version = ?")
+            .bind(database::latest_migration_version())
+            .execute(&pool)
+            .await
+            .unwrap();
+        let readiness = service.readiness().await;
+        assert!(!readiness.ready);
+        assert!(readiness.database.ok);
+        assert!(!readiness.migrations.ok);
+
+        shutdown.cancel();
+        pool.close().await;
+        let readiness = service.readiness().await;
+        assert!(!readiness.ready);
+        assert!(readiness.shutting_down);
+        assert!(!readiness.database.ok);
+        assert_eq!(readiness.migrations.applied, None);
+    }
+}
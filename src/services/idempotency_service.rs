@@ -0,0 +1,514 @@
+use crate::database::DatabasePool;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, SqlitePool};
+use std::time::Duration;
+
+/// A claim that has not completed within this time is considered abandoned
+/// (e.g. the server restarted mid-request) and may be taken over.
+const IN_PROGRESS_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Bodies buffered to hash and store them, unless a route accepts more.
+const DEFAULT_MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Identifies one idempotent operation: the client's key, scoped to the
+/// caller and the endpoint it was sent to, so clients that happen to pick the
+/// same key never see each other's responses.
+#[derive(Debug, Clone)]
+pub struct KeyScope {
+    pub actor: String,
+    pub key: String,
+    pub method: String,
+    pub path: String,
+}
+
+/// The first response produced for a key, replayed for later retries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Claim {
+    /// First use of the key; the caller must run the request and then
+    /// `complete` or `release` the key.
+    Acquired,
+    Replay(StoredResponse),
+    /// The key was used before with a different request.
+    Mismatch,
+    /// Another request with this key is still being processed.
+    InProgress,
+}
+
+/// What is stored for a key that was claimed before.
+#[derive(sqlx::FromRow)]
+struct KeyRow {
+    request_hash: String,
+    status: Option<i64>,
+    response_headers: Option<String>,
+    response_body: Option<Vec<u8>>,
+    /// Claimed longer than [`IN_PROGRESS_TIMEOUT`] ago.
+    abandoned: bool,
+}
+
+impl KeyRow {
+    /// The claim for a repeated key; `None` when the earlier request was
+    /// abandoned and the key may be taken over.
+    fn into_claim(self, request_hash: &str) -> Result<Option<Claim>> {
+        if self.request_hash != request_hash {
+            return Ok(Some(Claim::Mismatch));
+        }
+        let Some(status) = self.status else {
+            return Ok((!self.abandoned).then_some(Claim::InProgress));
+        };
+        let headers = match self.response_headers {
+            Some(json) => serde_json::from_str(&json)?,
+            None => Vec::new(),
+        };
+
+        Ok(Some(Claim::Replay(StoredResponse {
+            status: u16::try_from(status)?,
+            headers,
+            body: self.response_body.unwrap_or_default(),
+        })))
+    }
+}
+
+/// Stores keys in the server's database, SQLite or PostgreSQL.
+#[derive(Clone)]
+pub struct IdempotencyService {
+    pool: DatabasePool,
+    retention: Duration,
+    max_body_bytes: usize,
+}
+
+impl IdempotencyService {
+    pub fn new(pool: impl Into<DatabasePool>, retention: Duration) -> Self {
+        Self {
+            pool: pool.into(),
+            retention,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+        }
+    }
+
+    /// Buffers request and response bodies up to `bytes`, when that is more
+    /// than the default, so no route's accepted uploads are refused.
+    pub fn with_max_body_bytes(self, bytes: usize) -> Self {
+        Self {
+            max_body_bytes: bytes.max(DEFAULT_MAX_BODY_BYTES),
+            ..self
+        }
+    }
+
+    /// The largest request or response body the middleware buffers.
+    pub fn max_body_bytes(&self) -> usize {
+        self.max_body_bytes
+    }
+
+    /// The moment `age` ago, or the earliest one there is when `age` reaches
+    /// further back, so that nothing counts as older.
+    fn cutoff(&self, age: Duration) -> DateTime<Utc> {
+        chrono::Duration::from_std(age)
+            .ok()
+            .and_then(|age| Utc::now().checked_sub_signed(age))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+
+    pub async fn claim(&self, scope: &KeyScope, request_hash: &str) -> Result<Claim> {
+        let expired = self.cutoff(self.retention);
+        let abandoned = self.cutoff(IN_PROGRESS_TIMEOUT);
+        match &self.pool {
+            DatabasePool::Sqlite(pool) => {
+                claim_sqlite(pool, scope, request_hash, expired, abandoned).await
+            }
+            DatabasePool::Postgres(pool) => {
+                claim_postgres(pool, scope, request_hash, expired, abandoned).await
+            }
+        }
+    }
+
+    /// Stores the response of an acquired key so retries replay it.
+    pub async fn complete(&self, scope: &KeyScope, response: &StoredResponse) -> Result<()> {
+        let status = i64::from(response.status);
+        let headers = serde_json::to_string(&response.headers)?;
+        match &self.pool {
+            DatabasePool::Sqlite(pool) => {
+                sqlx::query(
+                    "UPDATE idempotency_keys \
+                     SET status = ?, response_headers = ?, response_body = ? \
+                     WHERE actor = ? AND key = ? AND method = ? AND path = ?",
+                )
+                .bind(status)
+                .bind(&headers)
+                .bind(&response.body)
+                .bind(&scope.actor)
+                .bind(&scope.key)
+                .bind(&scope.method)
+                .bind(&scope.path)
+                .execute(pool)
+                .await?;
+            }
+            DatabasePool::Postgres(pool) => {
+                sqlx::query(
+                    "UPDATE idempotency_keys \
+                     SET status = $1, response_headers = $2, response_body = $3 \
+                     WHERE actor = $4 AND key = $5 AND method = $6 AND path = $7",
+                )
+                .bind(status)
+                .bind(&headers)
+                .bind(&response.body)
+                .bind(&scope.actor)
+                .bind(&scope.key)
+                .bind(&scope.method)
+                .bind(&scope.path)
+                .execute(pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gives up an acquired key without storing a response, so the client
+    /// can retry it (used when the request failed on the server side).
+    pub async fn release(&self, scope: &KeyScope) -> Result<()> {
+        match &self.pool {
+            DatabasePool::Sqlite(pool) => {
+                sqlx::query(
+                    "DELETE FROM idempotency_keys \
+                     WHERE actor = ? AND key = ? AND method = ? AND path = ? \
+                     AND status IS NULL",
+                )
+                .bind(&scope.actor)
+                .bind(&scope.key)
+                .bind(&scope.method)
+                .bind(&scope.path)
+                .execute(pool)
+                .await?;
+            }
+            DatabasePool::Postgres(pool) => {
+                sqlx::query(
+                    "DELETE FROM idempotency_keys \
+                     WHERE actor = $1 AND key = $2 AND method = $3 AND path = $4 \
+                     AND status IS NULL",
+                )
+                .bind(&scope.actor)
+                .bind(&scope.key)
+                .bind(&scope.method)
+                .bind(&scope.path)
+                .execute(pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes keys older than the retention window. Returns how many were
+    /// removed.
+    pub async fn purge_expired(&self) -> Result<u64> {
+        let cutoff = self.cutoff(self.retention);
+        let result = match &self.pool {
+            DatabasePool::Sqlite(pool) => sqlx::query(
+                "DELETE FROM idempotency_keys WHERE julianday(created_at) < julianday(?)",
+            )
+            .bind(cutoff)
+            .execute(pool)
+            .await?
+            .rows_affected(),
+            DatabasePool::Postgres(pool) => {
+                sqlx::query("DELETE FROM idempotency_keys WHERE created_at < $1")
+                    .bind(cutoff)
+                    .execute(pool)
+                    .await?
+                    .rows_affected()
+            }
+        };
+
+        Ok(result)
+    }
+}
+
+/// Claims a key in SQLite. Timestamps are compared with `julianday` as
+/// stored ones vary in precision.
+async fn claim_sqlite(
+    pool: &SqlitePool,
+    scope: &KeyScope,
+    request_hash: &str,
+    expired: DateTime<Utc>,
+    abandoned: DateTime<Utc>,
+) -> Result<Claim> {
+    let mut tx = pool.begin().await?;
+
+    // Keys past the retention window are forgotten and may be reused.
+    sqlx::query(
+        "DELETE FROM idempotency_keys \
+         WHERE actor = ? AND key = ? AND method = ? AND path = ? \
+         AND julianday(created_at) < julianday(?)",
+    )
+    .bind(&scope.actor)
+    .bind(&scope.key)
+    .bind(&scope.method)
+    .bind(&scope.path)
+    .bind(expired)
+    .execute(&mut *tx)
+    .await?;
+
+    let inserted = sqlx::query(
+        "INSERT INTO idempotency_keys (actor, key, method, path, request_hash, created_at) \
+         VALUES (?, ?, ?, ?, ?, ?) ON CONFLICT DO NOTHING",
+    )
+    .bind(&scope.actor)
+    .bind(&scope.key)
+    .bind(&scope.method)
+    .bind(&scope.path)
+    .bind(request_hash)
+    .bind(Utc::now())
+    .execute(&mut *tx)
+    .await?;
+
+    if inserted.rows_affected() == 1 {
+        tx.commit().await?;
+        return Ok(Claim::Acquired);
+    }
+
+    let row: KeyRow = sqlx::query_as(
+        "SELECT request_hash, status, response_headers, response_body, \
+         julianday(created_at) < julianday(?) AS abandoned \
+         FROM idempotency_keys WHERE actor = ? AND key = ? AND method = ? AND path = ?",
+    )
+    .bind(abandoned)
+    .bind(&scope.actor)
+    .bind(&scope.key)
+    .bind(&scope.method)
+    .bind(&scope.path)
+    .fetch_one(&mut *tx)
+    .await?;
+    if let Some(claim) = row.into_claim(request_hash)? {
+        return Ok(claim);
+    }
+
+    sqlx::query(
+        "UPDATE idempotency_keys SET created_at = ? \
+         WHERE actor = ? AND key = ? AND method = ? AND path = ?",
+    )
+    .bind(Utc::now())
+    .bind(&scope.actor)
+    .bind(&scope.key)
+    .bind(&scope.method)
+    .bind(&scope.path)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Claim::Acquired)
+}
+
+/// Claims a key in PostgreSQL, the same way as [`claim_sqlite`].
+async fn claim_postgres(
+    pool: &PgPool,
+    scope: &KeyScope,
+    request_hash: &str,
+    expired: DateTime<Utc>,
+    abandoned: DateTime<Utc>,
+) -> Result<Claim> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "DELETE FROM idempotency_keys \
+         WHERE actor = $1 AND key = $2 AND method = $3 AND path = $4 \
+         AND created_at < $5",
+    )
+    .bind(&scope.actor)
+    .bind(&scope.key)
+    .bind(&scope.method)
+    .bind(&scope.path)
+    .bind(expired)
+    .execute(&mut *tx)
+    .await?;
+
+    let inserted = sqlx::query(
+        "INSERT INTO idempotency_keys (actor, key, method, path, request_hash, created_at) \
+         VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING",
+    )
+    .bind(&scope.actor)
+    .bind(&scope.key)
+    .bind(&scope.method)
+    .bind(&scope.path)
+    .bind(request_hash)
+    .bind(Utc::now())
+    .execute(&mut *tx)
+    .await?;
+
+    if inserted.rows_affected() == 1 {
+        tx.commit().await?;
+        return Ok(Claim::Acquired);
+    }
+
+    // Locks the row, so only one of several servers takes over an
+    // abandoned key.
+    let row: KeyRow = sqlx::query_as(
+        "SELECT request_hash, status, response_headers, response_body, \
+         created_at < $1 AS abandoned \
+         FROM idempotency_keys \
+         WHERE actor = $2 AND key = $3 AND method = $4 AND path = $5 FOR UPDATE",
+    )
+    .bind(abandoned)
+    .bind(&scope.actor)
+    .bind(&scope.key)
+    .bind(&scope.method)
+    .bind(&scope.path)
+    .fetch_one(&mut *tx)
+    .await?;
+    if let Some(claim) = row.into_claim(request_hash)? {
+        return Ok(claim);
+    }
+
+    sqlx::query(
+        "UPDATE idempotency_keys SET created_at = $1 \
+         WHERE actor = $2 AND key = $3 AND method = $4 AND path = $5",
+    )
+    .bind(Utc::now())
+    .bind(&scope.actor)
+    .bind(&scope.key)
+    .bind(&scope.method)
+    .bind(&scope.path)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Claim::Acquired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_pool;
+
+    async fn create_service(retention: Duration) -> IdempotencyService {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+        IdempotencyService::new(pool, retention)
+    }
+
+    fn scope(key: &str) -> KeyScope {
+        KeyScope {
+            actor: "alice".to_string(),
+            key: key.to_string(),
+            method: "POST".to_string(),
+            path: "/expenses".to_string(),
+        }
+    }
+
+    fn response() -> StoredResponse {
+        StoredResponse {
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: br#"{"id":"1"}"#.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_first_claim_is_acquired_and_retry_replays() {
+        let service = create_service(Duration::from_secs(3600)).await;
+
+        assert_eq!(
+            service.claim(&scope("k1"), "hash").await.unwrap(),
+            Claim::Acquired
+        );
+        service.complete(&scope("k1"), &response()).await.unwrap();
+
+        assert_eq!(
+            service.claim(&scope("k1"), "hash").await.unwrap(),
+            Claim::Replay(response())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reuse_with_different_request_is_rejected() {
+        let service = create_service(Duration::from_secs(3600)).await;
+
+        service.claim(&scope("k1"), "hash").await.unwrap();
+        service.complete(&scope("k1"), &response()).await.unwrap();
+
+        assert_eq!(
+            service.claim(&scope("k1"), "other").await.unwrap(),
+            Claim::Mismatch
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_claim_is_in_progress_until_released() {
+        let service = create_service(Duration::from_secs(3600)).await;
+
+        service.claim(&scope("k1"), "hash").await.unwrap();
+        assert_eq!(
+            service.claim(&scope("k1"), "hash").await.unwrap(),
+            Claim::InProgress
+        );
+
+        service.release(&scope("k1")).await.unwrap();
+        assert_eq!(
+            service.claim(&scope("k1"), "hash").await.unwrap(),
+            Claim::Acquired
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_scoped_to_endpoint() {
+        let service = create_service(Duration::from_secs(3600)).await;
+        let other_path = KeyScope {
+            path: "/expenses/batch".to_string(),
+            ..scope("k1")
+        };
+
+        service.claim(&scope("k1"), "hash").await.unwrap();
+
+        assert_eq!(
+            service.claim(&other_path, "other").await.unwrap(),
+            Claim::Acquired
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_scoped_to_actor() {
+        let service = create_service(Duration::from_secs(3600)).await;
+        let bob = KeyScope {
+            actor: "bob".to_string(),
+            ..scope("k1")
+        };
+
+        service.claim(&scope("k1"), "hash").await.unwrap();
+        service.complete(&scope("k1"), &response()).await.unwrap();
+
+        assert_eq!(service.claim(&bob, "hash").await.unwrap(), Claim::Acquired);
+    }
+
+    #[tokio::test]
+    async fn test_retention_beyond_any_date_keeps_keys() {
+        let service = create_service(Duration::MAX).await;
+
+        service.claim(&scope("k1"), "hash").await.unwrap();
+        service.complete(&scope("k1"), &response()).await.unwrap();
+
+        assert_eq!(
+            service.claim(&scope("k1"), "hash").await.unwrap(),
+            Claim::Replay(response())
+        );
+        assert_eq!(service.purge_expired().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_expired_keys_are_forgotten() {
+        let service = create_service(Duration::ZERO).await;
+
+        service.claim(&scope("k1"), "hash").await.unwrap();
+        service.complete(&scope("k1"), &response()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert_eq!(
+            service.claim(&scope("k1"), "other").await.unwrap(),
+            Claim::Acquired
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(service.purge_expired().await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,406 @@
+use crate::context::RequestContext;
+use crate::models::audit::AuditAction;
+use crate::models::income::{Income, IncomeQuery, IncomeRequest};
+use crate::services::account_service;
+use crate::services::audit_service::{self, ENTITY_INCOME, NewAuditEntry};
+use crate::services::integrity_service::decode_row;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
+use uuid::Uuid;
+use uuid::fmt::Hyphenated;
+
+#[derive(Clone)]
+pub struct IncomeService {
+    pool: SqlitePool,
+}
+
+impl IncomeService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn add_income(&self, ctx: &RequestContext, request: IncomeRequest) -> Result<Income> {
+        let mut tx = self.pool.begin().await?;
+        account_service::check_account(&mut tx, request.account_id).await?;
+
+        let now = Utc::now();
+        let income = Income {
+            id: Uuid::new_v4(),
+            amount: request.amount,
+            category: request.category,
+            source: request.source,
+            notes: request.notes,
+            account_id: request.account_id,
+            date: request.date.unwrap_or(now),
+            created_at: now,
+        };
+        sqlx::query(
+            "INSERT INTO income \
+             (id, amount, category, source, notes, account_id, date, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(income.id.to_string())
+        .bind(income.amount)
+        .bind(&income.category)
+        .bind(&income.source)
+        .bind(&income.notes)
+        .bind(income.account_id.map(|id| id.to_string()))
+        .bind(income.date)
+        .bind(income.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_INCOME,
+                entity_id: income.id.to_string(),
+                action: AuditAction::Create,
+                before: None,
+                after: Some(serde_json::to_value(&income)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(income)
+    }
+
+    /// Income matching `query`, newest first.
+    pub async fn list_income(&self, query: &IncomeQuery) -> Result<Vec<Income>> {
+        let mut builder = QueryBuilder::<Sqlite>::new("SELECT * FROM income WHERE 1 = 1");
+        if let Some(category) = &query.category {
+            builder
+                .push(" AND category = ")
+                .push_bind(category.clone())
+                .push(" COLLATE NOCASE");
+        }
+        if let Some(account_id) = query.account_id {
+            builder
+                .push(" AND account_id = ")
+                .push_bind(account_id.to_string());
+        }
+        if let Some(from) = query.from {
+            builder.push(" AND date >= ").push_bind(from);
+        }
+        if let Some(to) = query.to {
+            builder.push(" AND date < ").push_bind(to);
+        }
+        builder.push(" ORDER BY date DESC, id");
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        rows.into_iter().map(row_to_income).collect()
+    }
+
+    pub async fn get_income(&self, id: Uuid) -> Result<Option<Income>> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_income(&mut conn, id).await
+    }
+
+    pub async fn update_income(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        request: IncomeRequest,
+    ) -> Result<Option<Income>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(before) = fetch_income(&mut tx, id).await? else {
+            return Ok(None);
+        };
+        account_service::check_account(&mut tx, request.account_id).await?;
+
+        let after = Income {
+            amount: request.amount,
+            category: request.category,
+            source: request.source,
+            notes: request.notes,
+            account_id: request.account_id,
+            date: request.date.unwrap_or(before.date),
+            ..before.clone()
+        };
+        sqlx::query(
+            "UPDATE income SET amount = ?, category = ?, source = ?, notes = ?, \
+             account_id = ?, date = ? WHERE id = ?",
+        )
+        .bind(after.amount)
+        .bind(&after.category)
+        .bind(&after.source)
+        .bind(&after.notes)
+        .bind(after.account_id.map(|id| id.to_string()))
+        .bind(after.date)
+        .bind(id.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_INCOME,
+                entity_id: id.to_string(),
+                action: AuditAction::Update,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(after))
+    }
+
+    pub async fn delete_income(&self, ctx: &RequestContext, id: Uuid) -> Result<Option<Income>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(income) = fetch_income(&mut tx, id).await? else {
+            return Ok(None);
+        };
+        sqlx::query("DELETE FROM income WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_INCOME,
+                entity_id: id.to_string(),
+                action: AuditAction::Delete,
+                before: Some(serde_json::to_value(&income)?),
+                after: None,
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(income))
+    }
+}
+
+async fn fetch_income(conn: &mut SqliteConnection, id: Uuid) -> Result<Option<Income>> {
+    let row = sqlx::query("SELECT * FROM income WHERE id = ?")
+        .bind(id.to_string())
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    row.map(row_to_income).transpose()
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct IncomeRow {
+    id: Hyphenated,
+    amount: f64,
+    category: String,
+    source: Option<String>,
+    notes: Option<String>,
+    account_id: Option<Hyphenated>,
+    date: DateTime<Utc>,
+    created_at: DateTime<Utc>,
+}
+
+fn row_to_income(row: SqliteRow) -> Result<Income> {
+    let row: IncomeRow = decode_row("income", &row)?;
+    Ok(Income {
+        id: row.id.into_uuid(),
+        amount: row.amount,
+        category: row.category,
+        source: row.source,
+        notes: row.notes,
+        account_id: row.account_id.map(Hyphenated::into_uuid),
+        date: row.date,
+        created_at: row.created_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::account::{AccountKind, AccountRequest, StatementEntryKind, UnknownAccount};
+    use crate::models::expense::CreateExpenseRequest;
+    use crate::models::summary::{CashFlow, CashFlowQuery, ExpenseFilter, Period};
+    use crate::services::account_service::{AccountService, AccountWrite};
+    use crate::services::expense_service::ExpenseService;
+    use chrono::TimeZone;
+
+    fn test_ctx() -> RequestContext {
+        RequestContext::new("tester", "test-request")
+    }
+
+    async fn create_services() -> (IncomeService, ExpenseService) {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        (IncomeService::new(pool.clone()), ExpenseService::new(pool))
+    }
+
+    fn salary(amount: f64, date: DateTime<Utc>) -> IncomeRequest {
+        IncomeRequest {
+            amount,
+            category: "Salary".to_string(),
+            source: Some("ACME".to_string()),
+            date: Some(date),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_income_crud() {
+        let (service, _) = create_services().await;
+        let date = Utc.with_ymd_and_hms(2025, 1, 31, 9, 0, 0).unwrap();
+        let income = service
+            .add_income(&test_ctx(), salary(3000.0, date))
+            .await
+            .unwrap();
+
+        let updated = service
+            .update_income(&test_ctx(), income.id, salary(3100.0, date))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.amount, 3100.0);
+        assert_eq!(updated.date, date);
+
+        let query = IncomeQuery {
+            category: Some("salary".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(service.list_income(&query).await.unwrap().len(), 1);
+
+        service
+            .delete_income(&test_ctx(), income.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(service.get_income(income.id).await.unwrap().is_none());
+
+        let request = IncomeRequest {
+            account_id: Some(Uuid::new_v4()),
+            ..salary(1.0, date)
+        };
+        let error = service.add_income(&test_ctx(), request).await.unwrap_err();
+        assert!(error.downcast_ref::<UnknownAccount>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_income_stays_out_of_expense_queries() {
+        let (income, expenses) = create_services().await;
+        income
+            .add_income(&test_ctx(), salary(5000.0, Utc::now()))
+            .await
+            .unwrap();
+        let request = CreateExpenseRequest {
+            amount: 20.0,
+            category: "Food".to_string(),
+            ..Default::default()
+        };
+        expenses.add_expense(&test_ctx(), request).await.unwrap();
+
+        let highest = expenses.get_highest_expense().await.unwrap().unwrap();
+        assert_eq!(highest.amount, 20.0);
+        let summary = expenses.summarize(&ExpenseFilter::default()).await.unwrap();
+        assert_eq!(summary.total, 20.0);
+    }
+
+    #[tokio::test]
+    async fn test_cash_flow_per_period() {
+        let (income, expenses) = create_services().await;
+        let january = Utc.with_ymd_and_hms(2025, 1, 31, 9, 0, 0).unwrap();
+        let february = Utc.with_ymd_and_hms(2025, 2, 28, 9, 0, 0).unwrap();
+        income
+            .add_income(&test_ctx(), salary(3000.0, january))
+            .await
+            .unwrap();
+        income
+            .add_income(&test_ctx(), salary(3000.0, february))
+            .await
+            .unwrap();
+        let request = CreateExpenseRequest {
+            amount: 500.0,
+            category: "Rent".to_string(),
+            ..Default::default()
+        };
+        expenses.add_expense(&test_ctx(), request).await.unwrap();
+
+        let query = CashFlowQuery {
+            period: Period::Month,
+            from: Some(Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap()),
+            to: None,
+        };
+        let report = expenses.cash_flow(&query).await.unwrap();
+
+        assert_eq!(report.rows.len(), 2);
+        assert_eq!(report.rows[0].period, "2025-02");
+        assert_eq!(report.rows[0].flow, CashFlow::new(3000.0, 0.0));
+        assert_eq!(report.rows[1].flow, CashFlow::new(0.0, 500.0));
+        assert_eq!(report.total, CashFlow::new(3000.0, 500.0));
+        assert_eq!(report.total.net, 2500.0);
+    }
+
+    #[tokio::test]
+    async fn test_cash_flow_weeks_span_new_year() {
+        let (income, expenses) = create_services().await;
+        let monday = Utc.with_ymd_and_hms(2024, 12, 30, 9, 0, 0).unwrap();
+        let friday = Utc.with_ymd_and_hms(2025, 1, 3, 9, 0, 0).unwrap();
+        for date in [monday, friday] {
+            income
+                .add_income(&test_ctx(), salary(1500.0, date))
+                .await
+                .unwrap();
+        }
+
+        let query = CashFlowQuery {
+            period: Period::Week,
+            from: None,
+            to: Some(Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap()),
+        };
+        let report = expenses.cash_flow(&query).await.unwrap();
+
+        assert_eq!(report.rows.len(), 1);
+        assert_eq!(report.rows[0].period, "2025-W01");
+        assert_eq!(report.rows[0].flow, CashFlow::new(3000.0, 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_income_raises_account_balance() {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        let service = IncomeService::new(pool.clone());
+        let accounts = AccountService::new(pool);
+        let request = AccountRequest {
+            name: "Checking".to_string(),
+            kind: AccountKind::Checking,
+            currency: "EUR".to_string(),
+            opening_balance: 100.0,
+        };
+        let AccountWrite::Saved(account) =
+            accounts.create_account(&test_ctx(), request).await.unwrap()
+        else {
+            panic!("account not created");
+        };
+        let request = IncomeRequest {
+            account_id: Some(account.id),
+            ..salary(2000.0, Utc::now())
+        };
+        service.add_income(&test_ctx(), request).await.unwrap();
+
+        let balance = accounts.balance(account.id).await.unwrap().unwrap();
+        assert_eq!(balance.income, 2000.0);
+        assert_eq!(balance.balance, 2100.0);
+        let statement = accounts
+            .statement(account.id, &Default::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(statement.entries[0].kind, StatementEntryKind::Income);
+        assert_eq!(statement.entries[0].description, "ACME (Salary)");
+    }
+}
@@ -0,0 +1,320 @@
+use crate::models::integrity::{CorruptRow, IntegrityReport, TableCheck};
+use crate::services::account_service::{AccountRow, TransferRow};
+use crate::services::attachment_service::AttachmentRow;
+use crate::services::audit_service::AuditRow;
+use crate::services::expense_report_service::{CommentRow, ReportRow};
+use crate::services::expense_service::{EXPENSE_COLUMNS, ExpenseRow};
+use crate::services::income_service::IncomeRow;
+use crate::services::payee_service::{AliasRow, PayeeRow};
+use crate::services::refund_service::RefundRow;
+use crate::services::rule_service::RuleRow;
+use crate::services::tag_service::{TAG_COLUMNS, TagRow};
+use anyhow::Result;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, Row, SqlitePool};
+
+/// Decodes a row into its typed form, describing what is wrong with the row
+/// instead of panicking when it does not fit.
+pub(crate) fn decode_row<T>(table: &str, row: &SqliteRow) -> Result<T, CorruptRow>
+where
+    T: for<'r> FromRow<'r, SqliteRow>,
+{
+    T::from_row(row).map_err(|error| {
+        let (column, reason) = match error {
+            // The index is the column name in `Debug` form, i.e. quoted.
+            sqlx::Error::ColumnDecode { index, source } => (
+                Some(index.trim_matches('"').to_string()),
+                source.to_string(),
+            ),
+            sqlx::Error::ColumnNotFound(column) => (Some(column), "column is missing".to_string()),
+            error => (None, error.to_string()),
+        };
+        CorruptRow {
+            table: table.to_string(),
+            id: row.try_get::<String, _>("id").ok(),
+            column,
+            reason,
+        }
+    })
+}
+
+#[derive(Clone)]
+pub struct IntegrityService {
+    pool: SqlitePool,
+}
+
+impl IntegrityService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Decodes every row of the tables the API reads models from, trashed
+    /// expenses included, and lists the rows that cannot be read.
+    pub async fn check(&self) -> Result<IntegrityReport> {
+        let mut tables = Vec::new();
+        let mut corrupt_rows = Vec::new();
+
+        let expenses = format!("SELECT {EXPENSE_COLUMNS} FROM expenses ORDER BY rowid");
+        self.scan::<ExpenseRow>("expenses", &expenses, &mut tables, &mut corrupt_rows)
+            .await?;
+        self.scan::<IncomeRow>(
+            "income",
+            "SELECT * FROM income ORDER BY rowid",
+            &mut tables,
+            &mut corrupt_rows,
+        )
+        .await?;
+        self.scan::<RefundRow>(
+            "refunds",
+            "SELECT * FROM refunds ORDER BY rowid",
+            &mut tables,
+            &mut corrupt_rows,
+        )
+        .await?;
+        self.scan::<AccountRow>(
+            "accounts",
+            "SELECT * FROM accounts ORDER BY rowid",
+            &mut tables,
+            &mut corrupt_rows,
+        )
+        .await?;
+        self.scan::<TransferRow>(
+            "transfers",
+            "SELECT * FROM transfers ORDER BY rowid",
+            &mut tables,
+            &mut corrupt_rows,
+        )
+        .await?;
+        self.scan::<AttachmentRow>(
+            "attachments",
+            "SELECT * FROM attachments ORDER BY rowid",
+            &mut tables,
+            &mut corrupt_rows,
+        )
+        .await?;
+        self.scan::<PayeeRow>(
+            "payees",
+            "SELECT * FROM payees ORDER BY rowid",
+            &mut tables,
+            &mut corrupt_rows,
+        )
+        .await?;
+        self.scan::<AliasRow>(
+            "payee_aliases",
+            "SELECT * FROM payee_aliases ORDER BY rowid",
+            &mut tables,
+            &mut corrupt_rows,
+        )
+        .await?;
+        self.scan::<RuleRow>(
+            "rules",
+            "SELECT * FROM rules ORDER BY rowid",
+            &mut tables,
+            &mut corrupt_rows,
+        )
+        .await?;
+        let tags = format!("SELECT {TAG_COLUMNS} FROM tags ORDER BY rowid");
+        self.scan::<TagRow>("tags", &tags, &mut tables, &mut corrupt_rows)
+            .await?;
+        self.scan::<ReportRow>(
+            "expense_reports",
+            "SELECT * FROM expense_reports ORDER BY rowid",
+            &mut tables,
+            &mut corrupt_rows,
+        )
+        .await?;
+        self.scan::<CommentRow>(
+            "expense_report_comments",
+            "SELECT * FROM expense_report_comments ORDER BY rowid",
+            &mut tables,
+            &mut corrupt_rows,
+        )
+        .await?;
+        self.scan::<AuditRow>(
+            "audit_log",
+            "SELECT * FROM audit_log ORDER BY seq",
+            &mut tables,
+            &mut corrupt_rows,
+        )
+        .await?;
+
+        Ok(IntegrityReport {
+            ok: corrupt_rows.is_empty(),
+            tables,
+            corrupt_rows,
+        })
+    }
+
+    async fn scan<T>(
+        &self,
+        table: &str,
+        sql: &str,
+        tables: &mut Vec<TableCheck>,
+        corrupt_rows: &mut Vec<CorruptRow>,
+    ) -> Result<()>
+    where
+        T: for<'r> FromRow<'r, SqliteRow>,
+    {
+        let rows = sqlx::query(sql).fetch_all(&self.pool).await?;
+        let before = corrupt_rows.len();
+        corrupt_rows.extend(
+            rows.iter()
+                .filter_map(|row| decode_row::<T>(table, row).err()),
+        );
+        tables.push(TableCheck {
+            table: table.to_string(),
+            rows_checked: rows.len(),
+            corrupt: corrupt_rows.len() - before,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::RequestContext;
+    use crate::error::AppError;
+    use crate::models::expense::CreateExpenseRequest;
+    use crate::models::summary::ExpenseFilter;
+    use crate::services::expense_service::ExpenseService;
+    use crate::services::payee_service::PayeeService;
+    use crate::services::tag_service::TagService;
+
+    #[tokio::test]
+    async fn test_corrupt_rows_are_reported_not_panicked_on() {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        let expenses = ExpenseService::new(pool.clone());
+        let integrity = IntegrityService::new(pool.clone());
+        let ctx = RequestContext::new("tester", "test-request");
+
+        let request = CreateExpenseRequest {
+            amount: 12.5,
+            category: "Food".to_string(),
+            ..Default::default()
+        };
+        let good = expenses.add_expense(&ctx, request).await.unwrap();
+
+        // SQLite's own timestamp format is readable, not corrupt.
+        sqlx::query(
+            "INSERT INTO expenses (id, amount, category, date) \
+             VALUES ('8d5bbf61-6a5e-4a3c-9a55-6f2e2b0a6c01', 3.0, 'Food', '2024-03-01 10:00:00')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO expenses (id, amount, category, date) \
+             VALUES ('bad-id', 4.0, 'Food', 'yesterday')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let error = expenses
+            .list_expenses(&ExpenseFilter::default())
+            .await
+            .unwrap_err();
+        let corrupt = error.downcast_ref::<CorruptRow>().unwrap();
+        assert_eq!(corrupt.table, "expenses");
+        assert_eq!(corrupt.id.as_deref(), Some("bad-id"));
+        assert!(matches!(AppError::from(error), AppError::CorruptData(_)));
+        assert_eq!(
+            expenses.get_highest_expense().await.unwrap().unwrap().id,
+            good.id
+        );
+
+        let report = integrity.check().await.unwrap();
+        assert!(!report.ok);
+        assert_eq!(
+            report.tables[0],
+            TableCheck {
+                table: "expenses".to_string(),
+                rows_checked: 3,
+                corrupt: 1,
+            }
+        );
+        assert_eq!(report.corrupt_rows.len(), 1);
+        assert_eq!(report.corrupt_rows[0].id.as_deref(), Some("bad-id"));
+        assert_eq!(report.corrupt_rows[0].column.as_deref(), Some("id"));
+
+        sqlx::query("DELETE FROM expenses WHERE id = 'bad-id'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let report = integrity.check().await.unwrap();
+        assert!(report.ok);
+        assert_eq!(
+            expenses
+                .list_expenses(&ExpenseFilter::default())
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_every_model_table_is_checked() {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        let integrity = IntegrityService::new(pool.clone());
+
+        // SQLite's own timestamp format is readable here too.
+        sqlx::query(
+            "INSERT INTO tags (id, name, created_at) \
+             VALUES ('4f1c2a9e-7b3d-4e8a-9c6f-2d5e8b1a0c37', 'travel', '2024-03-01 10:00:00')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO payees (id, name, created_at) \
+             VALUES ('9a7e3c1b-2d4f-4a6b-8c0e-1f3a5b7d9e2c', 'Rail, Inc.', 'last week')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let tags = TagService::new(pool.clone()).list_tags().await.unwrap();
+        assert_eq!(tags[0].created_at.to_rfc3339(), "2024-03-01T10:00:00+00:00");
+        let error = PayeeService::new(pool.clone())
+            .list_payees()
+            .await
+            .unwrap_err();
+        assert!(matches!(AppError::from(error), AppError::CorruptData(_)));
+
+        let report = integrity.check().await.unwrap();
+        let checked: Vec<&str> = report.tables.iter().map(|t| t.table.as_str()).collect();
+        assert_eq!(
+            checked,
+            vec![
+                "expenses",
+                "income",
+                "refunds",
+                "accounts",
+                "transfers",
+                "attachments",
+                "payees",
+                "payee_aliases",
+                "rules",
+                "tags",
+                "expense_reports",
+                "expense_report_comments",
+                "audit_log",
+            ]
+        );
+        assert_eq!(
+            report.corrupt_rows,
+            vec![CorruptRow {
+                table: "payees".to_string(),
+                id: Some("9a7e3c1b-2d4f-4a6b-8c0e-1f3a5b7d9e2c".to_string()),
+                column: Some("created_at".to_string()),
+                reason: report.corrupt_rows[0].reason.clone(),
+            }]
+        );
+    }
+}
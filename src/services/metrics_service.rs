@@ -0,0 +1,72 @@
+use crate::database::DatabasePool;
+use crate::metrics::Metrics;
+use crate::models::summary::ExpenseFilter;
+use crate::services::expense_service::ExpenseService;
+use anyhow::Result;
+
+/// Refreshes the gauges that are read from the database and renders all
+/// metrics for a scrape.
+#[derive(Clone)]
+pub struct MetricsService {
+    pool: DatabasePool,
+    metrics: Metrics,
+    expenses: ExpenseService,
+}
+
+impl MetricsService {
+    pub fn new(pool: impl Into<DatabasePool>, expenses: ExpenseService, metrics: Metrics) -> Self {
+        Self {
+            pool: pool.into(),
+            metrics,
+            expenses,
+        }
+    }
+
+    pub async fn render(&self) -> Result<String> {
+        let (idle, in_use, max) = self.pool.connections();
+        self.metrics.set_pool(idle, in_use, max);
+
+        let summary = self.expenses.summarize(&ExpenseFilter::default()).await?;
+        self.metrics.set_categories(
+            summary
+                .by_category
+                .iter()
+                .map(|c| (c.category.as_str(), c.expense_count, c.total)),
+        );
+
+        Ok(self.metrics.encode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::RequestContext;
+    use crate::models::expense::CreateExpenseRequest;
+
+    #[tokio::test]
+    async fn test_render_reports_pool_and_categories() {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        let expenses = ExpenseService::new(pool.clone());
+        let service = MetricsService::new(pool, expenses.clone(), Metrics::new());
+        for (amount, category) in [(10.0, "Food"), (5.5, "Food"), (30.0, "Travel")] {
+            let request = CreateExpenseRequest {
+                amount,
+                category: category.to_string(),
+                ..Default::default()
+            };
+            expenses
+                .add_expense(&RequestContext::new("tester", "test-request"), request)
+                .await
+                .unwrap();
+        }
+
+        let text = service.render().await.unwrap();
+        assert!(text.contains(r#"expenses{category="Food"} 2"#));
+        assert!(text.contains(r#"expense_amount{category="Food"} 15.5"#));
+        assert!(text.contains(r#"expenses{category="Travel"} 1"#));
+        assert!(text.contains("db_pool_max_connections 8"));
+    }
+}
@@ -1 +1,16 @@
+pub mod account_service;
+pub mod attachment_service;
+pub mod audit_service;
+pub mod backup_service;
+pub mod expense_report_service;
 pub mod expense_service;
+pub mod health_service;
+pub mod idempotency_service;
+pub mod income_service;
+pub mod integrity_service;
+pub mod metrics_service;
+pub mod payee_service;
+pub mod refund_service;
+pub mod rule_service;
+pub mod suggestion_service;
+pub mod tag_service;
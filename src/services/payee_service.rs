@@ -0,0 +1,996 @@
+use crate::context::RequestContext;
+use crate::models::audit::AuditAction;
+use crate::models::payee::{
+    AliasKind, CreateAliasRequest, CreatePayeeRequest, Payee, PayeeAlias, PayeeSummary,
+    PayeeSummaryQuery, ResolvedPayee, UpdatePayeeRequest,
+};
+use crate::models::summary::CategoryTotal;
+use crate::services::audit_service::{self, ENTITY_PAYEE, NewAuditEntry};
+use crate::services::expense_service::{self, NET_AMOUNT};
+use crate::services::integrity_service::decode_row;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+use uuid::fmt::Hyphenated;
+
+/// Result of a payee write that can be rejected.
+#[derive(Debug)]
+pub enum PayeeWrite {
+    Saved(Payee),
+    NotFound,
+    /// The name or alias pattern is already taken.
+    Conflict(String),
+    /// The alias pattern is not usable (e.g. an invalid regex).
+    Invalid(String),
+}
+
+#[derive(Clone)]
+pub struct PayeeService {
+    pool: SqlitePool,
+}
+
+impl PayeeService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_payee(
+        &self,
+        ctx: &RequestContext,
+        request: CreatePayeeRequest,
+    ) -> Result<PayeeWrite> {
+        let mut tx = self.pool.begin().await?;
+        let name = request.name.trim().to_string();
+
+        if find_by_name(&mut tx, &name).await?.is_some() {
+            return Ok(PayeeWrite::Conflict(format!(
+                "Payee '{}' already exists",
+                name
+            )));
+        }
+
+        let payee = Payee {
+            id: Uuid::new_v4(),
+            name,
+            created_at: Utc::now(),
+            aliases: Vec::new(),
+        };
+        sqlx::query("INSERT INTO payees (id, name, created_at) VALUES (?, ?, ?)")
+            .bind(payee.id.to_string())
+            .bind(&payee.name)
+            .bind(payee.created_at)
+            .execute(&mut *tx)
+            .await?;
+
+        for alias in request.aliases {
+            if let Some(rejected) = insert_alias(&mut tx, payee.id, alias).await? {
+                return Ok(rejected);
+            }
+        }
+
+        let payee = fetch_payee(&mut tx, payee.id)
+            .await?
+            .ok_or_else(|| anyhow!("payee vanished during creation"))?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_PAYEE,
+                entity_id: payee.id.to_string(),
+                action: AuditAction::Create,
+                before: None,
+                after: Some(serde_json::to_value(&payee)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(PayeeWrite::Saved(payee))
+    }
+
+    pub async fn list_payees(&self) -> Result<Vec<Payee>> {
+        let rows = sqlx::query("SELECT id, name, created_at FROM payees ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        let aliases = sqlx::query(&format!(
+            "SELECT {ALIAS_COLUMNS} FROM payee_aliases ORDER BY created_at"
+        ))
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(row_to_alias)
+        .collect::<Result<Vec<_>>>()?;
+
+        rows.into_iter()
+            .map(|row| {
+                let mut payee = row_to_payee(row)?;
+                payee.aliases = aliases
+                    .iter()
+                    .filter(|alias| alias.payee_id == payee.id)
+                    .cloned()
+                    .collect();
+                Ok(payee)
+            })
+            .collect()
+    }
+
+    pub async fn get_payee(&self, id: Uuid) -> Result<Option<Payee>> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_payee(&mut conn, id).await
+    }
+
+    /// Renames a payee. Linked expenses take the new name.
+    pub async fn rename_payee(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        request: UpdatePayeeRequest,
+    ) -> Result<PayeeWrite> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(before) = fetch_payee(&mut tx, id).await? else {
+            return Ok(PayeeWrite::NotFound);
+        };
+        let name = request.name.trim().to_string();
+        if find_by_name(&mut tx, &name)
+            .await?
+            .is_some_and(|existing| existing.payee_id != id)
+        {
+            return Ok(PayeeWrite::Conflict(format!(
+                "Payee '{}' already exists",
+                name
+            )));
+        }
+
+        sqlx::query("UPDATE payees SET name = ? WHERE id = ?")
+            .bind(&name)
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        let after = Payee {
+            name,
+            ..before.clone()
+        };
+        let resolved = ResolvedPayee {
+            payee_id: id,
+            name: after.name.clone(),
+        };
+        expense_service::reassign_payee(&mut tx, ctx, id, Some(&resolved)).await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_PAYEE,
+                entity_id: id.to_string(),
+                action: AuditAction::Update,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(PayeeWrite::Saved(after))
+    }
+
+    /// Deletes a payee and its aliases. Linked expenses keep their payee
+    /// text but are no longer linked. Returns `false` if it did not exist.
+    pub async fn delete_payee(&self, ctx: &RequestContext, id: Uuid) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(before) = fetch_payee(&mut tx, id).await? else {
+            return Ok(false);
+        };
+
+        expense_service::reassign_payee(&mut tx, ctx, id, None).await?;
+
+        sqlx::query("DELETE FROM payee_aliases WHERE payee_id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM payees WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_PAYEE,
+                entity_id: id.to_string(),
+                action: AuditAction::Delete,
+                before: Some(serde_json::to_value(&before)?),
+                after: None,
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Adds an alias to a payee. Only expenses ingested afterwards are
+    /// normalized with it.
+    pub async fn add_alias(
+        &self,
+        ctx: &RequestContext,
+        payee_id: Uuid,
+        request: CreateAliasRequest,
+    ) -> Result<PayeeWrite> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(before) = fetch_payee(&mut tx, payee_id).await? else {
+            return Ok(PayeeWrite::NotFound);
+        };
+        if let Some(rejected) = insert_alias(&mut tx, payee_id, request).await? {
+            return Ok(rejected);
+        }
+        let after = fetch_payee(&mut tx, payee_id)
+            .await?
+            .ok_or_else(|| anyhow!("payee vanished while adding an alias"))?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_PAYEE,
+                entity_id: payee_id.to_string(),
+                action: AuditAction::Update,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(PayeeWrite::Saved(after))
+    }
+
+    pub async fn remove_alias(
+        &self,
+        ctx: &RequestContext,
+        payee_id: Uuid,
+        alias_id: Uuid,
+    ) -> Result<PayeeWrite> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(before) = fetch_payee(&mut tx, payee_id).await? else {
+            return Ok(PayeeWrite::NotFound);
+        };
+        let removed = sqlx::query("DELETE FROM payee_aliases WHERE id = ? AND payee_id = ?")
+            .bind(alias_id.to_string())
+            .bind(payee_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        if removed.rows_affected() == 0 {
+            return Ok(PayeeWrite::NotFound);
+        }
+        let after = Payee {
+            aliases: before
+                .aliases
+                .iter()
+                .filter(|alias| alias.id != alias_id)
+                .cloned()
+                .collect(),
+            ..before.clone()
+        };
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_PAYEE,
+                entity_id: payee_id.to_string(),
+                action: AuditAction::Update,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(PayeeWrite::Saved(after))
+    }
+
+    /// Shows which managed payee a raw payee string would be normalized to.
+    pub async fn resolve(&self, raw: &str) -> Result<Option<ResolvedPayee>> {
+        let mut conn = self.pool.acquire().await?;
+        resolve(&mut conn, raw).await
+    }
+
+    /// Spending per payee over non-deleted expenses, highest total first.
+    /// Payees without expenses in the range are included with zero totals.
+    pub async fn summaries(&self, query: &PayeeSummaryQuery) -> Result<Vec<PayeeSummary>> {
+        let rows = sqlx::query(&format!(
+            "SELECT p.id, p.name, COUNT(expenses.id) AS expense_count, \
+                    COALESCE(SUM({NET_AMOUNT}), 0.0) AS total, \
+                    MIN(expenses.date) AS first_expense, MAX(expenses.date) AS last_expense \
+             FROM payees p LEFT JOIN expenses \
+                 ON expenses.payee_id = p.id AND expenses.deleted_at IS NULL \
+                 AND (? IS NULL OR expenses.date >= ?) \
+                 AND (? IS NULL OR expenses.date < ?) \
+             GROUP BY p.id, p.name \
+             ORDER BY total DESC, p.name"
+        ))
+        .bind(query.from)
+        .bind(query.from)
+        .bind(query.to)
+        .bind(query.to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(row_to_summary).collect()
+    }
+
+    /// Spending for one payee, broken down by category.
+    pub async fn summary(
+        &self,
+        id: Uuid,
+        query: &PayeeSummaryQuery,
+    ) -> Result<Option<PayeeSummary>> {
+        let row = sqlx::query(&format!(
+            "SELECT p.id, p.name, COUNT(expenses.id) AS expense_count, \
+                    COALESCE(SUM({NET_AMOUNT}), 0.0) AS total, \
+                    MIN(expenses.date) AS first_expense, MAX(expenses.date) AS last_expense \
+             FROM payees p LEFT JOIN expenses \
+                 ON expenses.payee_id = p.id AND expenses.deleted_at IS NULL \
+                 AND (? IS NULL OR expenses.date >= ?) \
+                 AND (? IS NULL OR expenses.date < ?) \
+             WHERE p.id = ? \
+             GROUP BY p.id, p.name"
+        ))
+        .bind(query.from)
+        .bind(query.from)
+        .bind(query.to)
+        .bind(query.to)
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let mut summary = row_to_summary(row)?;
+
+        summary.by_category = sqlx::query(&format!(
+            "SELECT category, COUNT(*) AS expense_count, SUM({NET_AMOUNT}) AS total \
+             FROM expenses \
+             WHERE payee_id = ? AND deleted_at IS NULL \
+             AND (? IS NULL OR date >= ?) \
+             AND (? IS NULL OR date < ?) \
+             GROUP BY category \
+             ORDER BY total DESC, category"
+        ))
+        .bind(id.to_string())
+        .bind(query.from)
+        .bind(query.from)
+        .bind(query.to)
+        .bind(query.to)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| CategoryTotal {
+            category: row.get("category"),
+            expense_count: row.get("expense_count"),
+            total: row.get("total"),
+        })
+        .collect();
+
+        Ok(Some(summary))
+    }
+}
+
+/// Finds the managed payee for a raw payee string.
+///
+/// The payee's own name matches first, then exact aliases, then the
+/// longest matching prefix alias, then regex aliases in creation order.
+/// Exact and prefix matching ignore case and surrounding whitespace.
+pub(crate) async fn resolve(
+    conn: &mut SqliteConnection,
+    raw: &str,
+) -> Result<Option<ResolvedPayee>> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Ok(None);
+    }
+    if let Some(payee) = find_by_name(conn, raw).await? {
+        return Ok(Some(payee));
+    }
+
+    let generation: String = sqlx::query_scalar("SELECT token FROM payee_alias_generation")
+        .fetch_one(&mut *conn)
+        .await?;
+    let matcher = match cached_aliases(&generation) {
+        Some(matcher) => matcher,
+        None => {
+            let matcher = Arc::new(AliasMatcher::new(load_aliases(conn).await?));
+            *COMPILED_ALIASES.lock().unwrap() = Some((generation, Arc::clone(&matcher)));
+            matcher
+        }
+    };
+
+    Ok(matcher.find(raw).cloned())
+}
+
+/// Aliases compiled for the generation token they were loaded under. Any
+/// change to the aliases draws a new token, so a stale entry is never used.
+static COMPILED_ALIASES: Mutex<Option<(String, Arc<AliasMatcher>)>> = Mutex::new(None);
+
+fn cached_aliases(generation: &str) -> Option<Arc<AliasMatcher>> {
+    match &*COMPILED_ALIASES.lock().unwrap() {
+        Some((token, matcher)) if token == generation => Some(Arc::clone(matcher)),
+        _ => None,
+    }
+}
+
+async fn load_aliases(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<(AliasKind, String, ResolvedPayee)>> {
+    let rows = sqlx::query(
+        "SELECT a.kind, a.pattern, p.id, p.name \
+         FROM payee_aliases a JOIN payees p ON p.id = a.payee_id \
+         ORDER BY a.created_at, a.id",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let kind = AliasKind::parse(row.get("kind"))?;
+            let payee = ResolvedPayee {
+                payee_id: Uuid::parse_str(row.get("id")).ok()?,
+                name: row.get("name"),
+            };
+            Some((kind, row.get::<String, _>("pattern"), payee))
+        })
+        .collect())
+}
+
+/// Aliases ready for matching: patterns trimmed and lowercased, regexes
+/// compiled once. Each list keeps the aliases in creation order.
+struct AliasMatcher {
+    exact: Vec<(String, ResolvedPayee)>,
+    prefix: Vec<(String, ResolvedPayee)>,
+    regex: Vec<(Regex, ResolvedPayee)>,
+}
+
+impl AliasMatcher {
+    fn new(candidates: Vec<(AliasKind, String, ResolvedPayee)>) -> Self {
+        let mut matcher = Self {
+            exact: Vec::new(),
+            prefix: Vec::new(),
+            regex: Vec::new(),
+        };
+        for (kind, pattern, payee) in candidates {
+            match kind {
+                AliasKind::Exact => matcher.exact.push((pattern.trim().to_lowercase(), payee)),
+                AliasKind::Prefix => matcher.prefix.push((pattern.trim().to_lowercase(), payee)),
+                AliasKind::Regex => {
+                    if let Ok(regex) = Regex::new(&pattern) {
+                        matcher.regex.push((regex, payee));
+                    }
+                }
+            }
+        }
+        matcher
+    }
+
+    fn find(&self, raw: &str) -> Option<&ResolvedPayee> {
+        let lowered = raw.to_lowercase();
+        self.exact
+            .iter()
+            .find(|(pattern, _)| *pattern == lowered)
+            .or_else(|| {
+                self.prefix
+                    .iter()
+                    .filter(|(pattern, _)| lowered.starts_with(pattern.as_str()))
+                    .max_by_key(|(pattern, _)| pattern.len())
+            })
+            .map(|(_, payee)| payee)
+            .or_else(|| {
+                self.regex
+                    .iter()
+                    .find(|(regex, _)| regex.is_match(raw))
+                    .map(|(_, payee)| payee)
+            })
+    }
+}
+
+async fn find_by_name(conn: &mut SqliteConnection, name: &str) -> Result<Option<ResolvedPayee>> {
+    let row = sqlx::query("SELECT id, name, created_at FROM payees WHERE name = ?")
+        .bind(name)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    row.map(|row| {
+        let payee = row_to_payee(row)?;
+        Ok(ResolvedPayee {
+            payee_id: payee.id,
+            name: payee.name,
+        })
+    })
+    .transpose()
+}
+
+/// Inserts an alias, or returns why it was rejected.
+async fn insert_alias(
+    conn: &mut SqliteConnection,
+    payee_id: Uuid,
+    request: CreateAliasRequest,
+) -> Result<Option<PayeeWrite>> {
+    let pattern = match request.kind {
+        AliasKind::Exact | AliasKind::Prefix => request.pattern.trim().to_string(),
+        AliasKind::Regex => request.pattern,
+    };
+    if pattern.is_empty() {
+        return Ok(Some(PayeeWrite::Invalid(
+            "Pattern must not be blank".to_string(),
+        )));
+    }
+    if request.kind == AliasKind::Regex
+        && let Err(error) = Regex::new(&pattern)
+    {
+        return Ok(Some(PayeeWrite::Invalid(format!(
+            "Invalid regex pattern: {}",
+            error
+        ))));
+    }
+
+    let taken: Option<String> = sqlx::query_scalar(
+        "SELECT p.name FROM payee_aliases a JOIN payees p ON p.id = a.payee_id \
+         WHERE a.kind = ? AND a.pattern = ?",
+    )
+    .bind(request.kind.as_str())
+    .bind(&pattern)
+    .fetch_optional(&mut *conn)
+    .await?;
+    if let Some(owner) = taken {
+        return Ok(Some(PayeeWrite::Conflict(format!(
+            "{} alias '{}' already belongs to payee '{}'",
+            request.kind.as_str(),
+            pattern,
+            owner
+        ))));
+    }
+
+    sqlx::query(
+        "INSERT INTO payee_aliases (id, payee_id, kind, pattern, created_at) \
+         VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(payee_id.to_string())
+    .bind(request.kind.as_str())
+    .bind(&pattern)
+    .bind(Utc::now())
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(None)
+}
+
+const ALIAS_COLUMNS: &str = "id, payee_id, kind, pattern, created_at";
+
+async fn fetch_payee(conn: &mut SqliteConnection, id: Uuid) -> Result<Option<Payee>> {
+    let Some(row) = sqlx::query("SELECT id, name, created_at FROM payees WHERE id = ?")
+        .bind(id.to_string())
+        .fetch_optional(&mut *conn)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let mut payee = row_to_payee(row)?;
+
+    payee.aliases = sqlx::query(&format!(
+        "SELECT {ALIAS_COLUMNS} FROM payee_aliases WHERE payee_id = ? ORDER BY created_at"
+    ))
+    .bind(id.to_string())
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(row_to_alias)
+    .collect::<Result<_>>()?;
+
+    Ok(Some(payee))
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct PayeeRow {
+    id: Hyphenated,
+    name: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct AliasRow {
+    id: Hyphenated,
+    payee_id: Hyphenated,
+    #[sqlx(try_from = "String")]
+    kind: AliasKind,
+    pattern: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SummaryRow {
+    id: Hyphenated,
+    name: String,
+    expense_count: i64,
+    total: f64,
+    first_expense: Option<DateTime<Utc>>,
+    last_expense: Option<DateTime<Utc>>,
+}
+
+fn row_to_payee(row: SqliteRow) -> Result<Payee> {
+    let row: PayeeRow = decode_row("payees", &row)?;
+    Ok(Payee {
+        id: row.id.into_uuid(),
+        name: row.name,
+        created_at: row.created_at,
+        aliases: Vec::new(),
+    })
+}
+
+fn row_to_alias(row: SqliteRow) -> Result<PayeeAlias> {
+    let row: AliasRow = decode_row("payee_aliases", &row)?;
+    Ok(PayeeAlias {
+        id: row.id.into_uuid(),
+        payee_id: row.payee_id.into_uuid(),
+        kind: row.kind,
+        pattern: row.pattern,
+        created_at: row.created_at,
+    })
+}
+
+fn row_to_summary(row: SqliteRow) -> Result<PayeeSummary> {
+    let row: SummaryRow = decode_row("payees", &row)?;
+    Ok(PayeeSummary {
+        payee_id: row.id.into_uuid(),
+        name: row.name,
+        expense_count: row.expense_count,
+        total: row.total,
+        average: if row.expense_count > 0 {
+            row.total / row.expense_count as f64
+        } else {
+            0.0
+        },
+        first_expense: row.first_expense,
+        last_expense: row.last_expense,
+        by_category: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::expense::{CreateExpenseRequest, UpdateExpenseRequest};
+    use crate::services::expense_service::{ExpenseService, VersionedUpdate};
+
+    fn test_ctx() -> RequestContext {
+        RequestContext::new("tester", "test-request")
+    }
+
+    async fn create_services() -> (PayeeService, ExpenseService) {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        (PayeeService::new(pool.clone()), ExpenseService::new(pool))
+    }
+
+    fn alias(kind: AliasKind, pattern: &str) -> CreateAliasRequest {
+        CreateAliasRequest {
+            kind,
+            pattern: pattern.to_string(),
+        }
+    }
+
+    async fn create_payee(
+        service: &PayeeService,
+        name: &str,
+        aliases: Vec<CreateAliasRequest>,
+    ) -> Payee {
+        let request = CreatePayeeRequest {
+            name: name.to_string(),
+            aliases,
+        };
+        match service.create_payee(&test_ctx(), request).await.unwrap() {
+            PayeeWrite::Saved(payee) => payee,
+            other => panic!("payee not created: {:?}", other),
+        }
+    }
+
+    async fn add_expense(
+        service: &ExpenseService,
+        amount: f64,
+        payee: &str,
+    ) -> crate::models::expense::Expense {
+        let request = CreateExpenseRequest {
+            amount,
+            category: "Shopping".to_string(),
+            payee: Some(payee.to_string()),
+            ..Default::default()
+        };
+        service.add_expense(&test_ctx(), request).await.unwrap()
+    }
+
+    fn resolved(name: &str) -> ResolvedPayee {
+        ResolvedPayee {
+            payee_id: Uuid::nil(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_match_alias_precedence() {
+        let candidates = vec![
+            (
+                AliasKind::Regex,
+                r"(?i)^amzn".to_string(),
+                resolved("Regex"),
+            ),
+            (AliasKind::Prefix, "AMZN".to_string(), resolved("Short")),
+            (AliasKind::Prefix, "AMZN MKTP".to_string(), resolved("Long")),
+            (
+                AliasKind::Exact,
+                "amzn mktp us".to_string(),
+                resolved("Exact"),
+            ),
+        ];
+
+        let matcher = AliasMatcher::new(candidates);
+        let name = |raw| matcher.find(raw).map(|p| p.name.as_str());
+        assert_eq!(name("AMZN Mktp US"), Some("Exact"));
+        assert_eq!(name("AMZN MKTP DE*123"), Some("Long"));
+        assert_eq!(name("amzn digital"), Some("Short"));
+        assert_eq!(name("Walmart"), None);
+    }
+
+    #[test]
+    fn test_match_alias_falls_back_to_regex() {
+        let candidates = vec![(
+            AliasKind::Regex,
+            r"^SQ \*.*COFFEE".to_string(),
+            resolved("Coffee Shop"),
+        )];
+
+        let matcher = AliasMatcher::new(candidates);
+        assert_eq!(
+            matcher.find("SQ *BLUE COFFEE 42").map(|p| p.name.as_str()),
+            Some("Coffee Shop")
+        );
+        assert!(matcher.find("sq *blue coffee").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_expenses_are_normalized_on_create() {
+        let (payees, expenses) = create_services().await;
+        let amazon = create_payee(
+            &payees,
+            "Amazon",
+            vec![alias(AliasKind::Prefix, "AMZN MKTP")],
+        )
+        .await;
+
+        let matched = add_expense(&expenses, 10.0, "AMZN MKTP US*2K3").await;
+        let by_name = add_expense(&expenses, 5.0, "  amazon ").await;
+        let unmatched = add_expense(&expenses, 3.0, "Corner Store").await;
+
+        assert_eq!(matched.payee.as_deref(), Some("Amazon"));
+        assert_eq!(matched.payee_id, Some(amazon.id));
+        assert_eq!(by_name.payee_id, Some(amazon.id));
+        assert_eq!(unmatched.payee.as_deref(), Some("Corner Store"));
+        assert!(unmatched.payee_id.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_expenses_are_normalized_on_update() {
+        let (payees, expenses) = create_services().await;
+        let uber = create_payee(&payees, "Uber", vec![alias(AliasKind::Regex, r"^UBER\b")]).await;
+        let expense = add_expense(&expenses, 12.0, "Taxi").await;
+
+        let request = UpdateExpenseRequest {
+            amount: 12.0,
+            category: "Transport".to_string(),
+            payee: Some("UBER *TRIP".to_string()),
+            ..Default::default()
+        };
+        let VersionedUpdate::Updated(updated) = expenses
+            .update_expense(&test_ctx(), expense.id, None, request)
+            .await
+            .unwrap()
+        else {
+            panic!("expected update");
+        };
+
+        assert_eq!(updated.payee.as_deref(), Some("Uber"));
+        assert_eq!(updated.payee_id, Some(uber.id));
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_names_and_aliases_conflict() {
+        let (payees, _) = create_services().await;
+        let amazon = create_payee(&payees, "Amazon", vec![alias(AliasKind::Exact, "AMZN")]).await;
+
+        let duplicate = CreatePayeeRequest {
+            name: "AMAZON".to_string(),
+            aliases: Vec::new(),
+        };
+        assert!(matches!(
+            payees.create_payee(&test_ctx(), duplicate).await.unwrap(),
+            PayeeWrite::Conflict(_)
+        ));
+
+        let other = create_payee(&payees, "Other", Vec::new()).await;
+        assert!(matches!(
+            payees
+                .add_alias(&test_ctx(), other.id, alias(AliasKind::Exact, "AMZN"))
+                .await
+                .unwrap(),
+            PayeeWrite::Conflict(_)
+        ));
+        assert_eq!(
+            payees
+                .get_payee(amazon.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .aliases
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_regex_alias_is_rejected() {
+        let (payees, _) = create_services().await;
+        let payee = create_payee(&payees, "Shop", Vec::new()).await;
+
+        let outcome = payees
+            .add_alias(&test_ctx(), payee.id, alias(AliasKind::Regex, "(unclosed"))
+            .await
+            .unwrap();
+
+        assert!(matches!(outcome, PayeeWrite::Invalid(_)));
+    }
+
+    #[tokio::test]
+    async fn test_rename_updates_linked_expenses() {
+        let (payees, expenses) = create_services().await;
+        let payee = create_payee(&payees, "Amzn", Vec::new()).await;
+        let expense = add_expense(&expenses, 10.0, "amzn").await;
+
+        let request = UpdatePayeeRequest {
+            name: "Amazon".to_string(),
+        };
+        payees
+            .rename_payee(&test_ctx(), payee.id, request)
+            .await
+            .unwrap();
+
+        let renamed = expenses.get_expense(expense.id).await.unwrap().unwrap();
+        assert_eq!(renamed.payee.as_deref(), Some("Amazon"));
+        assert_eq!(renamed.version, expense.version + 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete_unlinks_expenses() {
+        let (payees, expenses) = create_services().await;
+        let payee = create_payee(&payees, "Amazon", vec![alias(AliasKind::Exact, "AMZN")]).await;
+        let expense = add_expense(&expenses, 10.0, "AMZN").await;
+
+        assert!(payees.delete_payee(&test_ctx(), payee.id).await.unwrap());
+        assert!(!payees.delete_payee(&test_ctx(), payee.id).await.unwrap());
+
+        let unlinked = expenses.get_expense(expense.id).await.unwrap().unwrap();
+        assert_eq!(unlinked.payee.as_deref(), Some("Amazon"));
+        assert!(unlinked.payee_id.is_none());
+        assert!(payees.resolve("AMZN").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_summaries() {
+        let (payees, expenses) = create_services().await;
+        let amazon = create_payee(&payees, "Amazon", vec![alias(AliasKind::Prefix, "AMZN")]).await;
+        create_payee(&payees, "Idle", Vec::new()).await;
+        add_expense(&expenses, 10.0, "AMZN 1").await;
+        add_expense(&expenses, 30.0, "AMZN 2").await;
+        let trashed = add_expense(&expenses, 100.0, "AMZN 3").await;
+        expenses
+            .delete_expense(&test_ctx(), trashed.id, None)
+            .await
+            .unwrap();
+
+        let all = payees
+            .summaries(&PayeeSummaryQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].name, "Amazon");
+        assert_eq!(all[0].expense_count, 2);
+        assert_eq!(all[0].total, 40.0);
+        assert_eq!(all[0].average, 20.0);
+        assert_eq!(all[1].expense_count, 0);
+        assert!(all[1].first_expense.is_none());
+
+        let one = payees
+            .summary(amazon.id, &PayeeSummaryQuery::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(one.by_category.len(), 1);
+        assert_eq!(one.by_category[0].total, 40.0);
+
+        let future = PayeeSummaryQuery {
+            from: Some(Utc::now() + chrono::Duration::days(1)),
+            to: None,
+        };
+        let none = payees.summary(amazon.id, &future).await.unwrap().unwrap();
+        assert_eq!(none.expense_count, 0);
+        assert!(none.by_category.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_follows_alias_changes() {
+        let (payees, _) = create_services().await;
+        let coffee = create_payee(
+            &payees,
+            "Coffee Shop",
+            vec![alias(AliasKind::Regex, r"^SQ \*.*COFFEE")],
+        )
+        .await;
+        let name = |resolved: Option<ResolvedPayee>| resolved.map(|p| p.name);
+
+        assert_eq!(
+            name(payees.resolve("SQ *BLUE COFFEE").await.unwrap()),
+            Some("Coffee Shop".to_string())
+        );
+        assert_eq!(name(payees.resolve("BEAN BAR 7").await.unwrap()), None);
+
+        let PayeeWrite::Saved(updated) = payees
+            .add_alias(&test_ctx(), coffee.id, alias(AliasKind::Prefix, "bean bar"))
+            .await
+            .unwrap()
+        else {
+            panic!("alias not added");
+        };
+        assert_eq!(
+            name(payees.resolve("BEAN BAR 7").await.unwrap()),
+            Some("Coffee Shop".to_string())
+        );
+
+        let request = UpdatePayeeRequest {
+            name: "Corner Cafe".to_string(),
+        };
+        payees
+            .rename_payee(&test_ctx(), coffee.id, request)
+            .await
+            .unwrap();
+        assert_eq!(
+            name(payees.resolve("SQ *BLUE COFFEE").await.unwrap()),
+            Some("Corner Cafe".to_string())
+        );
+
+        let regex_alias = updated
+            .aliases
+            .iter()
+            .find(|alias| alias.kind == AliasKind::Regex)
+            .unwrap();
+        payees
+            .remove_alias(&test_ctx(), coffee.id, regex_alias.id)
+            .await
+            .unwrap();
+        assert_eq!(name(payees.resolve("SQ *BLUE COFFEE").await.unwrap()), None);
+    }
+}
@@ -0,0 +1,326 @@
+use crate::context::RequestContext;
+use crate::models::audit::AuditAction;
+use crate::models::refund::{CreateRefundRequest, Refund};
+use crate::services::audit_service::{self, ENTITY_REFUND, NewAuditEntry};
+use crate::services::integrity_service::decode_row;
+use crate::services::{expense_report_service, expense_service};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{SqliteConnection, SqlitePool};
+use uuid::Uuid;
+use uuid::fmt::Hyphenated;
+
+/// Tolerance for comparing money sums that went through floating point.
+const CENT_EPSILON: f64 = 1e-9;
+
+/// Result of recording a refund.
+#[derive(Debug)]
+pub enum RefundWrite {
+    Saved(Refund),
+    /// The expense does not exist or is in the trash.
+    NotFound,
+    /// The refund would exceed what is left of the expense.
+    Invalid(String),
+}
+
+#[derive(Clone)]
+pub struct RefundService {
+    pool: SqlitePool,
+}
+
+impl RefundService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Records a refund against a non-deleted expense. The expense's version
+    /// is bumped because its `refunded` total changes.
+    pub async fn add_refund(
+        &self,
+        ctx: &RequestContext,
+        expense_id: Uuid,
+        request: CreateRefundRequest,
+    ) -> Result<RefundWrite> {
+        let mut tx = self.pool.begin().await?;
+
+        let expense = expense_service::fetch_expense(&mut tx, expense_id).await?;
+        let Some(expense) = expense.filter(|e| e.deleted_at.is_none()) else {
+            return Ok(RefundWrite::NotFound);
+        };
+        expense_report_service::check_unlocked(&mut tx, expense_id).await?;
+        let refundable = expense.amount - expense.refunded;
+        if request.amount > refundable + CENT_EPSILON {
+            return Ok(RefundWrite::Invalid(format!(
+                "Refund of {:.2} exceeds the {:.2} left to refund on this expense",
+                request.amount, refundable
+            )));
+        }
+
+        let now = Utc::now();
+        let refund = Refund {
+            id: Uuid::new_v4(),
+            expense_id,
+            amount: request.amount,
+            reason: request.reason,
+            date: request.date.unwrap_or(now),
+            created_at: now,
+        };
+        sqlx::query(
+            "INSERT INTO refunds (id, expense_id, amount, reason, date, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(refund.id.to_string())
+        .bind(expense_id.to_string())
+        .bind(refund.amount)
+        .bind(&refund.reason)
+        .bind(refund.date)
+        .bind(refund.created_at)
+        .execute(&mut *tx)
+        .await?;
+        bump_expense_version(&mut tx, expense_id).await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_REFUND,
+                entity_id: refund.id.to_string(),
+                action: AuditAction::Create,
+                before: None,
+                after: Some(serde_json::to_value(&refund)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(RefundWrite::Saved(refund))
+    }
+
+    /// Refunds of a non-deleted expense, oldest first.
+    pub async fn list_refunds(&self, expense_id: Uuid) -> Result<Option<Vec<Refund>>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let expense = expense_service::fetch_expense(&mut conn, expense_id).await?;
+        if expense.is_none_or(|e| e.deleted_at.is_some()) {
+            return Ok(None);
+        }
+
+        let rows = sqlx::query("SELECT * FROM refunds WHERE expense_id = ? ORDER BY date, id")
+            .bind(expense_id.to_string())
+            .fetch_all(&mut *conn)
+            .await?;
+
+        rows.into_iter()
+            .map(row_to_refund)
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+
+    pub async fn get_refund(&self, id: Uuid) -> Result<Option<Refund>> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_refund(&mut conn, id).await
+    }
+
+    pub async fn delete_refund(&self, ctx: &RequestContext, id: Uuid) -> Result<Option<Refund>> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(refund) = fetch_refund(&mut tx, id).await? else {
+            return Ok(None);
+        };
+        expense_report_service::check_unlocked(&mut tx, refund.expense_id).await?;
+        sqlx::query("DELETE FROM refunds WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        bump_expense_version(&mut tx, refund.expense_id).await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_REFUND,
+                entity_id: id.to_string(),
+                action: AuditAction::Delete,
+                before: Some(serde_json::to_value(&refund)?),
+                after: None,
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(refund))
+    }
+}
+
+async fn bump_expense_version(conn: &mut SqliteConnection, expense_id: Uuid) -> Result<()> {
+    sqlx::query("UPDATE expenses SET version = version + 1 WHERE id = ?")
+        .bind(expense_id.to_string())
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// A refund whose expense is not in the trash.
+async fn fetch_refund(conn: &mut SqliteConnection, id: Uuid) -> Result<Option<Refund>> {
+    let row = sqlx::query(
+        "SELECT r.* FROM refunds r JOIN expenses e ON e.id = r.expense_id \
+         WHERE r.id = ? AND e.deleted_at IS NULL",
+    )
+    .bind(id.to_string())
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    row.map(row_to_refund).transpose()
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct RefundRow {
+    id: Hyphenated,
+    expense_id: Hyphenated,
+    amount: f64,
+    reason: Option<String>,
+    date: DateTime<Utc>,
+    created_at: DateTime<Utc>,
+}
+
+fn row_to_refund(row: SqliteRow) -> Result<Refund> {
+    let row: RefundRow = decode_row("refunds", &row)?;
+    Ok(Refund {
+        id: row.id.into_uuid(),
+        expense_id: row.expense_id.into_uuid(),
+        amount: row.amount,
+        reason: row.reason,
+        date: row.date,
+        created_at: row.created_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::expense::{CreateExpenseRequest, Expense, UpdateExpenseRequest};
+    use crate::models::refund::AmountBelowRefunded;
+    use crate::models::summary::{CashFlowQuery, ExpenseFilter};
+    use crate::services::expense_service::{ExpenseService, VersionedUpdate};
+
+    fn test_ctx() -> RequestContext {
+        RequestContext::new("tester", "test-request")
+    }
+
+    async fn create_services() -> (RefundService, ExpenseService) {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        (RefundService::new(pool.clone()), ExpenseService::new(pool))
+    }
+
+    async fn add_expense(service: &ExpenseService, amount: f64, category: &str) -> Expense {
+        let request = CreateExpenseRequest {
+            amount,
+            category: category.to_string(),
+            ..Default::default()
+        };
+        service.add_expense(&test_ctx(), request).await.unwrap()
+    }
+
+    fn refund(amount: f64) -> CreateRefundRequest {
+        CreateRefundRequest {
+            amount,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refunds_cannot_exceed_expense() {
+        let (refunds, expenses) = create_services().await;
+        let expense = add_expense(&expenses, 50.0, "Clothes").await;
+
+        assert!(matches!(
+            refunds
+                .add_refund(&test_ctx(), expense.id, refund(30.0))
+                .await
+                .unwrap(),
+            RefundWrite::Saved(_)
+        ));
+        assert!(matches!(
+            refunds
+                .add_refund(&test_ctx(), expense.id, refund(20.01))
+                .await
+                .unwrap(),
+            RefundWrite::Invalid(_)
+        ));
+        assert!(matches!(
+            refunds
+                .add_refund(&test_ctx(), expense.id, refund(20.0))
+                .await
+                .unwrap(),
+            RefundWrite::Saved(_)
+        ));
+        assert!(matches!(
+            refunds
+                .add_refund(&test_ctx(), Uuid::new_v4(), refund(1.0))
+                .await
+                .unwrap(),
+            RefundWrite::NotFound
+        ));
+
+        let expense = expenses.get_expense(expense.id).await.unwrap().unwrap();
+        assert_eq!(expense.refunded, 50.0);
+        assert_eq!(expense.version, 3);
+
+        let request = UpdateExpenseRequest {
+            amount: 40.0,
+            category: "Clothes".to_string(),
+            ..Default::default()
+        };
+        let error = expenses
+            .update_expense(&test_ctx(), expense.id, None, request)
+            .await
+            .unwrap_err();
+        assert!(error.downcast_ref::<AmountBelowRefunded>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_aggregates_are_net_of_refunds() {
+        let (refunds, expenses) = create_services().await;
+        let shoes = add_expense(&expenses, 80.0, "Clothes").await;
+        add_expense(&expenses, 20.0, "Food").await;
+        refunds
+            .add_refund(&test_ctx(), shoes.id, refund(60.0))
+            .await
+            .unwrap();
+
+        let summary = expenses.summarize(&ExpenseFilter::default()).await.unwrap();
+        assert_eq!(summary.total, 40.0);
+        let by_category: Vec<(&str, f64)> = summary
+            .by_category
+            .iter()
+            .map(|c| (c.category.as_str(), c.total))
+            .collect();
+        assert_eq!(by_category, vec![("Clothes", 20.0), ("Food", 20.0)]);
+
+        let report = expenses.cash_flow(&CashFlowQuery::default()).await.unwrap();
+        assert_eq!(report.total.expenses, 40.0);
+
+        let listed = refunds.list_refunds(shoes.id).await.unwrap().unwrap();
+        refunds
+            .delete_refund(&test_ctx(), listed[0].id)
+            .await
+            .unwrap()
+            .unwrap();
+        let summary = expenses.summarize(&ExpenseFilter::default()).await.unwrap();
+        assert_eq!(summary.total, 100.0);
+
+        let VersionedUpdate::Updated(_) = expenses
+            .delete_expense(&test_ctx(), shoes.id, None)
+            .await
+            .unwrap()
+        else {
+            panic!("expense not deleted");
+        };
+        assert!(refunds.list_refunds(shoes.id).await.unwrap().is_none());
+    }
+}
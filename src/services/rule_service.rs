@@ -0,0 +1,633 @@
+use crate::context::RequestContext;
+use crate::models::audit::AuditAction;
+use crate::models::expense::{Expense, UpdateExpenseRequest};
+use crate::models::rule::{
+    FieldChange, Rule, RuleActions, RuleChange, RuleConditions, RuleInput, RuleRequest,
+    RuleRunRequest, RuleRunResponse, RuleSet, check_rule,
+};
+use crate::models::tag::normalize_tags;
+use crate::services::audit_service::{self, ENTITY_RULE, NewAuditEntry};
+use crate::services::expense_service::{self, VersionedUpdate};
+use crate::services::integrity_service::decode_row;
+use crate::services::{expense_report_service, payee_service};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::types::Json;
+use sqlx::{SqliteConnection, SqlitePool};
+use uuid::Uuid;
+use uuid::fmt::Hyphenated;
+
+/// Result of a rule write that can be rejected.
+#[derive(Debug)]
+pub enum RuleWrite {
+    Saved(Box<Rule>),
+    NotFound,
+    Invalid(String),
+}
+
+#[derive(Clone)]
+pub struct RuleService {
+    pool: SqlitePool,
+}
+
+impl RuleService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_rule(
+        &self,
+        ctx: &RequestContext,
+        request: RuleRequest,
+    ) -> Result<RuleWrite> {
+        if let Err(message) = check_rule(&request) {
+            return Ok(RuleWrite::Invalid(message));
+        }
+
+        let now = Utc::now();
+        let rule = Rule {
+            id: Uuid::new_v4(),
+            name: request.name,
+            priority: request.priority,
+            enabled: request.enabled,
+            conditions: request.conditions,
+            actions: request.actions,
+            created_at: now,
+            updated_at: now,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO rules (id, name, priority, enabled, conditions, actions, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(rule.id.to_string())
+        .bind(&rule.name)
+        .bind(rule.priority)
+        .bind(rule.enabled)
+        .bind(serde_json::to_string(&rule.conditions)?)
+        .bind(serde_json::to_string(&rule.actions)?)
+        .bind(rule.created_at)
+        .bind(rule.updated_at)
+        .execute(&mut *tx)
+        .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_RULE,
+                entity_id: rule.id.to_string(),
+                action: AuditAction::Create,
+                before: None,
+                after: Some(serde_json::to_value(&rule)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(RuleWrite::Saved(Box::new(rule)))
+    }
+
+    /// All rules in evaluation order.
+    pub async fn list_rules(&self) -> Result<Vec<Rule>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {RULE_COLUMNS} FROM rules ORDER BY priority, created_at, id"
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(row_to_rule).collect()
+    }
+
+    pub async fn get_rule(&self, id: Uuid) -> Result<Option<Rule>> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_rule(&mut conn, id).await
+    }
+
+    pub async fn update_rule(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        request: RuleRequest,
+    ) -> Result<RuleWrite> {
+        if let Err(message) = check_rule(&request) {
+            return Ok(RuleWrite::Invalid(message));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = fetch_rule(&mut tx, id).await? else {
+            return Ok(RuleWrite::NotFound);
+        };
+
+        let after = Rule {
+            name: request.name,
+            priority: request.priority,
+            enabled: request.enabled,
+            conditions: request.conditions,
+            actions: request.actions,
+            updated_at: Utc::now(),
+            ..before.clone()
+        };
+
+        sqlx::query(
+            "UPDATE rules SET name = ?, priority = ?, enabled = ?, conditions = ?, actions = ?, \
+             updated_at = ? WHERE id = ?",
+        )
+        .bind(&after.name)
+        .bind(after.priority)
+        .bind(after.enabled)
+        .bind(serde_json::to_string(&after.conditions)?)
+        .bind(serde_json::to_string(&after.actions)?)
+        .bind(after.updated_at)
+        .bind(id.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_RULE,
+                entity_id: id.to_string(),
+                action: AuditAction::Update,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(RuleWrite::Saved(Box::new(after)))
+    }
+
+    /// Returns `false` if the rule did not exist.
+    pub async fn delete_rule(&self, ctx: &RequestContext, id: Uuid) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = fetch_rule(&mut tx, id).await? else {
+            return Ok(false);
+        };
+
+        sqlx::query("DELETE FROM rules WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_RULE,
+                entity_id: id.to_string(),
+                action: AuditAction::Delete,
+                before: Some(serde_json::to_value(&before)?),
+                after: None,
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Reports what the selected rules would change on existing expenses
+    /// without writing anything.
+    pub async fn dry_run(&self, request: &RuleRunRequest) -> Result<RuleRunResponse> {
+        let mut conn = self.pool.acquire().await?;
+        let rules = select_rule_set(&mut conn, request).await?;
+        let expenses = changeable_expenses(&mut conn).await?;
+
+        let mut changes = Vec::new();
+        for expense in &expenses {
+            if let Some(change) = plan_change(&mut conn, &rules, expense).await? {
+                changes.push(change);
+            }
+        }
+
+        Ok(RuleRunResponse {
+            applied: false,
+            expenses_checked: expenses.len(),
+            changes,
+        })
+    }
+
+    /// Applies the selected rules to existing expenses in one transaction.
+    /// Unlike on create, rule actions overwrite the current values. Every
+    /// changed expense gets a new version and an audit entry.
+    pub async fn apply(
+        &self,
+        ctx: &RequestContext,
+        request: &RuleRunRequest,
+    ) -> Result<RuleRunResponse> {
+        let mut tx = self.pool.begin().await?;
+        let rules = select_rule_set(&mut tx, request).await?;
+        let expenses = changeable_expenses(&mut tx).await?;
+
+        let mut changes = Vec::new();
+        for expense in &expenses {
+            let Some(change) = plan_change(&mut tx, &rules, expense).await? else {
+                continue;
+            };
+
+            let update = UpdateExpenseRequest {
+                amount: expense.amount,
+                category: change
+                    .category
+                    .as_ref()
+                    .map_or_else(|| expense.category.clone(), |c| c.to.clone()),
+                payee: change
+                    .payee
+                    .as_ref()
+                    .map(|p| p.to.clone())
+                    .or_else(|| expense.payee.clone()),
+                notes: expense.notes.clone(),
+                tags: [expense.tags.clone(), change.tags_added.clone()].concat(),
+                account_id: expense.account_id,
+                reimbursable: expense.reimbursable,
+            };
+            let outcome = expense_service::update_expense(
+                &mut tx,
+                ctx,
+                expense.id,
+                Some(expense.version),
+                update,
+            )
+            .await?;
+            if matches!(outcome, VersionedUpdate::Updated(_)) {
+                changes.push(change);
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(RuleRunResponse {
+            applied: true,
+            expenses_checked: expenses.len(),
+            changes,
+        })
+    }
+}
+
+/// Non-deleted expenses that are not frozen by an expense report under
+/// review or settled.
+async fn changeable_expenses(conn: &mut SqliteConnection) -> Result<Vec<Expense>> {
+    let locked = expense_report_service::locked_expense_ids(conn).await?;
+    let mut expenses = expense_service::fetch_active_expenses(conn).await?;
+    expenses.retain(|expense| !locked.contains(&expense.id));
+    Ok(expenses)
+}
+
+/// The enabled rules in evaluation order, as applied to new expenses.
+pub(crate) async fn enabled_rules(conn: &mut SqliteConnection) -> Result<RuleSet> {
+    select_rule_set(conn, &RuleRunRequest::default()).await
+}
+
+async fn select_rule_set(conn: &mut SqliteConnection, request: &RuleRunRequest) -> Result<RuleSet> {
+    let rows = sqlx::query(&format!(
+        "SELECT {RULE_COLUMNS} FROM rules ORDER BY priority, created_at, id"
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let rules = rows
+        .into_iter()
+        .map(row_to_rule)
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|rule| match &request.rule_ids {
+            Some(ids) => ids.contains(&rule.id),
+            None => rule.enabled,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(RuleSet::new(&rules)?)
+}
+
+/// What the rules would change on `expense`, with payees normalized the
+/// way an update would store them. `None` if nothing would change.
+async fn plan_change(
+    conn: &mut SqliteConnection,
+    rules: &RuleSet,
+    expense: &Expense,
+) -> Result<Option<RuleChange>> {
+    let outcome = rules.evaluate(&RuleInput::from(expense));
+
+    let category = outcome
+        .category
+        .filter(|category| *category != expense.category)
+        .map(|to| FieldChange {
+            from: Some(expense.category.clone()),
+            to,
+        });
+
+    let payee = match outcome.payee {
+        Some(raw) => {
+            let to = payee_service::resolve(conn, &raw)
+                .await?
+                .map_or(raw, |resolved| resolved.name);
+            (expense.payee.as_deref() != Some(to.as_str())).then(|| FieldChange {
+                from: expense.payee.clone(),
+                to,
+            })
+        }
+        None => None,
+    };
+
+    let tags_added: Vec<String> = outcome
+        .tags
+        .into_iter()
+        .filter(|tag| !expense.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+        .collect();
+
+    if category.is_none() && payee.is_none() && tags_added.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(RuleChange {
+        expense_id: expense.id,
+        matched_rules: outcome.matched_rules,
+        category,
+        payee,
+        tags_added,
+    }))
+}
+
+/// Rewrites tag `from` to `to` (ignoring case) in the tags action of every
+/// rule, so renamed or merged tags are not recreated by rules.
+pub(crate) async fn replace_tag_in_rules(
+    conn: &mut SqliteConnection,
+    ctx: &RequestContext,
+    from: &str,
+    to: &str,
+) -> Result<()> {
+    let rows = sqlx::query(&format!("SELECT {RULE_COLUMNS} FROM rules"))
+        .fetch_all(&mut *conn)
+        .await?;
+
+    for before in rows.into_iter().map(row_to_rule) {
+        let before = before?;
+        if !before
+            .actions
+            .tags
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(from))
+        {
+            continue;
+        }
+        let mut after = before.clone();
+        after.actions.tags = normalize_tags(
+            &before
+                .actions
+                .tags
+                .iter()
+                .map(|t| if t.eq_ignore_ascii_case(from) { to } else { t }.to_string())
+                .collect::<Vec<_>>(),
+        );
+        after.updated_at = Utc::now();
+
+        sqlx::query("UPDATE rules SET actions = ?, updated_at = ? WHERE id = ?")
+            .bind(serde_json::to_string(&after.actions)?)
+            .bind(after.updated_at)
+            .bind(after.id.to_string())
+            .execute(&mut *conn)
+            .await?;
+
+        audit_service::append(
+            conn,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_RULE,
+                entity_id: after.id.to_string(),
+                action: AuditAction::Update,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+const RULE_COLUMNS: &str =
+    "id, name, priority, enabled, conditions, actions, created_at, updated_at";
+
+async fn fetch_rule(conn: &mut SqliteConnection, id: Uuid) -> Result<Option<Rule>> {
+    let row = sqlx::query(&format!("SELECT {RULE_COLUMNS} FROM rules WHERE id = ?"))
+        .bind(id.to_string())
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    row.map(row_to_rule).transpose()
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct RuleRow {
+    id: Hyphenated,
+    name: String,
+    priority: i64,
+    enabled: bool,
+    conditions: Json<RuleConditions>,
+    actions: Json<RuleActions>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+fn row_to_rule(row: SqliteRow) -> Result<Rule> {
+    let row: RuleRow = decode_row("rules", &row)?;
+    Ok(Rule {
+        id: row.id.into_uuid(),
+        name: row.name,
+        priority: row.priority,
+        enabled: row.enabled,
+        conditions: row.conditions.0,
+        actions: row.actions.0,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::expense::CreateExpenseRequest;
+    use crate::models::rule::{RuleActions, RuleConditions, TextCondition, TextOp};
+    use crate::services::expense_service::ExpenseService;
+
+    fn test_ctx() -> RequestContext {
+        RequestContext::new("tester", "test-request")
+    }
+
+    async fn create_services() -> (RuleService, ExpenseService) {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        (RuleService::new(pool.clone()), ExpenseService::new(pool))
+    }
+
+    fn payee_rule(name: &str, priority: i64, contains: &str, category: &str) -> RuleRequest {
+        RuleRequest {
+            name: name.to_string(),
+            priority,
+            enabled: true,
+            conditions: RuleConditions {
+                payee: Some(TextCondition {
+                    op: TextOp::Contains,
+                    value: contains.to_string(),
+                }),
+                ..Default::default()
+            },
+            actions: RuleActions {
+                category: Some(category.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    async fn create_rule(service: &RuleService, request: RuleRequest) -> Rule {
+        match service.create_rule(&test_ctx(), request).await.unwrap() {
+            RuleWrite::Saved(rule) => *rule,
+            other => panic!("rule not created: {:?}", other),
+        }
+    }
+
+    async fn add_expense(service: &ExpenseService, category: &str, payee: &str) -> Expense {
+        let request = CreateExpenseRequest {
+            amount: 10.0,
+            category: category.to_string(),
+            payee: Some(payee.to_string()),
+            ..Default::default()
+        };
+        service.add_expense(&test_ctx(), request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rules_are_listed_in_priority_order() {
+        let (rules, _) = create_services().await;
+        create_rule(&rules, payee_rule("late", 50, "a", "A")).await;
+        create_rule(&rules, payee_rule("early", 10, "b", "B")).await;
+
+        let names: Vec<String> = rules
+            .list_rules()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|rule| rule.name)
+            .collect();
+
+        assert_eq!(names, vec!["early", "late"]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_rule_is_rejected() {
+        let (rules, _) = create_services().await;
+        let mut request = payee_rule("r", 1, "x", "X");
+        request.actions = RuleActions::default();
+
+        assert!(matches!(
+            rules.create_rule(&test_ctx(), request).await.unwrap(),
+            RuleWrite::Invalid(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_rules_are_not_applied_to_new_expenses() {
+        let (rules, expenses) = create_services().await;
+        let mut disabled = payee_rule("off", 1, "shell", "Fuel");
+        disabled.enabled = false;
+        create_rule(&rules, disabled).await;
+
+        let request = CreateExpenseRequest {
+            amount: 5.0,
+            payee: Some("Shell".to_string()),
+            ..Default::default()
+        };
+        let request = expenses.apply_rules(request).await.unwrap();
+
+        assert!(request.category.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_reports_without_writing() {
+        let (rules, expenses) = create_services().await;
+        create_rule(&rules, payee_rule("fuel", 1, "shell", "Fuel")).await;
+        let shell = add_expense(&expenses, "Misc", "SHELL 42").await;
+        add_expense(&expenses, "Food", "Cafe").await;
+
+        let report = rules.dry_run(&RuleRunRequest::default()).await.unwrap();
+
+        assert!(!report.applied);
+        assert_eq!(report.expenses_checked, 2);
+        assert_eq!(report.changes.len(), 1);
+        assert_eq!(report.changes[0].expense_id, shell.id);
+        assert_eq!(
+            report.changes[0].category,
+            Some(FieldChange {
+                from: Some("Misc".to_string()),
+                to: "Fuel".to_string(),
+            })
+        );
+        let unchanged = expenses.get_expense(shell.id).await.unwrap().unwrap();
+        assert_eq!(unchanged.category, "Misc");
+    }
+
+    #[tokio::test]
+    async fn test_apply_updates_matching_expenses() {
+        let (rules, expenses) = create_services().await;
+        let draft = {
+            let mut request = payee_rule("fuel", 1, "shell", "Fuel");
+            request.enabled = false;
+            create_rule(&rules, request).await
+        };
+        let shell = add_expense(&expenses, "Misc", "SHELL 42").await;
+
+        let all_enabled = rules
+            .apply(&test_ctx(), &RuleRunRequest::default())
+            .await
+            .unwrap();
+        assert!(all_enabled.changes.is_empty());
+
+        let selected = RuleRunRequest {
+            rule_ids: Some(vec![draft.id]),
+        };
+        let report = rules.apply(&test_ctx(), &selected).await.unwrap();
+        assert!(report.applied);
+        assert_eq!(report.changes.len(), 1);
+
+        let updated = expenses.get_expense(shell.id).await.unwrap().unwrap();
+        assert_eq!(updated.category, "Fuel");
+        assert_eq!(updated.version, shell.version + 1);
+
+        let again = rules.apply(&test_ctx(), &selected).await.unwrap();
+        assert!(again.changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete_rule() {
+        let (rules, _) = create_services().await;
+        let rule = create_rule(&rules, payee_rule("r", 1, "x", "X")).await;
+
+        let RuleWrite::Saved(updated) = rules
+            .update_rule(&test_ctx(), rule.id, payee_rule("renamed", 5, "y", "Y"))
+            .await
+            .unwrap()
+        else {
+            panic!("expected update");
+        };
+        assert_eq!(updated.name, "renamed");
+        assert_eq!(updated.created_at, rule.created_at);
+
+        assert!(rules.delete_rule(&test_ctx(), rule.id).await.unwrap());
+        assert!(rules.get_rule(rule.id).await.unwrap().is_none());
+        assert!(matches!(
+            rules
+                .update_rule(&test_ctx(), rule.id, payee_rule("r", 1, "x", "X"))
+                .await
+                .unwrap(),
+            RuleWrite::NotFound
+        ));
+    }
+}
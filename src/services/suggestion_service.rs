@@ -0,0 +1,490 @@
+use crate::models::audit::AuditAction;
+use crate::models::expense::Expense;
+use crate::models::suggestion::{
+    CategorySuggestion, SuggestCategoryRequest, SuggestCategoryResponse,
+};
+use crate::services::audit_service::{ENTITY_BACKUP, ENTITY_EXPENSE};
+use crate::services::expense_service;
+use anyhow::{Result, anyhow};
+use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Amounts are bucketed by powers of two so that 4.50 and 5.20 look alike
+/// but 4.50 and 450.00 do not.
+fn amount_bucket(amount: f64) -> i32 {
+    amount.max(0.01).log2().floor() as i32
+}
+
+/// Turns the known fields of an expense into classifier features: lowercase
+/// word tokens of payee and notes (numbers dropped, they are mostly store
+/// or reference numbers) and the amount bucket.
+fn features(payee: Option<&str>, notes: Option<&str>, amount: Option<f64>) -> Vec<String> {
+    let tokens = |prefix: &str, text: Option<&str>| {
+        text.unwrap_or_default()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|token| token.chars().count() > 1)
+            .filter(|token| !token.chars().all(|c| c.is_ascii_digit()))
+            .map(|token| format!("{}:{}", prefix, token.to_lowercase()))
+            .collect::<Vec<_>>()
+    };
+
+    let mut features = tokens("payee", payee);
+    features.extend(tokens("notes", notes));
+    if let Some(amount) = amount {
+        features.push(format!("amount:{}", amount_bucket(amount)));
+    }
+    features
+}
+
+fn expense_features(expense: &Expense) -> Vec<String> {
+    features(
+        expense.payee.as_deref(),
+        expense.notes.as_deref(),
+        Some(expense.amount),
+    )
+}
+
+#[derive(Default)]
+struct CategoryCounts {
+    documents: u64,
+    feature_total: u64,
+    features: HashMap<String, u64>,
+}
+
+/// Multinomial naive Bayes with Laplace smoothing. Examples can be added
+/// and removed, so the model follows edits without a full retrain.
+#[derive(Default)]
+struct NaiveBayes {
+    documents: u64,
+    categories: HashMap<String, CategoryCounts>,
+    /// Occurrences of each feature over all categories.
+    vocabulary: HashMap<String, u64>,
+}
+
+impl NaiveBayes {
+    fn add(&mut self, category: &str, features: &[String]) {
+        self.documents += 1;
+        let counts = self.categories.entry(category.to_string()).or_default();
+        counts.documents += 1;
+        for feature in features {
+            counts.feature_total += 1;
+            *counts.features.entry(feature.clone()).or_default() += 1;
+            *self.vocabulary.entry(feature.clone()).or_default() += 1;
+        }
+    }
+
+    /// Undoes an earlier `add` with the same arguments.
+    fn remove(&mut self, category: &str, features: &[String]) {
+        let Some(counts) = self.categories.get_mut(category) else {
+            return;
+        };
+        self.documents = self.documents.saturating_sub(1);
+        counts.documents = counts.documents.saturating_sub(1);
+        for feature in features {
+            counts.feature_total = counts.feature_total.saturating_sub(1);
+            decrement(&mut counts.features, feature);
+            decrement(&mut self.vocabulary, feature);
+        }
+        if counts.documents == 0 {
+            self.categories.remove(category);
+        }
+    }
+
+    /// Every known category with its posterior probability, best first.
+    /// Features never seen in training carry no information and are ignored.
+    fn predict(&self, features: &[String]) -> Vec<CategorySuggestion> {
+        let category_count = self.categories.len() as f64;
+        let vocabulary_size = self.vocabulary.len() as f64;
+        let known: Vec<&String> = features
+            .iter()
+            .filter(|feature| self.vocabulary.contains_key(*feature))
+            .collect();
+
+        let scores: Vec<(&String, f64)> = self
+            .categories
+            .iter()
+            .map(|(category, counts)| {
+                let prior = ((counts.documents as f64 + 1.0)
+                    / (self.documents as f64 + category_count))
+                    .ln();
+                let denominator = counts.feature_total as f64 + vocabulary_size;
+                let likelihood: f64 = known
+                    .iter()
+                    .map(|feature| {
+                        let count = counts.features.get(*feature).copied().unwrap_or(0) as f64;
+                        ((count + 1.0) / denominator).ln()
+                    })
+                    .sum();
+                (category, prior + likelihood)
+            })
+            .collect();
+
+        // Softmax over log scores, shifted by the maximum for stability.
+        let max = scores
+            .iter()
+            .map(|(_, score)| *score)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let total: f64 = scores.iter().map(|(_, score)| (score - max).exp()).sum();
+
+        let mut suggestions: Vec<CategorySuggestion> = scores
+            .into_iter()
+            .map(|(category, score)| CategorySuggestion {
+                category: category.clone(),
+                confidence: (score - max).exp() / total,
+            })
+            .collect();
+        suggestions.sort_by(|a, b| {
+            b.confidence
+                .total_cmp(&a.confidence)
+                .then_with(|| a.category.cmp(&b.category))
+        });
+        suggestions
+    }
+
+    fn train(&mut self, expense: &Expense) {
+        self.add(&expense.category, &expense_features(expense));
+    }
+
+    fn untrain(&mut self, expense: &Expense) {
+        self.remove(&expense.category, &expense_features(expense));
+    }
+}
+
+fn decrement(counts: &mut HashMap<String, u64>, key: &str) {
+    if let Some(count) = counts.get_mut(key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(key);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Model {
+    classifier: NaiveBayes,
+    /// Last expense or backup restore `seq` of the audit log folded into the
+    /// classifier; `None` until the first build.
+    watermark: Option<i64>,
+    /// Hash of the entry at `watermark`, to notice the log being replaced by
+    /// a restore; `None` while no expense was ever recorded.
+    watermark_hash: Option<String>,
+}
+
+/// Suggests categories for new expenses from past ones.
+///
+/// The model is built from the `expenses` table on first use and then kept
+/// current by replaying expense changes from the audit log, so every write
+/// path (single, batch, rules, payee renames) is covered. It is rebuilt when
+/// a backup restore is recorded, or when the entry it last replayed is gone.
+#[derive(Clone)]
+pub struct SuggestionService {
+    pool: SqlitePool,
+    model: Arc<RwLock<Model>>,
+}
+
+impl SuggestionService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            model: Arc::new(RwLock::new(Model::default())),
+        }
+    }
+
+    pub async fn suggest(
+        &self,
+        request: &SuggestCategoryRequest,
+    ) -> Result<SuggestCategoryResponse> {
+        self.refresh().await?;
+
+        let model = self
+            .model
+            .read()
+            .map_err(|_| anyhow!("suggestion model lock poisoned"))?;
+        let features = features(
+            request.payee.as_deref(),
+            request.notes.as_deref(),
+            request.amount,
+        );
+        let mut suggestions = model.classifier.predict(&features);
+        suggestions.truncate(request.limit());
+
+        Ok(SuggestCategoryResponse {
+            suggestions,
+            trained_on: model.classifier.documents,
+        })
+    }
+
+    /// Brings the model up to date with the audit log.
+    async fn refresh(&self) -> Result<()> {
+        let (watermark, watermark_hash) = {
+            let model = self
+                .model
+                .read()
+                .map_err(|_| anyhow!("suggestion model lock poisoned"))?;
+            (model.watermark, model.watermark_hash.clone())
+        };
+
+        let Some(watermark) = watermark else {
+            return self.rebuild().await;
+        };
+        let hash: Option<String> = sqlx::query_scalar("SELECT hash FROM audit_log WHERE seq = ?")
+            .bind(watermark)
+            .fetch_optional(&self.pool)
+            .await?;
+        if hash != watermark_hash {
+            return self.retrain().await;
+        }
+
+        let rows = sqlx::query(
+            "SELECT seq, hash, entity_type, before_json, after_json FROM audit_log \
+             WHERE seq > ? AND (entity_type = ? OR (entity_type = ? AND action = ?)) \
+             ORDER BY seq",
+        )
+        .bind(watermark)
+        .bind(ENTITY_EXPENSE)
+        .bind(ENTITY_BACKUP)
+        .bind(AuditAction::Restore.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+        // A restored backup replaced the expenses the model was trained on.
+        if rows
+            .iter()
+            .any(|row| row.get::<String, _>("entity_type") == ENTITY_BACKUP)
+        {
+            return self.retrain().await;
+        }
+
+        let mut model = self
+            .model
+            .write()
+            .map_err(|_| anyhow!("suggestion model lock poisoned"))?;
+        for row in rows {
+            let seq: i64 = row.get("seq");
+            // Another request may have applied these already.
+            if model.watermark.is_some_and(|applied| seq <= applied) {
+                continue;
+            }
+            if let Some(before) = active_expense(row.get("before_json")) {
+                model.classifier.untrain(&before);
+            }
+            if let Some(after) = active_expense(row.get("after_json")) {
+                model.classifier.train(&after);
+            }
+            model.watermark = Some(seq);
+            model.watermark_hash = Some(row.get("hash"));
+        }
+
+        Ok(())
+    }
+
+    /// Drops the model and trains a new one.
+    async fn retrain(&self) -> Result<()> {
+        *self
+            .model
+            .write()
+            .map_err(|_| anyhow!("suggestion model lock poisoned"))? = Model::default();
+        self.rebuild().await
+    }
+
+    /// Trains a fresh model on all non-deleted expenses.
+    async fn rebuild(&self) -> Result<()> {
+        // One read transaction so the watermark matches the expenses read.
+        let mut tx = self.pool.begin().await?;
+        let (watermark, watermark_hash): (i64, Option<String>) = sqlx::query_as(
+            "SELECT seq, hash FROM audit_log \
+             WHERE entity_type = ? OR (entity_type = ? AND action = ?) \
+             ORDER BY seq DESC LIMIT 1",
+        )
+        .bind(ENTITY_EXPENSE)
+        .bind(ENTITY_BACKUP)
+        .bind(AuditAction::Restore.as_str())
+        .fetch_optional(&mut *tx)
+        .await?
+        .map_or((0, None), |(seq, hash): (i64, String)| (seq, Some(hash)));
+        let expenses = expense_service::fetch_active_expenses(&mut tx).await?;
+        tx.commit().await?;
+
+        let mut classifier = NaiveBayes::default();
+        for expense in &expenses {
+            classifier.train(expense);
+        }
+
+        let mut model = self
+            .model
+            .write()
+            .map_err(|_| anyhow!("suggestion model lock poisoned"))?;
+        if model.watermark.is_none() {
+            *model = Model {
+                classifier,
+                watermark: Some(watermark),
+                watermark_hash,
+            };
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses an audit snapshot, keeping it only if the expense was not trashed.
+fn active_expense(json: Option<String>) -> Option<Expense> {
+    let expense: Expense = serde_json::from_str(&json?)
+        .inspect_err(|e| tracing::warn!(error = %e, "Skipping unreadable expense snapshot"))
+        .ok()?;
+    expense.deleted_at.is_none().then_some(expense)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::RequestContext;
+    use crate::models::expense::{CreateExpenseRequest, UpdateExpenseRequest};
+    use crate::services::expense_service::ExpenseService;
+
+    fn test_ctx() -> RequestContext {
+        RequestContext::new("tester", "test-request")
+    }
+
+    async fn create_services() -> (SuggestionService, ExpenseService) {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        (
+            SuggestionService::new(pool.clone()),
+            ExpenseService::new(pool),
+        )
+    }
+
+    async fn add_expense(
+        service: &ExpenseService,
+        amount: f64,
+        category: &str,
+        payee: &str,
+    ) -> Expense {
+        let request = CreateExpenseRequest {
+            amount,
+            category: category.to_string(),
+            payee: Some(payee.to_string()),
+            ..Default::default()
+        };
+        service.add_expense(&test_ctx(), request).await.unwrap()
+    }
+
+    fn ask(payee: &str, amount: f64) -> SuggestCategoryRequest {
+        SuggestCategoryRequest {
+            payee: Some(payee.to_string()),
+            amount: Some(amount),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_features() {
+        assert_eq!(
+            features(Some("SHELL #1234 Oil"), Some("a car wash"), Some(40.0)),
+            vec![
+                "payee:shell",
+                "payee:oil",
+                "notes:car",
+                "notes:wash",
+                "amount:5"
+            ]
+        );
+        assert!(features(None, None, None).is_empty());
+    }
+
+    #[test]
+    fn test_remove_undoes_add() {
+        let mut classifier = NaiveBayes::default();
+        let shell = vec!["payee:shell".to_string()];
+        classifier.add("Fuel", &shell);
+        classifier.add("Food", &["payee:cafe".to_string()]);
+        classifier.remove("Fuel", &shell);
+
+        assert_eq!(classifier.documents, 1);
+        assert!(!classifier.categories.contains_key("Fuel"));
+        assert!(!classifier.vocabulary.contains_key("payee:shell"));
+    }
+
+    #[test]
+    fn test_confidences_sum_to_one() {
+        let mut classifier = NaiveBayes::default();
+        classifier.add("Fuel", &["payee:shell".to_string()]);
+        classifier.add("Food", &["payee:cafe".to_string()]);
+        classifier.add("Food", &["payee:deli".to_string()]);
+
+        let suggestions = classifier.predict(&["payee:shell".to_string()]);
+        let total: f64 = suggestions.iter().map(|s| s.confidence).sum();
+
+        assert_eq!(suggestions[0].category, "Fuel");
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_no_history_gives_no_suggestions() {
+        let (suggestions, _) = create_services().await;
+
+        let response = suggestions.suggest(&ask("Shell", 40.0)).await.unwrap();
+
+        assert!(response.suggestions.is_empty());
+        assert_eq!(response.trained_on, 0);
+    }
+
+    #[tokio::test]
+    async fn test_suggests_from_history() {
+        let (suggestions, expenses) = create_services().await;
+        add_expense(&expenses, 45.0, "Fuel", "SHELL 1234").await;
+        add_expense(&expenses, 52.0, "Fuel", "Shell Station").await;
+        add_expense(&expenses, 4.5, "Coffee", "Blue Bottle").await;
+        add_expense(&expenses, 5.0, "Coffee", "Corner Cafe").await;
+
+        let response = suggestions.suggest(&ask("SHELL 9876", 48.0)).await.unwrap();
+
+        assert_eq!(response.trained_on, 4);
+        assert_eq!(response.suggestions[0].category, "Fuel");
+        assert!(response.suggestions[0].confidence > 0.5);
+
+        let small = suggestions.suggest(&ask("Some Cafe", 4.8)).await.unwrap();
+        assert_eq!(small.suggestions[0].category, "Coffee");
+    }
+
+    #[tokio::test]
+    async fn test_model_follows_changes_incrementally() {
+        let (suggestions, expenses) = create_services().await;
+        let first = add_expense(&expenses, 45.0, "Fuel", "Shell").await;
+        suggestions.suggest(&ask("Shell", 45.0)).await.unwrap();
+
+        // Added after the first build: picked up from the audit log.
+        add_expense(&expenses, 45.0, "Fuel", "Shell").await;
+        let response = suggestions.suggest(&ask("Shell", 45.0)).await.unwrap();
+        assert_eq!(response.trained_on, 2);
+
+        // Recategorizing moves the example to the new category.
+        let request = UpdateExpenseRequest {
+            amount: 45.0,
+            category: "Car".to_string(),
+            payee: Some("Shell".to_string()),
+            ..Default::default()
+        };
+        expenses
+            .update_expense(&test_ctx(), first.id, None, request)
+            .await
+            .unwrap();
+        let response = suggestions.suggest(&ask("Shell", 45.0)).await.unwrap();
+        assert_eq!(response.trained_on, 2);
+        assert_eq!(response.suggestions.len(), 2);
+
+        // Trashed expenses no longer count.
+        expenses
+            .delete_expense(&test_ctx(), first.id, None)
+            .await
+            .unwrap();
+        let response = suggestions.suggest(&ask("Shell", 45.0)).await.unwrap();
+        assert_eq!(response.trained_on, 1);
+        assert_eq!(response.suggestions.len(), 1);
+        assert_eq!(response.suggestions[0].category, "Fuel");
+    }
+}
@@ -0,0 +1,353 @@
+use crate::context::RequestContext;
+use crate::models::audit::AuditAction;
+use crate::models::expense::Expense;
+use crate::models::tag::{MergeTagRequest, RenameTagRequest, Tag};
+use crate::services::audit_service::{self, ENTITY_TAG, NewAuditEntry};
+use crate::services::integrity_service::decode_row;
+use crate::services::{expense_service, rule_service};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{SqliteConnection, SqlitePool};
+use uuid::Uuid;
+use uuid::fmt::Hyphenated;
+
+/// Result of a tag write that can be rejected.
+#[derive(Debug)]
+pub enum TagWrite {
+    Saved(Tag),
+    NotFound,
+    Conflict(String),
+    Invalid(String),
+}
+
+#[derive(Clone)]
+pub struct TagService {
+    pool: SqlitePool,
+}
+
+impl TagService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list_tags(&self) -> Result<Vec<Tag>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {TAG_COLUMNS} FROM tags ORDER BY name COLLATE NOCASE"
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(row_to_tag).collect()
+    }
+
+    /// Renames a tag on every expense and rule that uses it. Renaming onto
+    /// the name of another tag is a conflict; merge the tags instead.
+    pub async fn rename_tag(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        request: RenameTagRequest,
+    ) -> Result<TagWrite> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(before) = fetch_tag(&mut tx, id).await? else {
+            return Ok(TagWrite::NotFound);
+        };
+        let name = request.name.trim().to_string();
+        let taken: Option<String> = sqlx::query_scalar("SELECT id FROM tags WHERE name = ?")
+            .bind(&name)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if taken.is_some_and(|other| other != id.to_string()) {
+            return Ok(TagWrite::Conflict(format!(
+                "Tag '{}' already exists; merge the tags instead",
+                name
+            )));
+        }
+
+        let tagged = tagged_expenses(&mut tx, id).await?;
+        sqlx::query("UPDATE tags SET name = ? WHERE id = ?")
+            .bind(&name)
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        expense_service::record_related_change(&mut tx, ctx, &tagged).await?;
+        rule_service::replace_tag_in_rules(&mut tx, ctx, &before.name, &name).await?;
+
+        let after = fetch_tag(&mut tx, id)
+            .await?
+            .ok_or_else(|| anyhow!("tag vanished during rename"))?;
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_TAG,
+                entity_id: id.to_string(),
+                action: AuditAction::Update,
+                before: Some(serde_json::to_value(&before)?),
+                after: Some(serde_json::to_value(&after)?),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(TagWrite::Saved(after))
+    }
+
+    /// Moves every use of tag `id` to `request.into` and deletes `id`.
+    /// Returns the surviving tag.
+    pub async fn merge_tag(
+        &self,
+        ctx: &RequestContext,
+        id: Uuid,
+        request: MergeTagRequest,
+    ) -> Result<TagWrite> {
+        if id == request.into {
+            return Ok(TagWrite::Invalid(
+                "A tag cannot be merged into itself".to_string(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let Some(source) = fetch_tag(&mut tx, id).await? else {
+            return Ok(TagWrite::NotFound);
+        };
+        let Some(target) = fetch_tag(&mut tx, request.into).await? else {
+            return Ok(TagWrite::NotFound);
+        };
+
+        let tagged = tagged_expenses(&mut tx, id).await?;
+        sqlx::query(
+            "INSERT INTO expense_tags (expense_id, tag_id) \
+             SELECT expense_id, ? FROM expense_tags WHERE tag_id = ? \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(target.id.to_string())
+        .bind(id.to_string())
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM expense_tags WHERE tag_id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM tags WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        expense_service::record_related_change(&mut tx, ctx, &tagged).await?;
+        rule_service::replace_tag_in_rules(&mut tx, ctx, &source.name, &target.name).await?;
+
+        audit_service::append(
+            &mut tx,
+            ctx,
+            NewAuditEntry {
+                entity_type: ENTITY_TAG,
+                entity_id: id.to_string(),
+                action: AuditAction::Delete,
+                before: Some(serde_json::to_value(&source)?),
+                after: None,
+            },
+        )
+        .await?;
+
+        let merged = fetch_tag(&mut tx, target.id)
+            .await?
+            .ok_or_else(|| anyhow!("tag vanished during merge"))?;
+        tx.commit().await?;
+
+        Ok(TagWrite::Saved(merged))
+    }
+}
+
+/// Every expense carrying the tag, trashed ones included.
+async fn tagged_expenses(conn: &mut SqliteConnection, tag_id: Uuid) -> Result<Vec<Expense>> {
+    let ids: Vec<String> =
+        sqlx::query_scalar("SELECT expense_id FROM expense_tags WHERE tag_id = ?")
+            .bind(tag_id.to_string())
+            .fetch_all(&mut *conn)
+            .await?;
+
+    let mut expenses = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(expense) = expense_service::fetch_expense(conn, Uuid::parse_str(&id)?).await? {
+            expenses.push(expense);
+        }
+    }
+    Ok(expenses)
+}
+
+pub(crate) const TAG_COLUMNS: &str = "id, name, created_at, \
+     (SELECT COUNT(*) FROM expense_tags et JOIN expenses e ON e.id = et.expense_id \
+      WHERE et.tag_id = tags.id AND e.deleted_at IS NULL) AS expense_count";
+
+async fn fetch_tag(conn: &mut SqliteConnection, id: Uuid) -> Result<Option<Tag>> {
+    let row = sqlx::query(&format!("SELECT {TAG_COLUMNS} FROM tags WHERE id = ?"))
+        .bind(id.to_string())
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    row.map(row_to_tag).transpose()
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct TagRow {
+    id: Hyphenated,
+    name: String,
+    created_at: DateTime<Utc>,
+    expense_count: i64,
+}
+
+fn row_to_tag(row: SqliteRow) -> Result<Tag> {
+    let row: TagRow = decode_row("tags", &row)?;
+    Ok(Tag {
+        id: row.id.into_uuid(),
+        name: row.name,
+        created_at: row.created_at,
+        expense_count: row.expense_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::expense::CreateExpenseRequest;
+    use crate::models::rule::{RuleActions, RuleRequest};
+    use crate::services::expense_service::ExpenseService;
+    use crate::services::rule_service::RuleService;
+
+    fn test_ctx() -> RequestContext {
+        RequestContext::new("tester", "test-request")
+    }
+
+    async fn create_services() -> (TagService, ExpenseService, RuleService) {
+        let pool = crate::database::create_pool("sqlite::memory:")
+            .await
+            .unwrap();
+        (
+            TagService::new(pool.clone()),
+            ExpenseService::new(pool.clone()),
+            RuleService::new(pool),
+        )
+    }
+
+    async fn add_expense(service: &ExpenseService, tags: &[&str]) -> Expense {
+        let request = CreateExpenseRequest {
+            amount: 10.0,
+            category: "Travel".to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        service.add_expense(&test_ctx(), request).await.unwrap()
+    }
+
+    async fn tag_id(service: &TagService, name: &str) -> Uuid {
+        service
+            .list_tags()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|tag| tag.name == name)
+            .unwrap()
+            .id
+    }
+
+    #[tokio::test]
+    async fn test_list_counts_non_deleted_expenses() {
+        let (tags, expenses, _) = create_services().await;
+        add_expense(&expenses, &["work"]).await;
+        let trashed = add_expense(&expenses, &["work", "trip"]).await;
+        expenses
+            .delete_expense(&test_ctx(), trashed.id, None)
+            .await
+            .unwrap();
+
+        let listed = tags.list_tags().await.unwrap();
+
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].name, "trip");
+        assert_eq!(listed[0].expense_count, 0);
+        assert_eq!(listed[1].expense_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_rename_updates_expenses_and_rules() {
+        let (tags, expenses, rules) = create_services().await;
+        let expense = add_expense(&expenses, &["biz"]).await;
+        let request = RuleRequest {
+            name: "tag".to_string(),
+            priority: 1,
+            enabled: true,
+            conditions: Default::default(),
+            actions: RuleActions {
+                tags: vec!["biz".to_string()],
+                ..Default::default()
+            },
+        };
+        rules.create_rule(&test_ctx(), request).await.unwrap();
+
+        let id = tag_id(&tags, "biz").await;
+        let request = RenameTagRequest {
+            name: "business".to_string(),
+        };
+        let TagWrite::Saved(renamed) = tags.rename_tag(&test_ctx(), id, request).await.unwrap()
+        else {
+            panic!("expected rename");
+        };
+
+        assert_eq!(renamed.name, "business");
+        let updated = expenses.get_expense(expense.id).await.unwrap().unwrap();
+        assert_eq!(updated.tags, vec!["business"]);
+        assert_eq!(updated.version, expense.version + 1);
+        let rule = rules.list_rules().await.unwrap().remove(0);
+        assert_eq!(rule.actions.tags, vec!["business"]);
+    }
+
+    #[tokio::test]
+    async fn test_rename_onto_existing_tag_conflicts() {
+        let (tags, expenses, _) = create_services().await;
+        add_expense(&expenses, &["a", "b"]).await;
+
+        let request = RenameTagRequest {
+            name: "B".to_string(),
+        };
+        let outcome = tags
+            .rename_tag(&test_ctx(), tag_id(&tags, "a").await, request)
+            .await
+            .unwrap();
+
+        assert!(matches!(outcome, TagWrite::Conflict(_)));
+    }
+
+    #[tokio::test]
+    async fn test_merge_moves_expenses_and_deletes_source() {
+        let (tags, expenses, _) = create_services().await;
+        let both = add_expense(&expenses, &["trip", "travel"]).await;
+        let only_source = add_expense(&expenses, &["trip"]).await;
+        let source = tag_id(&tags, "trip").await;
+        let target = tag_id(&tags, "travel").await;
+
+        let TagWrite::Saved(merged) = tags
+            .merge_tag(&test_ctx(), source, MergeTagRequest { into: target })
+            .await
+            .unwrap()
+        else {
+            panic!("expected merge");
+        };
+
+        assert_eq!(merged.expense_count, 2);
+        assert_eq!(tags.list_tags().await.unwrap().len(), 1);
+        for id in [both.id, only_source.id] {
+            let expense = expenses.get_expense(id).await.unwrap().unwrap();
+            assert_eq!(expense.tags, vec!["travel"]);
+        }
+        assert!(matches!(
+            tags.merge_tag(&test_ctx(), target, MergeTagRequest { into: target })
+                .await
+                .unwrap(),
+            TagWrite::Invalid(_)
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::config::Config;
+use crate::database::DatabasePool;
+use crate::metrics::Metrics;
+use crate::services::account_service::AccountService;
+use crate::services::attachment_service::AttachmentService;
+use crate::services::audit_service::AuditService;
+use crate::services::backup_service::BackupService;
+use crate::services::expense_report_service::ExpenseReportService;
+use crate::services::expense_service::ExpenseService;
+use crate::services::health_service::HealthService;
+use crate::services::idempotency_service::IdempotencyService;
+use crate::services::income_service::IncomeService;
+use crate::services::integrity_service::IntegrityService;
+use crate::services::metrics_service::MetricsService;
+use crate::services::payee_service::PayeeService;
+use crate::services::refund_service::RefundService;
+use crate::services::rule_service::RuleService;
+use crate::services::suggestion_service::SuggestionService;
+use crate::services::tag_service::TagService;
+use crate::storage::BlobStore;
+use axum::extract::FromRef;
+use sqlx::SqlitePool;
+use tokio_util::sync::CancellationToken;
+
+/// Shared router state; handlers extract the individual services via `FromRef`.
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub expenses: ExpenseService,
+    pub audit: AuditService,
+    pub idempotency: IdempotencyService,
+    pub payees: PayeeService,
+    pub rules: RuleService,
+    pub suggestions: SuggestionService,
+    pub tags: TagService,
+    pub attachments: AttachmentService,
+    pub accounts: AccountService,
+    pub income: IncomeService,
+    pub refunds: RefundService,
+    pub expense_reports: ExpenseReportService,
+    pub integrity: IntegrityService,
+    pub backups: BackupService,
+    pub health: HealthService,
+    pub metrics: Metrics,
+    pub metrics_service: MetricsService,
+}
+
+impl AppState {
+    pub fn new(
+        pool: SqlitePool,
+        config: &Config,
+        metrics: Metrics,
+        shutdown: CancellationToken,
+    ) -> Self {
+        Self {
+            expenses: ExpenseService::new(pool.clone()),
+            audit: AuditService::new(pool.clone()),
+            idempotency: IdempotencyService::new(pool.clone(), config.idempotency_retention)
+                .with_max_body_bytes(config.max_request_bytes()),
+            payees: PayeeService::new(pool.clone()),
+            rules: RuleService::new(pool.clone()),
+            suggestions: SuggestionService::new(pool.clone()),
+            tags: TagService::new(pool.clone()),
+            accounts: AccountService::new(pool.clone()),
+            income: IncomeService::new(pool.clone()),
+            refunds: RefundService::new(pool.clone()),
+            integrity: IntegrityService::new(pool.clone()),
+            backups: BackupService::new(
+                pool.clone(),
+                &config.backup_dir,
+                config.backup_keep,
+                config.encryption_key.clone(),
+            ),
+            health: HealthService::new(pool.clone(), shutdown),
+            metrics_service: MetricsService::new(
+                pool.clone(),
+                ExpenseService::new(pool.clone()),
+                metrics.clone(),
+            ),
+            metrics,
+            expense_reports: ExpenseReportService::new(
+                pool.clone(),
+                BlobStore::new(&config.attachments_dir, config.encryption_key.as_ref()),
+                config.approvers.clone(),
+            ),
+            attachments: AttachmentService::new(
+                pool,
+                BlobStore::new(&config.attachments_dir, config.encryption_key.as_ref()),
+                config.max_attachment_bytes,
+            ),
+        }
+    }
+}
+
+/// Router state when expenses live behind an `ExpenseRepository` other than
+/// SQLite (PostgreSQL, or memory in `--demo`): the expense routes plus the
+/// operational ones. `pool` holds idempotency keys and backs `/readyz`.
+#[derive(Clone, FromRef)]
+pub struct RepositoryState {
+    pub expenses: ExpenseService,
+    pub idempotency: IdempotencyService,
+    pub health: HealthService,
+    pub metrics: Metrics,
+    pub metrics_service: MetricsService,
+}
+
+impl RepositoryState {
+    pub fn new(
+        pool: impl Into<DatabasePool>,
+        expenses: ExpenseService,
+        config: &Config,
+        metrics: Metrics,
+        shutdown: CancellationToken,
+    ) -> Self {
+        let pool = pool.into();
+        Self {
+            idempotency: IdempotencyService::new(pool.clone(), config.idempotency_retention)
+                .with_max_body_bytes(config.max_request_bytes()),
+            health: HealthService::new(pool.clone(), shutdown),
+            metrics_service: MetricsService::new(pool, expenses.clone(), metrics.clone()),
+            metrics,
+            expenses,
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use crate::encryption::{EncryptionKey, FileCipher, sealed_fingerprint};
+use anyhow::{Result, anyhow, bail};
+use image::ImageFormat;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::{Cursor, ErrorKind};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Longest edge of generated thumbnails, in pixels.
+const THUMBNAIL_SIZE: u32 = 256;
+const BLOBS: &str = "blobs";
+const THUMBNAILS: &str = "thumbnails";
+
+/// Content-addressed file store for attachments. Blobs live under
+/// `blobs/<first two hex digits>/<sha256>` and thumbnails under
+/// `thumbnails/...` with the same layout; files are written to `tmp/` first
+/// and renamed into place so readers never see partial content.
+///
+/// With an encryption key, files are sealed as they are written. Files
+/// written before a key was configured are still read as they are, until
+/// `expence_tracker encryption rotate` seals them.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    root: PathBuf,
+    cipher: Option<FileCipher>,
+}
+
+pub fn sha256_hex(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+fn check_hash(sha256: &str) -> Result<()> {
+    if sha256.len() == 64 && sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(anyhow!("invalid content hash '{}'", sha256))
+    }
+}
+
+impl BlobStore {
+    pub fn new(root: impl Into<PathBuf>, key: Option<&EncryptionKey>) -> Self {
+        Self {
+            root: root.into(),
+            cipher: key.map(FileCipher::new),
+        }
+    }
+
+    fn path(&self, area: &str, sha256: &str) -> PathBuf {
+        self.root.join(area).join(&sha256[..2]).join(sha256)
+    }
+
+    /// Stores `content` under `sha256` unless it is already present.
+    pub async fn put(&self, sha256: &str, content: &[u8]) -> Result<()> {
+        check_hash(sha256)?;
+        self.write(BLOBS, sha256, content).await
+    }
+
+    pub async fn put_thumbnail(&self, sha256: &str, content: &[u8]) -> Result<()> {
+        check_hash(sha256)?;
+        self.write(THUMBNAILS, sha256, content).await
+    }
+
+    pub async fn read(&self, sha256: &str) -> Result<Vec<u8>> {
+        check_hash(sha256)?;
+        self.read_from(BLOBS, sha256).await
+    }
+
+    pub async fn read_thumbnail(&self, sha256: &str) -> Result<Vec<u8>> {
+        check_hash(sha256)?;
+        self.read_from(THUMBNAILS, sha256).await
+    }
+
+    /// Removes the blob and thumbnail stored under `sha256`, if any.
+    pub async fn remove(&self, sha256: &str) -> Result<()> {
+        check_hash(sha256)?;
+        for path in [self.path(BLOBS, sha256), self.path(THUMBNAILS, sha256)] {
+            match tokio::fs::remove_file(&path).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Hashes of everything in the store, blobs and thumbnails alike.
+    pub async fn stored_hashes(&self) -> Result<HashSet<String>> {
+        Ok(self
+            .stored_files()
+            .await?
+            .into_iter()
+            .map(|(_, sha256)| sha256)
+            .collect())
+    }
+
+    /// Seals every file under this store's key that is stored plain or
+    /// sealed with `old`, and returns how many were. Files already sealed
+    /// under this store's key are skipped, so an interrupted rotation can be
+    /// run again. Nothing else may write to the store meanwhile.
+    pub async fn reencrypt(&self, old: Option<&EncryptionKey>) -> Result<usize> {
+        let Some(cipher) = &self.cipher else {
+            bail!("re-encrypting attachments needs the new key");
+        };
+        let old = BlobStore::new(&self.root, old);
+
+        let mut sealed = 0;
+        for (area, sha256) in self.stored_files().await? {
+            let content = tokio::fs::read(self.path(area, &sha256)).await?;
+            if cipher.sealed_by_this_key(&content) {
+                continue;
+            }
+            let content = old.unseal(area, &sha256, content)?;
+            self.replace(area, &sha256, &content).await?;
+            sealed += 1;
+        }
+        Ok(sealed)
+    }
+
+    async fn stored_files(&self) -> Result<Vec<(&'static str, String)>> {
+        let mut files = Vec::new();
+        for area in [BLOBS, THUMBNAILS] {
+            let mut shards = match tokio::fs::read_dir(self.root.join(area)).await {
+                Ok(shards) => shards,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(shard) = shards.next_entry().await? {
+                if !shard.file_type().await?.is_dir() {
+                    continue;
+                }
+                let mut entries = tokio::fs::read_dir(shard.path()).await?;
+                while let Some(file) = entries.next_entry().await? {
+                    if let Some(name) = file.file_name().to_str()
+                        && check_hash(name).is_ok()
+                    {
+                        files.push((area, name.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    async fn read_from(&self, area: &str, sha256: &str) -> Result<Vec<u8>> {
+        let content = tokio::fs::read(self.path(area, sha256)).await?;
+        self.unseal(area, sha256, content)
+    }
+
+    fn unseal(&self, area: &str, sha256: &str, content: Vec<u8>) -> Result<Vec<u8>> {
+        match (&self.cipher, sealed_fingerprint(&content)) {
+            (_, None) => Ok(content),
+            (Some(cipher), Some(_)) => cipher.open(&format!("{area}/{sha256}"), &content),
+            (None, Some(_)) => bail!("{area}/{sha256} is encrypted and ENCRYPTION_KEY is not set"),
+        }
+    }
+
+    async fn write(&self, area: &str, sha256: &str, content: &[u8]) -> Result<()> {
+        if tokio::fs::try_exists(self.path(area, sha256)).await? {
+            return Ok(());
+        }
+        self.replace(area, sha256, content).await
+    }
+
+    async fn replace(&self, area: &str, sha256: &str, content: &[u8]) -> Result<()> {
+        let content = match &self.cipher {
+            Some(cipher) => cipher.seal(&format!("{area}/{sha256}"), content)?,
+            None => content.to_vec(),
+        };
+        let path = self.path(area, sha256);
+
+        let tmp_dir = self.root.join("tmp");
+        tokio::fs::create_dir_all(&tmp_dir).await?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let tmp = tmp_dir.join(Uuid::new_v4().to_string());
+        tokio::fs::write(&tmp, content).await?;
+        if let Err(e) = tokio::fs::rename(&tmp, &path).await {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+/// Decodes an image and renders a JPEG thumbnail that fits in
+/// `THUMBNAIL_SIZE` pixels. CPU-bound; run it off the async runtime.
+pub fn render_thumbnail(content: &[u8]) -> Result<Vec<u8>> {
+    let image = image::load_from_memory(content)?;
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).into_rgb8();
+
+    let mut encoded = Cursor::new(Vec::new());
+    thumbnail.write_to(&mut encoded, ImageFormat::Jpeg)?;
+    Ok(encoded.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store() -> BlobStore {
+        BlobStore::new(
+            std::env::temp_dir().join(format!("blob-store-{}", Uuid::new_v4())),
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_put_read_and_remove() {
+        let store = temp_store();
+        let content = b"%PDF-1.7 receipt";
+        let sha = sha256_hex(content);
+
+        store.put(&sha, content).await.unwrap();
+        store.put(&sha, content).await.unwrap();
+
+        assert_eq!(store.read(&sha).await.unwrap(), content);
+        assert_eq!(
+            store.stored_hashes().await.unwrap(),
+            HashSet::from([sha.clone()])
+        );
+
+        store.remove(&sha).await.unwrap();
+        store.remove(&sha).await.unwrap();
+        assert!(store.read(&sha).await.is_err());
+        assert!(store.stored_hashes().await.unwrap().is_empty());
+
+        tokio::fs::remove_dir_all(&store.root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_store_seals_and_reencrypts() {
+        let plain = temp_store();
+        let content = b"%PDF-1.7 receipt";
+        let sha = sha256_hex(content);
+        plain.put(&sha, content).await.unwrap();
+
+        let first = EncryptionKey::parse(&"01".repeat(32)).unwrap();
+        let store = BlobStore::new(&plain.root, Some(&first));
+        // Files stored before the key was set stay readable.
+        assert_eq!(store.read(&sha).await.unwrap(), content);
+        assert_eq!(store.reencrypt(None).await.unwrap(), 1);
+        assert_eq!(store.reencrypt(None).await.unwrap(), 0);
+
+        let on_disk = tokio::fs::read(store.path(BLOBS, &sha)).await.unwrap();
+        assert!(!on_disk.windows(8).any(|window| window == b"%PDF-1.7"));
+        assert_eq!(store.read(&sha).await.unwrap(), content);
+        assert!(plain.read(&sha).await.is_err());
+
+        let second = EncryptionKey::parse(&"02".repeat(32)).unwrap();
+        let rotated = BlobStore::new(&plain.root, Some(&second));
+        assert!(rotated.read(&sha).await.is_err());
+        assert_eq!(rotated.reencrypt(Some(&first)).await.unwrap(), 1);
+        assert_eq!(rotated.read(&sha).await.unwrap(), content);
+        assert!(store.read(&sha).await.is_err());
+
+        tokio::fs::remove_dir_all(&plain.root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rejects_malformed_hashes() {
+        let store = temp_store();
+
+        assert!(store.put("../../escape", b"x").await.is_err());
+        assert!(store.read(&"g".repeat(64)).await.is_err());
+    }
+
+    #[test]
+    fn test_render_thumbnail_fits_bounds() {
+        let image = image::RgbImage::new(1024, 512);
+        let mut png = Cursor::new(Vec::new());
+        image.write_to(&mut png, ImageFormat::Png).unwrap();
+
+        let thumbnail = render_thumbnail(png.get_ref()).unwrap();
+        let decoded = image::load_from_memory(&thumbnail).unwrap();
+
+        assert_eq!((decoded.width(), decoded.height()), (256, 128));
+        assert!(render_thumbnail(b"not an image").is_err());
+    }
+}